@@ -12,48 +12,266 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
+
 use gpui::{prelude::*, *};
 use gpui_component::{
+    button::{Button, ButtonVariants as _},
     resizable::{h_resizable, resizable_panel},
-    v_flex, Root, TitleBar,
+    v_flex, Disableable as _, IconName, Root, Sizable as _, TitleBar, WindowExt as _,
 };
 
 use crate::{
-    components::AgentSwitcher,
-    models::AgentRegistry,
-    panels::{AgentPanel, MessagePanel},
+    client::AcpConnection,
+    components::{AgentSwitcher, SessionForm},
+    models::{
+        AgentConfig, AgentId, AgentRegistry, AgentStatus, FileSettingsStore, SettingsStore as _,
+    },
+    panels::{AgentPanel, ComparePanel, MessagePanel},
 };
 
-pub struct AcpDebugger {
+/// The connection of one agent together with its chat and message panels.
+struct Workspace {
+    connection: Entity<AcpConnection>,
     agent_panel: Entity<AgentPanel>,
+    message_panel: Entity<MessagePanel>,
+}
+
+impl Workspace {
+    fn new(window: &mut Window, cx: &mut App) -> Self {
+        let connection = cx.new(AcpConnection::new);
+        let agent_panel = cx.new(|cx| AgentPanel::new(window, cx));
+        let message_panel = cx.new(|cx| MessagePanel::new(connection.clone(), window, cx));
+        Self { connection, agent_panel, message_panel }
+    }
+}
+
+pub struct AcpDebugger {
+    agent_registry: Entity<AgentRegistry>,
     agent_switcher: Entity<AgentSwitcher>,
+    /// One workspace per agent that has been selected, kept connected in the
+    /// background while another agent is shown.
+    workspaces: HashMap<AgentId, Workspace>,
+    /// Shown while no agent is selected.
+    idle: Workspace,
+    active_agent_id: Option<AgentId>,
+    /// Where the registry is saved; `None` if the saved agents failed to load,
+    /// so that the file is not overwritten.
+    store: Option<FileSettingsStore>,
 }
 
 impl AcpDebugger {
     pub fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
-        let agent_registry = cx.new(|_| AgentRegistry::new());
-        let agent_panel = cx.new(|cx| AgentPanel::new(window, cx));
-        let agent_switcher = cx.new(|cx| AgentSwitcher::new(agent_registry, cx));
-        Self { agent_panel, agent_switcher }
+        let store = FileSettingsStore::new();
+        let (registry, store) = match store.load_agents() {
+            Ok(saved) => (AgentRegistry::from_saved(saved), Some(store)),
+            Err(e) => {
+                log::error!("Failed to load agents: {}", e);
+                (AgentRegistry::new(), None)
+            }
+        };
+        let agent_registry = cx.new(|_| registry);
+        let agent_switcher = cx.new(|cx| AgentSwitcher::new(agent_registry.clone(), cx));
+        let idle = Workspace::new(window, cx);
+
+        // Show (and connect) whichever agent is selected in the switcher.
+        cx.observe_in(&agent_registry, window, |this, registry, window, cx| {
+            this.save_agents(&registry, cx);
+            this.sync_agents(registry, window, cx)
+        })
+        .detach();
+        // Reconnect the agent that was active when the app was last closed.
+        let registry = agent_registry.clone();
+        cx.defer_in(window, move |this, window, cx| this.sync_agents(registry, window, cx));
+
+        Self {
+            agent_registry,
+            agent_switcher,
+            workspaces: HashMap::new(),
+            idle,
+            active_agent_id: None,
+            store,
+        }
+    }
+
+    fn save_agents(&self, registry: &Entity<AgentRegistry>, cx: &App) {
+        if let Some(store) = &self.store {
+            if let Err(e) = store.save_agents(&registry.read(cx).to_saved()) {
+                log::error!("Failed to save agents: {}", e);
+            }
+        }
+    }
+
+    fn sync_agents(
+        &mut self,
+        registry: Entity<AgentRegistry>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let registry = registry.read(cx);
+        let agents: Vec<AgentConfig> = registry.agents().to_vec();
+        let active = registry.active_agent().cloned();
+
+        // Disconnect agents removed from the registry.
+        let removed: Vec<AgentId> = self
+            .workspaces
+            .keys()
+            .filter(|id| !agents.iter().any(|agent| &agent.id == *id))
+            .cloned()
+            .collect();
+        for id in &removed {
+            if let Some(workspace) = self.workspaces.remove(id) {
+                workspace.connection.update(cx, |conn, cx| conn.disconnect(cx));
+            }
+        }
+        if !removed.is_empty() {
+            self.update_switcher(cx);
+        }
+
+        // Hand edited configs to running agents; the workspace offers a
+        // restart when the process needs one to pick them up.
+        for agent in &agents {
+            if let Some(workspace) = self.workspaces.get(&agent.id) {
+                let config = agent.clone();
+                workspace.connection.update(cx, |conn, cx| conn.update_config(config, cx));
+            }
+        }
+
+        let active_id = active.as_ref().map(|agent| agent.id.clone());
+        if active_id != self.active_agent_id {
+            self.active_agent_id = active_id;
+            if let Some(config) = active {
+                self.connect_agent(config, window, cx);
+            }
+        }
+        cx.notify();
+    }
+
+    /// The connection of `config`'s workspace, created and connected on first
+    /// use, or reconnected after the agent went away.
+    pub fn connect_agent(
+        &mut self,
+        config: AgentConfig,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Entity<AcpConnection> {
+        let created = !self.workspaces.contains_key(&config.id);
+        let connection = self
+            .workspaces
+            .entry(config.id.clone())
+            .or_insert_with(|| Workspace::new(window, cx))
+            .connection
+            .clone();
+        let status = connection.read(cx).status();
+        if matches!(status, AgentStatus::Disconnected | AgentStatus::Error) {
+            connection.update(cx, |conn, cx| conn.connect(config, cx));
+        }
+
+        if created {
+            self.update_switcher(cx);
+        }
+        connection
+    }
+
+    /// Hand the switcher the current connections so it shows their status.
+    fn update_switcher(&mut self, cx: &mut Context<Self>) {
+        let connections = self
+            .workspaces
+            .iter()
+            .map(|(id, workspace)| (id.clone(), workspace.connection.clone()))
+            .collect();
+        self.agent_switcher.update(cx, |switcher, cx| switcher.set_connections(connections, cx));
+    }
+
+    fn open_compare(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let registry = self.agent_registry.clone();
+        let debugger = cx.weak_entity();
+        let panel = cx.new(|cx| ComparePanel::new(registry, debugger, window, cx));
+        window.open_sheet(cx, move |sheet, _, _| {
+            sheet.size(px(960.)).title("Compare Agents").child(panel.clone())
+        });
+    }
+
+    /// Create a session on the active agent, with its cwd and MCP servers.
+    fn open_new_session(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(config) = self.agent_registry.read(cx).active_agent().cloned() else {
+            return;
+        };
+        let connection = self.active_workspace().connection.clone();
+        SessionForm::open(&config, self.agent_registry.clone(), connection, window, cx);
+    }
+
+    fn active_workspace(&self) -> &Workspace {
+        self.active_agent_id.as_ref().and_then(|id| self.workspaces.get(id)).unwrap_or(&self.idle)
     }
 }
 
 impl Render for AcpDebugger {
     fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
         let sheet_layer = Root::render_sheet_layer(window, cx);
+        let restart_to_apply = self.active_workspace().connection.read(cx).restart_to_apply();
 
         v_flex()
             .size_full()
             .child(
                 TitleBar::new().text_xs().child("ACP Debugger").child(
-                    div().flex().items_center().mr(px(9.0)).child(self.agent_switcher.clone()),
+                    div()
+                        .flex()
+                        .items_center()
+                        .gap_2()
+                        .mr(px(9.0))
+                        .child(
+                            Button::new("new-session")
+                                .xsmall()
+                                .rounded_lg()
+                                .icon(IconName::Plus)
+                                .label("New Session")
+                                .disabled(self.active_agent_id.is_none())
+                                .on_click(cx.listener(|this, _, window, cx| {
+                                    this.open_new_session(window, cx)
+                                })),
+                        )
+                        .when(restart_to_apply, |this| {
+                            this.child(
+                                Button::new("restart-to-apply")
+                                    .xsmall()
+                                    .rounded_lg()
+                                    .warning()
+                                    .icon(IconName::Redo)
+                                    .label("Restart to Apply")
+                                    .tooltip("The agent was edited while running")
+                                    .on_click(cx.listener(|this, _, _, cx| {
+                                        let connection = this.active_workspace().connection.clone();
+                                        connection.update(cx, |conn, cx| conn.restart(cx));
+                                    })),
+                            )
+                        })
+                        .child(
+                            Button::new("compare-agents")
+                                .xsmall()
+                                .rounded_lg()
+                                .icon(IconName::PanelRight)
+                                .label("Compare")
+                                .on_click(
+                                    cx.listener(|this, _, window, cx| {
+                                        this.open_compare(window, cx)
+                                    }),
+                                ),
+                        )
+                        .child(self.agent_switcher.clone()),
                 ),
             )
             .child(
                 div().flex_1().w_full().overflow_hidden().child(
                     h_resizable("layout")
-                        .child(resizable_panel().size(px(400.)).child(self.agent_panel.clone()))
-                        .child(resizable_panel().child(MessagePanel)),
+                        .child(
+                            resizable_panel()
+                                .size(px(400.))
+                                .child(self.active_workspace().agent_panel.clone()),
+                        )
+                        .child(
+                            resizable_panel().child(self.active_workspace().message_panel.clone()),
+                        ),
                 ),
             )
             .children(sheet_layer)
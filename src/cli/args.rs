@@ -0,0 +1,253 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::PathBuf;
+
+use crate::models::AgentConfig;
+
+pub const USAGE: &str = "\
+Usage: acp-debugger [COMMAND] [OPTIONS] [-- AGENT [ARGS...]]
+
+Without a command, the debugger window is opened.
+
+Commands:
+  connect                   Run the ACP handshake and print the agent's capabilities
+  prompt <TEXT> [--json]    Send one prompt and stream session updates to stdout
+  record -o <FILE> [--prompt <TEXT>]
+                            Capture the traffic of a session to a trace file
+  validate [--prompt <TEXT>]
+                            Run protocol conformance checks against an agent
+  scenario <FILE>           Run a JSON or TOML scenario file; the agent after `--`
+                            overrides the one declared in the file
+  fuzz [-o <DIR>] [--only <TEXT>]
+                            Send malformed and edge-case requests and report crashes,
+                            hangs and non-spec errors; reproducers are saved to DIR
+                            (default: fuzz-findings)
+  replay <TRACE>            Impersonate a recorded agent over stdio
+  help                      Print this message
+
+Agent options:
+  --env <KEY=VALUE>         Set an environment variable for the agent (repeatable)
+  --cwd <DIR>               Working directory sent with session/new (default: current)
+  --timeout <MS>            Handshake and idle timeout in milliseconds (default: 30000)
+  -- <AGENT> [ARGS...]      The agent command line (required)
+";
+
+/// A headless subcommand.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Connect { agent: AgentArgs },
+    Prompt { agent: AgentArgs, text: String, json: bool },
+    Record { agent: AgentArgs, output: PathBuf, prompt: Option<String> },
+    Validate { agent: AgentArgs, prompt: Option<String> },
+    Scenario { file: PathBuf, agent: Option<AgentArgs>, cwd: Option<String> },
+    Fuzz { agent: AgentArgs, output: PathBuf, only: Option<String> },
+    Replay { trace: PathBuf },
+    Help,
+}
+
+/// Options describing how to launch the agent under test.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgentArgs {
+    pub endpoint: String,
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+    pub cwd: Option<String>,
+    pub timeout_ms: u64,
+}
+
+impl AgentArgs {
+    pub fn config(&self) -> AgentConfig {
+        AgentConfig {
+            id: "cli".into(),
+            name: self.endpoint.clone(),
+            endpoint: self.endpoint.clone(),
+            args: self.args.clone(),
+            env: self.env.clone(),
+            timeout_ms: self.timeout_ms,
+            ..AgentConfig::default()
+        }
+    }
+
+    /// The cwd for `session/new`, defaulting to the current directory.
+    pub fn session_cwd(&self) -> String {
+        self.cwd.clone().unwrap_or_else(|| {
+            std::env::current_dir().map(|p| p.display().to_string()).unwrap_or_else(|_| ".".into())
+        })
+    }
+}
+
+/// Parse the process arguments (without the binary name).
+///
+/// Returns `Ok(None)` when no subcommand was given and the GUI should start.
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Option<Command>, String> {
+    let mut args = args.into_iter();
+    let Some(name) = args.next() else {
+        return Ok(None);
+    };
+
+    let mut positional = Vec::new();
+    let mut env = Vec::new();
+    let mut cwd = None;
+    let mut timeout_ms = 30_000;
+    let mut output = None;
+    let mut prompt = None;
+    let mut json = false;
+    let mut only = None;
+    let mut agent = Vec::new();
+
+    while let Some(arg) = args.next() {
+        let mut value =
+            |flag: &str| args.next().ok_or_else(|| format!("{} requires a value", flag));
+        match arg.as_str() {
+            "--" => {
+                agent.extend(args.by_ref());
+            }
+            "--env" => {
+                let pair = value("--env")?;
+                let (key, val) = pair
+                    .split_once('=')
+                    .ok_or_else(|| format!("invalid --env `{}`, expected KEY=VALUE", pair))?;
+                env.push((key.to_string(), val.to_string()));
+            }
+            "--cwd" => cwd = Some(value("--cwd")?),
+            "--timeout" => {
+                let raw = value("--timeout")?;
+                timeout_ms = raw.parse().map_err(|_| format!("invalid --timeout `{}`", raw))?;
+            }
+            "-o" | "--output" => output = Some(PathBuf::from(value("--output")?)),
+            "--prompt" => prompt = Some(value("--prompt")?),
+            "--json" => json = true,
+            "--only" => only = Some(value("--only")?),
+            flag if flag.starts_with("--") => return Err(format!("unknown option `{}`", flag)),
+            _ => positional.push(arg),
+        }
+    }
+
+    let agent_args = || {
+        let mut agent = agent.clone().into_iter();
+        let endpoint = agent.next().ok_or("missing agent command after `--`")?;
+        Ok::<_, String>(AgentArgs {
+            endpoint,
+            args: agent.collect(),
+            env: env.clone(),
+            cwd: cwd.clone(),
+            timeout_ms,
+        })
+    };
+    let no_positional = |positional: &[String]| match positional.first() {
+        Some(extra) => Err(format!("unexpected argument `{}`", extra)),
+        None => Ok(()),
+    };
+
+    let command = match name.as_str() {
+        "connect" => {
+            no_positional(&positional)?;
+            Command::Connect { agent: agent_args()? }
+        }
+        "prompt" => {
+            let text = match positional.as_slice() {
+                [text] => text.clone(),
+                [] => return Err("prompt requires the prompt text".into()),
+                [_, extra, ..] => return Err(format!("unexpected argument `{}`", extra)),
+            };
+            Command::Prompt { agent: agent_args()?, text, json }
+        }
+        "record" => {
+            no_positional(&positional)?;
+            let output = output.ok_or("record requires -o <FILE>")?;
+            Command::Record { agent: agent_args()?, output, prompt }
+        }
+        "validate" => {
+            no_positional(&positional)?;
+            Command::Validate { agent: agent_args()?, prompt }
+        }
+        "scenario" => {
+            let file = match positional.as_slice() {
+                [file] => PathBuf::from(file),
+                _ => return Err("scenario requires exactly one scenario file".into()),
+            };
+            let agent = if agent.is_empty() { None } else { Some(agent_args()?) };
+            Command::Scenario { file, agent, cwd }
+        }
+        "fuzz" => {
+            no_positional(&positional)?;
+            let output = output.unwrap_or_else(|| PathBuf::from("fuzz-findings"));
+            Command::Fuzz { agent: agent_args()?, output, only }
+        }
+        "replay" => match positional.as_slice() {
+            [trace] => Command::Replay { trace: PathBuf::from(trace) },
+            _ => return Err("replay requires exactly one trace file".into()),
+        },
+        "help" | "--help" | "-h" => Command::Help,
+        other => return Err(format!("unknown command `{}`", other)),
+    };
+    Ok(Some(command))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Command>, String> {
+        parse_args(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn test_no_args_starts_gui() {
+        assert_eq!(parse(&[]), Ok(None));
+    }
+
+    #[test]
+    fn test_parse_prompt() {
+        let command =
+            parse(&["prompt", "hello", "--env", "A=1", "--json", "--", "agent", "--acp"]).unwrap();
+        let Some(Command::Prompt { agent, text, json }) = command else {
+            panic!("expected prompt command");
+        };
+        assert_eq!(text, "hello");
+        assert!(json);
+        assert_eq!(agent.endpoint, "agent");
+        assert_eq!(agent.args, vec!["--acp".to_string()]);
+        assert_eq!(agent.env, vec![("A".to_string(), "1".to_string())]);
+        assert_eq!(agent.timeout_ms, 30_000);
+    }
+
+    #[test]
+    fn test_agent_args_after_separator_are_not_options() {
+        let command = parse(&["connect", "--", "agent", "--timeout", "x"]).unwrap();
+        let Some(Command::Connect { agent }) = command else {
+            panic!("expected connect command");
+        };
+        assert_eq!(agent.args, vec!["--timeout".to_string(), "x".to_string()]);
+    }
+
+    #[test]
+    fn test_scenario_agent_is_optional() {
+        let command = parse(&["scenario", "flow.toml"]).unwrap();
+        assert!(matches!(command, Some(Command::Scenario { agent: None, .. })));
+
+        let command = parse(&["scenario", "flow.toml", "--", "agent"]).unwrap();
+        assert!(matches!(command, Some(Command::Scenario { agent: Some(_), .. })));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse(&["connect"]).unwrap_err().contains("missing agent command"));
+        assert!(parse(&["record", "--", "agent"]).unwrap_err().contains("-o"));
+        assert!(parse(&["connect", "--timeout", "soon", "--", "a"]).unwrap_err().contains("soon"));
+        assert!(parse(&["scenario"]).unwrap_err().contains("scenario file"));
+        assert!(parse(&["frobnicate"]).unwrap_err().contains("unknown command"));
+    }
+}
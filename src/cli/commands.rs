@@ -0,0 +1,355 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    io::Write as _,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use gpui::AsyncApp;
+use serde_json::Value;
+
+use crate::{
+    client::{ClientError, ConnectionEvent},
+    fuzz::{self, FuzzOptions},
+    models::AcpMessage,
+};
+
+use super::{
+    args::{AgentArgs, Command},
+    headless::HeadlessAgent,
+    scenario::{self, Action, Scenario},
+    validate::{self, Check},
+};
+
+/// Run a command that needs the GPUI executor. Returns the exit code.
+pub async fn execute(command: Command, cx: &mut AsyncApp) -> anyhow::Result<i32> {
+    match command {
+        Command::Connect { agent } => connect(agent, cx).await,
+        Command::Prompt { agent, text, json } => prompt(agent, &text, json, cx).await,
+        Command::Record { agent, output, prompt } => {
+            record(agent, &output, prompt.as_deref(), cx).await
+        }
+        Command::Validate { agent, prompt } => validate(agent, prompt.as_deref(), cx).await,
+        Command::Scenario { file, agent, cwd } => run_scenario(&file, agent, cwd, cx).await,
+        Command::Fuzz { agent, output, only } => run_fuzz(agent, output, only, cx).await,
+        Command::Replay { .. } | Command::Help => unreachable!("handled before startup"),
+    }
+}
+
+async fn connect(args: AgentArgs, cx: &mut AsyncApp) -> anyhow::Result<i32> {
+    let agent = HeadlessAgent::connect(args.config(), cx).await?;
+    let result = agent.connection().read_with(cx, |conn, _| conn.initialize_result().cloned());
+    agent.disconnect(cx).await;
+    let result = result?;
+
+    println!("{}", serde_json::to_string_pretty(&result.unwrap_or_default())?);
+    Ok(0)
+}
+
+async fn prompt(args: AgentArgs, text: &str, json: bool, cx: &mut AsyncApp) -> anyhow::Result<i32> {
+    let mut agent = HeadlessAgent::connect(args.config(), cx).await?;
+    let outcome = async {
+        let session_id = agent.new_session(&args.session_cwd(), cx).await?;
+        agent
+            .prompt(&session_id, text, cx, |_, event| {
+                if let ConnectionEvent::SessionNotification { params, .. } = event {
+                    if json {
+                        println!("{}", params);
+                    } else if let Some(line) = describe_update(params) {
+                        print!("{}", line);
+                        let _ = std::io::stdout().flush();
+                    }
+                }
+            })
+            .await
+    }
+    .await;
+    agent.disconnect(cx).await;
+
+    let result = outcome?;
+    if json {
+        println!("{}", serde_json::json!({ "result": result }));
+    } else {
+        let reason = result.get("stopReason").and_then(|v| v.as_str()).unwrap_or("unknown");
+        println!("\n[stop: {}]", reason);
+    }
+    Ok(0)
+}
+
+async fn record(
+    args: AgentArgs,
+    output: &Path,
+    prompt: Option<&str>,
+    cx: &mut AsyncApp,
+) -> anyhow::Result<i32> {
+    let mut agent = HeadlessAgent::connect(args.config(), cx).await?;
+    let outcome = async {
+        if let Some(text) = prompt {
+            let session_id = agent.new_session(&args.session_cwd(), cx).await?;
+            agent.prompt(&session_id, text, cx, |_, _| {}).await?;
+        }
+        anyhow::Ok(())
+    }
+    .await;
+    agent.disconnect(cx).await;
+
+    // Save whatever was captured, even if the session failed midway.
+    let trace = agent.trace();
+    trace.save(output)?;
+    eprintln!("recorded {} frames to {}", trace.frames.len(), output.display());
+    outcome?;
+    Ok(0)
+}
+
+async fn validate(args: AgentArgs, prompt: Option<&str>, cx: &mut AsyncApp) -> anyhow::Result<i32> {
+    let mut checks = Vec::new();
+
+    let mut agent = match HeadlessAgent::connect(args.config(), cx).await {
+        Ok(agent) => {
+            checks.push(Check::pass("agent completes the initialize handshake"));
+            agent
+        }
+        Err(e) => {
+            checks.push(Check::fail("agent completes the initialize handshake", e.to_string()));
+            return Ok(report(&checks));
+        }
+    };
+
+    let init = agent.connection().read_with(cx, |conn, _| conn.initialize_result().cloned());
+    checks.extend(validate::check_initialize(&init.ok().flatten().unwrap_or_default()));
+
+    let session_id = match agent.new_session(&args.session_cwd(), cx).await {
+        Ok(session_id) => {
+            checks.push(Check::pass("session/new returns a sessionId"));
+            Some(session_id)
+        }
+        Err(e) => {
+            checks.push(Check::fail("session/new returns a sessionId", e.to_string()));
+            None
+        }
+    };
+
+    if let (Some(text), Some(session_id)) = (prompt, session_id.as_deref()) {
+        match agent.prompt(session_id, text, cx, |_, _| {}).await {
+            Ok(result) => checks.push(validate::check_prompt_result(&result)),
+            Err(e) => checks.push(Check::fail("session/prompt completes", e.to_string())),
+        }
+    }
+    agent.disconnect(cx).await;
+
+    let incoming: Vec<AcpMessage> = agent.captured().filter(|m| m.is_response).cloned().collect();
+    checks.extend(validate::check_incoming(&incoming, session_id.as_deref()));
+
+    Ok(report(&checks))
+}
+
+async fn run_scenario(
+    file: &Path,
+    agent: Option<AgentArgs>,
+    cwd: Option<String>,
+    cx: &mut AsyncApp,
+) -> anyhow::Result<i32> {
+    let scenario = Scenario::load(file)?;
+    let args = match agent {
+        Some(agent) => agent,
+        None => scenario.agent_args(cwd)?,
+    };
+    println!("scenario: {}\n", scenario.name.as_deref().unwrap_or(&file.display().to_string()));
+
+    let mut checks = Vec::new();
+    let mut agent = match HeadlessAgent::connect(args.config(), cx).await {
+        Ok(agent) => agent,
+        Err(e) => {
+            checks.push(Check::fail("connect", format!("{:#}", e)));
+            return Ok(report(&checks));
+        }
+    };
+
+    let mut session_id: Option<String> = None;
+    for (ix, step) in scenario.steps.iter().enumerate() {
+        let label = format!("step {}: {}", ix + 1, step.action.label());
+        // The handshake already happened in `connect`, so `initialize` looks
+        // at the whole log instead of the traffic of this step.
+        let start = match step.action {
+            Action::Initialize {} => 0,
+            _ => agent.captured().count(),
+        };
+
+        let outcome = run_step(&mut agent, &step.action, &args, &mut session_id, cx).await;
+        if let Err(e) = outcome {
+            // Error responses are still responses; anything else ends the run.
+            if !matches!(e.downcast_ref::<ClientError>(), Some(ClientError::AgentError { .. })) {
+                checks.push(Check::fail(label, format!("{:#}", e)));
+                let skipped = scenario.steps.len() - ix - 1;
+                if skipped > 0 {
+                    println!("skipping {} remaining step(s)", skipped);
+                }
+                break;
+            }
+        }
+
+        let messages: Vec<&AcpMessage> = agent.captured().skip(start).collect();
+        if !step.expect.is_empty() {
+            let name = format!("{} response {}", label, scenario::describe(&step.expect));
+            let checked = match response_frame(&messages, &step.action) {
+                Some(frame) => scenario::evaluate(&step.expect, &frame),
+                None => Err("no response captured".into()),
+            };
+            checks.push(match checked {
+                Ok(()) => Check::pass(name),
+                Err(detail) => Check::fail(name, detail),
+            });
+        }
+
+        let notifications: Vec<Value> = messages
+            .iter()
+            .filter(|m| m.is_response && m.method.is_some() && m.id.is_none())
+            .map(|m| serde_json::from_str(&m.raw_json).unwrap_or_default())
+            .collect();
+        for mut check in scenario::evaluate_in_order(&step.notifications, &notifications) {
+            check.name = format!("{} {}", label, check.name);
+            checks.push(check);
+        }
+        if step.expect.is_empty() && step.notifications.is_empty() {
+            checks.push(Check::pass(label));
+        }
+    }
+    agent.disconnect(cx).await;
+
+    Ok(report(&checks))
+}
+
+async fn run_fuzz(
+    args: AgentArgs,
+    output: PathBuf,
+    only: Option<String>,
+    cx: &mut AsyncApp,
+) -> anyhow::Result<i32> {
+    let options = FuzzOptions {
+        config: args.config(),
+        cwd: args.session_cwd(),
+        timeout: Duration::from_millis(args.timeout_ms),
+        output_dir: output,
+        filter: only,
+    };
+    let report = fuzz::run(&options, cx.background_executor(), |case, findings| {
+        if findings.is_empty() {
+            println!("[ok] {}", case.name);
+        }
+        for finding in findings {
+            println!("[{}] {}: {}", finding.kind, finding.case, finding.detail);
+        }
+        if let Some(path) = findings.first().and_then(|f| f.reproducer.as_ref()) {
+            println!("    reproducer: {}", path.display());
+        }
+    })
+    .await?;
+
+    println!("\n{} cases, {} findings", report.cases, report.findings.len());
+    Ok(if report.findings.is_empty() { 0 } else { 1 })
+}
+
+/// Perform one scenario action, remembering the session it creates.
+async fn run_step(
+    agent: &mut HeadlessAgent,
+    action: &Action,
+    args: &AgentArgs,
+    session_id: &mut Option<String>,
+    cx: &mut AsyncApp,
+) -> anyhow::Result<()> {
+    match action {
+        Action::Initialize {} => {}
+        Action::NewSession { cwd } => {
+            let cwd = cwd.clone().unwrap_or_else(|| args.session_cwd());
+            let params = serde_json::json!({ "cwd": cwd, "mcpServers": [] });
+            let result = agent.request("session/new", params, cx, |_, _| {}).await?;
+            *session_id = result.get("sessionId").and_then(|v| v.as_str()).map(str::to_string);
+        }
+        Action::Prompt(text) => {
+            let session_id = session_id
+                .as_deref()
+                .ok_or_else(|| anyhow::anyhow!("prompt before new_session"))?;
+            agent.prompt(session_id, text, cx, |_, _| {}).await?;
+        }
+        Action::Request { method, params } => {
+            let params = scenario::substitute(params, session_id.as_deref());
+            agent.request(method, params, cx, |_, _| {}).await?;
+        }
+        Action::Notify { method, params } => {
+            agent.notify(method, scenario::substitute(params, session_id.as_deref()), cx)?;
+        }
+    }
+    Ok(())
+}
+
+/// The response to the request sent by `action`, as a raw JSON-RPC frame.
+fn response_frame(messages: &[&AcpMessage], action: &Action) -> Option<Value> {
+    let method = match action {
+        Action::Initialize {} => "initialize",
+        Action::NewSession { .. } => "session/new",
+        Action::Prompt(_) => "session/prompt",
+        Action::Request { method, .. } => method,
+        Action::Notify { .. } => return None,
+    };
+    let request = messages
+        .iter()
+        .rev()
+        .find(|m| !m.is_response && m.method.as_ref().map(|m| m.as_ref()) == Some(method))?;
+    let id = request.id.as_ref()?;
+    let response = messages
+        .iter()
+        .find(|m| m.is_response && m.method.is_none() && m.id.as_ref() == Some(id))?;
+    serde_json::from_str(&response.raw_json).ok()
+}
+
+/// Print the checklist and return the exit code.
+fn report(checks: &[Check]) -> i32 {
+    for check in checks {
+        println!("{}", check);
+    }
+    let failed = checks.iter().filter(|c| !c.passed).count();
+    println!("\n{} passed, {} failed", checks.len() - failed, failed);
+    if failed == 0 {
+        0
+    } else {
+        1
+    }
+}
+
+/// Render a `session/update` as terminal output.
+fn describe_update(params: &Value) -> Option<String> {
+    let update = params.get("update")?;
+    let kind = update.get("sessionUpdate")?.as_str()?;
+    let field = |name: &str| update.get(name).and_then(|v| v.as_str()).unwrap_or("");
+    match kind {
+        "agent_message_chunk" => update.pointer("/content/text")?.as_str().map(str::to_string),
+        "agent_thought_chunk" | "user_message_chunk" => None,
+        "tool_call" => Some(format!(
+            "\n[tool_call] {} ({}, {})\n",
+            field("title"),
+            field("kind"),
+            field("status")
+        )),
+        "tool_call_update" if !field("status").is_empty() => {
+            Some(format!("[tool_call_update] {} {}\n", field("toolCallId"), field("status")))
+        }
+        "plan" => {
+            let entries = update.get("entries").and_then(|v| v.as_array()).map_or(0, |e| e.len());
+            Some(format!("\n[plan] {} entries\n", entries))
+        }
+        _ => None,
+    }
+}
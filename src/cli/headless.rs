@@ -0,0 +1,320 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail};
+use futures::{
+    channel::mpsc,
+    future::{self, Either},
+    StreamExt,
+};
+use gpui::*;
+use serde_json::{json, Value};
+
+use crate::{
+    client::{AcpConnection, ClientError, ConnectionEvent},
+    models::{AcpMessage, AgentConfig, AgentStatus, Trace, TraceDirection, TraceFrame},
+};
+
+/// Drives an `AcpConnection` from async CLI code.
+///
+/// Every event the connection emits is forwarded to a channel together with
+/// the instant it was emitted, so commands can consume them in order. Captured
+/// messages are also kept in a log for recording and validation.
+pub struct HeadlessAgent {
+    connection: Entity<AcpConnection>,
+    events: mpsc::UnboundedReceiver<(Instant, ConnectionEvent)>,
+    timeout: Duration,
+    started_at: Instant,
+    captured: Vec<(Instant, AcpMessage)>,
+    _subscription: Subscription,
+}
+
+impl HeadlessAgent {
+    /// Spawn the agent and wait for the `initialize` handshake to finish.
+    pub async fn connect(config: AgentConfig, cx: &mut AsyncApp) -> anyhow::Result<Self> {
+        let timeout = Duration::from_millis(config.timeout_ms);
+        let connection = cx.new(AcpConnection::new)?;
+
+        let (tx, events) = mpsc::unbounded();
+        let subscription = cx.subscribe(&connection, move |_, event: &ConnectionEvent, _| {
+            let _ = tx.unbounded_send((Instant::now(), event.clone()));
+        })?;
+
+        let mut agent = Self {
+            connection,
+            events,
+            timeout,
+            started_at: Instant::now(),
+            captured: Vec::new(),
+            _subscription: subscription,
+        };
+        agent.connection.update(cx, |conn, cx| conn.connect(config, cx))?;
+
+        let handshake = loop {
+            match agent.next_event(cx).await {
+                Ok(ConnectionEvent::StateChanged(AgentStatus::Connected)) => break Ok(()),
+                Ok(ConnectionEvent::StateChanged(AgentStatus::Error)) => {
+                    break Err(anyhow!("agent failed to start or rejected initialize"))
+                }
+                Ok(ConnectionEvent::StateChanged(AgentStatus::Disconnected)) => {
+                    break Err(anyhow!("agent exited during the handshake"))
+                }
+                Ok(_) => {}
+                Err(e) => break Err(e),
+            }
+        };
+        if let Err(e) = handshake {
+            agent.disconnect(cx).await;
+            return Err(e);
+        }
+        Ok(agent)
+    }
+
+    pub fn connection(&self) -> &Entity<AcpConnection> {
+        &self.connection
+    }
+
+    /// Every message sent or received so far, in order.
+    pub fn captured(&self) -> impl Iterator<Item = &AcpMessage> {
+        self.captured.iter().map(|(_, message)| message)
+    }
+
+    /// The captured traffic as a trace, relative to when `connect` started.
+    pub fn trace(&self) -> Trace {
+        let frames = self
+            .captured
+            .iter()
+            .map(|(at, message)| TraceFrame {
+                offset_ms: at.duration_since(self.started_at).as_millis() as u64,
+                direction: if message.is_response {
+                    TraceDirection::Incoming
+                } else {
+                    TraceDirection::Outgoing
+                },
+                message: serde_json::from_str(&message.raw_json).unwrap_or_default(),
+            })
+            .collect();
+        Trace { frames }
+    }
+
+    /// Wait for the next event, failing after the idle timeout.
+    pub async fn next_event(&mut self, cx: &AsyncApp) -> anyhow::Result<ConnectionEvent> {
+        let (_, event) = self.next_timed_event(cx).await?;
+        Ok(event)
+    }
+
+    /// Like [`Self::next_event`], also returning when the event was emitted.
+    pub async fn next_timed_event(
+        &mut self,
+        cx: &AsyncApp,
+    ) -> anyhow::Result<(Instant, ConnectionEvent)> {
+        let (at, event) = with_timeout(cx, self.timeout, self.events.next())
+            .await?
+            .ok_or_else(|| anyhow!("connection dropped"))?;
+        self.observe(at, &event);
+        Ok((at, event))
+    }
+
+    /// Drain events that were already emitted without waiting.
+    pub fn pending_events(&mut self) -> Vec<(Instant, ConnectionEvent)> {
+        let events: Vec<_> = std::iter::from_fn(|| self.events.try_recv().ok()).collect();
+        for (at, event) in &events {
+            self.observe(*at, event);
+        }
+        events
+    }
+
+    fn observe(&mut self, at: Instant, event: &ConnectionEvent) {
+        if let ConnectionEvent::MessageCaptured { message, .. } = event {
+            self.captured.push((at, (**message).clone()));
+        }
+    }
+
+    /// Send `session/new` for the given cwd and return the session id.
+    pub async fn new_session(&mut self, cwd: &str, cx: &mut AsyncApp) -> anyhow::Result<String> {
+        let task = self.connection.update(cx, |conn, cx| conn.new_session(cwd, Vec::new(), cx))?;
+        Ok(with_timeout(cx, self.timeout, task).await??)
+    }
+
+    /// Send a text prompt, calling `on_event` for every event emitted while
+    /// the turn is running.
+    pub async fn prompt(
+        &mut self,
+        session_id: &str,
+        text: &str,
+        cx: &mut AsyncApp,
+        on_event: impl FnMut(Instant, &ConnectionEvent),
+    ) -> anyhow::Result<Value> {
+        let content = vec![serde_json::json!({ "type": "text", "text": text })];
+        let task = self.connection.update(cx, |conn, cx| conn.prompt(session_id, content, cx))?;
+        self.drive(task, cx, on_event).await
+    }
+
+    /// Send an arbitrary request, calling `on_event` for every event emitted
+    /// until the response arrives.
+    pub async fn request(
+        &mut self,
+        method: &str,
+        params: Value,
+        cx: &mut AsyncApp,
+        on_event: impl FnMut(Instant, &ConnectionEvent),
+    ) -> anyhow::Result<Value> {
+        let task = self.connection.update(cx, |conn, cx| conn.send_request(method, params, cx))?;
+        self.drive(task, cx, on_event).await
+    }
+
+    /// Send a notification and pick up any events already emitted.
+    pub fn notify(&mut self, method: &str, params: Value, cx: &mut AsyncApp) -> anyhow::Result<()> {
+        self.connection.update(cx, |conn, cx| conn.send_notification(method, params, cx))??;
+        self.pending_events();
+        Ok(())
+    }
+
+    /// Await a request while consuming events. The idle timeout restarts with
+    /// each event, so long-running turns that keep streaming don't time out.
+    async fn drive(
+        &mut self,
+        mut task: Task<Result<Value, ClientError>>,
+        cx: &mut AsyncApp,
+        mut on_event: impl FnMut(Instant, &ConnectionEvent),
+    ) -> anyhow::Result<Value> {
+        loop {
+            let timer = cx.background_executor().timer(self.timeout);
+            let step =
+                match future::select(future::select(&mut task, self.events.next()), timer).await {
+                    Either::Left((Either::Left((result, _)), _)) => DriveStep::Finished(result),
+                    Either::Left((Either::Right((Some((at, event)), _)), _)) => {
+                        DriveStep::Event(at, event)
+                    }
+                    Either::Left((Either::Right((None, _)), _)) => DriveStep::Closed,
+                    Either::Right(_) => DriveStep::Idle,
+                };
+
+            match step {
+                DriveStep::Finished(result) => {
+                    for (at, event) in self.pending_events() {
+                        on_event(at, &event);
+                    }
+                    return Ok(result?);
+                }
+                DriveStep::Event(at, event) => {
+                    self.observe(at, &event);
+                    self.answer(&event, cx)?;
+                    on_event(at, &event);
+                }
+                DriveStep::Closed => bail!("connection dropped"),
+                DriveStep::Idle => {
+                    bail!(
+                        "no traffic for {}ms while waiting for a response",
+                        self.timeout.as_millis()
+                    )
+                }
+            }
+        }
+    }
+
+    /// Reply to a request from the agent, which would otherwise wait for
+    /// the answer and stall the turn.
+    fn answer(&self, event: &ConnectionEvent, cx: &mut AsyncApp) -> anyhow::Result<()> {
+        let ConnectionEvent::MessageCaptured { message, is_incoming: true } = event else {
+            return Ok(());
+        };
+        let frame = serde_json::from_str(&message.raw_json).unwrap_or_default();
+        if let Some(reply) = reply_to_agent_request(&frame) {
+            self.connection.update(cx, |conn, cx| conn.send_response(reply, cx))??;
+        }
+        Ok(())
+    }
+
+    /// Stop the agent process and wait until it has been reaped, so that
+    /// nothing is left running when the command exits.
+    pub async fn disconnect(&self, cx: &mut AsyncApp) {
+        if let Ok(task) = self.connection.update(cx, |conn, cx| conn.disconnect_and_wait(cx)) {
+            task.await;
+        }
+    }
+}
+
+/// Resolve `task`, or fail once `timeout` elapses.
+pub async fn with_timeout<T>(
+    cx: &AsyncApp,
+    timeout: Duration,
+    task: impl Future<Output = T>,
+) -> anyhow::Result<T> {
+    let timer = cx.background_executor().timer(timeout);
+    futures::pin_mut!(task);
+    match future::select(task, timer).await {
+        Either::Left((value, _)) => Ok(value),
+        Either::Right(_) => Err(anyhow!("timed out after {}ms", timeout.as_millis())),
+    }
+}
+
+/// The headless client's answer to `frame` if it is a request: permission
+/// requests are cancelled and anything else is declined, as there is nobody
+/// to ask.
+fn reply_to_agent_request(frame: &Value) -> Option<Value> {
+    let id = frame.get("id")?;
+    let method = frame.get("method").and_then(Value::as_str)?;
+    let reply = if method == "session/request_permission" {
+        json!({ "jsonrpc": "2.0", "id": id, "result": { "outcome": { "outcome": "cancelled" } } })
+    } else {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": -32601, "message": format!("{} is not supported headless", method) },
+        })
+    };
+    Some(reply)
+}
+
+/// One wake-up of [`HeadlessAgent::drive`].
+enum DriveStep {
+    Finished(Result<Value, ClientError>),
+    Event(Instant, ConnectionEvent),
+    Closed,
+    Idle,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::reply_to_agent_request;
+
+    #[test]
+    fn test_reply_to_agent_request() {
+        let permission = json!({
+            "jsonrpc": "2.0",
+            "id": 4,
+            "method": "session/request_permission",
+            "params": { "sessionId": "s1", "options": [] },
+        });
+        let reply = reply_to_agent_request(&permission).unwrap();
+        assert_eq!(reply["id"], 4);
+        assert_eq!(reply["result"]["outcome"]["outcome"], "cancelled");
+
+        let read = json!({ "jsonrpc": "2.0", "id": "r1", "method": "fs/read_text_file" });
+        let reply = reply_to_agent_request(&read).unwrap();
+        assert_eq!((&reply["id"], &reply["error"]["code"]), (&json!("r1"), &json!(-32601)));
+
+        let update = json!({ "jsonrpc": "2.0", "method": "session/update", "params": {} });
+        assert!(reply_to_agent_request(&update).is_none());
+        assert!(reply_to_agent_request(&json!({ "id": 4, "result": {} })).is_none());
+    }
+}
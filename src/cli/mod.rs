@@ -0,0 +1,70 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Headless subcommands, so the debugger's logic can run without a display.
+
+mod args;
+mod commands;
+mod headless;
+mod scenario;
+mod validate;
+
+use std::io::Write as _;
+
+use gpui::Application;
+
+use crate::replay;
+
+pub use args::{parse_args, Command, USAGE};
+
+/// Run a headless command and exit the process with its status code.
+pub fn run(command: Command) -> ! {
+    match command {
+        Command::Help => {
+            print!("{}", USAGE);
+            std::process::exit(0);
+        }
+        Command::Replay { trace } => {
+            let code = match replay::replay_stdio(&trace) {
+                Ok(report) if report.divergences.is_empty() => 0,
+                Ok(report) => {
+                    eprintln!("replay finished with {} divergence(s)", report.divergences.len());
+                    1
+                }
+                Err(e) => {
+                    eprintln!("replay failed: {}", e);
+                    2
+                }
+            };
+            std::process::exit(code);
+        }
+        _ => {}
+    }
+
+    Application::headless().run(move |cx| {
+        cx.spawn(async move |cx| {
+            let code = match commands::execute(command, cx).await {
+                Ok(code) => code,
+                Err(e) => {
+                    eprintln!("error: {:#}", e);
+                    2
+                }
+            };
+            let _ = std::io::stdout().flush();
+            std::process::exit(code);
+        })
+        .detach();
+    });
+    std::process::exit(0)
+}
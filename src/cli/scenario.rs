@@ -0,0 +1,339 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Declarative scenario files run by `acp-debugger scenario`.
+//!
+//! A scenario is a list of steps. Each step performs one action and may
+//! assert on the response frame (`expect`) and on the notifications the agent
+//! sent while the step ran (`notifications`, matched in order). Assertions map
+//! a JSON pointer into the JSON-RPC frame to a matcher:
+//!
+//! ```toml
+//! [[steps]]
+//! prompt = "hello"
+//! expect = { "/result/stopReason" = "end_turn" }
+//!
+//! [[steps.notifications]]
+//! "/params/update/sessionUpdate" = "tool_call"
+//! "/params/update/kind" = { matches = "^read$" }
+//! ```
+
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::Context as _;
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{args::AgentArgs, validate::Check};
+
+/// Placeholder replaced with the id returned by the last `new_session` step.
+pub const SESSION_ID_VAR: &str = "${sessionId}";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub name: Option<String>,
+    pub agent: Option<ScenarioAgent>,
+    #[serde(default)]
+    pub steps: Vec<Step>,
+}
+
+/// The agent to launch when none is given on the command line.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioAgent {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Step {
+    #[serde(flatten)]
+    pub action: Action,
+    #[serde(default)]
+    pub expect: Assertions,
+    #[serde(default)]
+    pub notifications: Vec<Assertions>,
+}
+
+/// What a step does. Exactly one key per step.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// Assert on the `initialize` exchange performed while connecting.
+    Initialize {},
+    /// Send `session/new`; `cwd` defaults to the current directory.
+    NewSession { cwd: Option<String> },
+    /// Send a text prompt to the current session.
+    Prompt(String),
+    /// Send an arbitrary request.
+    Request {
+        method: String,
+        #[serde(default)]
+        params: Value,
+    },
+    /// Send a notification.
+    Notify {
+        method: String,
+        #[serde(default)]
+        params: Value,
+    },
+}
+
+impl Action {
+    pub fn label(&self) -> String {
+        match self {
+            Action::Initialize {} => "initialize".into(),
+            Action::NewSession { .. } => "session/new".into(),
+            Action::Prompt(text) => format!("session/prompt {:?}", text),
+            Action::Request { method, .. } => method.clone(),
+            Action::Notify { method, .. } => format!("notify {}", method),
+        }
+    }
+}
+
+/// JSON pointer to matcher.
+pub type Assertions = BTreeMap<String, Matcher>;
+
+/// How a pointed-to value is checked. A plain value means equality.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Matcher {
+    Matches { matches: String },
+    Exists { exists: bool },
+    Equals(Value),
+}
+
+impl Matcher {
+    /// Check `actual`, describing the mismatch on failure.
+    pub fn check(&self, actual: Option<&Value>) -> Result<(), String> {
+        match (self, actual) {
+            (Matcher::Exists { exists: true }, None) => Err("missing".into()),
+            (Matcher::Exists { exists: false }, Some(v)) => {
+                Err(format!("expected absent, got {}", v))
+            }
+            (Matcher::Exists { .. }, _) => Ok(()),
+            (_, None) => Err("missing".into()),
+            (Matcher::Equals(expected), Some(v)) if expected == v => Ok(()),
+            (Matcher::Equals(expected), Some(v)) => {
+                Err(format!("expected {}, got {}", expected, v))
+            }
+            (Matcher::Matches { matches }, Some(v)) => {
+                let regex = Regex::new(matches).map_err(|e| format!("invalid regex: {}", e))?;
+                // Match strings by content, anything else by its JSON text.
+                let text = v.as_str().map_or_else(|| v.to_string(), str::to_string);
+                if regex.is_match(&text) {
+                    Ok(())
+                } else {
+                    Err(format!("{} does not match /{}/", v, matches))
+                }
+            }
+        }
+    }
+}
+
+/// Check every assertion against `frame`, returning the first mismatch.
+pub fn evaluate(assertions: &Assertions, frame: &Value) -> Result<(), String> {
+    for (pointer, matcher) in assertions {
+        matcher.check(frame.pointer(pointer)).map_err(|e| format!("{}: {}", pointer, e))?;
+    }
+    Ok(())
+}
+
+/// Check that each expectation matches a notification, in order.
+pub fn evaluate_in_order(expectations: &[Assertions], notifications: &[Value]) -> Vec<Check> {
+    let mut remaining = notifications.iter();
+    expectations
+        .iter()
+        .enumerate()
+        .map(|(ix, assertions)| {
+            let name = format!("notification #{} {}", ix + 1, describe(assertions));
+            match remaining.find(|frame| evaluate(assertions, frame).is_ok()) {
+                Some(_) => Check::pass(name),
+                None => Check::fail(name, "no matching notification"),
+            }
+        })
+        .collect()
+}
+
+/// A short, single-line rendering of an assertion set.
+pub fn describe(assertions: &Assertions) -> String {
+    let parts: Vec<String> = assertions
+        .iter()
+        .map(|(pointer, matcher)| match matcher {
+            Matcher::Equals(v) => format!("{} == {}", pointer, v),
+            Matcher::Matches { matches } => format!("{} =~ /{}/", pointer, matches),
+            Matcher::Exists { exists: true } => format!("{} exists", pointer),
+            Matcher::Exists { exists: false } => format!("{} absent", pointer),
+        })
+        .collect();
+    parts.join(", ")
+}
+
+/// Replace `${sessionId}` in every string of `value`.
+pub fn substitute(value: &Value, session_id: Option<&str>) -> Value {
+    match value {
+        Value::String(s) => match session_id {
+            Some(id) => Value::String(s.replace(SESSION_ID_VAR, id)),
+            None => value.clone(),
+        },
+        Value::Array(items) => {
+            Value::Array(items.iter().map(|v| substitute(v, session_id)).collect())
+        }
+        Value::Object(map) => {
+            Value::Object(map.iter().map(|(k, v)| (k.clone(), substitute(v, session_id))).collect())
+        }
+        _ => value.clone(),
+    }
+}
+
+impl Scenario {
+    /// Parse a scenario, as TOML if `format_hint` ends in `.toml` and as JSON
+    /// otherwise.
+    pub fn parse(content: &str, format_hint: &str) -> anyhow::Result<Self> {
+        if format_hint.ends_with(".toml") {
+            Ok(toml::from_str(content)?)
+        } else {
+            Ok(serde_json::from_str(content)?)
+        }
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Self::parse(&content, &path.to_string_lossy())
+            .with_context(|| format!("invalid scenario {}", path.display()))
+    }
+
+    /// The agent declared in the file, launched with `cwd` for `session/new`.
+    pub fn agent_args(&self, cwd: Option<String>) -> anyhow::Result<AgentArgs> {
+        let agent = self.agent.as_ref().context("scenario has no [agent]; pass one after `--`")?;
+        Ok(AgentArgs {
+            endpoint: agent.command.clone(),
+            args: agent.args.clone(),
+            env: agent.env.clone().into_iter().collect(),
+            cwd,
+            timeout_ms: agent.timeout_ms.unwrap_or(30_000),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const SCENARIO: &str = r#"
+name = "read then answer"
+
+[agent]
+command = "my-agent"
+args = ["--acp"]
+
+[[steps]]
+initialize = {}
+expect = { "/result/protocolVersion" = 1 }
+
+[[steps]]
+new_session = { cwd = "/tmp" }
+expect = { "/result/sessionId" = { exists = true } }
+
+[[steps]]
+prompt = "hello"
+expect = { "/result/stopReason" = "end_turn" }
+
+[[steps.notifications]]
+"/params/update/sessionUpdate" = "tool_call"
+"/params/update/kind" = { matches = "^read$" }
+
+[[steps.notifications]]
+"/params/update/sessionUpdate" = "agent_message_chunk"
+"#;
+
+    #[test]
+    fn test_parse_toml_scenario() {
+        let scenario = Scenario::parse(SCENARIO, "flow.toml").unwrap();
+        assert_eq!(scenario.steps.len(), 3);
+        assert!(matches!(scenario.steps[0].action, Action::Initialize {}));
+        assert!(
+            matches!(&scenario.steps[1].action, Action::NewSession { cwd: Some(c) } if c == "/tmp")
+        );
+        assert!(matches!(&scenario.steps[2].action, Action::Prompt(t) if t == "hello"));
+        assert_eq!(scenario.steps[2].notifications.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_json_scenario() {
+        let scenario = Scenario::parse(
+            r#"{"steps": [{"request": {"method": "x/ping"}, "expect": {"/error/code": -32601}}]}"#,
+            "flow.json",
+        )
+        .unwrap();
+        assert!(
+            matches!(&scenario.steps[0].action, Action::Request { method, .. } if method == "x/ping")
+        );
+    }
+
+    #[test]
+    fn test_matchers() {
+        let frame = json!({"result": {"stopReason": "end_turn", "count": 3}});
+        let assertions = |value: Value| -> Assertions { serde_json::from_value(value).unwrap() };
+
+        assert!(evaluate(&assertions(json!({"/result/stopReason": "end_turn"})), &frame).is_ok());
+        assert!(evaluate(&assertions(json!({"/result/count": {"matches": "^[0-9]+$"}})), &frame)
+            .is_ok());
+        assert!(evaluate(&assertions(json!({"/error": {"exists": false}})), &frame).is_ok());
+
+        let err =
+            evaluate(&assertions(json!({"/result/stopReason": "cancelled"})), &frame).unwrap_err();
+        assert!(err.starts_with("/result/stopReason: expected"));
+        assert!(evaluate(&assertions(json!({"/result/missing": 1})), &frame).is_err());
+    }
+
+    #[test]
+    fn test_notifications_match_in_order() {
+        let notifications = vec![
+            json!({"params": {"update": {"sessionUpdate": "agent_message_chunk"}}}),
+            json!({"params": {"update": {"sessionUpdate": "tool_call", "kind": "read"}}}),
+        ];
+        let expect = |kind: &str| -> Assertions {
+            serde_json::from_value(json!({"/params/update/sessionUpdate": kind})).unwrap()
+        };
+
+        let checks = evaluate_in_order(
+            &[expect("agent_message_chunk"), expect("tool_call")],
+            &notifications,
+        );
+        assert!(checks.iter().all(|c| c.passed));
+
+        let checks = evaluate_in_order(
+            &[expect("tool_call"), expect("agent_message_chunk")],
+            &notifications,
+        );
+        assert!(checks[0].passed);
+        assert!(!checks[1].passed);
+    }
+
+    #[test]
+    fn test_substitute_session_id() {
+        let params = json!({"sessionId": "${sessionId}", "nested": ["x-${sessionId}"]});
+        assert_eq!(substitute(&params, Some("s1")), json!({"sessionId": "s1", "nested": ["x-s1"]}));
+    }
+}
@@ -0,0 +1,211 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Protocol conformance checks run by `acp-debugger validate`.
+
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::models::AcpMessage;
+
+/// `sessionUpdate` kinds defined by the ACP schema.
+pub const SESSION_UPDATE_KINDS: &[&str] = &[
+    "user_message_chunk",
+    "agent_message_chunk",
+    "agent_thought_chunk",
+    "tool_call",
+    "tool_call_update",
+    "plan",
+    "available_commands_update",
+    "current_mode_update",
+];
+
+/// Stop reasons allowed in a `session/prompt` response.
+pub const STOP_REASONS: &[&str] =
+    &["end_turn", "max_tokens", "max_turn_requests", "refusal", "cancelled"];
+
+/// Outcome of a single conformance check.
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: String,
+    pub passed: bool,
+    pub detail: Option<String>,
+}
+
+impl Check {
+    pub fn pass(name: impl Into<String>) -> Self {
+        Self { name: name.into(), passed: true, detail: None }
+    }
+
+    pub fn fail(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self { name: name.into(), passed: false, detail: Some(detail.into()) }
+    }
+
+    fn expect(name: impl Into<String>, result: Result<(), String>) -> Self {
+        match result {
+            Ok(()) => Self::pass(name),
+            Err(detail) => Self::fail(name, detail),
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mark = if self.passed { "PASS" } else { "FAIL" };
+        write!(f, "[{}] {}", mark, self.name)?;
+        if let Some(detail) = &self.detail {
+            write!(f, ": {}", detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check the shape of an `initialize` result.
+pub fn check_initialize(result: &Value) -> Vec<Check> {
+    vec![
+        Check::expect(
+            "initialize returns an integer protocolVersion",
+            match result.get("protocolVersion") {
+                Some(v) if v.is_u64() => Ok(()),
+                Some(v) => Err(format!("got {}", v)),
+                None => Err("missing".into()),
+            },
+        ),
+        Check::expect(
+            "agentCapabilities is an object",
+            match result.get("agentCapabilities") {
+                None => Ok(()),
+                Some(v) if v.is_object() => Ok(()),
+                Some(v) => Err(format!("got {}", v)),
+            },
+        ),
+        Check::expect(
+            "authMethods is an array",
+            match result.get("authMethods") {
+                None => Ok(()),
+                Some(v) if v.is_array() => Ok(()),
+                Some(v) => Err(format!("got {}", v)),
+            },
+        ),
+    ]
+}
+
+/// Check the shape of a `session/prompt` result.
+pub fn check_prompt_result(result: &Value) -> Check {
+    Check::expect(
+        "session/prompt returns a valid stopReason",
+        match result.get("stopReason").and_then(|v| v.as_str()) {
+            Some(reason) if STOP_REASONS.contains(&reason) => Ok(()),
+            Some(reason) => Err(format!("unknown stopReason `{}`", reason)),
+            None => Err("missing stopReason".into()),
+        },
+    )
+}
+
+/// Check every message the agent sent during the run.
+pub fn check_incoming(messages: &[AcpMessage], session_id: Option<&str>) -> Vec<Check> {
+    let mut version_errors = Vec::new();
+    let mut error_shape_errors = Vec::new();
+    let mut update_errors = Vec::new();
+
+    for message in messages {
+        if message.jsonrpc_version.as_ref().map(|v| v.as_ref()) != Some("2.0") {
+            version_errors
+                .push(format!("`{}` has jsonrpc {:?}", message.title, message.jsonrpc_version));
+        }
+
+        let parsed: Value = serde_json::from_str(&message.raw_json).unwrap_or(Value::Null);
+        if let Some(error) = parsed.get("error") {
+            if message.error.is_none() {
+                error_shape_errors.push(format!("malformed error object {}", error));
+            }
+        }
+
+        if message.method.as_ref().map(|m| m.as_ref()) == Some("session/update") {
+            let params = message.params.as_ref().unwrap_or(&Value::Null);
+            match (session_id, params.get("sessionId").and_then(|v| v.as_str())) {
+                (_, None) => update_errors.push("session/update without sessionId".to_string()),
+                (Some(expected), Some(actual)) if expected != actual => {
+                    update_errors.push(format!("session/update for unknown session `{}`", actual))
+                }
+                _ => {}
+            }
+            match params.pointer("/update/sessionUpdate").and_then(|v| v.as_str()) {
+                Some(kind) if SESSION_UPDATE_KINDS.contains(&kind) => {}
+                Some(kind) => update_errors.push(format!("unknown sessionUpdate `{}`", kind)),
+                None => update_errors.push("session/update without update.sessionUpdate".into()),
+            }
+        }
+    }
+
+    let summarize = |errors: Vec<String>| match errors.as_slice() {
+        [] => Ok(()),
+        [only] => Err(only.clone()),
+        [first, rest @ ..] => Err(format!("{} (and {} more)", first, rest.len())),
+    };
+
+    vec![
+        Check::expect("every message declares jsonrpc \"2.0\"", summarize(version_errors)),
+        Check::expect(
+            "error responses carry integer code and message",
+            summarize(error_shape_errors),
+        ),
+        Check::expect("session/update notifications are well-formed", summarize(update_errors)),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_initialize() {
+        let checks = check_initialize(&serde_json::json!({
+            "protocolVersion": 1,
+            "agentCapabilities": {"loadSession": true},
+        }));
+        assert!(checks.iter().all(|c| c.passed));
+
+        let checks = check_initialize(&serde_json::json!({"protocolVersion": "1"}));
+        assert!(!checks[0].passed);
+    }
+
+    #[test]
+    fn test_check_prompt_result() {
+        assert!(check_prompt_result(&serde_json::json!({"stopReason": "end_turn"})).passed);
+        assert!(!check_prompt_result(&serde_json::json!({"stopReason": "done"})).passed);
+    }
+
+    #[test]
+    fn test_check_incoming_session_updates() {
+        let messages = vec![
+            AcpMessage::parse(
+                "session/update",
+                r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"plan","entries":[]}}}"#,
+                true,
+            ),
+            AcpMessage::parse(
+                "session/update",
+                r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s2","update":{"sessionUpdate":"bogus"}}}"#,
+                true,
+            ),
+        ];
+        let checks = check_incoming(&messages, Some("s1"));
+        assert!(checks[0].passed);
+        assert!(checks[1].passed);
+        assert!(!checks[2].passed);
+        assert!(checks[2].detail.as_deref().unwrap().contains("and 1 more"));
+    }
+}
@@ -12,17 +12,24 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
 
 use futures::{channel::oneshot, StreamExt};
 use gpui::*;
 use serde_json::Value;
 
-use crate::models::{AcpMessage, AgentConfig, AgentStatus};
+use crate::models::{
+    read_tree, secrets, AcpMessage, AgentConfig, AgentStatus, JsonPredicate, McpServer, QueueItem,
+    QueueItemStatus, ResourceHistory,
+};
 
 use super::{
     error::ClientError,
-    transport::{StdioTransport, TransportMessage},
+    intercept::{FrameDirection, Interceptor},
+    transport::{ProcessExit, StdioTransport, TransportMessage, SHUTDOWN_GRACE},
 };
 
 /// Events emitted by AcpConnection for UI subscribers.
@@ -31,17 +38,71 @@ pub enum ConnectionEvent {
     /// Connection state changed.
     StateChanged(AgentStatus),
 
+    /// A new connection was started by the user; history of earlier runs
+    /// was discarded. Automatic restarts keep it.
+    Reset,
+
     /// A raw JSON-RPC message was sent or received (for debugger UI).
     MessageCaptured { message: Box<AcpMessage>, is_incoming: bool },
 
+    /// The agent's resource use was sampled again.
+    ResourcesSampled,
+
     /// A session notification received from the agent.
     SessionNotification { session_id: String, method: String, params: Value },
+
+    /// The agent process went away without being asked to.
+    ProcessExited(ProcessExit),
+
+    /// The agent will be restarted after `delay`.
+    Restarting { attempt: u32, max_attempts: u32, delay: Duration },
+
+    /// The agent kept exiting and restarts were given up.
+    RestartFailed { attempts: u32 },
+
+    /// After a restart, the previous session was loaded again, or replaced
+    /// by a new one when the agent can't load sessions.
+    SessionRestored { previous: String, session_id: String, loaded: bool },
+}
+
+/// Agents rarely log this much; older lines are dropped past the limit.
+const MAX_STDERR_LINES: usize = 10_000;
+
+/// A restart counts as successful once the agent stays up this long.
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+
+/// How often the agent's resource use is sampled.
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long to wait for the process to be reaped after stdout closed.
+const EXIT_WAIT: Duration = Duration::from_secs(1);
+
+/// Shutdown grace per step when the app quits, which only waits briefly
+/// for quit handlers.
+const QUIT_GRACE: Duration = Duration::from_millis(30);
+
+/// A line the agent wrote to stderr.
+#[derive(Debug, Clone)]
+pub struct StderrLine {
+    pub timestamp: Instant,
+    pub line: String,
+}
+
+/// The last session created or loaded, restored after a restart.
+#[derive(Debug, Clone)]
+struct LastSession {
+    id: String,
+    cwd: String,
+    /// As configured, with secret references left in.
+    mcp_servers: Vec<McpServer>,
 }
 
 struct PendingRequest {
-    #[allow(dead_code)]
     method: String,
     sender: oneshot::Sender<Result<Value, ClientError>>,
+    /// The MCP servers of a `session/new` or `session/load` before their
+    /// references were resolved.
+    mcp_servers: Option<Vec<McpServer>>,
 }
 
 /// GPUI Entity managing the ACP client connection lifecycle.
@@ -55,21 +116,65 @@ pub struct AcpConnection {
     transport: Option<StdioTransport>,
     next_request_id: u64,
     pending_requests: HashMap<u64, PendingRequest>,
+    initialize_result: Option<Value>,
+    interceptor: Interceptor,
+    /// Every request sent on this connection, oldest first.
+    queue: Vec<QueueItem>,
+    stderr: Vec<StderrLine>,
+    resources: ResourceHistory,
+    last_exit: Option<ProcessExit>,
+    /// Processes the last shutdown left running.
+    shutdown_warning: Option<String>,
+    last_session: Option<LastSession>,
+    /// Secret values sent to the agent, masked in everything captured.
+    redactions: Vec<String>,
+    connected_at: Option<Instant>,
+    /// Restarts in a row that did not stay up for [`STABLE_AFTER`].
+    restart_attempt: u32,
+    /// Set while a restart is waiting out its backoff.
+    restart_pending: bool,
+    /// Set from a restart until its handshake completes.
+    restarted: bool,
+    /// Set when the config was edited in a way the running process only
+    /// picks up when started again.
+    restart_to_apply: bool,
     _message_pump_task: Option<Task<()>>,
+    _restart_task: Option<Task<()>>,
+    _sampler_task: Option<Task<()>>,
+    _quit_subscription: Subscription,
 }
 
 impl EventEmitter<ConnectionEvent> for AcpConnection {}
 
 impl AcpConnection {
     /// Create a new disconnected connection.
-    pub fn new(_cx: &mut Context<Self>) -> Self {
+    pub fn new(cx: &mut Context<Self>) -> Self {
+        // Stop the agent politely rather than leave it to `Drop`'s SIGKILL.
+        let quit_subscription = cx.on_app_quit(|conn, cx| conn.stop(QUIT_GRACE, cx));
         Self {
             status: AgentStatus::Disconnected,
             config: None,
             transport: None,
             next_request_id: 0,
             pending_requests: HashMap::new(),
+            initialize_result: None,
+            interceptor: Interceptor::default(),
+            queue: Vec::new(),
+            stderr: Vec::new(),
+            resources: ResourceHistory::default(),
+            last_exit: None,
+            shutdown_warning: None,
+            last_session: None,
+            redactions: Vec::new(),
+            connected_at: None,
+            restart_attempt: 0,
+            restart_pending: false,
+            restarted: false,
+            restart_to_apply: false,
             _message_pump_task: None,
+            _restart_task: None,
+            _sampler_task: None,
+            _quit_subscription: quit_subscription,
         }
     }
 
@@ -78,6 +183,70 @@ impl AcpConnection {
         self.status
     }
 
+    /// How the agent process last exited on its own.
+    pub fn last_exit(&self) -> Option<ProcessExit> {
+        self.last_exit
+    }
+
+    /// A short note on a pending restart or why the agent went away.
+    pub fn status_detail(&self) -> Option<String> {
+        if self.restart_pending {
+            let max_attempts = self.config.as_ref().map_or(0, |c| c.restart.max_attempts);
+            return Some(format!("restarting, attempt {}/{}", self.restart_attempt, max_attempts));
+        }
+        match self.status {
+            AgentStatus::Disconnected | AgentStatus::Error => {
+                let exit = self.last_exit.map(|exit| exit.to_string());
+                let notes: Vec<String> =
+                    exit.into_iter().chain(self.shutdown_warning.clone()).collect();
+                (!notes.is_empty()).then(|| notes.join("; "))
+            }
+            AgentStatus::Connected | AgentStatus::Connecting if self.restart_to_apply => {
+                Some("restart to apply changes".into())
+            }
+            _ => None,
+        }
+    }
+
+    /// The config of the running agent.
+    pub fn config(&self) -> Option<&AgentConfig> {
+        self.config.as_ref()
+    }
+
+    /// Whether the running process predates an edit to its config.
+    pub fn restart_to_apply(&self) -> bool {
+        self.restart_to_apply
+    }
+
+    /// Take an edited config for the running agent. Settings read as needed,
+    /// like the restart policy, apply at once; the rest wait for the next
+    /// start, which [`Self::restart_to_apply`] reports.
+    pub fn update_config(&mut self, config: AgentConfig, cx: &mut Context<Self>) {
+        let Some(current) = self.config.as_mut() else {
+            return;
+        };
+        if *current == config {
+            return;
+        }
+        self.restart_to_apply |= !current.launches_like(&config);
+        *current = config;
+        cx.notify();
+    }
+
+    /// Stop the agent and start it again with its current config.
+    pub fn restart(&mut self, cx: &mut Context<Self>) {
+        if let Some(config) = self.config.clone() {
+            self.disconnect(cx);
+            self.connect(config, cx);
+        }
+    }
+
+    /// Result of the `initialize` handshake (protocol version, agent
+    /// capabilities, auth methods), once connected.
+    pub fn initialize_result(&self) -> Option<&Value> {
+        self.initialize_result.as_ref()
+    }
+
     /// Connect to an agent using the given config.
     ///
     /// Spawns the child process, starts the message pump,
@@ -88,40 +257,71 @@ impl AcpConnection {
         }
 
         self.set_status(AgentStatus::Connecting, cx);
-        self.config = Some(config.clone());
-
-        let mut transport = match StdioTransport::spawn(&config) {
-            Ok(t) => t,
-            Err(_) => {
-                self.set_status(AgentStatus::Error, cx);
-                return;
-            }
-        };
+        self.queue.clear();
+        self.stderr.clear();
+        self.resources = ResourceHistory::default();
+        self.last_exit = None;
+        self.shutdown_warning = None;
+        self.last_session = None;
+        self.redactions.clear();
+        self.restart_attempt = 0;
+        cx.emit(ConnectionEvent::Reset);
+        self.start(config, cx);
+    }
 
-        let incoming_rx = transport.take_incoming();
-        self.transport = Some(transport);
+    /// `text` with every secret the agent was given masked, for display.
+    pub fn redact(&self, text: &str) -> String {
+        secrets::redact(text, &self.redactions)
+    }
 
-        if let Some(incoming_rx) = incoming_rx {
-            self.start_message_pump(incoming_rx, cx);
+    /// Mask `values` in captured traffic from now on.
+    pub(super) fn add_redactions(&mut self, values: impl IntoIterator<Item = String>) {
+        for value in values {
+            if !self.redactions.contains(&value) {
+                self.redactions.push(value);
+            }
         }
+    }
 
-        self.send_initialize(cx);
+    /// The `authenticate` credentials of the running agent.
+    pub(super) fn credentials(&self) -> Option<Value> {
+        self.transport.as_ref().map(|transport| transport.auth().credentials())
     }
 
     /// Disconnect from the agent.
     pub fn disconnect(&mut self, cx: &mut Context<Self>) {
-        if let Some(transport) = self.transport.as_mut() {
-            transport.kill();
-        }
-        self.transport = None;
+        self.stop(SHUTDOWN_GRACE, cx).detach();
+    }
+
+    /// Disconnect, resolving once the agent process has been stopped and
+    /// reaped, for callers that exit right after.
+    pub fn disconnect_and_wait(&mut self, cx: &mut Context<Self>) -> Task<()> {
+        self.stop(SHUTDOWN_GRACE, cx)
+    }
+
+    fn stop(&mut self, grace: Duration, cx: &mut Context<Self>) -> Task<()> {
+        let shutdown = self.transport.take().map(|transport| self.shut_down(transport, grace, cx));
         self.config = None;
+        self.initialize_result = None;
+        self.connected_at = None;
+        self.last_session = None;
+        self.restart_pending = false;
+        self.restarted = false;
+        self.restart_to_apply = false;
         self._message_pump_task = None;
+        self._restart_task = None;
+        self._sampler_task = None;
+        self.interceptor.release_all();
 
         for (id, pending) in self.pending_requests.drain() {
             let _ = pending.sender.send(Err(ClientError::Cancelled(id)));
         }
+        for item in self.queue.iter_mut().filter(|item| item.is_open()) {
+            item.complete(None, Some("Disconnected".into()));
+        }
 
         self.set_status(AgentStatus::Disconnected, cx);
+        shutdown.unwrap_or_else(|| Task::ready(()))
     }
 
     /// Send a JSON-RPC request and return a Task resolving to the response.
@@ -130,6 +330,28 @@ impl AcpConnection {
         method: &str,
         params: Value,
         cx: &mut Context<Self>,
+    ) -> Task<Result<Value, ClientError>> {
+        self.send_tracked_request(method, params, None, cx)
+    }
+
+    /// Send a session request, remembering its unresolved MCP servers in
+    /// case the session has to be restored.
+    pub(super) fn send_session_request(
+        &mut self,
+        method: &str,
+        params: Value,
+        mcp_servers: Vec<McpServer>,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<Value, ClientError>> {
+        self.send_tracked_request(method, params, Some(mcp_servers), cx)
+    }
+
+    fn send_tracked_request(
+        &mut self,
+        method: &str,
+        params: Value,
+        mcp_servers: Option<Vec<McpServer>>,
+        cx: &mut Context<Self>,
     ) -> Task<Result<Value, ClientError>> {
         let id = self.next_id();
         let request = serde_json::json!({
@@ -138,27 +360,14 @@ impl AcpConnection {
             "method": method,
             "params": params,
         });
-        let json_str = serde_json::to_string(&request).unwrap();
-
-        // Capture outgoing message for debugger UI
-        let method_owned = method.to_string();
-        let acp_msg = AcpMessage::parse(&method_owned, &json_str, false);
-        cx.emit(ConnectionEvent::MessageCaptured {
-            message: Box::new(acp_msg),
-            is_incoming: false,
-        });
 
         let (tx, rx) = oneshot::channel();
-        self.pending_requests.insert(id, PendingRequest { method: method_owned, sender: tx });
+        self.pending_requests
+            .insert(id, PendingRequest { method: method.to_string(), sender: tx, mcp_servers });
 
-        if let Some(transport) = &self.transport {
-            if let Err(e) = transport.send(json_str) {
-                self.pending_requests.remove(&id);
-                return cx.spawn(async move |_, _| Err(e));
-            }
-        } else {
+        if let Err(e) = self.write_outgoing(request, false, cx) {
             self.pending_requests.remove(&id);
-            return cx.spawn(async move |_, _| Err(ClientError::NotConnected));
+            return cx.spawn(async move |_, _| Err(e));
         }
 
         cx.spawn(async move |_, _| rx.await.map_err(|_| ClientError::TransportClosed)?)
@@ -176,37 +385,261 @@ impl AcpConnection {
             "method": method,
             "params": params,
         });
-        let json_str = serde_json::to_string(&notification).unwrap();
+        self.write_outgoing(notification, false, cx)
+    }
 
-        let method_owned = method.to_string();
-        let acp_msg = AcpMessage::parse(&method_owned, &json_str, false);
-        cx.emit(ConnectionEvent::MessageCaptured {
-            message: Box::new(acp_msg),
-            is_incoming: false,
-        });
+    /// Answer a request the agent sent with a `result` or `error` frame.
+    pub fn send_response(
+        &mut self,
+        response: Value,
+        cx: &mut Context<Self>,
+    ) -> Result<(), ClientError> {
+        self.write_outgoing(response, false, cx)
+    }
 
-        if let Some(transport) = &self.transport {
-            transport.send(json_str)
-        } else {
-            Err(ClientError::NotConnected)
+    /// Send a hand-edited JSON-RPC frame to the agent as is.
+    ///
+    /// Requests keep their id; it is tracked so the agent's response is
+    /// correlated like any other. An id still awaiting a response is refused.
+    pub fn send_crafted(
+        &mut self,
+        frame: Value,
+        cx: &mut Context<Self>,
+    ) -> Result<(), ClientError> {
+        let method = frame.get("method").and_then(|v| v.as_str()).map(str::to_string);
+        let id = frame.get("id").and_then(|v| v.as_u64());
+        let tracked = match (method, id) {
+            (Some(method), Some(id)) => {
+                self.check_not_pending(id)?;
+                // Nobody awaits the result; the response only shows up in the capture.
+                let (tx, _) = oneshot::channel();
+                self.pending_requests
+                    .insert(id, PendingRequest { method, sender: tx, mcp_servers: None });
+                self.next_request_id = self.next_request_id.max(id.saturating_add(1));
+                Some(id)
+            }
+            _ => None,
+        };
+
+        let result = self.write_outgoing(frame, true, cx);
+        if let (Err(_), Some(id)) = (&result, tracked) {
+            self.pending_requests.remove(&id);
+        }
+        result
+    }
+
+    /// Handle a hand-written frame as if the agent had sent it.
+    ///
+    /// A response to a request still awaiting the agent's reply is refused,
+    /// so that it cannot be handed to that request's caller.
+    pub fn inject_incoming(
+        &mut self,
+        frame: Value,
+        cx: &mut Context<Self>,
+    ) -> Result<(), ClientError> {
+        let is_response = frame.get("result").is_some() || frame.get("error").is_some();
+        if let Some(id) = frame.get("id").and_then(|v| v.as_u64()).filter(|_| is_response) {
+            self.check_not_pending(id)?;
+        }
+        self.read_incoming(frame, true, cx);
+        Ok(())
+    }
+
+    /// Breakpoints and frames currently held by them.
+    pub fn interceptor(&self) -> &Interceptor {
+        &self.interceptor
+    }
+
+    pub fn add_breakpoint(
+        &mut self,
+        method: Option<String>,
+        direction: Option<FrameDirection>,
+        predicate: Option<JsonPredicate>,
+        cx: &mut Context<Self>,
+    ) {
+        self.interceptor.add_breakpoint(method, direction, predicate);
+        cx.notify();
+    }
+
+    pub fn remove_breakpoint(&mut self, id: u64, cx: &mut Context<Self>) {
+        self.interceptor.remove_breakpoint(id);
+        cx.notify();
+    }
+
+    pub fn set_breakpoint_enabled(&mut self, id: u64, enabled: bool, cx: &mut Context<Self>) {
+        self.interceptor.set_enabled(id, enabled);
+        cx.notify();
+    }
+
+    /// Let a held frame continue, optionally replaced by an edited frame.
+    pub fn forward_held(
+        &mut self,
+        id: u64,
+        edited: Option<Value>,
+        cx: &mut Context<Self>,
+    ) -> Result<(), ClientError> {
+        let Some(held) = self.interceptor.release(id) else {
+            return Ok(());
+        };
+        let crafted = held.crafted || edited.is_some();
+        let frame = edited.unwrap_or(held.frame);
+        cx.notify();
+        match held.direction {
+            FrameDirection::Outgoing => self.deliver_outgoing(frame, crafted, cx),
+            FrameDirection::Incoming => {
+                self.handle_incoming(frame, crafted, cx);
+                Ok(())
+            }
+        }
+    }
+
+    /// Forward every held frame in arrival order.
+    pub fn forward_all_held(&mut self, cx: &mut Context<Self>) -> Result<(), ClientError> {
+        let mut result = Ok(());
+        for held in self.interceptor.release_all() {
+            let delivered = match held.direction {
+                FrameDirection::Outgoing => self.deliver_outgoing(held.frame, held.crafted, cx),
+                FrameDirection::Incoming => {
+                    self.handle_incoming(held.frame, held.crafted, cx);
+                    Ok(())
+                }
+            };
+            result = result.and(delivered);
         }
+        cx.notify();
+        result
+    }
+
+    /// Discard a held frame. A dropped outgoing request fails locally.
+    pub fn drop_held(&mut self, id: u64, cx: &mut Context<Self>) {
+        let Some(held) = self.interceptor.release(id) else {
+            return;
+        };
+        if held.direction == FrameDirection::Outgoing && held.frame.get("method").is_some() {
+            if let Some(id) = held.frame.get("id").and_then(|v| v.as_u64()) {
+                if let Some(pending) = self.pending_requests.remove(&id) {
+                    let _ = pending.sender.send(Err(ClientError::Cancelled(id)));
+                }
+                self.complete_queue_item(id, None, Some("Dropped at breakpoint".into()));
+            }
+        }
+        cx.notify();
+    }
+
+    /// CPU, memory, thread and fd use of the agent's process tree.
+    pub fn resources(&self) -> &ResourceHistory {
+        &self.resources
+    }
+
+    /// Lines the agent wrote to stderr, oldest first.
+    pub fn stderr(&self) -> &[StderrLine] {
+        &self.stderr
+    }
+
+    /// Requests sent on this connection, oldest first.
+    pub fn queue(&self) -> &[QueueItem] {
+        &self.queue
+    }
+
+    /// Remove finished requests from the queue.
+    pub fn clear_completed(&mut self, cx: &mut Context<Self>) {
+        self.queue.retain(|item| item.is_open());
+        cx.notify();
+    }
+
+    /// Stop waiting for a request without telling the agent.
+    pub fn cancel_locally(&mut self, id: u64, cx: &mut Context<Self>) {
+        if let Some(pending) = self.pending_requests.remove(&id) {
+            let _ = pending.sender.send(Err(ClientError::Cancelled(id)));
+        }
+        // A request still held at a breakpoint never reaches the agent.
+        let held = self.interceptor.held().find(|h| {
+            h.direction == FrameDirection::Outgoing &&
+                h.frame.get("method").is_some() &&
+                h.frame.get("id").and_then(|v| v.as_u64()) == Some(id)
+        });
+        if let Some(held_id) = held.map(|h| h.id) {
+            self.interceptor.release(held_id);
+        }
+        self.complete_queue_item(id, None, Some("Cancelled locally".into()));
+        cx.notify();
+    }
+
+    /// Send the request with `id` again under a new id.
+    pub fn resend(&mut self, id: u64, cx: &mut Context<Self>) -> Task<Result<Value, ClientError>> {
+        let request = self
+            .queue
+            .iter()
+            .rev()
+            .find(|item| item.id == id)
+            .and_then(|item| serde_json::from_str::<Value>(&item.request_json).ok());
+        let Some(request) = request else {
+            return Task::ready(Err(ClientError::SendFailed(format!("no request with id {}", id))));
+        };
+        let method = request.get("method").and_then(|m| m.as_str()).unwrap_or_default().to_string();
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+        self.send_request(&method, params, cx)
     }
 }
 
 // Internal methods
 impl AcpConnection {
+    /// The next request id, skipping ids taken by crafted requests.
     fn next_id(&mut self) -> u64 {
+        while self.pending_requests.contains_key(&self.next_request_id) {
+            self.next_request_id = self.next_request_id.wrapping_add(1);
+        }
         let id = self.next_request_id;
-        self.next_request_id += 1;
+        self.next_request_id = self.next_request_id.wrapping_add(1);
         id
     }
 
+    fn check_not_pending(&self, id: u64) -> Result<(), ClientError> {
+        match self.pending_requests.get(&id) {
+            Some(pending) => Err(ClientError::SendFailed(format!(
+                "request id {} is still awaiting a response to {}",
+                id, pending.method
+            ))),
+            None => Ok(()),
+        }
+    }
+
     fn set_status(&mut self, status: AgentStatus, cx: &mut Context<Self>) {
         self.status = status;
         cx.emit(ConnectionEvent::StateChanged(status));
         cx.notify();
     }
 
+    /// Spawn the agent and start the handshake, keeping the history of
+    /// earlier runs.
+    fn start(&mut self, config: AgentConfig, cx: &mut Context<Self>) {
+        self.config = Some(config.clone());
+        self.restart_to_apply = false;
+        let mut transport = match StdioTransport::spawn(&config) {
+            Ok(t) => t,
+            Err(e) => {
+                log::error!("Failed to start {}: {}", config.name, e);
+                if std::mem::take(&mut self.restarted) {
+                    self.retry_or_give_up(cx);
+                } else {
+                    self.set_status(AgentStatus::Error, cx);
+                }
+                return;
+            }
+        };
+
+        let incoming_rx = transport.take_incoming();
+        self.add_redactions(transport.revealed().to_vec());
+        self.start_sampler(transport.pid(), cx);
+        self.transport = Some(transport);
+
+        if let Some(incoming_rx) = incoming_rx {
+            self.start_message_pump(incoming_rx, cx);
+        }
+
+        self.send_initialize(cx);
+    }
+
     /// Start the async message pump that reads from the transport
     /// incoming channel and dispatches messages.
     fn start_message_pump(
@@ -229,62 +662,368 @@ impl AcpConnection {
                 }
             }
 
-            // Transport closed: update status
-            if let Some(entity) = this.upgrade() {
-                let _ = cx.update_entity(&entity, |conn, cx| {
-                    if conn.status != AgentStatus::Disconnected {
-                        conn.set_status(AgentStatus::Disconnected, cx);
-                    }
+            // Transport closed: give the process a moment to be reaped.
+            let started = Instant::now();
+            let exit = loop {
+                let Ok(status) = this.update(cx, |conn, _| {
+                    conn.transport.as_mut().and_then(|transport| transport.try_wait())
+                }) else {
+                    return;
+                };
+                if status.is_some() || started.elapsed() >= EXIT_WAIT {
+                    break status.map(ProcessExit::from);
+                }
+                cx.background_executor().timer(Duration::from_millis(50)).await;
+            };
+            let _ = this.update(cx, |conn, cx| conn.handle_exit(exit, cx));
+        }));
+    }
+
+    /// Read the agent's process tree from `/proc` until it goes away.
+    fn start_sampler(&mut self, pid: u32, cx: &mut Context<Self>) {
+        self._sampler_task = Some(cx.spawn(async move |this, cx| loop {
+            let read = cx.background_executor().spawn(async move { read_tree(pid) });
+            let Ok(tree) = read.await else {
+                break;
+            };
+            let recorded = this.update(cx, |conn, cx| {
+                conn.resources.record(Instant::now(), tree);
+                // Only the timeline draws these; don't re-render every observer.
+                cx.emit(ConnectionEvent::ResourcesSampled);
+            });
+            if recorded.is_err() {
+                break;
+            }
+            cx.background_executor().timer(SAMPLE_INTERVAL).await;
+        }));
+    }
+
+    /// Stop the agent process off the UI thread and warn about processes it
+    /// left running. The returned task resolves once it has been reaped.
+    fn shut_down(
+        &mut self,
+        mut transport: StdioTransport,
+        grace: Duration,
+        cx: &mut Context<Self>,
+    ) -> Task<()> {
+        self.shutdown_warning = None;
+        let name = self.config.as_ref().map(|c| c.name.clone()).unwrap_or_default();
+        let (tx, rx) = oneshot::channel();
+        // Runs on its own thread, so it also makes progress while the app
+        // blocks on quit handlers.
+        let task = cx.background_executor().spawn({
+            let name = name.clone();
+            async move {
+                let report = transport.shutdown(grace);
+                log::info!("Agent {} {}", name, report);
+                let _ = tx.send(report);
+            }
+        });
+        cx.spawn(async move |this, cx| {
+            let Ok(report) = rx.await else {
+                return;
+            };
+            if let Some(warning) = report.leftover_warning() {
+                log::warn!("Agent {}: {}", name, warning);
+                let _ = this.update(cx, |conn, cx| {
+                    conn.shutdown_warning = Some(warning);
+                    cx.notify();
                 });
             }
+        })
+        .detach();
+        task
+    }
+
+    /// The agent went away on its own: record how, and restart it if the
+    /// policy says so.
+    fn handle_exit(&mut self, exit: Option<ProcessExit>, cx: &mut Context<Self>) {
+        if let Some(transport) = self.transport.take() {
+            // It may have closed stdout and still be running, and may have
+            // left children behind.
+            self.shut_down(transport, SHUTDOWN_GRACE, cx).detach();
+        }
+        self._sampler_task = None;
+        self.initialize_result = None;
+        self.restarted = false;
+        for (_, pending) in self.pending_requests.drain() {
+            let _ = pending.sender.send(Err(ClientError::TransportClosed));
+        }
+        for item in self.queue.iter_mut().filter(|item| item.is_open()) {
+            item.complete(None, Some("Agent exited".into()));
+        }
+
+        let name = self.config.as_ref().map(|c| c.name.clone()).unwrap_or_default();
+        match exit {
+            Some(exit) => {
+                log::warn!("Agent {} {}", name, exit);
+                cx.emit(ConnectionEvent::ProcessExited(exit));
+            }
+            None => log::warn!("Agent {} closed stdout", name),
+        }
+        self.last_exit = exit;
+
+        let stable = self.connected_at.take().is_some_and(|at| at.elapsed() >= STABLE_AFTER);
+        if stable {
+            self.restart_attempt = 0;
+        }
+        let policy = self.config.as_ref().map(|c| c.restart.clone()).unwrap_or_default();
+        if policy.enabled {
+            self.retry_or_give_up(cx);
+        } else {
+            self.set_status(AgentStatus::Disconnected, cx);
+        }
+    }
+
+    /// Restart after an exit or a failed respawn while attempts remain.
+    fn retry_or_give_up(&mut self, cx: &mut Context<Self>) {
+        let policy = self.config.as_ref().map(|c| c.restart.clone()).unwrap_or_default();
+        if let Some(delay) = policy.next_restart(self.restart_attempt) {
+            self.schedule_restart(delay, cx);
+        } else {
+            let name = self.config.as_ref().map(|c| c.name.clone()).unwrap_or_default();
+            log::error!(
+                "Agent {} kept failing; giving up after {} restarts",
+                name,
+                policy.max_attempts
+            );
+            cx.emit(ConnectionEvent::RestartFailed { attempts: self.restart_attempt });
+            self.set_status(AgentStatus::Error, cx);
+        }
+    }
+
+    fn schedule_restart(&mut self, delay: Duration, cx: &mut Context<Self>) {
+        self.restart_attempt += 1;
+        self.restart_pending = true;
+        let max_attempts = self.config.as_ref().map_or(0, |c| c.restart.max_attempts);
+        cx.emit(ConnectionEvent::Restarting { attempt: self.restart_attempt, max_attempts, delay });
+        self.set_status(AgentStatus::Connecting, cx);
+
+        self._restart_task = Some(cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(delay).await;
+            let _ = this.update(cx, |conn, cx| {
+                conn.restart_pending = false;
+                conn.restarted = true;
+                if let Some(config) = conn.config.clone() {
+                    conn.start(config, cx);
+                }
+            });
         }));
     }
 
+    /// After a restart, load the previous session again if the agent can,
+    /// or start a new one in its cwd.
+    fn restore_session(&mut self, cx: &mut Context<Self>) {
+        let restore = self.config.as_ref().is_some_and(|c| c.restart.restore_session);
+        let Some(previous) = self.last_session.clone().filter(|_| restore) else {
+            return;
+        };
+        let loaded = self
+            .initialize_result
+            .as_ref()
+            .and_then(|result| result.pointer("/agentCapabilities/loadSession"))
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        let task = if loaded {
+            self.load_session(&previous.id, &previous.cwd, previous.mcp_servers.clone(), cx)
+        } else {
+            self.new_session(&previous.cwd, previous.mcp_servers.clone(), cx)
+        };
+        cx.spawn(async move |this, cx| match task.await {
+            Ok(session_id) => {
+                let _ = this.update(cx, |_, cx| {
+                    cx.emit(ConnectionEvent::SessionRestored {
+                        previous: previous.id,
+                        session_id,
+                        loaded,
+                    })
+                });
+            }
+            Err(e) => log::warn!("Failed to restore session {}: {}", previous.id, e),
+        })
+        .detach();
+    }
+
+    /// Remember the session a successful `session/new` or `session/load`
+    /// response refers to.
+    fn track_session(
+        &mut self,
+        id: u64,
+        method: &str,
+        mcp_servers: Option<Vec<McpServer>>,
+        response: &Value,
+    ) {
+        if response.get("error").is_some() || !matches!(method, "session/new" | "session/load") {
+            return;
+        }
+        let params = self
+            .queue
+            .iter()
+            .rev()
+            .find(|item| item.id == id)
+            .and_then(|item| serde_json::from_str::<Value>(&item.request_json).ok())
+            .and_then(|request| request.get("params").cloned())
+            .unwrap_or(Value::Null);
+        let session_id = response.pointer("/result/sessionId").or_else(|| params.get("sessionId"));
+        if let (Some(id), Some(cwd)) =
+            (session_id.and_then(Value::as_str), params.get("cwd").and_then(Value::as_str))
+        {
+            // Requests sent by hand carry the servers as they were sent.
+            let mcp_servers = mcp_servers.unwrap_or_else(|| {
+                params
+                    .get("mcpServers")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(McpServer::from_acp)
+                    .collect()
+            });
+            self.last_session =
+                Some(LastSession { id: id.to_string(), cwd: cwd.to_string(), mcp_servers });
+        }
+    }
+
+    /// Send a frame to the agent unless a breakpoint holds it.
+    fn write_outgoing(
+        &mut self,
+        frame: Value,
+        crafted: bool,
+        cx: &mut Context<Self>,
+    ) -> Result<(), ClientError> {
+        if self.transport.is_none() {
+            return Err(ClientError::NotConnected);
+        }
+        if let (Some(method), Some(id)) =
+            (frame.get("method").and_then(|v| v.as_str()), frame.get("id").and_then(|v| v.as_u64()))
+        {
+            let agent_id = self.config.as_ref().map(|c| c.id.clone()).unwrap_or_default();
+            let request_json = serde_json::to_string(&frame).unwrap();
+            self.queue.push(QueueItem::new(id, agent_id, method.to_string(), request_json));
+        }
+        match self.interceptor.intercept(FrameDirection::Outgoing, frame, crafted) {
+            Some((frame, crafted)) => self.deliver_outgoing(frame, crafted, cx),
+            None => {
+                cx.notify();
+                Ok(())
+            }
+        }
+    }
+
+    /// Capture a frame and write it to the transport.
+    fn deliver_outgoing(
+        &mut self,
+        frame: Value,
+        crafted: bool,
+        cx: &mut Context<Self>,
+    ) -> Result<(), ClientError> {
+        let json_str = serde_json::to_string(&frame).unwrap();
+        let title = frame.get("method").and_then(|v| v.as_str()).unwrap_or("response");
+
+        if frame.get("method").is_some() {
+            let id = frame.get("id").and_then(|v| v.as_u64());
+            let item = self
+                .queue
+                .iter_mut()
+                .rev()
+                .find(|item| Some(item.id) == id && item.status == QueueItemStatus::Pending);
+            if let Some(item) = item {
+                // Record what was actually sent, which may have been edited.
+                item.request_json = json_str.clone();
+                item.status = QueueItemStatus::InFlight;
+                cx.notify();
+            }
+        }
+
+        // Capture outgoing message for debugger UI
+        let acp_msg =
+            AcpMessage::parse(title.to_string(), self.redact(&json_str), false).crafted(crafted);
+        cx.emit(ConnectionEvent::MessageCaptured {
+            message: Box::new(acp_msg),
+            is_incoming: false,
+        });
+
+        match &self.transport {
+            Some(transport) => transport.send(json_str),
+            None => Err(ClientError::NotConnected),
+        }
+    }
+
+    /// Handle a frame from the agent unless a breakpoint holds it.
+    fn read_incoming(&mut self, frame: Value, crafted: bool, cx: &mut Context<Self>) {
+        match self.interceptor.intercept(FrameDirection::Incoming, frame, crafted) {
+            Some((frame, crafted)) => self.handle_incoming(frame, crafted, cx),
+            None => cx.notify(),
+        }
+    }
+
     /// Handle a single transport message.
     /// Returns true to continue pumping, false to stop.
     fn handle_transport_message(&mut self, msg: TransportMessage, cx: &mut Context<Self>) -> bool {
         match msg {
             TransportMessage::Json(value) => {
-                let raw = serde_json::to_string(&value).unwrap_or_default();
-                let title =
-                    value.get("method").and_then(|v| v.as_str()).unwrap_or("response").to_string();
-                let is_response = value.get("result").is_some() || value.get("error").is_some();
-
-                // Capture every incoming message for debugger
-                let acp_msg = AcpMessage::parse(&title, &raw, true);
-                cx.emit(ConnectionEvent::MessageCaptured {
-                    message: Box::new(acp_msg),
-                    is_incoming: true,
-                });
-
-                // Dispatch response to pending request
-                if is_response {
-                    if let Some(id) = value.get("id").and_then(|v| v.as_u64()) {
-                        self.handle_response(id, &value);
-                    } else {
-                        log::warn!(
-                            "Received JSON-RPC response with missing or non-u64 id: {}",
-                            raw
-                        );
-                    }
-                }
-
-                // Dispatch notification
-                if !is_response {
-                    if let Some(method) = value.get("method").and_then(|v| v.as_str()) {
-                        let params = value.get("params").cloned().unwrap_or(Value::Null);
-                        self.handle_notification(method, &params, cx);
-                    }
-                }
-
+                self.read_incoming(value, false, cx);
                 true
             }
             TransportMessage::ParseError { .. } => true,
+            TransportMessage::Stderr(line) => {
+                if self.stderr.len() >= MAX_STDERR_LINES {
+                    self.stderr.drain(..MAX_STDERR_LINES / 10);
+                }
+                self.stderr.push(StderrLine { timestamp: Instant::now(), line });
+                cx.notify();
+                true
+            }
             TransportMessage::Closed => false,
         }
     }
 
+    /// Capture and dispatch one message from the agent.
+    fn handle_incoming(&mut self, value: Value, crafted: bool, cx: &mut Context<Self>) {
+        let raw = serde_json::to_string(&value).unwrap_or_default();
+        let is_response = value.get("result").is_some() || value.get("error").is_some();
+        // Responses are titled after the request they answer.
+        let title = match value.get("method").and_then(|v| v.as_str()) {
+            Some(method) => method.to_string(),
+            None => value
+                .get("id")
+                .and_then(|v| v.as_u64())
+                .and_then(|id| self.pending_requests.get(&id))
+                .map_or_else(|| "response".to_string(), |p| p.method.clone()),
+        };
+
+        // Capture every incoming message for debugger
+        let acp_msg = AcpMessage::parse(&title, self.redact(&raw), true).crafted(crafted);
+        cx.emit(ConnectionEvent::MessageCaptured { message: Box::new(acp_msg), is_incoming: true });
+
+        // Dispatch response to pending request
+        if is_response {
+            if let Some(id) = value.get("id").and_then(|v| v.as_u64()) {
+                self.handle_response(id, &value);
+                cx.notify();
+            } else {
+                log::warn!("Received JSON-RPC response with missing or non-u64 id: {}", raw);
+            }
+        }
+
+        // Dispatch notification
+        if !is_response {
+            if let Some(method) = value.get("method").and_then(|v| v.as_str()) {
+                let params = value.get("params").cloned().unwrap_or(Value::Null);
+                self.handle_notification(method, &params, cx);
+            }
+        }
+    }
+
     fn handle_response(&mut self, id: u64, value: &Value) {
+        if let Some(pending) = self.pending_requests.get(&id) {
+            let (method, mcp_servers) = (pending.method.clone(), pending.mcp_servers.clone());
+            self.track_session(id, &method, mcp_servers, value);
+        }
+        let error = value.get("error").map(|error| {
+            error.get("message").and_then(|m| m.as_str()).unwrap_or("unknown error").to_string()
+        });
+        self.complete_queue_item(id, serde_json::to_string(value).ok(), error);
+
         if let Some(pending) = self.pending_requests.remove(&id) {
             if let Some(error) = value.get("error") {
                 let code = error.get("code").and_then(|c| c.as_i64()).unwrap_or(0);
@@ -303,6 +1042,14 @@ impl AcpConnection {
         }
     }
 
+    /// Finish the latest open queue entry for request `id`.
+    fn complete_queue_item(&mut self, id: u64, response: Option<String>, error: Option<String>) {
+        if let Some(item) = self.queue.iter_mut().rev().find(|item| item.id == id && item.is_open())
+        {
+            item.complete(response, error);
+        }
+    }
+
     fn handle_notification(&mut self, method: &str, params: &Value, cx: &mut Context<Self>) {
         if let Some(session_id) = params.get("sessionId").and_then(|v| v.as_str()) {
             cx.emit(ConnectionEvent::SessionNotification {
@@ -334,17 +1081,25 @@ impl AcpConnection {
         let task = self.send_request("initialize", params, cx);
 
         cx.spawn(async move |this, cx| match task.await {
-            Ok(_) => {
+            Ok(result) => {
                 if let Some(entity) = this.upgrade() {
                     let _ = cx.update_entity(&entity, |conn, cx| {
+                        conn.initialize_result = Some(result);
+                        conn.connected_at = Some(Instant::now());
                         conn.set_status(AgentStatus::Connected, cx);
+                        if std::mem::take(&mut conn.restarted) {
+                            conn.restore_session(cx);
+                        }
                     });
                 }
             }
             Err(_) => {
                 if let Some(entity) = this.upgrade() {
                     let _ = cx.update_entity(&entity, |conn, cx| {
-                        conn.set_status(AgentStatus::Error, cx);
+                        // A process that went away has already been handled.
+                        if conn.transport.is_some() {
+                            conn.set_status(AgentStatus::Error, cx);
+                        }
                     });
                 }
             }
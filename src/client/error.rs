@@ -20,6 +20,9 @@ pub enum ClientError {
     #[error("failed to spawn agent process: {0}")]
     SpawnFailed(#[source] std::io::Error),
 
+    #[error("cannot resolve secret: {0}")]
+    SecretUnresolved(String),
+
     #[error("transport closed")]
     TransportClosed,
 
@@ -0,0 +1,253 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The "Test Connection" health check.
+
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use futures::{
+    channel::mpsc,
+    future::{self, Either},
+    StreamExt,
+};
+use gpui::BackgroundExecutor;
+use serde_json::{json, Value};
+
+use crate::models::{resolve_endpoint, AcpMethod, AgentConfig, HealthReport, LaunchPlan};
+
+use super::transport::{
+    ProcessExit, ShutdownStep, StdioTransport, TransportMessage, SHUTDOWN_GRACE,
+};
+
+/// Lines of stderr kept in the report.
+const STDERR_TAIL: usize = 50;
+
+/// Spawn the agent, run `initialize` and a throwaway `session/new` in `cwd`,
+/// then shut it down, recording each step.
+pub async fn check_health(
+    config: &AgentConfig,
+    cwd: &str,
+    executor: &BackgroundExecutor,
+) -> HealthReport {
+    let mut report = HealthReport::new(&config.name);
+
+    // A login-shell lookup runs the shell.
+    let started = Instant::now();
+    let resolve = {
+        let config = config.clone();
+        executor.spawn(async move { resolve_endpoint(&config) })
+    };
+    let path = match resolve.await {
+        Ok(path) => path,
+        Err(detail) => {
+            report.record("Endpoint resolves", Err(detail), Some(started.elapsed()));
+            report.skip_until(None);
+            return report;
+        }
+    };
+    let detail = format!("{} ({})", path.display(), LaunchPlan::for_agent(config).command_line());
+    report.record("Endpoint resolves", Ok(Some(detail)), Some(started.elapsed()));
+
+    if let Err(detail) = check_executable(&path) {
+        report.record("Endpoint is executable", Err(detail), None);
+        report.skip_until(None);
+        return report;
+    }
+    report.record("Endpoint is executable", Ok(None), None);
+
+    let started = Instant::now();
+    let mut probe = match Probe::spawn(config, executor) {
+        Ok(probe) => probe,
+        Err(detail) => {
+            report.record("Process starts", Err(detail), None);
+            report.skip_until(None);
+            return report;
+        }
+    };
+    report.record("Process starts", Ok(None), Some(started.elapsed()));
+
+    let exchange = async {
+        let started = Instant::now();
+        let template = AcpMethod::find("initialize").map(|m| m.template(None, cwd));
+        let result = probe.request("initialize", template.unwrap_or_default()).await;
+        let detail = result.as_ref().map(|result| {
+            let version = result.get("protocolVersion").map(Value::to_string);
+            let name = result.pointer("/agentInfo/name").and_then(Value::as_str);
+            Some(match (version, name) {
+                (Some(version), Some(name)) => format!("protocol v{}, {}", version, name),
+                (Some(version), None) => format!("protocol v{}", version),
+                _ => "no protocolVersion in result".to_string(),
+            })
+        });
+        report.record("initialize", detail.map_err(Clone::clone), Some(started.elapsed()));
+        if result.is_err() {
+            return;
+        }
+
+        let started = Instant::now();
+        let result = probe
+            .request("session/new", json!({ "cwd": cwd, "mcpServers": [] }))
+            .await
+            .and_then(|result| match result.get("sessionId").and_then(Value::as_str) {
+                Some(id) => Ok(Some(format!("sessionId {}", id))),
+                None => Err("result has no sessionId".to_string()),
+            });
+        report.record("session/new", result, Some(started.elapsed()));
+    };
+    exchange.await;
+    report.skip_until(Some("Clean shutdown"));
+
+    let started = Instant::now();
+    let (status, shutdown, stderr) = probe.shut_down().await;
+    report.exit_status = status;
+    report.record("Clean shutdown", shutdown, Some(started.elapsed()));
+    report.stderr = stderr;
+    report
+}
+
+#[cfg(unix)]
+fn check_executable(path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    let mode = std::fs::metadata(path).map_err(|e| e.to_string())?.permissions().mode();
+    if mode & 0o111 == 0 {
+        return Err(format!("{} is not executable (mode {:o})", path.display(), mode & 0o777));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_executable(_: &Path) -> Result<(), String> {
+    Ok(())
+}
+
+/// The agent process under test.
+struct Probe<'a> {
+    transport: StdioTransport,
+    incoming: mpsc::UnboundedReceiver<TransportMessage>,
+    executor: &'a BackgroundExecutor,
+    timeout: Duration,
+    stderr: Vec<String>,
+    next_id: u64,
+}
+
+impl<'a> Probe<'a> {
+    fn spawn(config: &AgentConfig, executor: &'a BackgroundExecutor) -> Result<Self, String> {
+        let mut transport = StdioTransport::spawn(config).map_err(|e| e.to_string())?;
+        let incoming = transport.take_incoming().ok_or("transport has no incoming channel")?;
+        Ok(Self {
+            transport,
+            incoming,
+            executor,
+            timeout: Duration::from_millis(config.timeout_ms),
+            stderr: Vec::new(),
+            next_id: 1,
+        })
+    }
+
+    /// Send a request and wait for its result.
+    async fn request(&mut self, method: &str, params: Value) -> Result<Value, String> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        self.transport.send(request.to_string()).map_err(|e| e.to_string())?;
+
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let timer = self.executor.timer(deadline.saturating_duration_since(Instant::now()));
+            let message = match future::select(self.incoming.next(), timer).await {
+                Either::Left((Some(message), _)) => message,
+                Either::Left((None, _)) => return Err(self.exit_detail(method).await),
+                Either::Right(_) => {
+                    return Err(format!("no response within {} ms", self.timeout.as_millis()))
+                }
+            };
+            match message {
+                TransportMessage::Json(value) => match (value.get("method"), value.get("id")) {
+                    // Decline agent requests so it doesn't block on us.
+                    (Some(_), Some(request_id)) => {
+                        let reply = json!({
+                            "jsonrpc": "2.0",
+                            "id": request_id,
+                            "error": { "code": -32601, "message": "not supported during a health check" },
+                        });
+                        let _ = self.transport.send(reply.to_string());
+                    }
+                    (None, Some(response_id)) if response_id == &json!(id) => {
+                        if let Some(error) = value.get("error") {
+                            return Err(format!("error response: {}", error));
+                        }
+                        return Ok(value.get("result").cloned().unwrap_or(Value::Null));
+                    }
+                    _ => {}
+                },
+                TransportMessage::ParseError { line, .. } => {
+                    let preview: String = line.chars().take(80).collect();
+                    return Err(format!("wrote non-JSON to stdout: {}", preview));
+                }
+                TransportMessage::Stderr(line) => self.push_stderr(line),
+                TransportMessage::Closed => return Err(self.exit_detail(method).await),
+            }
+        }
+    }
+
+    fn push_stderr(&mut self, line: String) {
+        push_tail(&mut self.stderr, line);
+    }
+
+    /// Describe how the process went away during `method`.
+    async fn exit_detail(&mut self, method: &str) -> String {
+        // Give the process a moment to be reaped after closing stdout.
+        self.executor.timer(Duration::from_millis(100)).await;
+        match self.transport.try_wait() {
+            Some(status) => format!("agent {} during {}", status, method),
+            None => format!("agent closed stdout during {}", method),
+        }
+    }
+
+    /// Shut the process down the way the debugger does. Returns how it
+    /// exited on its own (if it did), whether the shutdown was clean, and
+    /// the stderr tail.
+    async fn shut_down(self) -> (Option<String>, Result<Option<String>, String>, Vec<String>) {
+        let mut transport = self.transport;
+        let exited = transport.try_wait().map(|status| ProcessExit::from(status).to_string());
+        let shutdown = self.executor.spawn(async move { transport.shutdown(SHUTDOWN_GRACE) }).await;
+
+        // Drain stderr written before the exit.
+        let (mut incoming, mut stderr) = (self.incoming, self.stderr);
+        while let Ok(message) = incoming.try_recv() {
+            if let TransportMessage::Stderr(line) = message {
+                push_tail(&mut stderr, line);
+            }
+        }
+
+        let detail = shutdown.to_string();
+        let result = if shutdown.step == ShutdownStep::Killed || !shutdown.leftovers.is_empty() {
+            Err(detail)
+        } else {
+            Ok(Some(detail))
+        };
+        (exited, result, stderr)
+    }
+}
+
+fn push_tail(lines: &mut Vec<String>, line: String) {
+    if lines.len() == STDERR_TAIL {
+        lines.remove(0);
+    }
+    lines.push(line);
+}
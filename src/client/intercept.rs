@@ -0,0 +1,239 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::VecDeque;
+
+use serde_json::Value;
+
+use crate::models::JsonPredicate;
+
+/// Which way a frame travels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameDirection {
+    /// Client → agent.
+    Outgoing,
+    /// Agent → client.
+    Incoming,
+}
+
+impl FrameDirection {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Outgoing => "\u{2193} Outgoing",
+            Self::Incoming => "\u{2191} Incoming",
+        }
+    }
+}
+
+/// A rule that pauses matching frames.
+///
+/// Every condition that is set must hold: the method (exact, or a prefix
+/// ending in `*`), the direction and the JSON-path predicate.
+#[derive(Debug, Clone)]
+pub struct Breakpoint {
+    pub id: u64,
+    pub enabled: bool,
+    pub method: Option<String>,
+    pub direction: Option<FrameDirection>,
+    pub predicate: Option<JsonPredicate>,
+}
+
+impl Breakpoint {
+    pub fn matches(&self, direction: FrameDirection, frame: &Value) -> bool {
+        if !self.enabled || self.direction.is_some_and(|d| d != direction) {
+            return false;
+        }
+        if let Some(pattern) = &self.method {
+            let method = frame.get("method").and_then(|m| m.as_str()).unwrap_or_default();
+            let matched = match pattern.strip_suffix('*') {
+                Some(prefix) => method.starts_with(prefix),
+                None => method == pattern,
+            };
+            if !matched {
+                return false;
+            }
+        }
+        self.predicate.as_ref().is_none_or(|p| p.matches(frame))
+    }
+
+    /// Short human-readable summary of the conditions.
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(direction) = self.direction {
+            parts.push(direction.label().to_string());
+        }
+        if let Some(method) = &self.method {
+            parts.push(method.clone());
+        }
+        if let Some(predicate) = &self.predicate {
+            parts.push(format!("where {}", predicate));
+        }
+        if parts.is_empty() {
+            "Every message".into()
+        } else {
+            parts.join(" ")
+        }
+    }
+}
+
+/// A frame paused by the interceptor.
+#[derive(Debug, Clone)]
+pub struct HeldFrame {
+    pub id: u64,
+    pub direction: FrameDirection,
+    pub frame: Value,
+    /// The breakpoint that paused the frame, or `None` when it is queued
+    /// behind an earlier held frame.
+    pub breakpoint: Option<u64>,
+    pub crafted: bool,
+}
+
+/// Pauses frames between the transport and the connection.
+///
+/// Once a frame is held, later frames in the same direction are queued
+/// behind it so that forwarding preserves the original order.
+#[derive(Debug, Default)]
+pub struct Interceptor {
+    breakpoints: Vec<Breakpoint>,
+    held: VecDeque<HeldFrame>,
+    next_id: u64,
+}
+
+impl Interceptor {
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    pub fn held(&self) -> impl Iterator<Item = &HeldFrame> {
+        self.held.iter()
+    }
+
+    pub fn held_count(&self) -> usize {
+        self.held.len()
+    }
+
+    pub fn add_breakpoint(
+        &mut self,
+        method: Option<String>,
+        direction: Option<FrameDirection>,
+        predicate: Option<JsonPredicate>,
+    ) -> u64 {
+        let id = self.next_id();
+        self.breakpoints.push(Breakpoint { id, enabled: true, method, direction, predicate });
+        id
+    }
+
+    pub fn remove_breakpoint(&mut self, id: u64) {
+        self.breakpoints.retain(|b| b.id != id);
+    }
+
+    pub fn set_enabled(&mut self, id: u64, enabled: bool) {
+        if let Some(breakpoint) = self.breakpoints.iter_mut().find(|b| b.id == id) {
+            breakpoint.enabled = enabled;
+        }
+    }
+
+    /// Hold `frame` if a breakpoint matches or its direction is already
+    /// paused. Gives the frame back when it may pass.
+    pub fn intercept(
+        &mut self,
+        direction: FrameDirection,
+        frame: Value,
+        crafted: bool,
+    ) -> Option<(Value, bool)> {
+        let breakpoint =
+            self.breakpoints.iter().find(|b| b.matches(direction, &frame)).map(|b| b.id);
+        let queued = self.held.iter().any(|h| h.direction == direction);
+        if breakpoint.is_none() && !queued {
+            return Some((frame, crafted));
+        }
+
+        let id = self.next_id();
+        self.held.push_back(HeldFrame { id, direction, frame, breakpoint, crafted });
+        None
+    }
+
+    /// Remove a held frame so it can be forwarded or dropped.
+    pub fn release(&mut self, id: u64) -> Option<HeldFrame> {
+        let ix = self.held.iter().position(|h| h.id == id)?;
+        self.held.remove(ix)
+    }
+
+    /// Remove every held frame, oldest first.
+    pub fn release_all(&mut self) -> Vec<HeldFrame> {
+        self.held.drain(..).collect()
+    }
+
+    fn next_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_breakpoint_matching() {
+        let mut interceptor = Interceptor::default();
+        let id = interceptor.add_breakpoint(
+            Some("session/*".into()),
+            Some(FrameDirection::Incoming),
+            Some(JsonPredicate::parse("params.update.sessionUpdate == tool_call").unwrap()),
+        );
+        let breakpoint = &interceptor.breakpoints()[0];
+
+        let tool_call = json!({"method": "session/update", "params": {"update": {
+            "sessionUpdate": "tool_call"
+        }}});
+        let chunk = json!({"method": "session/update", "params": {"update": {
+            "sessionUpdate": "agent_message_chunk"
+        }}});
+        assert!(breakpoint.matches(FrameDirection::Incoming, &tool_call));
+        assert!(!breakpoint.matches(FrameDirection::Outgoing, &tool_call));
+        assert!(!breakpoint.matches(FrameDirection::Incoming, &chunk));
+
+        interceptor.set_enabled(id, false);
+        assert!(!interceptor.breakpoints()[0].matches(FrameDirection::Incoming, &tool_call));
+    }
+
+    #[test]
+    fn test_held_frames_queue_in_order() {
+        let mut interceptor = Interceptor::default();
+        interceptor.add_breakpoint(Some("session/prompt".into()), None, None);
+
+        let prompt = json!({"id": 1, "method": "session/prompt"});
+        let cancel = json!({"method": "session/cancel"});
+        let update = json!({"method": "session/update"});
+
+        assert!(interceptor.intercept(FrameDirection::Outgoing, prompt, false).is_none());
+        // Queued behind the held prompt, not matched by the breakpoint.
+        assert!(interceptor.intercept(FrameDirection::Outgoing, cancel, false).is_none());
+        // The other direction is unaffected.
+        assert!(interceptor.intercept(FrameDirection::Incoming, update, false).is_some());
+
+        let held: Vec<_> = interceptor.held().map(|h| (h.breakpoint.is_some(), h.id)).collect();
+        assert_eq!(held.len(), 2);
+        assert!(held[0].0 && !held[1].0);
+
+        let first = interceptor.release(held[0].1).unwrap();
+        assert_eq!(first.frame["method"], "session/prompt");
+        assert_eq!(interceptor.release_all().len(), 1);
+        assert_eq!(interceptor.held_count(), 0);
+    }
+}
@@ -14,10 +14,17 @@
 
 mod connection;
 mod error;
+mod health;
+mod intercept;
 mod session;
 mod transport;
 
 #[allow(unused_imports)]
-pub use connection::{AcpConnection, ConnectionEvent};
+pub use connection::{AcpConnection, ConnectionEvent, StderrLine, SAMPLE_INTERVAL};
 #[allow(unused_imports)]
 pub use error::ClientError;
+pub use health::check_health;
+#[allow(unused_imports)]
+pub use intercept::{Breakpoint, FrameDirection, HeldFrame, Interceptor};
+#[allow(unused_imports)]
+pub use transport::{ProcessExit, StdioTransport, TransportMessage};
@@ -15,20 +15,28 @@
 use gpui::*;
 use serde_json::Value;
 
+use crate::models::McpServer;
+
 use super::{connection::AcpConnection, error::ClientError};
 
 /// Session management methods on AcpConnection.
 impl AcpConnection {
     /// Create a new session.
     ///
-    /// Sends `session/new` and returns the session ID.
+    /// Sends `session/new` with the given MCP servers, their secret
+    /// references resolved, and returns the session ID.
     pub fn new_session(
         &mut self,
         cwd: &str,
+        mcp_servers: Vec<McpServer>,
         cx: &mut Context<Self>,
     ) -> Task<Result<String, ClientError>> {
-        let params = serde_json::json!({ "cwd": cwd });
-        let task = self.send_request("session/new", params, cx);
+        let resolved = match self.resolve_servers(&mcp_servers) {
+            Ok(resolved) => resolved,
+            Err(e) => return Task::ready(Err(e)),
+        };
+        let params = serde_json::json!({ "cwd": cwd, "mcpServers": resolved });
+        let task = self.send_session_request("session/new", params, mcp_servers, cx);
 
         cx.spawn(async move |_, _| {
             let result = task.await?;
@@ -40,6 +48,40 @@ impl AcpConnection {
         })
     }
 
+    /// Load an earlier session, replayed by the agent as session updates.
+    ///
+    /// Sends `session/load` and returns the session ID once it is loaded.
+    pub fn load_session(
+        &mut self,
+        session_id: &str,
+        cwd: &str,
+        mcp_servers: Vec<McpServer>,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<String, ClientError>> {
+        let resolved = match self.resolve_servers(&mcp_servers) {
+            Ok(resolved) => resolved,
+            Err(e) => return Task::ready(Err(e)),
+        };
+        let params =
+            serde_json::json!({ "sessionId": session_id, "cwd": cwd, "mcpServers": resolved });
+        let task = self.send_session_request("session/load", params, mcp_servers, cx);
+        let session_id = session_id.to_string();
+        cx.spawn(async move |_, _| task.await.map(|_| session_id))
+    }
+
+    /// The `mcpServers` entries for `servers`, resolved only now so that
+    /// secrets stay out of saved presets; resolved secrets are masked.
+    fn resolve_servers(&mut self, servers: &[McpServer]) -> Result<Vec<Value>, ClientError> {
+        let mut revealed = Vec::new();
+        let resolved = servers
+            .iter()
+            .map(|server| server.resolve(&mut revealed).map(|server| server.to_acp()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(ClientError::SecretUnresolved)?;
+        self.add_redactions(revealed);
+        Ok(resolved)
+    }
+
     /// Send a prompt to an existing session.
     ///
     /// Session updates arrive as `ConnectionEvent::SessionNotification`.
@@ -62,13 +104,15 @@ impl AcpConnection {
         self.send_notification("session/cancel", params, cx)
     }
 
-    /// Send an authenticate request.
+    /// Send an authenticate request with the agent's resolved credential.
     pub fn authenticate(
         &mut self,
         method: &str,
-        credentials: Value,
         cx: &mut Context<Self>,
     ) -> Task<Result<Value, ClientError>> {
+        let Some(credentials) = self.credentials() else {
+            return Task::ready(Err(ClientError::NotConnected));
+        };
         let params = serde_json::json!({
             "method": method,
             "credentials": credentials,
@@ -13,19 +13,23 @@
 // limitations under the License.
 
 use std::{
+    fmt,
     io::{BufRead, BufReader, Write},
-    process::{Child, Command, Stdio},
+    process::{Child, ExitStatus, Stdio},
     thread::JoinHandle,
+    time::{Duration, Instant},
 };
 
 use futures::{channel::mpsc, StreamExt};
 use serde_json::Value;
 
-use crate::models::AgentConfig;
+use crate::models::{
+    is_running, read_tree, secrets, AgentConfig, AuthMethod, LaunchPlan, ProcessInfo,
+};
 
 use super::error::ClientError;
 
-/// A message received from the agent's stdout.
+/// A message received from the agent's stdout or stderr.
 #[derive(Debug)]
 pub enum TransportMessage {
     /// A successfully parsed JSON-RPC message.
@@ -34,37 +38,176 @@ pub enum TransportMessage {
     Closed,
     /// A line that failed to parse as JSON.
     ParseError { line: String, error: String },
+    /// A line the agent wrote to stderr.
+    Stderr(String),
+}
+
+/// How the agent process ended: its exit code, or the signal that
+/// terminated it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessExit {
+    pub code: Option<i32>,
+    pub signal: Option<i32>,
+}
+
+impl ProcessExit {
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+impl From<ExitStatus> for ProcessExit {
+    fn from(status: ExitStatus) -> Self {
+        #[cfg(unix)]
+        let signal = std::os::unix::process::ExitStatusExt::signal(&status);
+        #[cfg(not(unix))]
+        let signal = None;
+        Self { code: status.code(), signal }
+    }
+}
+
+impl fmt::Display for ProcessExit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.code, self.signal) {
+            (Some(code), _) => write!(f, "exited with code {}", code),
+            (None, Some(signal)) => match signal_name(signal) {
+                Some(name) => write!(f, "killed by signal {} ({})", signal, name),
+                None => write!(f, "killed by signal {}", signal),
+            },
+            (None, None) => write!(f, "exited"),
+        }
+    }
+}
+
+fn signal_name(signal: i32) -> Option<&'static str> {
+    Some(match signal {
+        1 => "SIGHUP",
+        2 => "SIGINT",
+        3 => "SIGQUIT",
+        6 => "SIGABRT",
+        9 => "SIGKILL",
+        11 => "SIGSEGV",
+        13 => "SIGPIPE",
+        15 => "SIGTERM",
+        _ => return None,
+    })
+}
+
+/// How long each shutdown step waits for the agent to exit.
+pub const SHUTDOWN_GRACE: Duration = Duration::from_secs(2);
+
+/// The shutdown step the agent exited after.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownStep {
+    /// It exited on its own once stdin was closed.
+    StdinClosed,
+    /// Its process group was sent SIGTERM.
+    Terminated,
+    /// Its process group had to be sent SIGKILL.
+    Killed,
+}
+
+/// The outcome of [`StdioTransport::shutdown`].
+#[derive(Debug, Clone)]
+pub struct ShutdownReport {
+    pub step: ShutdownStep,
+    pub exit: Option<ProcessExit>,
+    /// Descendants still running afterwards, having left the agent's
+    /// process group.
+    pub leftovers: Vec<ProcessInfo>,
+}
+
+impl ShutdownReport {
+    /// A warning about processes left running, if any.
+    pub fn leftover_warning(&self) -> Option<String> {
+        if self.leftovers.is_empty() {
+            return None;
+        }
+        let processes: Vec<String> =
+            self.leftovers.iter().map(|p| format!("{} {}", p.pid, p.name)).collect();
+        Some(format!(
+            "{} process{} left running: {}",
+            processes.len(),
+            if processes.len() == 1 { "" } else { "es" },
+            processes.join(", ")
+        ))
+    }
+}
+
+impl fmt::Display for ShutdownReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let step = match self.step {
+            ShutdownStep::StdinClosed => "after stdin was closed",
+            ShutdownStep::Terminated => "after SIGTERM",
+            ShutdownStep::Killed => "after SIGKILL",
+        };
+        match self.exit {
+            Some(exit) => write!(f, "{} {}", exit, step)?,
+            None => write!(f, "stopped {}", step)?,
+        }
+        if let Some(warning) = self.leftover_warning() {
+            write!(f, "; {}", warning)?;
+        }
+        Ok(())
+    }
 }
 
 /// Handle for a running agent subprocess with stdio transport.
 ///
-/// Manages the child process and two I/O threads (reader + writer)
-/// that communicate via channels.
+/// Manages the child process and three I/O threads (stdout reader,
+/// stderr reader and writer) that communicate via channels.
 pub struct StdioTransport {
     child: Child,
-    outgoing_tx: mpsc::UnboundedSender<String>,
+    /// The agent's auth method with its credential resolved.
+    auth: AuthMethod,
+    /// Secret and credential values handed to the agent.
+    revealed: Vec<String>,
+    /// `None` once stdin was closed.
+    outgoing_tx: Option<mpsc::UnboundedSender<Vec<u8>>>,
     incoming_rx: Option<mpsc::UnboundedReceiver<TransportMessage>>,
     _writer_handle: JoinHandle<()>,
     _reader_handle: JoinHandle<()>,
+    _stderr_handle: JoinHandle<()>,
 }
 
 impl StdioTransport {
-    /// Spawn the agent subprocess and start I/O threads.
+    /// Spawn the agent subprocess as its [`LaunchPlan`] says and start I/O
+    /// threads.
+    ///
+    /// Secret references in env values and the auth credential are resolved
+    /// here, so they never appear in the config. Secrets, the credential and
+    /// env values with credential-like names are masked in stderr.
     pub fn spawn(config: &AgentConfig) -> Result<Self, ClientError> {
-        let mut cmd = Command::new(&config.endpoint);
-        cmd.args(&config.args).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::null());
+        let mut cmd = LaunchPlan::for_agent(config).command();
+        cmd.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped());
+        // Lead a process group of its own, so that shutdown reaches whatever
+        // the agent spawns.
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
 
+        let mut revealed = Vec::new();
         for (key, value) in &config.env {
+            let value = secrets::resolve(value, &mut revealed)
+                .map_err(|e| ClientError::SecretUnresolved(format!("{}: {}", key, e)))?;
+            if secrets::is_credential_name(key) {
+                revealed.push(value.clone());
+            }
             cmd.env(key, value);
         }
+        let auth = config
+            .auth
+            .resolve(&mut revealed)
+            .map_err(|e| ClientError::SecretUnresolved(format!("credential: {}", e)))?;
+        revealed.extend(auth.credential().map(str::to_string));
 
         let mut child = cmd.spawn().map_err(ClientError::SpawnFailed)?;
 
         let stdin = child.stdin.take().expect("stdin was piped");
         let stdout = child.stdout.take().expect("stdout was piped");
+        let stderr = child.stderr.take().expect("stderr was piped");
 
         // Outgoing channel: main thread → writer thread → child stdin
-        let (outgoing_tx, outgoing_rx) = mpsc::unbounded::<String>();
+        let (outgoing_tx, outgoing_rx) = mpsc::unbounded::<Vec<u8>>();
 
         // Incoming channel: child stdout → reader thread → main thread
         let (incoming_tx, incoming_rx) = mpsc::unbounded::<TransportMessage>();
@@ -73,19 +216,37 @@ impl StdioTransport {
             writer_loop(stdin, outgoing_rx);
         });
 
+        let stderr_tx = incoming_tx.clone();
         let reader_handle = std::thread::spawn(move || {
             reader_loop(stdout, incoming_tx);
         });
 
+        let stderr_secrets = revealed.clone();
+        let stderr_handle = std::thread::spawn(move || {
+            stderr_loop(stderr, stderr_tx, stderr_secrets);
+        });
+
         Ok(Self {
             child,
-            outgoing_tx,
+            auth,
+            revealed,
+            outgoing_tx: Some(outgoing_tx),
             incoming_rx: Some(incoming_rx),
             _writer_handle: writer_handle,
             _reader_handle: reader_handle,
+            _stderr_handle: stderr_handle,
         })
     }
 
+    pub fn auth(&self) -> &AuthMethod {
+        &self.auth
+    }
+
+    /// Values that must be masked wherever the agent's traffic is shown.
+    pub fn revealed(&self) -> &[String] {
+        &self.revealed
+    }
+
     /// Take the incoming message receiver (can only be called once).
     pub fn take_incoming(&mut self) -> Option<mpsc::UnboundedReceiver<TransportMessage>> {
         self.incoming_rx.take()
@@ -93,20 +254,141 @@ impl StdioTransport {
 
     /// Send a JSON string to the agent's stdin (non-blocking).
     pub fn send(&self, json_line: String) -> Result<(), ClientError> {
-        self.outgoing_tx.unbounded_send(json_line).map_err(|_| ClientError::TransportClosed)
+        self.send_raw(json_line.into_bytes())
+    }
+
+    /// Send an arbitrary line of bytes, which need not be valid JSON or UTF-8.
+    pub fn send_raw(&self, line: Vec<u8>) -> Result<(), ClientError> {
+        let outgoing_tx = self.outgoing_tx.as_ref().ok_or(ClientError::TransportClosed)?;
+        outgoing_tx.unbounded_send(line).map_err(|_| ClientError::TransportClosed)
+    }
+
+    /// Close the agent's stdin once pending writes are flushed.
+    pub fn close_stdin(&mut self) {
+        self.outgoing_tx = None;
+    }
+
+    /// The process id of the agent.
+    pub fn pid(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// The exit status, if the child process has exited.
+    pub fn try_wait(&mut self) -> Option<ExitStatus> {
+        self.child.try_wait().ok().flatten()
     }
 
-    /// Kill the child process and clean up.
+    /// Kill the agent's process group at once and reap the agent.
     pub fn kill(&mut self) {
-        let _ = self.child.kill();
+        self.close_stdin();
+        if !signal_group(self.pid(), Signal::Kill) {
+            let _ = self.child.kill();
+        }
+        let _ = self.child.wait();
+    }
+
+    /// Stop the agent politely: close stdin and wait up to `grace`, then
+    /// SIGTERM its process group and wait again, then SIGKILL it, and reap.
+    ///
+    /// This blocks for up to twice `grace`; run it off the UI thread.
+    pub fn shutdown(&mut self, grace: Duration) -> ShutdownReport {
+        let pid = self.pid();
+        // Remember the descendants to spot any that escape the group.
+        let descendants: Vec<ProcessInfo> =
+            read_tree(pid).map(|tree| tree.into_iter().skip(1).collect()).unwrap_or_default();
+
+        self.close_stdin();
+        let mut step = ShutdownStep::StdinClosed;
+        let mut exited = self.wait_for_exit(grace);
+        if !exited {
+            step = ShutdownStep::Terminated;
+            signal_group(pid, Signal::Term);
+            exited = self.wait_for_exit(grace);
+        }
+        if exited && group_alive(pid) {
+            // The agent is gone but its children are not.
+            if step == ShutdownStep::StdinClosed {
+                signal_group(pid, Signal::Term);
+            }
+            wait_until(grace, || !group_alive(pid));
+        }
+        if !exited || group_alive(pid) {
+            if !exited {
+                step = ShutdownStep::Killed;
+            }
+            if !signal_group(pid, Signal::Kill) {
+                let _ = self.child.kill();
+            }
+        }
+        let exit = self.child.wait().ok().map(ProcessExit::from);
+
+        // Killed processes take a moment to disappear.
+        wait_until(Duration::from_millis(200), || !descendants.iter().any(|p| is_running(p.pid)));
+        let leftovers = descendants.into_iter().filter(|p| is_running(p.pid)).collect();
+        ShutdownReport { step, exit, leftovers }
+    }
+
+    /// Poll until the agent exits, for at most `timeout`.
+    fn wait_for_exit(&mut self, timeout: Duration) -> bool {
+        wait_until(timeout, || self.try_wait().is_some())
+    }
+}
+
+impl Drop for StdioTransport {
+    fn drop(&mut self) {
+        if self.try_wait().is_none() {
+            self.kill();
+        }
     }
 }
 
+fn wait_until(timeout: Duration, mut done: impl FnMut() -> bool) -> bool {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if done() {
+            return true;
+        }
+        if Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+enum Signal {
+    /// Only checks that the group exists.
+    Probe,
+    Term,
+    Kill,
+}
+
+/// Send `signal` to the process group led by `pgid`; false if there is none.
+#[cfg(unix)]
+fn signal_group(pgid: u32, signal: Signal) -> bool {
+    let signal = match signal {
+        Signal::Probe => 0,
+        Signal::Term => libc::SIGTERM,
+        Signal::Kill => libc::SIGKILL,
+    };
+    // SAFETY: kill(2) has no memory-safety requirements.
+    unsafe { libc::kill(-(pgid as libc::pid_t), signal) == 0 }
+}
+
+#[cfg(not(unix))]
+fn signal_group(_pgid: u32, _signal: Signal) -> bool {
+    false
+}
+
+fn group_alive(pgid: u32) -> bool {
+    signal_group(pgid, Signal::Probe)
+}
+
 /// Writer thread: consumes outgoing messages and writes to stdin.
-fn writer_loop(mut stdin: std::process::ChildStdin, mut rx: mpsc::UnboundedReceiver<String>) {
+fn writer_loop(mut stdin: std::process::ChildStdin, mut rx: mpsc::UnboundedReceiver<Vec<u8>>) {
     futures::executor::block_on(async {
-        while let Some(line) = rx.next().await {
-            if writeln!(stdin, "{}", line).is_err() {
+        while let Some(mut line) = rx.next().await {
+            line.push(b'\n');
+            if stdin.write_all(&line).is_err() {
                 break;
             }
             if stdin.flush().is_err() {
@@ -137,6 +419,24 @@ fn reader_loop(stdout: std::process::ChildStdout, tx: mpsc::UnboundedSender<Tran
     let _ = tx.unbounded_send(TransportMessage::Closed);
 }
 
+/// Stderr thread: forwards each line the agent logs.
+fn stderr_loop(
+    stderr: std::process::ChildStderr,
+    tx: mpsc::UnboundedSender<TransportMessage>,
+    secrets: Vec<String>,
+) {
+    let reader = BufReader::new(stderr);
+    for line in reader.split(b'\n') {
+        let Ok(line) = line else {
+            break;
+        };
+        let line = secrets::redact(String::from_utf8_lossy(&line).trim_end(), &secrets);
+        if tx.unbounded_send(TransportMessage::Stderr(line)).is_err() {
+            break;
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -155,4 +455,46 @@ mod tests {
         };
         assert!(matches!(err, TransportMessage::ParseError { .. }));
     }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_shutdown_cleans_up_process_group() {
+        let agent = |script: &str| AgentConfig {
+            id: "sh".into(),
+            name: "sh".into(),
+            endpoint: "sh".into(),
+            args: vec!["-c".into(), script.into()],
+            timeout_ms: 1000,
+            ..AgentConfig::default()
+        };
+        let grace = Duration::from_millis(500);
+
+        // Exits on EOF; the background child is terminated with the group.
+        let mut transport = StdioTransport::spawn(&agent("sleep 30 & read line")).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        let report = transport.shutdown(grace);
+        assert_eq!(report.step, ShutdownStep::StdinClosed);
+        assert!(report.leftovers.is_empty(), "{}", report);
+
+        // Ignores stdin and SIGTERM, and starts a child outside the group.
+        let mut transport =
+            StdioTransport::spawn(&agent("trap '' TERM; setsid sleep 30 & sleep 30")).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        let report = transport.shutdown(grace);
+        assert_eq!(report.step, ShutdownStep::Killed);
+        assert_eq!(report.leftovers.len(), 1, "{}", report);
+        for process in &report.leftovers {
+            // SAFETY: kill(2) has no memory-safety requirements.
+            unsafe { libc::kill(process.pid as libc::pid_t, libc::SIGKILL) };
+        }
+    }
+
+    #[test]
+    fn test_process_exit_display() {
+        let exit = |code, signal| ProcessExit { code, signal }.to_string();
+        assert_eq!(exit(Some(1), None), "exited with code 1");
+        assert_eq!(exit(None, Some(9)), "killed by signal 9 (SIGKILL)");
+        assert_eq!(exit(None, Some(64)), "killed by signal 64");
+        assert!(ProcessExit { code: Some(0), signal: None }.success());
+    }
 }
@@ -12,29 +12,46 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::time::{SystemTime, UNIX_EPOCH};
-
 use gpui::{prelude::*, *};
 use gpui_component::{
     button::{Button, ButtonVariants as _},
+    checkbox::Checkbox,
     h_flex,
     input::{Input, InputState},
     radio::RadioGroup,
-    v_flex, ActiveTheme as _, StyledExt as _, WindowExt as _,
+    v_flex, ActiveTheme as _, Disableable as _, IconName, Sizable as _, StyledExt as _,
+    WindowExt as _,
 };
 
-use crate::models::{AgentConfig, AgentRegistry, AuthMethod};
+use crate::{
+    components::HealthReportView,
+    models::{
+        generate_agent_id, login_shell, parse_env, parse_timeout, resolve_endpoint, secrets,
+        AgentConfig, AgentId, AgentRegistry, AuthMethod, LaunchOptions, LaunchPlan, RestartPolicy,
+        CLEAN_ENV_KEEP,
+    },
+};
 
 pub struct AgentForm {
     name_input: Entity<InputState>,
     endpoint_input: Entity<InputState>,
     args_input: Entity<InputState>,
     env_input: Entity<InputState>,
+    /// The env being edited; credential values are masked in the input.
+    saved_env: Vec<(String, String)>,
     auth_method_index: Option<usize>,
     auth_credential_input: Entity<InputState>,
     timeout_input: Entity<InputState>,
+    cwd_input: Entity<InputState>,
+    inherit_env: bool,
+    login_shell: bool,
+    /// The last login-shell lookup, for the endpoint it was run for.
+    shell_resolution: Option<(String, Result<String, String>)>,
+    restart: RestartPolicy,
     error_message: Option<SharedString>,
     registry: Entity<AgentRegistry>,
+    /// The agent being edited, or `None` when creating one.
+    editing: Option<AgentId>,
 }
 
 impl AgentForm {
@@ -43,103 +60,336 @@ impl AgentForm {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) -> Self {
-        let name_input = cx.new(|cx| InputState::new(window, cx).placeholder("Agent name"));
+        Self::build(registry, None, window, cx)
+    }
 
-        let endpoint_input =
-            cx.new(|cx| InputState::new(window, cx).placeholder("/path/to/agent or command"));
+    /// A form prefilled with `config` that saves changes back to it.
+    pub fn edit(
+        registry: Entity<AgentRegistry>,
+        config: &AgentConfig,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        Self::build(registry, Some(config), window, cx)
+    }
+
+    fn build(
+        registry: Entity<AgentRegistry>,
+        config: Option<&AgentConfig>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let value = |f: fn(&AgentConfig) -> String| config.map(f).unwrap_or_default();
 
+        let name = value(|c| c.name.clone());
+        let name_input =
+            cx.new(|cx| InputState::new(window, cx).placeholder("Agent name").default_value(name));
+
+        let endpoint = value(|c| c.endpoint.clone());
+        let endpoint_input = cx.new(|cx| {
+            InputState::new(window, cx)
+                .placeholder("/path/to/agent or command")
+                .default_value(endpoint)
+        });
+
+        let args = value(|c| c.args.join("\n"));
         let args_input = cx.new(|cx| {
             InputState::new(window, cx)
                 .placeholder("One argument per line")
                 .multi_line(true)
                 .auto_grow(2, 5)
+                .default_value(args)
         });
 
+        let saved_env = config.map(|c| c.env.clone()).unwrap_or_default();
+        let env = secrets::mask_env(&saved_env)
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join("\n");
         let env_input = cx.new(|cx| {
             InputState::new(window, cx)
                 .placeholder("KEY=VALUE (one per line)")
                 .multi_line(true)
                 .auto_grow(2, 5)
+                .default_value(env)
+        });
+
+        let (auth_method_index, credential) = match config.map(|c| &c.auth) {
+            Some(AuthMethod::ApiKey { key }) => (1, key.clone()),
+            Some(AuthMethod::Bearer { token }) => (2, token.clone()),
+            _ => (0, String::new()),
+        };
+        let auth_credential_input = cx.new(|cx| {
+            InputState::new(window, cx)
+                .placeholder("API key, Bearer token or ${secret:NAME}")
+                .masked(true)
+                .default_value(credential)
         });
 
-        let auth_credential_input =
-            cx.new(|cx| InputState::new(window, cx).placeholder("API key or Bearer token"));
+        let timeout = value(|c| c.timeout_ms.to_string());
+        let timeout_input =
+            cx.new(|cx| InputState::new(window, cx).placeholder("30000").default_value(timeout));
 
-        let timeout_input = cx.new(|cx| InputState::new(window, cx).placeholder("30000"));
+        let launch = config.map(|c| c.launch.clone()).unwrap_or_default();
+        let cwd = launch.cwd.clone().unwrap_or_default();
+        let cwd_input = cx.new(|cx| {
+            InputState::new(window, cx)
+                .placeholder("The debugger's working directory")
+                .default_value(cwd)
+        });
 
         Self {
             name_input,
             endpoint_input,
             args_input,
             env_input,
-            auth_method_index: Some(0),
+            saved_env,
+            auth_method_index: Some(auth_method_index),
             auth_credential_input,
             timeout_input,
+            cwd_input,
+            inherit_env: launch.inherit_env,
+            login_shell: launch.login_shell,
+            shell_resolution: None,
+            restart: config.map(|c| c.restart.clone()).unwrap_or_default(),
             error_message: None,
             registry,
+            editing: config.map(|c| c.id.clone()),
         }
     }
 
-    fn submit(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+    /// The env as entered, with masked values put back.
+    fn env(&self, cx: &App) -> Result<Vec<(String, String)>, String> {
+        let env = parse_env(&self.env_input.read(cx).value())?;
+        Ok(secrets::unmask_env(env, &self.saved_env))
+    }
+
+    fn fail(&mut self, message: impl Into<SharedString>, cx: &mut Context<Self>) {
+        self.error_message = Some(message.into());
+        cx.notify();
+    }
+
+    /// The config described by the form, or the first validation error.
+    fn config(&self, cx: &App) -> Result<AgentConfig, String> {
         let name = self.name_input.read(cx).value().trim().to_string();
         let endpoint = self.endpoint_input.read(cx).value().trim().to_string();
 
         if name.is_empty() {
-            self.error_message = Some("Name is required".into());
-            cx.notify();
-            return;
+            return Err("Name is required".into());
         }
         if endpoint.is_empty() {
-            self.error_message = Some("Endpoint is required".into());
-            cx.notify();
-            return;
+            return Err("Endpoint is required".into());
+        }
+
+        let args = self.args(cx);
+        let env = self.env(cx)?;
+        let launch = self.launch(cx);
+        if let Some(cwd) = launch.cwd().filter(|cwd| !cwd.is_dir()) {
+            return Err(format!("Working directory not found: {}", cwd.display()));
         }
 
-        let args: Vec<String> = self
-            .args_input
+        let credential = self.auth_credential_input.read(cx).value().trim().to_string();
+        let mut values = env.iter().map(|(_, v)| v).chain(Some(&credential));
+        values.try_for_each(|v| secrets::validate_references(v))?;
+        let auth = match self.auth_method_index {
+            Some(1) => AuthMethod::ApiKey { key: credential },
+            Some(2) => AuthMethod::Bearer { token: credential },
+            _ => AuthMethod::None,
+        };
+
+        let timeout_ms = parse_timeout(&self.timeout_input.read(cx).value())?;
+
+        let id = self.editing.clone().unwrap_or_else(generate_agent_id);
+        let restart = self.restart.clone();
+        // Presets are edited from the session form, so keep the saved ones.
+        let session_presets = self
+            .registry
+            .read(cx)
+            .agents()
+            .iter()
+            .find(|agent| agent.id == id)
+            .map(|agent| agent.session_presets.clone())
+            .unwrap_or_default();
+        Ok(AgentConfig {
+            id,
+            name,
+            endpoint,
+            args,
+            env,
+            auth,
+            timeout_ms,
+            restart,
+            launch,
+            session_presets,
+        })
+    }
+
+    fn args(&self, cx: &App) -> Vec<String> {
+        self.args_input
             .read(cx)
             .value()
             .lines()
             .map(|l| l.trim().to_string())
             .filter(|l| !l.is_empty())
-            .collect();
+            .collect()
+    }
 
-        let env: Vec<(String, String)> = self
-            .env_input
-            .read(cx)
-            .value()
-            .lines()
-            .filter_map(|line| {
-                let line = line.trim();
-                if line.is_empty() {
-                    return None;
-                }
-                let (k, v) = line.split_once('=')?;
-                Some((k.trim().to_string(), v.trim().to_string()))
+    fn launch(&self, cx: &App) -> LaunchOptions {
+        let cwd = self.cwd_input.read(cx).value().trim().to_string();
+        LaunchOptions {
+            cwd: (!cwd.is_empty()).then_some(cwd),
+            inherit_env: self.inherit_env,
+            login_shell: self.login_shell,
+        }
+    }
+
+    /// The launch-related parts of the form, without validating the rest.
+    fn launch_preview(&self, cx: &App) -> AgentConfig {
+        AgentConfig {
+            endpoint: self.endpoint_input.read(cx).value().trim().to_string(),
+            args: self.args(cx),
+            env: self.env(cx).unwrap_or_default(),
+            restart: self.restart.clone(),
+            launch: self.launch(cx),
+            ..AgentConfig::default()
+        }
+    }
+
+    /// Ask the login shell where the endpoint is; this runs the shell.
+    fn check_login_shell(&mut self, cx: &mut Context<Self>) {
+        let config = self.launch_preview(cx);
+        let endpoint = config.endpoint.clone();
+        let task = cx.background_executor().spawn(async move { resolve_endpoint(&config) });
+        cx.spawn(async move |this, cx| {
+            let result = task.await.map(|path| path.display().to_string());
+            this.update(cx, |this, cx| {
+                this.shell_resolution = Some((endpoint, result));
+                cx.notify();
             })
-            .collect();
+            .ok();
+        })
+        .detach();
+    }
 
-        let credential = self.auth_credential_input.read(cx).value().trim().to_string();
-        let auth = match self.auth_method_index {
-            Some(1) => AuthMethod::ApiKey { key: credential },
-            Some(2) => AuthMethod::Bearer { token: credential },
-            _ => AuthMethod::None,
-        };
+    fn browse_cwd(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let paths = cx.prompt_for_paths(PathPromptOptions {
+            files: false,
+            directories: true,
+            multiple: false,
+            prompt: Some("Working Directory".into()),
+        });
+        cx.spawn_in(window, async move |this, cx| {
+            let Ok(Ok(Some(paths))) = paths.await else {
+                return;
+            };
+            let Some(path) = paths.into_iter().next() else {
+                return;
+            };
+            this.update_in(cx, |this, window, cx| {
+                this.cwd_input.update(cx, |input, cx| {
+                    input.set_value(path.display().to_string(), window, cx)
+                });
+            })
+            .ok();
+        })
+        .detach();
+    }
 
-        let timeout_ms: u64 = self.timeout_input.read(cx).value().trim().parse().unwrap_or(30_000);
+    /// The effective command line and where the endpoint resolves.
+    fn render_launch_preview(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let config = self.launch_preview(cx);
+        let plan = LaunchPlan::for_agent(&config);
+        let resolution = if config.endpoint.is_empty() {
+            None
+        } else if config.launch.login_shell {
+            self.shell_resolution
+                .as_ref()
+                .filter(|(endpoint, _)| *endpoint == config.endpoint)
+                .map(|(_, result)| result.clone())
+        } else {
+            Some(resolve_endpoint(&config).map(|path| path.display().to_string()))
+        };
+        let env = match (config.launch.inherit_env, config.env.len()) {
+            (true, 0) => "Environment: inherited".to_string(),
+            (true, n) => format!("Environment: inherited, plus {} set here", n),
+            (false, n) => {
+                format!("Environment: clean ({}), plus {} set here", CLEAN_ENV_KEEP.join(", "), n)
+            }
+        };
+        let muted = cx.theme().muted_foreground;
 
-        let id = generate_agent_id();
+        v_flex()
+            .gap_1()
+            .p_2()
+            .rounded_md()
+            .bg(cx.theme().muted)
+            .text_xs()
+            .child(div().font_family("monospace").child(plan.command_line()))
+            .child(div().text_color(muted).child(env))
+            .child(
+                h_flex()
+                    .gap_2()
+                    .child(match resolution {
+                        Some(Ok(path)) => {
+                            div().text_color(muted).child(format!("Resolves to {}", path))
+                        }
+                        Some(Err(e)) => div().text_color(cx.theme().danger).child(e),
+                        None if config.launch.login_shell && !config.endpoint.is_empty() => {
+                            div().text_color(muted).child("Resolved by the login shell at launch")
+                        }
+                        None => div(),
+                    })
+                    .when(config.launch.login_shell && !config.endpoint.is_empty(), |el| {
+                        el.child(
+                            Button::new("check-login-shell")
+                                .xsmall()
+                                .ghost()
+                                .label("Check")
+                                .on_click(cx.listener(|this, _, _, cx| this.check_login_shell(cx))),
+                        )
+                    }),
+            )
+    }
 
-        let config = AgentConfig { id: id.clone(), name, endpoint, args, env, auth, timeout_ms };
+    fn submit(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let config = match self.config(cx) {
+            Ok(config) => config,
+            Err(e) => return self.fail(e, cx),
+        };
+        // A login shell is only asked on demand, as that runs the shell.
+        if !config.launch.login_shell {
+            if let Err(e) = resolve_endpoint(&config) {
+                return self.fail(format!("Endpoint not found: {}", e), cx);
+            }
+        }
 
-        self.registry.update(cx, |reg, _| {
-            reg.add_agent(config);
-            reg.set_active(Some(id));
+        let editing = self.editing.is_some();
+        self.registry.update(cx, |reg, cx| {
+            if editing {
+                reg.update_agent(config);
+            } else {
+                let id = config.id.clone();
+                reg.add_agent(config);
+                reg.set_active(Some(id));
+            }
+            cx.notify();
         });
 
         self.error_message = None;
         window.close_sheet(cx);
     }
+
+    /// Run a health check of the config as currently entered.
+    fn test_connection(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        match self.config(cx) {
+            Ok(config) => {
+                self.error_message = None;
+                HealthReportView::open(config, window, cx);
+            }
+            Err(e) => self.fail(e, cx),
+        }
+    }
 }
 
 impl Render for AgentForm {
@@ -180,7 +430,46 @@ impl Render for AgentForm {
                 v_flex()
                     .gap_1()
                     .child(field_label("Environment", false, cx))
-                    .child(Input::new(&self.env_input)),
+                    .child(Input::new(&self.env_input))
+                    .child(div().text_xs().text_color(cx.theme().muted_foreground).child(
+                        "Keep secrets out of the config with ${env:NAME} or ${secret:NAME}. \
+                         Masked values are kept unless replaced.",
+                    )),
+            )
+            // Launch
+            .child(
+                v_flex().gap_1().child(field_label("Working Directory", false, cx)).child(
+                    h_flex()
+                        .gap_2()
+                        .child(div().flex_1().child(Input::new(&self.cwd_input)))
+                        .child(Button::new("browse-cwd").icon(IconName::Folder).on_click(
+                            cx.listener(|this, _, window, cx| this.browse_cwd(window, cx)),
+                        )),
+                ),
+            )
+            .child(
+                v_flex()
+                    .gap_1()
+                    .child(field_label("Launch", false, cx))
+                    .child(
+                        Checkbox::new("inherit-env")
+                            .label("Inherit the debugger's environment")
+                            .checked(self.inherit_env)
+                            .on_click(cx.listener(|this, checked: &bool, _, cx| {
+                                this.inherit_env = *checked;
+                                cx.notify();
+                            })),
+                    )
+                    .child(
+                        Checkbox::new("login-shell")
+                            .label(format!("Launch via login shell ({} -l)", login_shell()))
+                            .checked(self.login_shell)
+                            .on_click(cx.listener(|this, checked: &bool, _, cx| {
+                                this.login_shell = *checked;
+                                cx.notify();
+                            })),
+                    )
+                    .child(self.render_launch_preview(cx)),
             )
             // Auth Method
             .child(
@@ -205,7 +494,7 @@ impl Render for AgentForm {
                     v_flex()
                         .gap_1()
                         .child(field_label(label, false, cx))
-                        .child(Input::new(&self.auth_credential_input)),
+                        .child(Input::new(&self.auth_credential_input).mask_toggle()),
                 )
             })
             // Timeout
@@ -215,6 +504,31 @@ impl Render for AgentForm {
                     .child(field_label("Timeout (ms)", false, cx))
                     .child(Input::new(&self.timeout_input)),
             )
+            // Restart policy
+            .child(
+                v_flex()
+                    .gap_1()
+                    .child(field_label("When the agent exits", false, cx))
+                    .child(
+                        Checkbox::new("restart-enabled")
+                            .label("Restart automatically, with backoff")
+                            .checked(self.restart.enabled)
+                            .on_click(cx.listener(|this, checked: &bool, _, cx| {
+                                this.restart.enabled = *checked;
+                                cx.notify();
+                            })),
+                    )
+                    .child(
+                        Checkbox::new("restart-restore-session")
+                            .label("Restore the previous session after a restart")
+                            .checked(self.restart.restore_session)
+                            .disabled(!self.restart.enabled)
+                            .on_click(cx.listener(|this, checked: &bool, _, cx| {
+                                this.restart.restore_session = *checked;
+                                cx.notify();
+                            })),
+                    ),
+            )
             // Error message
             .when_some(self.error_message.clone(), |el, msg| {
                 el.child(div().text_sm().text_color(cx.theme().danger).child(msg))
@@ -224,16 +538,23 @@ impl Render for AgentForm {
                 h_flex()
                     .justify_end()
                     .gap_2()
+                    .child(Button::new("test-connection").label("Test Connection").on_click(
+                        cx.listener(|this, _, window, cx| this.test_connection(window, cx)),
+                    ))
+                    .child(div().flex_1())
                     .child(Button::new("cancel").label("Cancel").on_click(cx.listener(
                         |_, _, window, cx| {
                             window.close_sheet(cx);
                         },
                     )))
-                    .child(Button::new("create").primary().label("Create Agent").on_click(
-                        cx.listener(|this, _, window, cx| {
-                            this.submit(window, cx);
-                        }),
-                    )),
+                    .child(
+                        Button::new("create")
+                            .primary()
+                            .label(if self.editing.is_some() { "Save" } else { "Create Agent" })
+                            .on_click(cx.listener(|this, _, window, cx| {
+                                this.submit(window, cx);
+                            })),
+                    ),
             )
     }
 }
@@ -248,8 +569,3 @@ fn field_label(text: &str, required: bool, cx: &Context<AgentForm>) -> impl Into
         el
     }
 }
-
-fn generate_agent_id() -> String {
-    let ts = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
-    format!("agent-{:x}", ts)
-}
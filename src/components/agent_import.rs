@@ -0,0 +1,343 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::HashSet, path::PathBuf};
+
+use gpui::{prelude::*, *};
+use gpui_component::{
+    button::{Button, ButtonVariants as _},
+    checkbox::Checkbox,
+    h_flex,
+    input::{Input, InputState},
+    v_flex, ActiveTheme as _, Disableable as _, Sizable as _, StyledExt as _, WindowExt as _,
+};
+
+use crate::models::{
+    parse_agent_servers, secrets, zed_settings_path, AgentRegistry, ImportCandidate, ZedImport,
+};
+
+/// Previews the agents configured in Zed's `agent_servers` and adds the
+/// selected ones to the registry.
+pub struct AgentImport {
+    registry: Entity<AgentRegistry>,
+    path_input: Entity<InputState>,
+    import: Option<ZedImport>,
+    /// Indices of the candidates to import.
+    selected: HashSet<usize>,
+    /// Move credential env values into the secrets file on import.
+    move_credentials: bool,
+    busy: bool,
+    error: Option<String>,
+}
+
+impl AgentImport {
+    pub fn new(
+        registry: Entity<AgentRegistry>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let path = zed_settings_path().map(|p| p.display().to_string()).unwrap_or_default();
+        let path_input = cx.new(|cx| {
+            InputState::new(window, cx).placeholder("Path to Zed settings.json").default_value(path)
+        });
+        let mut this = Self {
+            registry,
+            path_input,
+            import: None,
+            selected: HashSet::new(),
+            move_credentials: true,
+            busy: false,
+            error: None,
+        };
+        this.load(cx);
+        this
+    }
+
+    fn load(&mut self, cx: &mut Context<Self>) {
+        let path = PathBuf::from(self.path_input.read(cx).value().trim());
+        let existing = self.registry.read(cx).agents().to_vec();
+        let result = std::fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|text| parse_agent_servers(&text, &existing));
+
+        match result {
+            Ok(import) => {
+                // Preselect everything that is not already configured.
+                self.selected = import
+                    .candidates
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, c)| c.duplicate_of.is_none())
+                    .map(|(ix, _)| ix)
+                    .collect();
+                self.import = Some(import);
+                self.error = None;
+            }
+            Err(e) => {
+                self.import = None;
+                self.selected.clear();
+                self.error = Some(format!("{}: {}", path.display(), e));
+            }
+        }
+        cx.notify();
+    }
+
+    fn browse(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let paths = cx.prompt_for_paths(PathPromptOptions {
+            files: true,
+            directories: false,
+            multiple: false,
+            prompt: Some("Open Zed Settings".into()),
+        });
+        cx.spawn_in(window, async move |this, cx| {
+            let Ok(Ok(Some(paths))) = paths.await else {
+                return;
+            };
+            let Some(path) = paths.into_iter().next() else {
+                return;
+            };
+            this.update_in(cx, |this, window, cx| {
+                this.path_input.update(cx, |input, cx| {
+                    input.set_value(path.display().to_string(), window, cx)
+                });
+                this.load(cx);
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    fn toggle(&mut self, ix: usize, cx: &mut Context<Self>) {
+        if !self.selected.remove(&ix) {
+            self.selected.insert(ix);
+        }
+        cx.notify();
+    }
+
+    fn submit(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(import) = &self.import else {
+            return;
+        };
+        let mut candidates: Vec<ImportCandidate> = import
+            .candidates
+            .iter()
+            .enumerate()
+            .filter(|(ix, _)| self.selected.contains(ix))
+            .map(|(_, c)| c.clone())
+            .collect();
+        let store = secrets::unlocked()
+            .filter(|_| self.move_credentials)
+            .filter(|_| candidates.iter().any(|c| !c.credentials().is_empty()));
+        let Some(mut store) = store else {
+            self.add_agents(candidates, window, cx);
+            return;
+        };
+
+        for candidate in &mut candidates {
+            candidate.move_credentials(&mut store);
+        }
+        self.busy = true;
+        cx.notify();
+        // Save the secrets first, so the references never point at nothing.
+        let task = cx.background_executor().spawn(async move { secrets::save_unlocked(store) });
+        cx.spawn_in(window, async move |this, cx| {
+            let result = task.await;
+            this.update_in(cx, |this, window, cx| {
+                this.busy = false;
+                match result {
+                    Ok(()) => this.add_agents(candidates, window, cx),
+                    Err(e) => {
+                        this.error = Some(format!("Failed to save secrets: {}", e));
+                        cx.notify();
+                    }
+                }
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    fn add_agents(
+        &mut self,
+        candidates: Vec<ImportCandidate>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.registry.update(cx, |registry, cx| {
+            for candidate in candidates {
+                registry.add_agent(candidate.config);
+            }
+            cx.notify();
+        });
+        window.close_sheet(cx);
+    }
+
+    /// The credentials notice, when a selected agent has any.
+    fn render_credentials(&self, cx: &mut Context<Self>) -> Option<impl IntoElement> {
+        let import = self.import.as_ref()?;
+        let any = import
+            .candidates
+            .iter()
+            .enumerate()
+            .any(|(ix, c)| self.selected.contains(&ix) && !c.credentials().is_empty());
+        if !any {
+            return None;
+        }
+
+        let unlocked = secrets::unlocked().is_some();
+        let note = if !unlocked {
+            "Unlock the secrets file to move credentials out of agents.json; \
+             otherwise they are saved there in plain text."
+        } else if self.move_credentials {
+            "Credentials are stored in the secrets file and referenced as ${secret:NAME}."
+        } else {
+            "Credentials will be saved in agents.json in plain text."
+        };
+        Some(
+            v_flex()
+                .gap_1()
+                .child(
+                    Checkbox::new("move-credentials")
+                        .label("Move credentials to the secrets file")
+                        .checked(unlocked && self.move_credentials)
+                        .disabled(!unlocked)
+                        .on_click(cx.listener(|this, checked: &bool, _, cx| {
+                            this.move_credentials = *checked;
+                            cx.notify();
+                        })),
+                )
+                .child(div().text_xs().text_color(cx.theme().warning).child(note)),
+        )
+    }
+}
+
+impl Render for AgentImport {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let muted = cx.theme().muted_foreground;
+        let candidates = self.import.as_ref().map(|i| i.candidates.clone()).unwrap_or_default();
+        let skipped = self.import.as_ref().map(|i| i.skipped.clone()).unwrap_or_default();
+
+        v_flex()
+            .id("agent-import")
+            .size_full()
+            .overflow_y_scroll()
+            .gap_3()
+            .p_4()
+            .child(
+                h_flex()
+                    .gap_2()
+                    .child(div().flex_1().child(Input::new(&self.path_input).small()))
+                    .child(
+                        Button::new("browse-zed-settings")
+                            .small()
+                            .outline()
+                            .label("Browse…")
+                            .on_click(cx.listener(|this, _, window, cx| this.browse(window, cx))),
+                    )
+                    .child(
+                        Button::new("load-zed-settings")
+                            .small()
+                            .outline()
+                            .label("Load")
+                            .on_click(cx.listener(|this, _, _, cx| this.load(cx))),
+                    ),
+            )
+            .when_some(self.error.clone(), |el, error| {
+                el.child(div().text_sm().text_color(cx.theme().danger).child(error))
+            })
+            .when(self.import.is_some() && candidates.is_empty(), |el| {
+                el.child(div().text_sm().text_color(muted).child("No agent_servers found."))
+            })
+            .children(candidates.into_iter().enumerate().map(|(ix, candidate)| {
+                let config = &candidate.config;
+                let credentials = candidate.credentials();
+                let command = std::iter::once(config.endpoint.clone())
+                    .chain(config.args.iter().cloned())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                h_flex()
+                    .gap_2()
+                    .py_1()
+                    .border_b_1()
+                    .border_color(cx.theme().border)
+                    .child(
+                        Checkbox::new(("import-agent", ix))
+                            .checked(self.selected.contains(&ix))
+                            .on_click(cx.listener(move |this, _, _, cx| this.toggle(ix, cx))),
+                    )
+                    .child(
+                        v_flex()
+                            .flex_1()
+                            .min_w_0()
+                            .child(div().text_sm().font_semibold().child(config.name.clone()))
+                            .child(div().text_xs().text_color(muted).truncate().child(command))
+                            .when(!config.env.is_empty(), |el| {
+                                let keys: Vec<_> =
+                                    config.env.iter().map(|(k, _)| k.as_str()).collect();
+                                el.child(
+                                    div()
+                                        .text_xs()
+                                        .text_color(muted)
+                                        .child(format!("env: {}", keys.join(", "))),
+                                )
+                            })
+                            .when(!credentials.is_empty(), |el| {
+                                el.child(div().text_xs().text_color(cx.theme().warning).child(
+                                    format!(
+                                        "Credentials in plain text: {}",
+                                        credentials.join(", ")
+                                    ),
+                                ))
+                            })
+                            .when_some(candidate.duplicate_of, |el, name| {
+                                el.child(
+                                    div()
+                                        .text_xs()
+                                        .text_color(cx.theme().warning)
+                                        .child(format!("Already configured as \"{}\"", name)),
+                                )
+                            }),
+                    )
+            }))
+            .when(!skipped.is_empty(), |el| {
+                el.child(div().text_xs().text_color(muted).child(format!(
+                    "Skipped: {}",
+                    skipped
+                        .iter()
+                        .map(|(name, reason)| format!("{} ({})", name, reason))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )))
+            })
+            .children(self.render_credentials(cx))
+            .child(
+                h_flex()
+                    .justify_end()
+                    .gap_2()
+                    .child(Button::new("cancel-import").label("Cancel").on_click(cx.listener(
+                        |_, _, window, cx| {
+                            window.close_sheet(cx);
+                        },
+                    )))
+                    .child(
+                        Button::new("import-agents")
+                            .primary()
+                            .label(format!("Import {} Agents", self.selected.len()))
+                            .loading(self.busy)
+                            .disabled(self.selected.is_empty() || self.busy)
+                            .on_click(cx.listener(|this, _, window, cx| this.submit(window, cx))),
+                    ),
+            )
+    }
+}
@@ -0,0 +1,210 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use gpui::{prelude::*, *};
+use gpui_component::{
+    button::{Button, ButtonVariant, ButtonVariants as _},
+    dialog::DialogButtonProps,
+    h_flex, v_flex, ActiveTheme as _, IconName, Sizable as _, StyledExt as _, WindowExt as _,
+};
+
+use crate::{
+    components::{AgentForm, HealthReportView},
+    models::{AgentConfig, AgentRegistry, LaunchPlan},
+};
+
+/// The row being dragged, by index.
+#[derive(Clone)]
+struct DraggedAgent {
+    ix: usize,
+    name: SharedString,
+}
+
+impl Render for DraggedAgent {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        div()
+            .px_2()
+            .py_1()
+            .rounded_md()
+            .bg(cx.theme().secondary)
+            .border_1()
+            .border_color(cx.theme().border)
+            .text_sm()
+            .child(self.name.clone())
+    }
+}
+
+/// Lists the configured agents for editing, duplicating, deleting and
+/// reordering by drag and drop.
+pub struct AgentList {
+    registry: Entity<AgentRegistry>,
+}
+
+impl AgentList {
+    pub fn new(registry: Entity<AgentRegistry>, cx: &mut Context<Self>) -> Self {
+        cx.observe(&registry, |_, _, cx| cx.notify()).detach();
+        Self { registry }
+    }
+
+    fn edit(&mut self, config: &AgentConfig, window: &mut Window, cx: &mut Context<Self>) {
+        let registry = self.registry.clone();
+        let form = cx.new(|cx| AgentForm::edit(registry, config, window, cx));
+        window.open_sheet(cx, move |sheet, _, _| {
+            sheet.size(px(450.)).title("Edit Agent").child(form.clone())
+        });
+    }
+
+    fn duplicate(&mut self, id: &str, cx: &mut Context<Self>) {
+        self.registry.update(cx, |registry, cx| {
+            registry.duplicate_agent(id);
+            cx.notify();
+        });
+    }
+
+    fn confirm_delete(
+        &mut self,
+        config: &AgentConfig,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let registry = self.registry.clone();
+        let id = config.id.clone();
+        let message = format!("Delete \"{}\"? Its connection will be closed.", config.name);
+        window.open_dialog(cx, move |dialog, _, _| {
+            let registry = registry.clone();
+            let id = id.clone();
+            dialog
+                .confirm()
+                .title("Delete Agent")
+                .child(message.clone())
+                .button_props(
+                    DialogButtonProps::default()
+                        .ok_text("Delete")
+                        .ok_variant(ButtonVariant::Danger),
+                )
+                .on_ok(move |_, _, cx| {
+                    registry.update(cx, |registry, cx| {
+                        registry.remove_agent(&id);
+                        cx.notify();
+                    });
+                    true
+                })
+        });
+    }
+
+    fn move_agent(&mut self, from: usize, to: usize, cx: &mut Context<Self>) {
+        self.registry.update(cx, |registry, cx| {
+            registry.move_agent(from, to);
+            cx.notify();
+        });
+    }
+
+    fn row(&self, ix: usize, config: &AgentConfig, cx: &mut Context<Self>) -> impl IntoElement {
+        let edit_config = config.clone();
+        let test_config = config.clone();
+        let delete_config = config.clone();
+        let id = config.id.clone();
+        let dragged = DraggedAgent { ix, name: config.name.clone().into() };
+
+        h_flex()
+            .id(("agent-row", ix))
+            .gap_2()
+            .px_2()
+            .py_1p5()
+            .border_b_1()
+            .border_color(cx.theme().border)
+            .on_drag(dragged, |dragged, _, _, cx| cx.new(|_| dragged.clone()))
+            .drag_over::<DraggedAgent>(|style, _, _, cx| style.bg(cx.theme().accent))
+            .on_drop(cx.listener(move |this, dragged: &DraggedAgent, _, cx| {
+                this.move_agent(dragged.ix, ix, cx)
+            }))
+            .child(
+                div().cursor_grab().text_color(cx.theme().muted_foreground).child(IconName::Menu),
+            )
+            .child(
+                v_flex()
+                    .flex_1()
+                    .min_w_0()
+                    .child(div().text_sm().font_semibold().child(config.name.clone()))
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(cx.theme().muted_foreground)
+                            .truncate()
+                            .child(LaunchPlan::for_agent(config).command_line()),
+                    ),
+            )
+            .child(Button::new(("edit-agent", ix)).xsmall().ghost().label("Edit").on_click(
+                cx.listener(move |this, _, window, cx| this.edit(&edit_config, window, cx)),
+            ))
+            .child(
+                Button::new(("test-agent", ix))
+                    .xsmall()
+                    .ghost()
+                    .icon(IconName::CircleCheck)
+                    .tooltip("Test Connection")
+                    .on_click(move |_, window, cx| {
+                        HealthReportView::open(test_config.clone(), window, cx)
+                    }),
+            )
+            .child(
+                Button::new(("duplicate-agent", ix))
+                    .xsmall()
+                    .ghost()
+                    .icon(IconName::Copy)
+                    .tooltip("Duplicate")
+                    .on_click(cx.listener(move |this, _, _, cx| this.duplicate(&id, cx))),
+            )
+            .child(
+                Button::new(("delete-agent", ix))
+                    .xsmall()
+                    .ghost()
+                    .icon(IconName::Delete)
+                    .tooltip("Delete")
+                    .on_click(cx.listener(move |this, _, window, cx| {
+                        this.confirm_delete(&delete_config, window, cx)
+                    })),
+            )
+    }
+}
+
+impl Render for AgentList {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let agents = self.registry.read(cx).agents().to_vec();
+
+        v_flex()
+            .id("agent-list")
+            .size_full()
+            .overflow_y_scroll()
+            .p_4()
+            .when(agents.is_empty(), |el| {
+                el.child(
+                    div()
+                        .text_sm()
+                        .text_color(cx.theme().muted_foreground)
+                        .child("No agents configured."),
+                )
+            })
+            .when(!agents.is_empty(), |el| {
+                el.child(
+                    div()
+                        .pb_2()
+                        .text_xs()
+                        .text_color(cx.theme().muted_foreground)
+                        .child("Drag agents to reorder them."),
+                )
+            })
+            .children(agents.iter().enumerate().map(|(ix, config)| self.row(ix, config, cx)))
+    }
+}
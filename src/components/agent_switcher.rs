@@ -12,41 +12,93 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
+
 use gpui::{prelude::*, *};
 use gpui_component::{
     button::Button,
     menu::{DropdownMenu as _, PopupMenuItem},
-    IconName, Sizable as _, WindowExt as _,
+    ActiveTheme as _, IconName, Sizable as _, WindowExt as _,
 };
 
-use crate::{components::AgentForm, models::AgentRegistry};
+use crate::{
+    client::AcpConnection,
+    components::{AgentForm, AgentImport, AgentList, SecretsForm},
+    models::{AgentId, AgentRegistry, AgentStatus},
+};
 
 pub struct AgentSwitcher {
     registry: Entity<AgentRegistry>,
+    /// Connections of the agents selected so far, for their live status.
+    connections: HashMap<AgentId, Entity<AcpConnection>>,
+    _subscriptions: Vec<Subscription>,
 }
 
 impl AgentSwitcher {
     pub fn new(registry: Entity<AgentRegistry>, cx: &mut Context<Self>) -> Self {
         cx.observe(&registry, |_, _, cx| cx.notify()).detach();
-        Self { registry }
+        Self { registry, connections: HashMap::new(), _subscriptions: Vec::new() }
+    }
+
+    pub fn set_connections(
+        &mut self,
+        connections: HashMap<AgentId, Entity<AcpConnection>>,
+        cx: &mut Context<Self>,
+    ) {
+        self._subscriptions = connections
+            .values()
+            .map(|connection| cx.observe(connection, |_, _, cx| cx.notify()))
+            .collect();
+        self.connections = connections;
+        cx.notify();
+    }
+
+    fn status(&self, id: &AgentId, cx: &App) -> AgentStatus {
+        self.connections
+            .get(id)
+            .map_or(AgentStatus::Disconnected, |connection| connection.read(cx).status())
+    }
+}
+
+fn status_color(status: AgentStatus, cx: &App) -> Hsla {
+    match status {
+        AgentStatus::Connected => cx.theme().success,
+        AgentStatus::Connecting => cx.theme().warning,
+        AgentStatus::Error => cx.theme().danger,
+        AgentStatus::Disconnected => cx.theme().muted_foreground,
     }
 }
 
 impl Render for AgentSwitcher {
     fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
         let registry = self.registry.clone();
-        let active_name: SharedString = self
-            .registry
-            .read(cx)
-            .active_agent()
-            .map(|a| SharedString::from(a.name.clone()))
+        let active = self.registry.read(cx).active_agent().map(|a| (a.id.clone(), a.name.clone()));
+        let active_status = active.as_ref().map(|(id, _)| self.status(id, cx));
+        let active_name: SharedString = active
+            .map(|(_, name)| SharedString::from(name))
             .unwrap_or_else(|| "Select Agent".into());
+        let statuses: HashMap<AgentId, (AgentStatus, Option<String>)> = self
+            .connections
+            .iter()
+            .map(|(id, connection)| {
+                let connection = connection.read(cx);
+                (id.clone(), (connection.status(), connection.status_detail()))
+            })
+            .collect();
 
         Button::new("agent-switcher")
             .xsmall()
             .rounded_lg()
             .child(
-                div().flex().items_center().gap_1().child(active_name).child(IconName::ChevronDown),
+                div()
+                    .flex()
+                    .items_center()
+                    .gap_1()
+                    .when_some(active_status, |el, status| {
+                        el.child(div().size(px(6.)).rounded_full().bg(status_color(status, cx)))
+                    })
+                    .child(active_name)
+                    .child(IconName::ChevronDown),
             )
             .dropdown_menu({
                 let registry = registry.clone();
@@ -63,21 +115,36 @@ impl Render for AgentSwitcher {
                             let is_active = active_id.as_ref() == Some(&agent.id);
                             let agent_id = agent.id.clone();
                             let reg = registry.clone();
-                            menu = menu.item(
-                                PopupMenuItem::new(agent.name.clone()).checked(is_active).on_click(
+                            let label = match statuses.get(&agent.id) {
+                                Some((status, Some(detail))) => {
+                                    format!("{} · {} ({})", agent.name, status.label(), detail)
+                                }
+                                Some((status, None)) => {
+                                    format!("{} · {}", agent.name, status.label())
+                                }
+                                None => format!(
+                                    "{} · {}",
+                                    agent.name,
+                                    AgentStatus::Disconnected.label()
+                                ),
+                            };
+                            menu =
+                                menu.item(PopupMenuItem::new(label).checked(is_active).on_click(
                                     move |_, _, cx| {
-                                        reg.update(cx, |r, _| {
+                                        reg.update(cx, |r, cx| {
                                             r.set_active(Some(agent_id.clone()));
+                                            cx.notify();
                                         });
                                     },
-                                ),
-                            );
+                                ));
                         }
                     }
 
                     let reg_for_form = registry.clone();
-                    menu.separator().item(
-                        PopupMenuItem::new("Add Agent...").icon(IconName::Plus).on_click(
+                    let reg_for_list = registry.clone();
+                    let reg_for_import = registry.clone();
+                    menu.separator()
+                        .item(PopupMenuItem::new("Add Agent...").icon(IconName::Plus).on_click(
                             move |_, window, cx| {
                                 let reg = reg_for_form.clone();
                                 let form = cx.new(|cx| AgentForm::new(reg, window, cx));
@@ -85,8 +152,42 @@ impl Render for AgentSwitcher {
                                     sheet.size(px(450.)).title("New Agent").child(form.clone())
                                 });
                             },
-                        ),
-                    )
+                        ))
+                        .item(
+                            PopupMenuItem::new("Import from Zed...")
+                                .icon(IconName::ArrowDown)
+                                .on_click(move |_, window, cx| {
+                                    let import = cx.new(|cx| {
+                                        AgentImport::new(reg_for_import.clone(), window, cx)
+                                    });
+                                    window.open_sheet(cx, move |sheet, _, _| {
+                                        sheet
+                                            .size(px(500.))
+                                            .title("Import from Zed")
+                                            .child(import.clone())
+                                    });
+                                }),
+                        )
+                        .item(PopupMenuItem::new("Secrets...").icon(IconName::EyeOff).on_click(
+                            |_, window, cx| {
+                                let form = cx.new(|cx| SecretsForm::new(window, cx));
+                                window.open_sheet(cx, move |sheet, _, _| {
+                                    sheet.size(px(450.)).title("Secrets").child(form.clone())
+                                });
+                            },
+                        ))
+                        .item(
+                            PopupMenuItem::new("Manage Agents...")
+                                .icon(IconName::Settings)
+                                .disabled(agents.is_empty())
+                                .on_click(move |_, window, cx| {
+                                    let list =
+                                        cx.new(|cx| AgentList::new(reg_for_list.clone(), cx));
+                                    window.open_sheet(cx, move |sheet, _, _| {
+                                        sheet.size(px(450.)).title("Agents").child(list.clone())
+                                    });
+                                }),
+                        )
                 }
             })
     }
@@ -0,0 +1,168 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use gpui::{prelude::*, *};
+use gpui_component::{
+    button::Button, h_flex, v_flex, ActiveTheme as _, Disableable as _, IconName, Sizable as _,
+    StyledExt as _, WindowExt as _,
+};
+
+use crate::{
+    client::check_health,
+    models::{AgentConfig, CheckOutcome, HealthReport},
+};
+
+/// Runs a health check of an agent config and shows the checklist.
+pub struct HealthReportView {
+    config: AgentConfig,
+    report: Option<HealthReport>,
+    _task: Option<Task<()>>,
+}
+
+impl HealthReportView {
+    /// Test `config` and show the report in a dialog.
+    pub fn open(config: AgentConfig, window: &mut Window, cx: &mut App) {
+        let title = format!("Test Connection: {}", config.name);
+        let view = cx.new(|cx| {
+            let mut view = Self { config, report: None, _task: None };
+            view.run(cx);
+            view
+        });
+        window.open_dialog(cx, move |dialog, _, _| {
+            dialog.title(title.clone()).width(px(560.)).child(view.clone())
+        });
+    }
+
+    fn run(&mut self, cx: &mut Context<Self>) {
+        let config = self.config.clone();
+        let cwd = config
+            .launch
+            .cwd()
+            .unwrap_or_else(|| std::env::current_dir().unwrap_or_default())
+            .display()
+            .to_string();
+        self.report = None;
+        self._task = Some(cx.spawn(async move |this, cx| {
+            let executor = cx.background_executor().clone();
+            let report = check_health(&config, &cwd, &executor).await;
+            this.update(cx, |this, cx| {
+                this.report = Some(report);
+                this._task = None;
+                cx.notify();
+            })
+            .ok();
+        }));
+        cx.notify();
+    }
+}
+
+impl Render for HealthReportView {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let muted = cx.theme().muted_foreground;
+        let Some(report) = self.report.clone() else {
+            return v_flex()
+                .gap_2()
+                .child(div().text_sm().text_color(muted).child("Running checks…"))
+                .into_any_element();
+        };
+
+        let (summary, color) = if report.passed() {
+            ("All checks passed", cx.theme().success)
+        } else {
+            ("Health check failed", cx.theme().danger)
+        };
+        let text = report.to_text();
+
+        v_flex()
+            .gap_3()
+            .child(div().text_sm().font_semibold().text_color(color).child(summary))
+            .child(v_flex().gap_1().children(report.checks.iter().map(|check| {
+                let (icon, color) = match check.outcome {
+                    CheckOutcome::Passed => (IconName::CircleCheck, cx.theme().success),
+                    CheckOutcome::Failed => (IconName::CircleX, cx.theme().danger),
+                    CheckOutcome::Skipped => (IconName::Minus, muted),
+                };
+                h_flex()
+                    .gap_2()
+                    .items_start()
+                    .text_sm()
+                    .child(div().text_color(color).child(icon))
+                    .child(
+                        v_flex()
+                            .flex_1()
+                            .min_w_0()
+                            .child(h_flex().gap_2().child(check.name).when_some(
+                                check.elapsed,
+                                |el, elapsed| {
+                                    el.child(
+                                        div()
+                                            .text_xs()
+                                            .text_color(muted)
+                                            .child(format!("{} ms", elapsed.as_millis())),
+                                    )
+                                },
+                            ))
+                            .when_some(check.detail.clone(), |el, detail| {
+                                el.child(div().text_xs().text_color(muted).child(detail))
+                            }),
+                    )
+            })))
+            .when_some(report.exit_status.clone(), |el, status| {
+                el.child(
+                    div()
+                        .text_xs()
+                        .text_color(cx.theme().danger)
+                        .child(format!("Exit status: {}", status)),
+                )
+            })
+            .when(!report.stderr.is_empty(), |el| {
+                el.child(
+                    v_flex()
+                        .id("health-stderr")
+                        .max_h(px(200.))
+                        .overflow_y_scroll()
+                        .p_2()
+                        .rounded_md()
+                        .bg(cx.theme().muted)
+                        .text_xs()
+                        .font_family("monospace")
+                        .children(report.stderr.iter().map(|line| div().child(line.clone()))),
+                )
+            })
+            .child(
+                h_flex()
+                    .justify_end()
+                    .gap_2()
+                    .child(
+                        Button::new("copy-health-report")
+                            .small()
+                            .outline()
+                            .icon(IconName::Copy)
+                            .label("Copy Report")
+                            .on_click(move |_, _, cx| {
+                                cx.write_to_clipboard(ClipboardItem::new_string(text.clone()))
+                            }),
+                    )
+                    .child(
+                        Button::new("rerun-health-check")
+                            .small()
+                            .outline()
+                            .label("Run Again")
+                            .disabled(self._task.is_some())
+                            .on_click(cx.listener(|this, _, _, cx| this.run(cx))),
+                    ),
+            )
+            .into_any_element()
+    }
+}
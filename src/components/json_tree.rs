@@ -0,0 +1,376 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use base64::Engine as _;
+use gpui::{prelude::*, *};
+use gpui_component::{
+    button::{Button, ButtonVariants as _},
+    h_flex, v_flex, ActiveTheme as _, IconName, Sizable as _,
+};
+use serde_json::Value;
+
+/// Containers up to this depth start expanded.
+const OPEN_DEPTH: usize = 2;
+/// Array items shown per "show more" step.
+const ARRAY_PAGE: usize = 100;
+/// Strings longer than this get a length badge.
+const BADGE_MIN_LEN: usize = 32;
+/// Strings longer than this are cut in the tree.
+const MAX_STRING_LEN: usize = 120;
+const INDENT: f32 = 14.;
+
+/// A decoded base64 `data` field of an image or audio content block.
+enum Media {
+    Image(Arc<Image>),
+    Audio {
+        mime_type: String,
+        bytes: Vec<u8>,
+    },
+    /// The data could not be decoded or the format is unsupported.
+    Invalid(String),
+}
+
+/// A collapsible, syntax-colored view of a JSON value.
+pub struct JsonTree {
+    value: Value,
+    /// Pointers of containers toggled away from their default state.
+    toggled: HashSet<String>,
+    /// Number of items shown for large arrays, by pointer.
+    shown: HashMap<String, usize>,
+    /// Media previews by the pointer of their content block.
+    media: HashMap<String, Media>,
+}
+
+impl JsonTree {
+    pub fn new(value: Value) -> Self {
+        let mut media = HashMap::new();
+        collect_media(&value, &mut String::new(), &mut media);
+        Self { value, toggled: HashSet::new(), shown: HashMap::new(), media }
+    }
+
+    fn is_open(&self, pointer: &str, depth: usize) -> bool {
+        (depth < OPEN_DEPTH) != self.toggled.contains(pointer)
+    }
+
+    fn toggle(&mut self, pointer: &str, cx: &mut Context<Self>) {
+        if !self.toggled.remove(pointer) {
+            self.toggled.insert(pointer.to_string());
+        }
+        cx.notify();
+    }
+
+    fn show_more(&mut self, pointer: &str, cx: &mut Context<Self>) {
+        *self.shown.entry(pointer.to_string()).or_insert(ARRAY_PAGE) += ARRAY_PAGE;
+        cx.notify();
+    }
+
+    /// Append the rows of `value` and its visible descendants.
+    fn rows(
+        &self,
+        key: Option<String>,
+        value: &Value,
+        pointer: &str,
+        depth: usize,
+        rows: &mut Vec<AnyElement>,
+        cx: &mut Context<Self>,
+    ) {
+        let ix = rows.len();
+        let open = self.is_open(pointer, depth);
+        let child_count = match value {
+            Value::Object(map) => map.len(),
+            Value::Array(items) => items.len(),
+            _ => 0,
+        };
+        let container = matches!(value, Value::Object(_) | Value::Array(_));
+
+        let toggle_pointer = pointer.to_string();
+        let row = h_flex()
+            .group("json-row")
+            .gap_1()
+            .pl(px(depth as f32 * INDENT))
+            .min_h(px(20.))
+            .text_xs()
+            .font_family("monospace")
+            .hover(|style| style.bg(cx.theme().list_hover))
+            .child(
+                h_flex()
+                    .id(("json-node", ix))
+                    .gap_1()
+                    .overflow_hidden()
+                    .child(div().flex_none().w(px(12.)).when(container, |el| {
+                        el.child(if open { IconName::ChevronDown } else { IconName::ChevronRight })
+                    }))
+                    .when_some(key, |el, key| {
+                        el.child(div().text_color(cx.theme().primary).child(format!("{}:", key)))
+                    })
+                    .child(self.value_preview(value, open, cx))
+                    .when(container, |el| {
+                        el.cursor_pointer().on_click(
+                            cx.listener(move |this, _, _, cx| this.toggle(&toggle_pointer, cx)),
+                        )
+                    }),
+            )
+            .child(div().flex_1())
+            .child(Self::copy_buttons(ix, pointer, cx))
+            .into_any_element();
+        rows.push(row);
+
+        if let Some(media) = self.media.get(pointer) {
+            rows.push(self.media_preview(ix, media, depth + 1, cx).into_any_element());
+        }
+
+        if !container || !open {
+            return;
+        }
+        let limit = match value {
+            Value::Array(_) => self.shown.get(pointer).copied().unwrap_or(ARRAY_PAGE),
+            _ => usize::MAX,
+        };
+        // Only the shown children are visited, so huge arrays stay cheap.
+        let children: Box<dyn Iterator<Item = (String, &Value)>> = match value {
+            Value::Object(map) => Box::new(map.iter().map(|(k, v)| (k.clone(), v))),
+            Value::Array(items) => {
+                Box::new(items.iter().enumerate().map(|(i, v)| (i.to_string(), v)))
+            }
+            _ => Box::new(std::iter::empty()),
+        };
+        for (key, child) in children.take(limit) {
+            let child_pointer = format!("{}/{}", pointer, escape(&key));
+            self.rows(Some(key), child, &child_pointer, depth + 1, rows, cx);
+        }
+        if child_count > limit {
+            let more_pointer = pointer.to_string();
+            let remaining = child_count - limit;
+            rows.push(
+                div()
+                    .pl(px((depth + 1) as f32 * INDENT))
+                    .child(
+                        Button::new(("json-more", ix))
+                            .xsmall()
+                            .ghost()
+                            .label(format!(
+                                "Show {} more of {}",
+                                remaining.min(ARRAY_PAGE),
+                                child_count
+                            ))
+                            .on_click(
+                                cx.listener(move |this, _, _, cx| {
+                                    this.show_more(&more_pointer, cx)
+                                }),
+                            ),
+                    )
+                    .into_any_element(),
+            );
+        }
+    }
+
+    fn value_preview(&self, value: &Value, open: bool, cx: &App) -> Div {
+        let theme = cx.theme();
+        match value {
+            Value::Object(map) if !open => {
+                div().text_color(theme.muted_foreground).child(format!("{{{} keys}}", map.len()))
+            }
+            Value::Array(items) if !open => {
+                div().text_color(theme.muted_foreground).child(format!("[{} items]", items.len()))
+            }
+            Value::Object(map) => {
+                div().text_color(theme.muted_foreground).child(format!("{} keys", map.len()))
+            }
+            Value::Array(items) => {
+                div().text_color(theme.muted_foreground).child(format!("{} items", items.len()))
+            }
+            Value::String(s) => {
+                let len = s.chars().count();
+                let text = if len > MAX_STRING_LEN {
+                    format!("\"{}…\"", s.chars().take(MAX_STRING_LEN).collect::<String>())
+                } else {
+                    format!("\"{}\"", s)
+                };
+                h_flex()
+                    .gap_1()
+                    .overflow_hidden()
+                    .child(div().text_color(theme.success).child(text.replace('\n', "\\n")))
+                    .when(len > BADGE_MIN_LEN, |el| {
+                        el.child(
+                            div()
+                                .flex_none()
+                                .px_1()
+                                .rounded(px(3.))
+                                .bg(theme.muted)
+                                .text_color(theme.muted_foreground)
+                                .child(format!("{} chars", len)),
+                        )
+                    })
+            }
+            Value::Number(n) => div().text_color(theme.info).child(n.to_string()),
+            Value::Bool(b) => div().text_color(theme.warning).child(b.to_string()),
+            Value::Null => div().text_color(theme.muted_foreground).child("null"),
+        }
+    }
+
+    /// Copy buttons for the row at `pointer`; the value is only serialized
+    /// when copied.
+    fn copy_buttons(ix: usize, pointer: &str, cx: &mut Context<Self>) -> impl IntoElement {
+        let value_pointer = pointer.to_string();
+        let pointer = pointer.to_string();
+        h_flex()
+            .invisible()
+            .group_hover("json-row", |style| style.visible())
+            .child(
+                Button::new(("copy-pointer", ix))
+                    .xsmall()
+                    .ghost()
+                    .label("Pointer")
+                    .tooltip("Copy JSON pointer")
+                    .on_click(move |_, _, cx| {
+                        let pointer =
+                            if pointer.is_empty() { "/".to_string() } else { pointer.clone() };
+                        cx.write_to_clipboard(ClipboardItem::new_string(pointer));
+                    }),
+            )
+            .child(
+                Button::new(("copy-value", ix))
+                    .xsmall()
+                    .ghost()
+                    .icon(IconName::Copy)
+                    .tooltip("Copy value")
+                    .on_click(cx.listener(move |this, _, _, cx| {
+                        let value = match this.value.pointer(&value_pointer) {
+                            Some(Value::String(s)) => s.clone(),
+                            Some(other) => serde_json::to_string_pretty(other).unwrap_or_default(),
+                            None => return,
+                        };
+                        cx.write_to_clipboard(ClipboardItem::new_string(value));
+                    })),
+            )
+    }
+
+    fn media_preview(
+        &self,
+        ix: usize,
+        media: &Media,
+        depth: usize,
+        cx: &mut Context<Self>,
+    ) -> impl IntoElement {
+        let container = div().pl(px(depth as f32 * INDENT)).py_1();
+        match media {
+            Media::Image(image) => container.child(
+                img(image.clone())
+                    .max_w(px(320.))
+                    .max_h(px(240.))
+                    .border_1()
+                    .border_color(cx.theme().border),
+            ),
+            Media::Audio { mime_type, bytes } => {
+                let extension = mime_type.rsplit('/').next().unwrap_or("bin").to_string();
+                let bytes = bytes.clone();
+                container.child(
+                    h_flex()
+                        .gap_2()
+                        .text_xs()
+                        .text_color(cx.theme().muted_foreground)
+                        .child(format!("{} audio, {} bytes", mime_type, bytes.len()))
+                        .child(
+                            Button::new(("save-audio", ix))
+                                .xsmall()
+                                .outline()
+                                .label("Save Audio…")
+                                .on_click(cx.listener(move |_, _, _, cx| {
+                                    save_bytes(bytes.clone(), &extension, cx)
+                                })),
+                        ),
+                )
+            }
+            Media::Invalid(reason) => {
+                container.text_xs().text_color(cx.theme().danger).child(reason.clone())
+            }
+        }
+    }
+}
+
+impl Render for JsonTree {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let mut rows = Vec::new();
+        self.rows(None, &self.value, "", 0, &mut rows, cx);
+        v_flex().w_full().children(rows)
+    }
+}
+
+/// Escape a key for use in a JSON pointer.
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// Decode the `data` of every image and audio content block in `value`.
+fn collect_media(value: &Value, pointer: &mut String, media: &mut HashMap<String, Media>) {
+    match value {
+        Value::Object(map) => {
+            let kind = map.get("type").and_then(Value::as_str);
+            let data = map.get("data").and_then(Value::as_str);
+            let mime_type = map.get("mimeType").and_then(Value::as_str).unwrap_or_default();
+            if let (Some(kind @ ("image" | "audio")), Some(data)) = (kind, data) {
+                media.insert(pointer.clone(), decode_media(kind, mime_type, data));
+            }
+            for (key, child) in map {
+                let len = pointer.len();
+                pointer.push('/');
+                pointer.push_str(&escape(key));
+                collect_media(child, pointer, media);
+                pointer.truncate(len);
+            }
+        }
+        Value::Array(items) => {
+            for (ix, child) in items.iter().enumerate() {
+                let len = pointer.len();
+                pointer.push_str(&format!("/{}", ix));
+                collect_media(child, pointer, media);
+                pointer.truncate(len);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn decode_media(kind: &str, mime_type: &str, data: &str) -> Media {
+    let bytes = match base64::engine::general_purpose::STANDARD.decode(data.trim()) {
+        Ok(bytes) => bytes,
+        Err(e) => return Media::Invalid(format!("Invalid base64 data: {}", e)),
+    };
+    if kind == "audio" {
+        return Media::Audio { mime_type: mime_type.to_string(), bytes };
+    }
+    match ImageFormat::from_mime_type(mime_type) {
+        Some(format) => Media::Image(Arc::new(Image::from_bytes(format, bytes))),
+        None => Media::Invalid(format!("Unsupported image type: {}", mime_type)),
+    }
+}
+
+fn save_bytes(bytes: Vec<u8>, extension: &str, cx: &mut App) {
+    let directory = std::env::current_dir().unwrap_or_default();
+    let path = cx.prompt_for_new_path(&directory, Some(&format!("audio.{}", extension)));
+    cx.background_executor()
+        .spawn(async move {
+            if let Ok(Ok(Some(path))) = path.await {
+                if let Err(e) = std::fs::write(&path, bytes) {
+                    log::error!("Failed to save {}: {}", path.display(), e);
+                }
+            }
+        })
+        .detach();
+}
@@ -12,7 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use gpui::*;
+use std::ops::Range;
+
+use gpui::{prelude::*, *};
 use gpui_component::{ActiveTheme as _, StyledExt};
 
 #[derive(IntoElement)]
@@ -20,6 +22,10 @@ pub struct MessageItem {
     title: SharedString,
     json_content: SharedString,
     is_response: bool,
+    reply_to: Option<SharedString>,
+    crafted: bool,
+    highlights: Vec<Range<usize>>,
+    selected: bool,
 }
 
 impl MessageItem {
@@ -28,7 +34,39 @@ impl MessageItem {
         json_content: impl Into<SharedString>,
         is_response: bool,
     ) -> Self {
-        Self { title: title.into(), json_content: json_content.into(), is_response }
+        Self {
+            title: title.into(),
+            json_content: json_content.into(),
+            is_response,
+            reply_to: None,
+            crafted: false,
+            highlights: Vec::new(),
+            selected: false,
+        }
+    }
+
+    /// Label of the request this message answers, e.g. `#3`.
+    pub fn reply_to(mut self, label: Option<impl Into<SharedString>>) -> Self {
+        self.reply_to = label.map(Into::into);
+        self
+    }
+
+    /// Mark the message as manually crafted.
+    pub fn crafted(mut self, crafted: bool) -> Self {
+        self.crafted = crafted;
+        self
+    }
+
+    /// Byte ranges of the JSON content to highlight as search matches.
+    pub fn highlights(mut self, highlights: Vec<Range<usize>>) -> Self {
+        self.highlights = highlights;
+        self
+    }
+
+    /// Mark the item as the current search match.
+    pub fn selected(mut self, selected: bool) -> Self {
+        self.selected = selected;
+        self
     }
 
     fn arrow_icon(&self) -> &'static str {
@@ -52,13 +90,19 @@ impl RenderOnce for MessageItem {
     fn render(self, _: &mut Window, cx: &mut App) -> impl IntoElement {
         let arrow = self.arrow_icon();
         let arrow_color = self.arrow_color();
+        let highlight = HighlightStyle {
+            background_color: Some(cx.theme().warning.opacity(0.35)),
+            ..Default::default()
+        };
+        let json_content = StyledText::new(self.json_content.clone())
+            .with_highlights(self.highlights.iter().map(|range| (range.clone(), highlight)));
 
         div()
             .flex()
             .flex_col()
             .bg(cx.theme().tab_bar)
             .border_1()
-            .border_color(cx.theme().border)
+            .border_color(if self.selected { cx.theme().warning } else { cx.theme().border })
             .rounded_md()
             .px_2()
             .py_1p5()
@@ -76,7 +120,23 @@ impl RenderOnce for MessageItem {
                             .font_semibold()
                             .text_color(cx.theme().foreground)
                             .child(self.title.clone()),
-                    ),
+                    )
+                    .when_some(self.reply_to.clone(), |el, label| {
+                        el.child(
+                            div()
+                                .text_xs()
+                                .text_color(cx.theme().muted_foreground)
+                                .child(format!("\u{21a9} {}", label)),
+                        )
+                    })
+                    .when(self.crafted, |el| {
+                        el.child(
+                            div()
+                                .text_xs()
+                                .text_color(cx.theme().warning)
+                                .child("\u{270e} crafted"),
+                        )
+                    }),
             )
             // JSON content area
             .child(
@@ -94,7 +154,7 @@ impl RenderOnce for MessageItem {
                             .text_color(cx.theme().secondary_foreground)
                             .overflow_hidden()
                             .text_ellipsis()
-                            .child(self.json_content.clone()),
+                            .child(json_content),
                     ),
             )
     }
@@ -13,14 +13,26 @@
 // limitations under the License.
 
 mod agent_form;
+mod agent_import;
+mod agent_list;
 mod agent_switcher;
 mod chat_message;
+mod health_report;
+mod json_tree;
 mod message_item;
+mod secrets_form;
+mod session_form;
 
 // Re-exports
 pub use agent_form::AgentForm;
+pub use agent_import::AgentImport;
+pub use agent_list::AgentList;
 pub use agent_switcher::AgentSwitcher;
 pub use chat_message::{
     ChatMessage, ChatMessageVariant, PlanEntry, PlanEntryStatus, Role, ToolCallKind, ToolCallStatus,
 };
+pub use health_report::HealthReportView;
+pub use json_tree::JsonTree;
 pub use message_item::MessageItem;
+pub use secrets_form::SecretsForm;
+pub use session_form::SessionForm;
@@ -0,0 +1,258 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use gpui::{prelude::*, *};
+use gpui_component::{
+    button::{Button, ButtonVariants as _},
+    h_flex,
+    input::{Input, InputState},
+    v_flex, ActiveTheme as _, Disableable as _, IconName, Sizable as _,
+};
+
+use crate::models::{secrets, SecretStore, MASK};
+
+/// Unlocks, creates and edits the passphrase-encrypted secrets file that
+/// `${secret:NAME}` references resolve from.
+pub struct SecretsForm {
+    passphrase_input: Entity<InputState>,
+    name_input: Entity<InputState>,
+    value_input: Entity<InputState>,
+    /// A copy of the unlocked store, for display.
+    store: Option<SecretStore>,
+    busy: bool,
+    error: Option<String>,
+}
+
+impl SecretsForm {
+    pub fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
+        let passphrase_input =
+            cx.new(|cx| InputState::new(window, cx).placeholder("Passphrase").masked(true));
+        let name_input = cx.new(|cx| InputState::new(window, cx).placeholder("NAME"));
+        let value_input =
+            cx.new(|cx| InputState::new(window, cx).placeholder("Value").masked(true));
+        Self {
+            passphrase_input,
+            name_input,
+            value_input,
+            store: secrets::unlocked(),
+            busy: false,
+            error: None,
+        }
+    }
+
+    /// Open the existing file, or create an empty one with this passphrase.
+    fn unlock(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let passphrase = self.passphrase_input.read(cx).value().to_string();
+        if passphrase.is_empty() {
+            self.error = Some("Enter a passphrase.".into());
+            cx.notify();
+            return;
+        }
+        self.busy = true;
+        self.error = None;
+        cx.notify();
+
+        // Key derivation is deliberately slow; keep it off the UI thread.
+        let task = cx.background_executor().spawn(async move {
+            let path = secrets::secrets_path();
+            let store = if path.exists() {
+                SecretStore::load(&path, &passphrase)?
+            } else {
+                let store = SecretStore::default();
+                store.save(&path, &passphrase)?;
+                store
+            };
+            secrets::unlock(store.clone(), passphrase);
+            anyhow::Ok(store)
+        });
+        cx.spawn_in(window, async move |this, cx| {
+            let result = task.await;
+            this.update_in(cx, |this, window, cx| {
+                this.busy = false;
+                match result {
+                    Ok(store) => {
+                        this.store = Some(store);
+                        this.passphrase_input
+                            .update(cx, |input, cx| input.set_value("", window, cx));
+                    }
+                    Err(e) => this.error = Some(e.to_string()),
+                }
+                cx.notify();
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    fn lock(&mut self, cx: &mut Context<Self>) {
+        secrets::lock();
+        self.store = None;
+        cx.notify();
+    }
+
+    fn update_store(&mut self, update: impl FnOnce(&mut SecretStore), cx: &mut Context<Self>) {
+        let Some(mut store) = self.store.clone() else {
+            return;
+        };
+        update(&mut store);
+        self.busy = true;
+        cx.notify();
+
+        let task = cx.background_executor().spawn({
+            let store = store.clone();
+            async move { secrets::save_unlocked(store) }
+        });
+        cx.spawn(async move |this, cx| {
+            let result = task.await;
+            this.update(cx, |this, cx| {
+                this.busy = false;
+                match result {
+                    Ok(()) => {
+                        this.store = Some(store);
+                        this.error = None;
+                    }
+                    Err(e) => this.error = Some(e.to_string()),
+                }
+                cx.notify();
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    fn add(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let name = self.name_input.read(cx).value().trim().to_string();
+        let value = self.value_input.read(cx).value().to_string();
+        if !secrets::is_valid_name(&name) {
+            self.error = Some("Names may use letters, digits, '_', '.' and '-'.".into());
+            cx.notify();
+            return;
+        }
+        self.name_input.update(cx, |input, cx| input.set_value("", window, cx));
+        self.value_input.update(cx, |input, cx| input.set_value("", window, cx));
+        self.update_store(move |store| store.set(name, value), cx);
+    }
+
+    fn render_locked(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let exists = secrets::secrets_path().exists();
+        v_flex()
+            .gap_2()
+            .child(div().text_sm().text_color(cx.theme().muted_foreground).child(if exists {
+                "Enter the passphrase to unlock the secrets file for this session."
+            } else {
+                "No secrets file yet. Choose a passphrase to create one."
+            }))
+            .child(Input::new(&self.passphrase_input).mask_toggle())
+            .child(
+                h_flex().justify_end().child(
+                    Button::new("unlock-secrets")
+                        .primary()
+                        .label(if exists { "Unlock" } else { "Create" })
+                        .loading(self.busy)
+                        .disabled(self.busy)
+                        .on_click(cx.listener(|this, _, window, cx| this.unlock(window, cx))),
+                ),
+            )
+    }
+
+    fn render_unlocked(&self, store: &SecretStore, cx: &mut Context<Self>) -> impl IntoElement {
+        let names: Vec<String> = store.names().map(str::to_string).collect();
+        v_flex()
+            .gap_2()
+            .when(names.is_empty(), |el| {
+                el.child(
+                    div()
+                        .text_sm()
+                        .text_color(cx.theme().muted_foreground)
+                        .child("No secrets yet."),
+                )
+            })
+            .children(names.into_iter().enumerate().map(|(ix, name)| {
+                let remove = name.clone();
+                h_flex()
+                    .gap_2()
+                    .py_1()
+                    .border_b_1()
+                    .border_color(cx.theme().border)
+                    .child(
+                        div()
+                            .flex_1()
+                            .text_sm()
+                            .font_family("monospace")
+                            .child(format!("${{secret:{}}}", name)),
+                    )
+                    .child(div().text_sm().text_color(cx.theme().muted_foreground).child(MASK))
+                    .child(
+                        Button::new(("remove-secret", ix))
+                            .xsmall()
+                            .ghost()
+                            .icon(IconName::Delete)
+                            .disabled(self.busy)
+                            .on_click(cx.listener(move |this, _, _, cx| {
+                                let name = remove.clone();
+                                this.update_store(move |store| store.remove(&name), cx)
+                            })),
+                    )
+            }))
+            .child(
+                h_flex()
+                    .gap_2()
+                    .child(div().w(px(140.)).child(Input::new(&self.name_input).small()))
+                    .child(
+                        div().flex_1().child(Input::new(&self.value_input).small().mask_toggle()),
+                    )
+                    .child(
+                        Button::new("add-secret")
+                            .small()
+                            .outline()
+                            .label("Add")
+                            .disabled(self.busy)
+                            .on_click(cx.listener(|this, _, window, cx| this.add(window, cx))),
+                    ),
+            )
+            .child(
+                h_flex().justify_end().child(
+                    Button::new("lock-secrets")
+                        .small()
+                        .label("Lock")
+                        .on_click(cx.listener(|this, _, _, cx| this.lock(cx))),
+                ),
+            )
+    }
+}
+
+impl Render for SecretsForm {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let body = match self.store.clone() {
+            Some(store) => self.render_unlocked(&store, cx).into_any_element(),
+            None => self.render_locked(cx).into_any_element(),
+        };
+        v_flex()
+            .id("secrets-form")
+            .size_full()
+            .overflow_y_scroll()
+            .gap_3()
+            .p_4()
+            .child(
+                div()
+                    .text_xs()
+                    .text_color(cx.theme().muted_foreground)
+                    .child(secrets::secrets_path().display().to_string()),
+            )
+            .when_some(self.error.clone(), |el, error| {
+                el.child(div().text_sm().text_color(cx.theme().danger).child(error))
+            })
+            .child(body)
+    }
+}
@@ -0,0 +1,591 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use gpui::{prelude::*, *};
+use gpui_component::{
+    button::{Button, ButtonVariants as _},
+    h_flex,
+    input::{Input, InputState},
+    radio::RadioGroup,
+    v_flex, ActiveTheme as _, Disableable as _, IconName, Sizable as _, StyledExt as _,
+    WindowExt as _,
+};
+
+use crate::{
+    client::AcpConnection,
+    models::{
+        expand_home, parse_env, AgentConfig, AgentId, AgentRegistry, AgentStatus, McpCapabilities,
+        McpKind, McpServer, McpTransport, SessionPreset,
+    },
+};
+
+/// Creates a session on an agent's connection, choosing its cwd and the MCP
+/// servers passed in `session/new`, and saves these as per-agent presets.
+pub struct SessionForm {
+    agent_id: AgentId,
+    registry: Entity<AgentRegistry>,
+    connection: Entity<AcpConnection>,
+    cwd_input: Entity<InputState>,
+    preset_name_input: Entity<InputState>,
+    servers: Vec<McpServer>,
+    server_kind: McpKind,
+    server_name_input: Entity<InputState>,
+    /// The command for stdio servers, the URL otherwise.
+    server_target_input: Entity<InputState>,
+    server_args_input: Entity<InputState>,
+    /// Env for stdio servers, HTTP headers otherwise, as `KEY=VALUE` lines.
+    server_pairs_input: Entity<InputState>,
+    busy: bool,
+    error: Option<SharedString>,
+    _subscriptions: Vec<Subscription>,
+}
+
+impl SessionForm {
+    /// Open the form in a sheet for `config`'s connection.
+    pub fn open(
+        config: &AgentConfig,
+        registry: Entity<AgentRegistry>,
+        connection: Entity<AcpConnection>,
+        window: &mut Window,
+        cx: &mut App,
+    ) {
+        let title = format!("New Session: {}", config.name);
+        let form = cx.new(|cx| Self::new(config, registry, connection, window, cx));
+        window.open_sheet(cx, move |sheet, _, _| {
+            sheet.size(px(500.)).title(title.clone()).child(form.clone())
+        });
+    }
+
+    fn new(
+        config: &AgentConfig,
+        registry: Entity<AgentRegistry>,
+        connection: Entity<AcpConnection>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let cwd = config
+            .launch
+            .cwd()
+            .or_else(|| std::env::current_dir().ok())
+            .map(|cwd| cwd.display().to_string())
+            .unwrap_or_default();
+        let cwd_input = cx.new(|cx| {
+            InputState::new(window, cx).placeholder("/path/to/project").default_value(cwd)
+        });
+        let preset_name_input = cx.new(|cx| InputState::new(window, cx).placeholder("Preset name"));
+        let server_name_input = cx.new(|cx| InputState::new(window, cx).placeholder("Name"));
+        let server_target_input = cx.new(|cx| InputState::new(window, cx));
+        let server_args_input = cx.new(|cx| {
+            InputState::new(window, cx)
+                .placeholder("Arguments, one per line")
+                .multi_line(true)
+                .auto_grow(1, 4)
+        });
+        let server_pairs_input = cx.new(|cx| {
+            InputState::new(window, cx)
+                .placeholder("KEY=VALUE (one per line)")
+                .multi_line(true)
+                .auto_grow(1, 4)
+        });
+
+        let mut form = Self {
+            agent_id: config.id.clone(),
+            registry,
+            connection: connection.clone(),
+            cwd_input,
+            preset_name_input,
+            servers: Vec::new(),
+            server_kind: McpKind::Stdio,
+            server_name_input,
+            server_target_input,
+            server_args_input,
+            server_pairs_input,
+            busy: false,
+            error: None,
+            // Capabilities arrive with the handshake.
+            _subscriptions: vec![cx.observe(&connection, |_, _, cx| cx.notify())],
+        };
+        form.set_server_kind(McpKind::Stdio, window, cx);
+        form
+    }
+
+    fn fail(&mut self, message: impl Into<SharedString>, cx: &mut Context<Self>) {
+        self.error = Some(message.into());
+        cx.notify();
+    }
+
+    fn capabilities(&self, cx: &App) -> McpCapabilities {
+        McpCapabilities::from_initialize(self.connection.read(cx).initialize_result())
+    }
+
+    fn presets(&self, cx: &App) -> Vec<SessionPreset> {
+        self.registry
+            .read(cx)
+            .agents()
+            .iter()
+            .find(|agent| agent.id == self.agent_id)
+            .map(|agent| agent.session_presets.clone())
+            .unwrap_or_default()
+    }
+
+    fn set_presets(&mut self, presets: Vec<SessionPreset>, cx: &mut Context<Self>) {
+        let agent_id = self.agent_id.clone();
+        self.registry.update(cx, |reg, cx| {
+            if let Some(agent) = reg.agents().iter().find(|agent| agent.id == agent_id) {
+                let config = AgentConfig { session_presets: presets, ..agent.clone() };
+                reg.update_agent(config);
+                cx.notify();
+            }
+        });
+        cx.notify();
+    }
+
+    fn apply_preset(&mut self, preset: SessionPreset, window: &mut Window, cx: &mut Context<Self>) {
+        self.cwd_input.update(cx, |input, cx| input.set_value(preset.cwd, window, cx));
+        self.preset_name_input.update(cx, |input, cx| input.set_value(preset.name, window, cx));
+        self.servers = preset.mcp_servers;
+        self.error = None;
+        cx.notify();
+    }
+
+    /// Save the form as a preset, replacing one with the same name.
+    fn save_preset(&mut self, cx: &mut Context<Self>) {
+        let name = self.preset_name_input.read(cx).value().trim().to_string();
+        if name.is_empty() {
+            return self.fail("Enter a name for the preset", cx);
+        }
+        let preset = SessionPreset {
+            name,
+            cwd: self.cwd_input.read(cx).value().trim().to_string(),
+            mcp_servers: self.servers.clone(),
+        };
+        let mut presets = self.presets(cx);
+        match presets.iter_mut().find(|p| p.name == preset.name) {
+            Some(existing) => *existing = preset,
+            None => presets.push(preset),
+        }
+        self.error = None;
+        self.set_presets(presets, cx);
+    }
+
+    fn delete_preset(&mut self, ix: usize, cx: &mut Context<Self>) {
+        let mut presets = self.presets(cx);
+        if ix < presets.len() {
+            presets.remove(ix);
+            self.set_presets(presets, cx);
+        }
+    }
+
+    fn set_server_kind(&mut self, kind: McpKind, window: &mut Window, cx: &mut Context<Self>) {
+        self.server_kind = kind;
+        let (target, pairs) = match kind {
+            McpKind::Stdio => ("Command", "Environment: KEY=VALUE (one per line)"),
+            McpKind::Http | McpKind::Sse => ("https://example.com/mcp", "Headers: KEY=VALUE"),
+        };
+        self.server_target_input.update(cx, |input, cx| input.set_placeholder(target, window, cx));
+        self.server_pairs_input.update(cx, |input, cx| input.set_placeholder(pairs, window, cx));
+        cx.notify();
+    }
+
+    /// The server described by the editor.
+    fn editor_server(&self, cx: &App) -> Result<McpServer, String> {
+        let name = self.server_name_input.read(cx).value().trim().to_string();
+        let target = self.server_target_input.read(cx).value().trim().to_string();
+        let pairs = parse_env(&self.server_pairs_input.read(cx).value())?;
+        let transport = match self.server_kind {
+            McpKind::Stdio => McpTransport::Stdio {
+                command: target,
+                args: self
+                    .server_args_input
+                    .read(cx)
+                    .value()
+                    .lines()
+                    .map(|l| l.trim().to_string())
+                    .filter(|l| !l.is_empty())
+                    .collect(),
+                env: pairs,
+            },
+            McpKind::Http => McpTransport::Http { url: target, headers: pairs },
+            McpKind::Sse => McpTransport::Sse { url: target, headers: pairs },
+        };
+        let server = McpServer { name, transport };
+        server.validate()?;
+        if self.servers.iter().any(|s| s.name == server.name) {
+            return Err(format!("An MCP server named {} is already listed", server.name));
+        }
+        Ok(server)
+    }
+
+    fn add_server(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let server = match self.editor_server(cx) {
+            Ok(server) => server,
+            Err(e) => return self.fail(e, cx),
+        };
+        self.servers.push(server);
+        for input in [
+            &self.server_name_input,
+            &self.server_target_input,
+            &self.server_args_input,
+            &self.server_pairs_input,
+        ] {
+            input.update(cx, |input, cx| input.set_value("", window, cx));
+        }
+        self.error = None;
+        cx.notify();
+    }
+
+    /// Move a listed server back into the editor.
+    fn edit_server(&mut self, ix: usize, window: &mut Window, cx: &mut Context<Self>) {
+        if ix >= self.servers.len() {
+            return;
+        }
+        let server = self.servers.remove(ix);
+        let join = |pairs: &[(String, String)]| {
+            pairs.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join("\n")
+        };
+        let (target, args, pairs) = match &server.transport {
+            McpTransport::Stdio { command, args, env } => {
+                (command.clone(), args.join("\n"), join(env))
+            }
+            McpTransport::Http { url, headers } | McpTransport::Sse { url, headers } => {
+                (url.clone(), String::new(), join(headers))
+            }
+        };
+        self.set_server_kind(server.kind(), window, cx);
+        for (input, value) in [
+            (&self.server_name_input, server.name.clone()),
+            (&self.server_target_input, target),
+            (&self.server_args_input, args),
+            (&self.server_pairs_input, pairs),
+        ] {
+            input.update(cx, |input, cx| input.set_value(value, window, cx));
+        }
+        cx.notify();
+    }
+
+    fn remove_server(&mut self, ix: usize, cx: &mut Context<Self>) {
+        if ix < self.servers.len() {
+            self.servers.remove(ix);
+            cx.notify();
+        }
+    }
+
+    fn browse_cwd(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let paths = cx.prompt_for_paths(PathPromptOptions {
+            files: false,
+            directories: true,
+            multiple: false,
+            prompt: Some("Session Directory".into()),
+        });
+        cx.spawn_in(window, async move |this, cx| {
+            let Ok(Ok(Some(paths))) = paths.await else {
+                return;
+            };
+            let Some(path) = paths.into_iter().next() else {
+                return;
+            };
+            this.update_in(cx, |this, window, cx| {
+                this.cwd_input.update(cx, |input, cx| {
+                    input.set_value(path.display().to_string(), window, cx)
+                });
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// The absolute cwd and MCP servers, or the first problem.
+    fn session_params(&self, cx: &App) -> Result<(String, Vec<McpServer>), String> {
+        let cwd = self.cwd_input.read(cx).value().trim().to_string();
+        if cwd.is_empty() {
+            return Err("Working directory is required".into());
+        }
+        let cwd = expand_home(&cwd);
+        if !cwd.is_absolute() {
+            return Err("The working directory must be an absolute path".into());
+        }
+        if !cwd.is_dir() {
+            return Err(format!("Working directory not found: {}", cwd.display()));
+        }
+        let capabilities = self.capabilities(cx);
+        for server in &self.servers {
+            server.validate()?;
+            if !capabilities.supports(server.kind()) {
+                return Err(format!(
+                    "{}: the agent does not support {} MCP servers",
+                    server.name,
+                    server.kind().label()
+                ));
+            }
+        }
+        Ok((cwd.display().to_string(), self.servers.clone()))
+    }
+
+    fn create(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let (cwd, servers) = match self.session_params(cx) {
+            Ok(params) => params,
+            Err(e) => return self.fail(e, cx),
+        };
+        let task = self.connection.update(cx, |conn, cx| conn.new_session(&cwd, servers, cx));
+        self.busy = true;
+        self.error = None;
+        cx.notify();
+        cx.spawn_in(window, async move |this, cx| {
+            let result = task.await;
+            this.update_in(cx, |this, window, cx| {
+                this.busy = false;
+                match result {
+                    Ok(_) => window.close_sheet(cx),
+                    Err(e) => this.fail(format!("session/new failed: {}", e), cx),
+                }
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    fn render_presets(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let presets = self.presets(cx);
+        v_flex()
+            .gap_1()
+            .child(field_label("Presets", cx))
+            .when(presets.is_empty(), |el| {
+                el.child(
+                    div()
+                        .text_xs()
+                        .text_color(cx.theme().muted_foreground)
+                        .child("Save the directory and MCP servers below to reuse them."),
+                )
+            })
+            .child(h_flex().flex_wrap().gap_1().children(presets.into_iter().enumerate().map(
+                |(ix, preset)| {
+                    h_flex()
+                        .child(
+                            Button::new(("apply-preset", ix))
+                                .xsmall()
+                                .outline()
+                                .label(preset.name.clone())
+                                .on_click(cx.listener(move |this, _, window, cx| {
+                                    this.apply_preset(preset.clone(), window, cx)
+                                })),
+                        )
+                        .child(
+                            Button::new(("delete-preset", ix))
+                                .xsmall()
+                                .ghost()
+                                .icon(IconName::Close)
+                                .on_click(
+                                    cx.listener(move |this, _, _, cx| this.delete_preset(ix, cx)),
+                                ),
+                        )
+                },
+            )))
+            .child(
+                h_flex()
+                    .gap_2()
+                    .child(div().flex_1().child(Input::new(&self.preset_name_input)))
+                    .child(
+                        Button::new("save-preset")
+                            .label("Save Preset")
+                            .on_click(cx.listener(|this, _, _, cx| this.save_preset(cx))),
+                    ),
+            )
+    }
+
+    fn render_servers(
+        &self,
+        capabilities: McpCapabilities,
+        cx: &mut Context<Self>,
+    ) -> impl IntoElement {
+        let kinds: Vec<McpKind> =
+            McpKind::ALL.into_iter().filter(|kind| capabilities.supports(*kind)).collect();
+        let selected = kinds.iter().position(|kind| *kind == self.server_kind);
+        let entity = cx.entity().clone();
+        let muted = cx.theme().muted_foreground;
+
+        v_flex()
+            .gap_1()
+            .child(field_label("MCP Servers", cx))
+            .children(self.servers.iter().enumerate().map(|(ix, server)| {
+                let supported = capabilities.supports(server.kind());
+                h_flex()
+                    .gap_2()
+                    .px_2()
+                    .py_1()
+                    .rounded_md()
+                    .border_1()
+                    .border_color(cx.theme().border)
+                    .child(
+                        v_flex()
+                            .flex_1()
+                            .min_w_0()
+                            .child(
+                                h_flex()
+                                    .gap_2()
+                                    .text_sm()
+                                    .child(div().font_semibold().child(server.name.clone()))
+                                    .child(
+                                        div()
+                                            .text_xs()
+                                            .text_color(if supported {
+                                                muted
+                                            } else {
+                                                cx.theme().danger
+                                            })
+                                            .child(if supported {
+                                                server.kind().label().to_string()
+                                            } else {
+                                                format!("{} (not supported)", server.kind().label())
+                                            }),
+                                    ),
+                            )
+                            .child(
+                                div()
+                                    .text_xs()
+                                    .text_color(muted)
+                                    .font_family("monospace")
+                                    .truncate()
+                                    .child(server.target()),
+                            ),
+                    )
+                    .child(
+                        Button::new(("edit-server", ix)).xsmall().ghost().label("Edit").on_click(
+                            cx.listener(move |this, _, window, cx| {
+                                this.edit_server(ix, window, cx)
+                            }),
+                        ),
+                    )
+                    .child(
+                        Button::new(("remove-server", ix))
+                            .xsmall()
+                            .ghost()
+                            .icon(IconName::Delete)
+                            .on_click(
+                                cx.listener(move |this, _, _, cx| this.remove_server(ix, cx)),
+                            ),
+                    )
+            }))
+            .child(
+                v_flex()
+                    .gap_2()
+                    .p_2()
+                    .rounded_md()
+                    .bg(cx.theme().muted)
+                    .child(
+                        kinds
+                            .iter()
+                            .fold(
+                                RadioGroup::horizontal("mcp-kind").selected_index(selected),
+                                |group, kind| group.child(kind.label()),
+                            )
+                            .on_click(move |ix, window, cx| {
+                                if let Some(kind) = kinds.get(*ix).copied() {
+                                    entity.update(cx, |this, cx| {
+                                        this.set_server_kind(kind, window, cx)
+                                    });
+                                }
+                            }),
+                    )
+                    .when(!capabilities.http || !capabilities.sse, |el| {
+                        el.child(
+                            div()
+                                .text_xs()
+                                .text_color(muted)
+                                .child("HTTP and SSE need the agent's mcpCapabilities."),
+                        )
+                    })
+                    .child(Input::new(&self.server_name_input))
+                    .child(Input::new(&self.server_target_input))
+                    .when(self.server_kind == McpKind::Stdio, |el| {
+                        el.child(Input::new(&self.server_args_input))
+                    })
+                    .child(Input::new(&self.server_pairs_input))
+                    .child(div().text_xs().text_color(cx.theme().muted_foreground).child(
+                        "Keep tokens out of presets with ${env:NAME} or ${secret:NAME}; \
+                         they are resolved when the session is created.",
+                    ))
+                    .child(
+                        h_flex().justify_end().child(
+                            Button::new("add-server")
+                                .small()
+                                .icon(IconName::Plus)
+                                .label("Add Server")
+                                .on_click(
+                                    cx.listener(|this, _, window, cx| this.add_server(window, cx)),
+                                ),
+                        ),
+                    ),
+            )
+    }
+}
+
+impl Render for SessionForm {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let connected = self.connection.read(cx).status() == AgentStatus::Connected;
+        let capabilities = self.capabilities(cx);
+
+        v_flex()
+            .id("session-form")
+            .size_full()
+            .overflow_y_scroll()
+            .gap_4()
+            .p_4()
+            .child(self.render_presets(cx))
+            // Working directory
+            .child(
+                v_flex().gap_1().child(field_label("Working Directory", cx)).child(
+                    h_flex()
+                        .gap_2()
+                        .child(div().flex_1().child(Input::new(&self.cwd_input)))
+                        .child(Button::new("browse-session-cwd").icon(IconName::Folder).on_click(
+                            cx.listener(|this, _, window, cx| this.browse_cwd(window, cx)),
+                        )),
+                ),
+            )
+            .child(self.render_servers(capabilities, cx))
+            .when(!connected, |el| {
+                el.child(
+                    div()
+                        .text_sm()
+                        .text_color(cx.theme().muted_foreground)
+                        .child("Connect the agent to create a session."),
+                )
+            })
+            .when_some(self.error.clone(), |el, msg| {
+                el.child(div().text_sm().text_color(cx.theme().danger).child(msg))
+            })
+            .child(
+                h_flex()
+                    .justify_end()
+                    .gap_2()
+                    .child(Button::new("cancel").label("Cancel").on_click(cx.listener(
+                        |_, _, window, cx| {
+                            window.close_sheet(cx);
+                        },
+                    )))
+                    .child(
+                        Button::new("create-session")
+                            .primary()
+                            .label("Create Session")
+                            .loading(self.busy)
+                            .disabled(!connected || self.busy)
+                            .on_click(cx.listener(|this, _, window, cx| this.create(window, cx))),
+                    ),
+            )
+    }
+}
+
+fn field_label(text: &str, cx: &Context<SessionForm>) -> impl IntoElement {
+    div().text_sm().font_semibold().text_color(cx.theme().foreground).child(text.to_string())
+}
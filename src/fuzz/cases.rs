@@ -0,0 +1,475 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Fuzz case generation: mutations of valid ACP requests.
+
+use serde_json::{json, Map, Value};
+
+/// JSON-RPC 2.0 error codes.
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+
+/// Length of the strings used by the huge-string mutation.
+pub const HUGE_STRING_LEN: usize = 1024 * 1024;
+
+/// Id used for the mutated request.
+pub const FUZZ_ID: u64 = 1000;
+
+/// Placeholder replaced with the session created by [`Prelude::Session`].
+pub const SESSION_PLACEHOLDER: &str = "$session";
+
+/// Valid requests sent before the payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Prelude {
+    /// Send the payload to a fresh process.
+    None,
+    /// Complete `initialize` first.
+    Initialize,
+    /// Complete `initialize` and `session/new` first.
+    Session,
+}
+
+/// One line written to the agent's stdin.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Payload {
+    Json(Value),
+    /// Bytes that are not a valid JSON document.
+    Raw(Vec<u8>),
+}
+
+impl Payload {
+    /// The id the agent should answer with, if a response is expected.
+    ///
+    /// Unparseable lines, and ids that are not a string, number or null,
+    /// must be answered with a `null` id.
+    pub fn response_id(&self) -> Option<Value> {
+        match self {
+            Payload::Json(value) => match value.get("id") {
+                Some(id) if value.get("method").is_some() => match id {
+                    Value::String(_) | Value::Number(_) | Value::Null => Some(id.clone()),
+                    _ => Some(Value::Null),
+                },
+                _ => None,
+            },
+            Payload::Raw(_) => Some(Value::Null),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Payload::Json(value) => serde_json::to_vec(value).unwrap_or_default(),
+            Payload::Raw(bytes) => bytes.clone(),
+        }
+    }
+}
+
+/// What the agent may answer to the payload.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expected {
+    /// An error with one of the listed codes.
+    Error(Vec<i64>),
+    /// Any error, the code is implementation defined.
+    AnyError,
+    /// A result or an error; only crashes and hangs are findings.
+    Anything,
+}
+
+#[derive(Debug, Clone)]
+pub struct FuzzCase {
+    pub name: String,
+    pub prelude: Prelude,
+    pub payloads: Vec<Payload>,
+    pub expected: Expected,
+}
+
+impl FuzzCase {
+    fn new(
+        name: impl Into<String>,
+        prelude: Prelude,
+        payload: Payload,
+        expected: Expected,
+    ) -> Self {
+        Self { name: name.into(), prelude, payloads: vec![payload], expected }
+    }
+}
+
+/// A valid request that the mutations start from.
+struct Template {
+    method: &'static str,
+    prelude: Prelude,
+    params: Value,
+}
+
+fn templates(cwd: &str) -> Vec<Template> {
+    vec![
+        Template {
+            method: "initialize",
+            prelude: Prelude::None,
+            params: json!({
+                "protocolVersion": 1,
+                "clientCapabilities": {
+                    "fs": { "readTextFile": false, "writeTextFile": false },
+                    "terminal": false,
+                },
+            }),
+        },
+        Template {
+            method: "session/new",
+            prelude: Prelude::Initialize,
+            params: json!({ "cwd": cwd, "mcpServers": [] }),
+        },
+        Template {
+            method: "session/load",
+            prelude: Prelude::Initialize,
+            params: json!({ "sessionId": "fuzz-unknown", "cwd": cwd, "mcpServers": [] }),
+        },
+        Template {
+            method: "session/prompt",
+            prelude: Prelude::Session,
+            params: json!({
+                "sessionId": SESSION_PLACEHOLDER,
+                "prompt": [{ "type": "text", "text": "hi" }],
+            }),
+        },
+    ]
+}
+
+pub fn request(id: Value, method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+/// A value of a different JSON type than `value`.
+fn wrong_type(value: &Value) -> Value {
+    match value {
+        Value::String(_) => json!(42),
+        Value::Number(_) => json!("42"),
+        Value::Bool(_) => json!("true"),
+        Value::Array(_) => json!({}),
+        Value::Object(_) => json!([]),
+        Value::Null => json!(0),
+    }
+}
+
+fn with_param(params: &Value, key: &str, value: Option<Value>) -> Value {
+    let mut map: Map<String, Value> = params.as_object().cloned().unwrap_or_default();
+    match value {
+        Some(value) => map.insert(key.to_string(), value),
+        None => map.remove(key),
+    };
+    Value::Object(map)
+}
+
+/// Every fuzz case, in a stable order.
+pub fn generate(cwd: &str) -> Vec<FuzzCase> {
+    let id = json!(FUZZ_ID);
+    let mut cases = Vec::new();
+
+    for template in templates(cwd) {
+        let Template { method, prelude, params } = template;
+        let keys: Vec<String> =
+            params.as_object().map(|m| m.keys().cloned().collect()).unwrap_or_default();
+
+        cases.push(FuzzCase::new(
+            format!("{} without params", method),
+            prelude,
+            Payload::Json(json!({ "jsonrpc": "2.0", "id": id, "method": method })),
+            Expected::Error(vec![INVALID_PARAMS]),
+        ));
+        for key in &keys {
+            cases.push(FuzzCase::new(
+                format!("{} missing `{}`", method, key),
+                prelude,
+                Payload::Json(request(id.clone(), method, with_param(&params, key, None))),
+                Expected::Error(vec![INVALID_PARAMS]),
+            ));
+            cases.push(FuzzCase::new(
+                format!("{} with wrong type for `{}`", method, key),
+                prelude,
+                Payload::Json(request(
+                    id.clone(),
+                    method,
+                    with_param(&params, key, Some(wrong_type(&params[key]))),
+                )),
+                Expected::Error(vec![INVALID_PARAMS]),
+            ));
+            if params[key].is_string() {
+                let huge = "A".repeat(HUGE_STRING_LEN);
+                cases.push(FuzzCase::new(
+                    format!("{} with huge `{}`", method, key),
+                    prelude,
+                    Payload::Json(request(
+                        id.clone(),
+                        method,
+                        with_param(&params, key, Some(json!(huge))),
+                    )),
+                    Expected::Anything,
+                ));
+            }
+        }
+        cases.push(FuzzCase::new(
+            format!("{} with params as array", method),
+            prelude,
+            Payload::Json(request(id.clone(), method, json!([params]))),
+            Expected::Error(vec![INVALID_PARAMS]),
+        ));
+    }
+
+    let ping = || request(json!(FUZZ_ID), "fuzz/unknownMethod", json!({}));
+    cases.push(FuzzCase::new(
+        "unknown method",
+        Prelude::Initialize,
+        Payload::Json(ping()),
+        Expected::Error(vec![METHOD_NOT_FOUND]),
+    ));
+    cases.push(FuzzCase::new(
+        "empty method name",
+        Prelude::Initialize,
+        Payload::Json(request(id.clone(), "", json!({}))),
+        Expected::Error(vec![METHOD_NOT_FOUND, INVALID_REQUEST]),
+    ));
+    cases.push(FuzzCase::new(
+        "unknown method before initialize",
+        Prelude::None,
+        Payload::Json(ping()),
+        Expected::AnyError,
+    ));
+
+    // Envelope mutations.
+    let mut envelope = |name: &str, mutate: fn(&mut Map<String, Value>)| {
+        let mut message = ping().as_object().cloned().unwrap_or_default();
+        mutate(&mut message);
+        cases.push(FuzzCase::new(
+            name,
+            Prelude::Initialize,
+            Payload::Json(Value::Object(message)),
+            Expected::Error(vec![INVALID_REQUEST, METHOD_NOT_FOUND]),
+        ));
+    };
+    envelope("request without jsonrpc", |m| {
+        m.remove("jsonrpc");
+    });
+    envelope("request with jsonrpc 1.0", |m| {
+        m.insert("jsonrpc".into(), json!("1.0"));
+    });
+    envelope("request with object id", |m| {
+        m.insert("id".into(), json!({ "nested": true }));
+    });
+    envelope("request with numeric method", |m| {
+        m.insert("method".into(), json!(42));
+    });
+
+    // Unparseable lines.
+    let valid = serde_json::to_vec(&ping()).unwrap_or_default();
+    cases.push(FuzzCase::new(
+        "truncated JSON",
+        Prelude::Initialize,
+        Payload::Raw(valid[..valid.len() / 2].to_vec()),
+        Expected::Error(vec![PARSE_ERROR]),
+    ));
+    cases.push(FuzzCase::new(
+        "non-JSON text",
+        Prelude::Initialize,
+        Payload::Raw(b"hello agent".to_vec()),
+        Expected::Error(vec![PARSE_ERROR]),
+    ));
+    cases.push(FuzzCase::new(
+        "invalid UTF-8",
+        Prelude::Initialize,
+        Payload::Raw(invalid_utf8(&ping())),
+        Expected::Error(vec![PARSE_ERROR]),
+    ));
+    cases.push(FuzzCase::new(
+        "JSON array batch",
+        Prelude::Initialize,
+        Payload::Raw(b"[]".to_vec()),
+        Expected::Error(vec![INVALID_REQUEST]),
+    ));
+
+    // Duplicate ids: both requests must be answered without crashing.
+    cases.push(FuzzCase {
+        name: "duplicate request ids".into(),
+        prelude: Prelude::Initialize,
+        payloads: vec![
+            Payload::Json(request(
+                id.clone(),
+                "session/new",
+                json!({ "cwd": cwd, "mcpServers": [] }),
+            )),
+            Payload::Json(request(
+                id.clone(),
+                "session/new",
+                json!({ "cwd": cwd, "mcpServers": [] }),
+            )),
+        ],
+        expected: Expected::Anything,
+    });
+
+    // Out-of-order session calls.
+    let prompt = |session_id: &str| {
+        request(
+            id.clone(),
+            "session/prompt",
+            json!({ "sessionId": session_id, "prompt": [{ "type": "text", "text": "hi" }] }),
+        )
+    };
+    cases.push(FuzzCase::new(
+        "session/new before initialize",
+        Prelude::None,
+        Payload::Json(request(id.clone(), "session/new", json!({ "cwd": cwd, "mcpServers": [] }))),
+        Expected::Anything,
+    ));
+    cases.push(FuzzCase::new(
+        "session/prompt before initialize",
+        Prelude::None,
+        Payload::Json(prompt("fuzz-unknown")),
+        Expected::AnyError,
+    ));
+    cases.push(FuzzCase::new(
+        "session/prompt for unknown session",
+        Prelude::Initialize,
+        Payload::Json(prompt("fuzz-unknown")),
+        Expected::AnyError,
+    ));
+    cases.push(FuzzCase::new(
+        "session/cancel for unknown session",
+        Prelude::Initialize,
+        Payload::Json(json!({
+            "jsonrpc": "2.0",
+            "method": "session/cancel",
+            "params": { "sessionId": "fuzz-unknown" },
+        })),
+        Expected::Anything,
+    ));
+
+    cases
+}
+
+/// `message` serialized with an invalid UTF-8 sequence inside the method.
+fn invalid_utf8(message: &Value) -> Vec<u8> {
+    let text = serde_json::to_string(message).unwrap_or_default();
+    let mut bytes = Vec::with_capacity(text.len() + 2);
+    match text.find("fuzz/") {
+        Some(at) => {
+            bytes.extend_from_slice(&text.as_bytes()[..at]);
+            bytes.extend_from_slice(&[0xff, 0xfe]);
+            bytes.extend_from_slice(&text.as_bytes()[at..]);
+        }
+        None => bytes.extend_from_slice(text.as_bytes()),
+    }
+    bytes
+}
+
+/// Check a response against the JSON-RPC spec and the case's expectation.
+pub fn check_response(response: &Value, expected: &Expected) -> Result<(), String> {
+    if response.get("jsonrpc").and_then(|v| v.as_str()) != Some("2.0") {
+        return Err("response without jsonrpc \"2.0\"".into());
+    }
+    let error = match (response.get("result"), response.get("error")) {
+        (Some(_), Some(_)) => return Err("response has both result and error".into()),
+        (None, None) => return Err("response has neither result nor error".into()),
+        (Some(_), None) => None,
+        (None, Some(error)) => Some(error),
+    };
+
+    let code = match error {
+        Some(error) => {
+            let code = error.get("code").and_then(|v| v.as_i64());
+            let message = error.get("message").and_then(|v| v.as_str());
+            match (code, message) {
+                (Some(code), Some(_)) => Some(code),
+                _ => return Err(format!("malformed error object {}", error)),
+            }
+        }
+        None => None,
+    };
+
+    match (expected, code) {
+        (Expected::Anything, _) => Ok(()),
+        (Expected::AnyError, Some(_)) => Ok(()),
+        (Expected::Error(codes), Some(code)) if codes.contains(&code) => Ok(()),
+        (Expected::Error(codes), Some(code)) => {
+            Err(format!("expected error code {:?}, got {}", codes, code))
+        }
+        (_, None) => Err("accepted an invalid request".into()),
+    }
+}
+
+/// Render bytes that may not be UTF-8, escaping invalid sequences as `\xNN`.
+pub fn escape_bytes(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for chunk in bytes.utf8_chunks() {
+        out.push_str(chunk.valid());
+        for byte in chunk.invalid() {
+            out.push_str(&format!("\\x{:02x}", byte));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_covers_each_mutation_kind() {
+        let cases = generate("/tmp");
+        let names: Vec<&str> = cases.iter().map(|c| c.name.as_str()).collect();
+        for expected in [
+            "session/new missing `cwd`",
+            "initialize with wrong type for `protocolVersion`",
+            "session/new with huge `cwd`",
+            "unknown method",
+            "invalid UTF-8",
+            "duplicate request ids",
+            "session/prompt before initialize",
+        ] {
+            assert!(names.contains(&expected), "missing case `{}`", expected);
+        }
+    }
+
+    #[test]
+    fn test_payload_response_id() {
+        assert_eq!(Payload::Json(request(json!(7), "x", json!({}))).response_id(), Some(json!(7)));
+        assert_eq!(Payload::Json(json!({"jsonrpc": "2.0", "method": "x"})).response_id(), None);
+        assert_eq!(Payload::Raw(b"{".to_vec()).response_id(), Some(Value::Null));
+        let object_id = request(json!({ "nested": true }), "x", json!({}));
+        assert_eq!(Payload::Json(object_id).response_id(), Some(Value::Null));
+    }
+
+    #[test]
+    fn test_check_response() {
+        let error =
+            |code: i64| json!({"jsonrpc": "2.0", "id": 1, "error": {"code": code, "message": "x"}});
+        let expected = Expected::Error(vec![INVALID_PARAMS]);
+
+        assert!(check_response(&error(INVALID_PARAMS), &expected).is_ok());
+        assert!(check_response(&error(-32603), &expected).unwrap_err().contains("-32603"));
+        assert!(
+            check_response(&json!({"jsonrpc": "2.0", "id": 1, "result": {}}), &expected).is_err()
+        );
+        assert!(check_response(
+            &json!({"jsonrpc": "2.0", "id": 1, "error": {"code": "bad"}}),
+            &Expected::Anything
+        )
+        .unwrap_err()
+        .contains("malformed"));
+    }
+
+    #[test]
+    fn test_escape_bytes() {
+        assert_eq!(escape_bytes(b"ab\xffc"), "ab\\xffc");
+    }
+}
@@ -0,0 +1,27 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Protocol fuzzer: sends mutated ACP requests to an agent over stdio and
+//! reports crashes, hangs and non-spec responses.
+//!
+//! Every case runs against a fresh agent process, so each reproducer trace
+//! holds only the valid requests the case needs plus the mutated payload.
+
+mod cases;
+mod runner;
+
+#[allow(unused_imports)]
+pub use cases::{generate, Expected, FuzzCase, Payload, Prelude};
+#[allow(unused_imports)]
+pub use runner::{run, Finding, FindingKind, FuzzOptions, FuzzReport};
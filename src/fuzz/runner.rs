@@ -0,0 +1,402 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    fmt,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context as _};
+use futures::{
+    channel::mpsc,
+    future::{self, Either},
+    StreamExt,
+};
+use gpui::BackgroundExecutor;
+use serde_json::{json, Value};
+
+use crate::{
+    client::{StdioTransport, TransportMessage},
+    models::{AgentConfig, Trace, TraceDirection, TraceFrame},
+};
+
+use super::cases::{self, Expected, FuzzCase, Payload, Prelude, SESSION_PLACEHOLDER};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FindingKind {
+    /// The agent exited or closed stdout.
+    Crash,
+    /// The agent stopped answering within the timeout.
+    Hang,
+    /// The agent answered in a way the JSON-RPC spec doesn't allow.
+    NonSpec,
+}
+
+impl fmt::Display for FindingKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FindingKind::Crash => "CRASH",
+            FindingKind::Hang => "HANG",
+            FindingKind::NonSpec => "NON-SPEC",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub case: String,
+    pub kind: FindingKind,
+    pub detail: String,
+    /// Trace of the failing exchange, replayable against the agent.
+    pub reproducer: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FuzzOptions {
+    pub config: AgentConfig,
+    /// The cwd used in `session/new` requests.
+    pub cwd: String,
+    /// How long to wait for each response.
+    pub timeout: Duration,
+    /// Where reproducer traces are written.
+    pub output_dir: PathBuf,
+    /// Only run cases whose name contains this text.
+    pub filter: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct FuzzReport {
+    pub cases: usize,
+    pub findings: Vec<Finding>,
+}
+
+/// Run every fuzz case against a fresh agent process.
+///
+/// `on_case` is called after each case with the findings it produced.
+pub async fn run(
+    options: &FuzzOptions,
+    executor: &BackgroundExecutor,
+    mut on_case: impl FnMut(&FuzzCase, &[Finding]),
+) -> anyhow::Result<FuzzReport> {
+    // Make sure the agent works at all before blaming the mutations.
+    let mut baseline = AgentProcess::spawn(options, executor)?;
+    let result = baseline.prelude(Prelude::Session, &options.cwd).await;
+    baseline.kill();
+    if let Err((_, detail)) = result {
+        bail!("agent failed a valid initialize/session/new exchange: {}", detail);
+    }
+
+    std::fs::create_dir_all(&options.output_dir)
+        .with_context(|| format!("failed to create {}", options.output_dir.display()))?;
+
+    let mut report = FuzzReport::default();
+    let cases = cases::generate(&options.cwd).into_iter().filter(|case| {
+        options.filter.as_ref().is_none_or(|filter| case.name.contains(filter.as_str()))
+    });
+    for case in cases {
+        report.cases += 1;
+        let (issues, trace) = run_case(&case, options, executor).await?;
+
+        let reproducer = if issues.is_empty() {
+            None
+        } else {
+            let path =
+                options.output_dir.join(format!("{:03}-{}.jsonl", report.cases, slug(&case.name)));
+            trace.save(&path)?;
+            Some(path)
+        };
+        let findings: Vec<Finding> = issues
+            .into_iter()
+            .map(|(kind, detail)| Finding {
+                case: case.name.clone(),
+                kind,
+                detail,
+                reproducer: reproducer.clone(),
+            })
+            .collect();
+        on_case(&case, &findings);
+        report.findings.extend(findings);
+    }
+    Ok(report)
+}
+
+type Issue = (FindingKind, String);
+
+async fn run_case(
+    case: &FuzzCase,
+    options: &FuzzOptions,
+    executor: &BackgroundExecutor,
+) -> anyhow::Result<(Vec<Issue>, Trace)> {
+    let mut agent = AgentProcess::spawn(options, executor)?;
+    if let Err(issue) = exercise(&mut agent, case, &options.cwd).await {
+        agent.issues.push(issue);
+    }
+    agent.kill();
+    Ok((std::mem::take(&mut agent.issues), agent.trace()))
+}
+
+/// Send the case's payloads and check the answers. Crashes and hangs end the
+/// case early.
+async fn exercise(agent: &mut AgentProcess<'_>, case: &FuzzCase, cwd: &str) -> Result<(), Issue> {
+    let session_id = agent.prelude(case.prelude, cwd).await?;
+
+    let payloads: Vec<Payload> = case
+        .payloads
+        .iter()
+        .map(|payload| match (payload, &session_id) {
+            (Payload::Json(value), Some(session_id)) => {
+                let text = value.to_string().replace(
+                    &format!("\"{}\"", SESSION_PLACEHOLDER),
+                    &Value::String(session_id.clone()).to_string(),
+                );
+                Payload::Json(serde_json::from_str(&text).unwrap_or_else(|_| value.clone()))
+            }
+            _ => payload.clone(),
+        })
+        .collect();
+    for payload in &payloads {
+        agent.send(payload);
+    }
+
+    // Requests are answered by id. Parse errors and invalid ids carry a null
+    // id, so they are looked for once a probe sent afterwards has been answered.
+    let mut expects_parse_error = false;
+    for payload in &payloads {
+        match payload.response_id() {
+            Some(Value::Null) => expects_parse_error = true,
+            Some(id) => {
+                let response = agent.response(&id, "the payload").await?;
+                agent.check(&response, &case.expected);
+            }
+            None => {}
+        }
+    }
+
+    agent.probe().await?;
+    if expects_parse_error {
+        match agent.take_response(&Value::Null) {
+            Some(response) => agent.check(&response, &case.expected),
+            None => agent.issues.push((
+                FindingKind::NonSpec,
+                "payload was not answered with a null-id error".into(),
+            )),
+        }
+    }
+    Ok(())
+}
+
+enum Wait {
+    Response(Value),
+    Closed,
+    Timeout,
+}
+
+/// One agent process for a single case, recording every frame exchanged.
+struct AgentProcess<'a> {
+    transport: StdioTransport,
+    incoming: mpsc::UnboundedReceiver<TransportMessage>,
+    executor: &'a BackgroundExecutor,
+    timeout: Duration,
+    started_at: Instant,
+    frames: Vec<TraceFrame>,
+    /// Responses that arrived while waiting for a different id.
+    responses: Vec<Value>,
+    issues: Vec<Issue>,
+    next_id: u64,
+}
+
+impl<'a> AgentProcess<'a> {
+    fn spawn(options: &FuzzOptions, executor: &'a BackgroundExecutor) -> anyhow::Result<Self> {
+        let mut transport = StdioTransport::spawn(&options.config)?;
+        let incoming = transport.take_incoming().context("transport has no incoming channel")?;
+        Ok(Self {
+            transport,
+            incoming,
+            executor,
+            timeout: options.timeout,
+            started_at: Instant::now(),
+            frames: Vec::new(),
+            responses: Vec::new(),
+            issues: Vec::new(),
+            next_id: 1,
+        })
+    }
+
+    /// Run the valid requests a case needs, returning the session id.
+    async fn prelude(&mut self, prelude: Prelude, cwd: &str) -> Result<Option<String>, Issue> {
+        if prelude == Prelude::None {
+            return Ok(None);
+        }
+        let params = json!({
+            "protocolVersion": 1,
+            "clientCapabilities": {
+                "fs": { "readTextFile": false, "writeTextFile": false },
+                "terminal": false,
+            },
+        });
+        self.expect_result("initialize", params).await?;
+        if prelude == Prelude::Initialize {
+            return Ok(None);
+        }
+        let result =
+            self.expect_result("session/new", json!({ "cwd": cwd, "mcpServers": [] })).await?;
+        match result.get("sessionId").and_then(|v| v.as_str()) {
+            Some(session_id) => Ok(Some(session_id.to_string())),
+            None => Err((FindingKind::NonSpec, "session/new result without sessionId".into())),
+        }
+    }
+
+    async fn expect_result(&mut self, method: &str, params: Value) -> Result<Value, Issue> {
+        let id = self.send_request(method, params);
+        let response = self.response(&id, method).await?;
+        match response.get("result") {
+            Some(result) => Ok(result.clone()),
+            None => Err((FindingKind::NonSpec, format!("valid {} failed: {}", method, response))),
+        }
+    }
+
+    /// Check the agent still answers requests.
+    async fn probe(&mut self) -> Result<(), Issue> {
+        let id = self.send_request("fuzz/ping", json!({}));
+        match self.wait_for(&id).await {
+            Wait::Response(_) => Ok(()),
+            Wait::Closed => Err((FindingKind::Crash, self.exit_detail().await)),
+            Wait::Timeout => {
+                Err((FindingKind::Hang, "stopped responding after the payload".into()))
+            }
+        }
+    }
+
+    /// Wait for the response with `id`, turning crashes and hangs into issues.
+    async fn response(&mut self, id: &Value, what: &str) -> Result<Value, Issue> {
+        match self.wait_for(id).await {
+            Wait::Response(response) => Ok(response),
+            Wait::Closed => {
+                Err((FindingKind::Crash, format!("{} after {}", self.exit_detail().await, what)))
+            }
+            Wait::Timeout => Err((
+                FindingKind::Hang,
+                format!("no response to {} within {}ms", what, self.timeout.as_millis()),
+            )),
+        }
+    }
+
+    fn check(&mut self, response: &Value, expected: &Expected) {
+        if let Err(detail) = cases::check_response(response, expected) {
+            self.issues.push((FindingKind::NonSpec, detail));
+        }
+    }
+
+    fn send_request(&mut self, method: &str, params: Value) -> Value {
+        let id = json!(self.next_id);
+        self.next_id += 1;
+        self.send(&Payload::Json(cases::request(id.clone(), method, params)));
+        id
+    }
+
+    fn send(&mut self, payload: &Payload) {
+        let bytes = payload.to_bytes();
+        let message = match payload {
+            Payload::Json(value) => value.clone(),
+            // Unparseable lines are kept as strings in the reproducer.
+            Payload::Raw(bytes) => Value::String(cases::escape_bytes(bytes)),
+        };
+        self.record(TraceDirection::Outgoing, message);
+        // A closed stdin shows up as `Closed` on the incoming side.
+        let _ = self.transport.send_raw(bytes);
+    }
+
+    fn record(&mut self, direction: TraceDirection, message: Value) {
+        let offset_ms = self.started_at.elapsed().as_millis() as u64;
+        self.frames.push(TraceFrame { offset_ms, direction, message });
+    }
+
+    fn take_response(&mut self, id: &Value) -> Option<Value> {
+        let ix = self.responses.iter().position(|r| r.get("id") == Some(id))?;
+        Some(self.responses.remove(ix))
+    }
+
+    /// Wait for the response with `id`, buffering any other responses.
+    async fn wait_for(&mut self, id: &Value) -> Wait {
+        if let Some(response) = self.take_response(id) {
+            return Wait::Response(response);
+        }
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let timer = self.executor.timer(deadline.saturating_duration_since(Instant::now()));
+            let message = match future::select(self.incoming.next(), timer).await {
+                Either::Left((Some(message), _)) => message,
+                Either::Left((None, _)) => return Wait::Closed,
+                Either::Right(_) => return Wait::Timeout,
+            };
+            match message {
+                TransportMessage::Json(value) => {
+                    self.record(TraceDirection::Incoming, value.clone());
+                    match (value.get("method"), value.get("id")) {
+                        // Decline requests from the agent so it doesn't block on us.
+                        (Some(_), Some(request_id)) => {
+                            let reply = json!({
+                                "jsonrpc": "2.0",
+                                "id": request_id,
+                                "error": { "code": cases::METHOD_NOT_FOUND, "message": "not supported by the fuzzer" },
+                            });
+                            self.send(&Payload::Json(reply));
+                        }
+                        (Some(_), None) => {}
+                        (None, _) if value.get("id") == Some(id) => return Wait::Response(value),
+                        (None, _) => self.responses.push(value),
+                    }
+                }
+                TransportMessage::ParseError { line, .. } => {
+                    let preview: String = line.chars().take(80).collect();
+                    self.record(TraceDirection::Incoming, Value::String(line));
+                    self.issues.push((
+                        FindingKind::NonSpec,
+                        format!("wrote non-JSON to stdout: {}", preview),
+                    ));
+                }
+                TransportMessage::Stderr(_) => {}
+                TransportMessage::Closed => return Wait::Closed,
+            }
+        }
+    }
+
+    /// Describe how the process went away.
+    async fn exit_detail(&mut self) -> String {
+        // Give the process a moment to be reaped after closing stdout.
+        self.executor.timer(Duration::from_millis(100)).await;
+        match self.transport.try_wait() {
+            Some(status) => format!("agent {}", status),
+            None => "agent closed stdout".into(),
+        }
+    }
+
+    fn kill(&mut self) {
+        self.transport.kill();
+    }
+
+    fn trace(&self) -> Trace {
+        Trace { frames: self.frames.clone() }
+    }
+}
+
+/// A file-name friendly version of a case name.
+fn slug(name: &str) -> String {
+    let slug: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect();
+    slug.split('-').filter(|part| !part.is_empty()).collect::<Vec<_>>().join("-")
+}
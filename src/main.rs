@@ -17,13 +17,25 @@ use gpui_component::{Root, TitleBar};
 use gpui_component_assets::Assets;
 
 mod app;
+mod cli;
 #[allow(dead_code)]
 mod client;
 mod components;
+mod fuzz;
 mod models;
 mod panels;
+mod replay;
 
 fn main() {
+    match cli::parse_args(std::env::args().skip(1)) {
+        Ok(Some(command)) => cli::run(command),
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    }
+
     let app = Application::new().with_assets(Assets);
 
     app.run(|cx| {
@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::time::Instant;
+
 use gpui::SharedString;
 use serde_json::Value;
 
@@ -56,6 +58,11 @@ pub struct AcpMessage {
     pub params: Option<Value>,
     pub result: Option<Value>,
     pub error: Option<AcpError>,
+    /// Whether the message was edited or injected by hand rather than
+    /// produced by the normal protocol flow.
+    pub crafted: bool,
+    /// When the message was captured.
+    pub timestamp: Instant,
 }
 
 impl AcpMessage {
@@ -114,18 +121,29 @@ impl AcpMessage {
             params,
             result,
             error,
+            crafted: false,
+            timestamp: Instant::now(),
         }
     }
 
-    /// Get the main payload (params or result) as pretty-printed JSON.
-    pub fn payload_json(&self) -> Option<String> {
-        let value = self
-            .params
+    /// Mark the message as manually crafted.
+    pub fn crafted(mut self, crafted: bool) -> Self {
+        self.crafted = crafted;
+        self
+    }
+
+    /// A message parsed from `raw` as if captured at `at`, for tests.
+    #[cfg(test)]
+    pub fn captured_at(raw: &str, is_response: bool, at: Instant) -> Self {
+        Self { timestamp: at, ..Self::parse("", raw.to_string(), is_response) }
+    }
+
+    /// Get the main payload (params or result).
+    pub fn payload(&self) -> Option<&Value> {
+        self.params
             .as_ref()
             .or(self.result.as_ref())
-            .or(self.error.as_ref().and_then(|e| e.data.as_ref()));
-
-        value.and_then(|v| serde_json::to_string_pretty(v).ok())
+            .or(self.error.as_ref().and_then(|e| e.data.as_ref()))
     }
 
     /// Get the full raw JSON pretty-printed.
@@ -136,6 +154,26 @@ impl AcpMessage {
             .unwrap_or_else(|| self.raw_json.to_string())
     }
 
+    /// The session this message belongs to, from `params.sessionId` or a
+    /// `session/new` result.
+    pub fn session_id(&self) -> Option<&str> {
+        self.params
+            .as_ref()
+            .or(self.result.as_ref())
+            .and_then(|v| v.get("sessionId"))
+            .and_then(|v| v.as_str())
+    }
+
+    /// Whether this message is the response to `request`: it travels the
+    /// other way and carries the same id.
+    pub fn is_reply_to(&self, request: &AcpMessage) -> bool {
+        self.method.is_none() &&
+            request.method.is_some() &&
+            self.is_response != request.is_response &&
+            self.id.is_some() &&
+            self.id == request.id
+    }
+
     /// Get the direction label.
     pub fn direction_label(&self) -> &'static str {
         if self.is_response {
@@ -150,6 +188,26 @@ impl AcpMessage {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_is_reply_to() {
+        let request = AcpMessage::parse(
+            "session/new",
+            r#"{"jsonrpc":"2.0","id":3,"method":"session/new","params":{}}"#,
+            false,
+        );
+        let response = AcpMessage::parse(
+            "session/new",
+            r#"{"jsonrpc":"2.0","id":3,"result":{"sessionId":"s1"}}"#,
+            true,
+        );
+        let other = AcpMessage::parse("response", r#"{"jsonrpc":"2.0","id":4,"result":{}}"#, true);
+
+        assert!(response.is_reply_to(&request));
+        assert_eq!(response.session_id(), Some("s1"));
+        assert!(!other.is_reply_to(&request));
+        assert!(!request.is_reply_to(&response));
+    }
+
     #[test]
     fn test_parse_request() {
         let msg = AcpMessage::parse(
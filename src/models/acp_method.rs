@@ -0,0 +1,117 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde_json::{json, Value};
+
+/// Whether a method expects a response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MethodKind {
+    Request,
+    Notification,
+}
+
+/// An ACP method a client can send to an agent.
+#[derive(Debug, Clone, Copy)]
+pub struct AcpMethod {
+    pub name: &'static str,
+    pub kind: MethodKind,
+    pub description: &'static str,
+}
+
+/// Client → agent methods defined by the ACP schema.
+pub const ACP_METHODS: &[AcpMethod] = &[
+    AcpMethod {
+        name: "initialize",
+        kind: MethodKind::Request,
+        description: "Negotiate protocol version and capabilities",
+    },
+    AcpMethod {
+        name: "authenticate",
+        kind: MethodKind::Request,
+        description: "Authenticate with one of the advertised auth methods",
+    },
+    AcpMethod {
+        name: "session/new",
+        kind: MethodKind::Request,
+        description: "Create a new session",
+    },
+    AcpMethod {
+        name: "session/load",
+        kind: MethodKind::Request,
+        description: "Resume a previous session",
+    },
+    AcpMethod {
+        name: "session/prompt",
+        kind: MethodKind::Request,
+        description: "Send a user prompt to a session",
+    },
+    AcpMethod {
+        name: "session/set_mode",
+        kind: MethodKind::Request,
+        description: "Switch the session mode",
+    },
+    AcpMethod {
+        name: "session/cancel",
+        kind: MethodKind::Notification,
+        description: "Cancel the running prompt turn",
+    },
+];
+
+impl AcpMethod {
+    pub fn find(name: &str) -> Option<&'static AcpMethod> {
+        ACP_METHODS.iter().find(|m| m.name == name)
+    }
+
+    /// Example params for this method, filled with the given session and cwd.
+    pub fn template(&self, session_id: Option<&str>, cwd: &str) -> Value {
+        let session_id = session_id.unwrap_or("sess_...");
+        match self.name {
+            "initialize" => json!({
+                "protocolVersion": 1,
+                "clientCapabilities": {
+                    "fs": { "readTextFile": true, "writeTextFile": true },
+                    "terminal": true,
+                },
+                "clientInfo": { "name": "acp-debugger", "version": env!("CARGO_PKG_VERSION") },
+            }),
+            "authenticate" => json!({ "methodId": "" }),
+            "session/new" => json!({ "cwd": cwd, "mcpServers": [] }),
+            "session/load" => json!({ "sessionId": session_id, "cwd": cwd, "mcpServers": [] }),
+            "session/prompt" => json!({
+                "sessionId": session_id,
+                "prompt": [{ "type": "text", "text": "Hello" }],
+            }),
+            "session/set_mode" => json!({ "sessionId": session_id, "modeId": "" }),
+            "session/cancel" => json!({ "sessionId": session_id }),
+            _ => json!({}),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_templates_fill_session_and_cwd() {
+        let prompt = AcpMethod::find("session/prompt").unwrap();
+        assert_eq!(prompt.template(Some("s1"), "/tmp")["sessionId"], "s1");
+
+        let new_session = AcpMethod::find("session/new").unwrap();
+        assert_eq!(new_session.template(None, "/tmp")["cwd"], "/tmp");
+
+        assert!(ACP_METHODS.iter().all(|m| m.template(None, "/").is_object()));
+        assert_eq!(AcpMethod::find("session/cancel").unwrap().kind, MethodKind::Notification);
+    }
+}
@@ -14,11 +14,28 @@
 
 #![allow(dead_code)]
 
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::{secrets, LaunchOptions, SessionPreset};
 
 pub type AgentId = String;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Timeout used when the agent form leaves it blank.
+pub const DEFAULT_TIMEOUT_MS: u64 = 30_000;
+
+pub fn generate_agent_id() -> AgentId {
+    let ts = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+    format!("agent-{:x}", ts)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum AuthMethod {
     None,
@@ -26,7 +43,35 @@ pub enum AuthMethod {
     Bearer { token: String },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl AuthMethod {
+    pub fn credential(&self) -> Option<&str> {
+        match self {
+            Self::None => None,
+            Self::ApiKey { key } => Some(key),
+            Self::Bearer { token } => Some(token),
+        }
+    }
+
+    /// The method with secret references in its credential substituted.
+    pub fn resolve(&self, revealed: &mut Vec<String>) -> Result<AuthMethod, String> {
+        Ok(match self {
+            Self::None => Self::None,
+            Self::ApiKey { key } => Self::ApiKey { key: secrets::resolve(key, revealed)? },
+            Self::Bearer { token } => Self::Bearer { token: secrets::resolve(token, revealed)? },
+        })
+    }
+
+    /// The `credentials` sent in `authenticate`.
+    pub fn credentials(&self) -> Value {
+        match self {
+            Self::None => Value::Null,
+            Self::ApiKey { key } => json!({ "apiKey": key }),
+            Self::Bearer { token } => json!({ "token": token }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct AgentConfig {
     pub id: AgentId,
     pub name: String,
@@ -35,6 +80,84 @@ pub struct AgentConfig {
     pub env: Vec<(String, String)>,
     pub auth: AuthMethod,
     pub timeout_ms: u64,
+    #[serde(default)]
+    pub restart: RestartPolicy,
+    #[serde(default)]
+    pub launch: LaunchOptions,
+    /// Saved `session/new` setups, offered when creating a session.
+    #[serde(default)]
+    pub session_presets: Vec<SessionPreset>,
+}
+
+impl Default for AgentConfig {
+    fn default() -> Self {
+        Self {
+            id: AgentId::new(),
+            name: String::new(),
+            endpoint: String::new(),
+            args: Vec::new(),
+            env: Vec::new(),
+            auth: AuthMethod::None,
+            timeout_ms: DEFAULT_TIMEOUT_MS,
+            restart: RestartPolicy::default(),
+            launch: LaunchOptions::default(),
+            session_presets: Vec::new(),
+        }
+    }
+}
+
+impl AgentConfig {
+    /// Whether `other` starts the agent process the same way, so a running
+    /// agent can take it without a restart.
+    pub fn launches_like(&self, other: &AgentConfig) -> bool {
+        self.endpoint == other.endpoint &&
+            self.args == other.args &&
+            self.env == other.env &&
+            self.auth == other.auth &&
+            self.launch == other.launch
+    }
+}
+
+/// What to do when the agent process exits without being asked to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RestartPolicy {
+    /// Restart automatically; off unless the user opts in.
+    pub enabled: bool,
+    /// Give up after this many restarts in a row that did not stay up.
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    /// `session/load` the previous session after a restart if the agent
+    /// supports it, or start a new one in the same cwd.
+    pub restore_session: bool,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_attempts: 5,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 30_000,
+            restore_session: true,
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// The delay before restart `attempt` (starting at 1), doubling each time.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u64.checked_shl(attempt.saturating_sub(1)).unwrap_or(u64::MAX);
+        let ms = self.initial_backoff_ms.saturating_mul(factor).min(self.max_backoff_ms);
+        Duration::from_millis(ms)
+    }
+
+    /// The delay before the next restart after `attempts` failed ones, or
+    /// `None` once they are used up.
+    pub fn next_restart(&self, attempts: u32) -> Option<Duration> {
+        (self.enabled && attempts < self.max_attempts).then(|| self.backoff(attempts + 1))
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -45,6 +168,34 @@ pub enum AgentStatus {
     Error,
 }
 
+impl AgentStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Disconnected => "Disconnected",
+            Self::Connecting => "Connecting",
+            Self::Connected => "Connected",
+            Self::Error => "Error",
+        }
+    }
+}
+
+/// Version of the agents file format written by this build.
+pub const AGENTS_SCHEMA_VERSION: u32 = 1;
+
+/// The agent registry as stored on disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedAgents {
+    pub version: u32,
+    pub agents: Vec<AgentConfig>,
+    pub active_agent_id: Option<AgentId>,
+}
+
+impl Default for SavedAgents {
+    fn default() -> Self {
+        Self { version: AGENTS_SCHEMA_VERSION, agents: Vec::new(), active_agent_id: None }
+    }
+}
+
 pub struct AgentRegistry {
     agents: Vec<AgentConfig>,
     active_agent_id: Option<AgentId>,
@@ -55,6 +206,21 @@ impl AgentRegistry {
         Self { agents: Vec::new(), active_agent_id: None }
     }
 
+    /// Restore a registry saved with [`AgentRegistry::to_saved`].
+    pub fn from_saved(saved: SavedAgents) -> Self {
+        let active_agent_id =
+            saved.active_agent_id.filter(|id| saved.agents.iter().any(|a| &a.id == id));
+        Self { agents: saved.agents, active_agent_id }
+    }
+
+    pub fn to_saved(&self) -> SavedAgents {
+        SavedAgents {
+            version: AGENTS_SCHEMA_VERSION,
+            agents: self.agents.clone(),
+            active_agent_id: self.active_agent_id.clone(),
+        }
+    }
+
     pub fn agents(&self) -> &[AgentConfig] {
         &self.agents
     }
@@ -81,4 +247,157 @@ impl AgentRegistry {
     pub fn set_active(&mut self, id: Option<AgentId>) {
         self.active_agent_id = id;
     }
+
+    /// Replace the agent with the same id, keeping its position.
+    pub fn update_agent(&mut self, config: AgentConfig) {
+        if let Some(agent) = self.agents.iter_mut().find(|a| a.id == config.id) {
+            *agent = config;
+        }
+    }
+
+    /// Insert a copy of an agent right after it and return the copy's id.
+    pub fn duplicate_agent(&mut self, id: &str) -> Option<AgentId> {
+        let ix = self.agents.iter().position(|a| a.id == id)?;
+        let mut copy = self.agents[ix].clone();
+        copy.id = generate_agent_id();
+        copy.name = format!("{} (copy)", copy.name);
+        let copy_id = copy.id.clone();
+        self.agents.insert(ix + 1, copy);
+        Some(copy_id)
+    }
+
+    /// Move the agent at `from` so that it ends up at index `to`.
+    pub fn move_agent(&mut self, from: usize, to: usize) {
+        if from < self.agents.len() && to < self.agents.len() && from != to {
+            let agent = self.agents.remove(from);
+            self.agents.insert(to, agent);
+        }
+    }
+}
+
+/// Parse `KEY=VALUE` lines, skipping blank ones.
+pub fn parse_env(text: &str) -> Result<Vec<(String, String)>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(ix, line)| {
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("Environment line {}: expected KEY=VALUE", ix + 1))?;
+            let key = key.trim();
+            if key.is_empty() || key.chars().any(|c| c.is_whitespace()) {
+                return Err(format!("Environment line {}: invalid variable name", ix + 1));
+            }
+            Ok((key.to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Parse a timeout in milliseconds; blank means [`DEFAULT_TIMEOUT_MS`].
+pub fn parse_timeout(text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(DEFAULT_TIMEOUT_MS);
+    }
+    match text.parse::<u64>() {
+        Ok(0) | Err(_) => {
+            Err(format!("Timeout must be a positive number of milliseconds: {}", text))
+        }
+        Ok(ms) => Ok(ms),
+    }
+}
+
+/// Locate an endpoint: a path as given, or a bare command looked up on `PATH`.
+pub fn resolve_command(endpoint: &str) -> Option<PathBuf> {
+    resolve_command_in(endpoint, std::env::var_os("PATH").as_deref())
+}
+
+/// Like [`resolve_command`], searching `paths` instead of this process's `PATH`.
+pub fn resolve_command_in(endpoint: &str, paths: Option<&OsStr>) -> Option<PathBuf> {
+    let path = Path::new(endpoint);
+    if path.components().count() > 1 || path.is_absolute() {
+        return path.is_file().then(|| path.to_path_buf());
+    }
+    std::env::split_paths(paths?).find_map(|dir| {
+        let candidate = dir.join(endpoint);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+        if cfg!(windows) {
+            let exe = candidate.with_extension("exe");
+            return exe.is_file().then_some(exe);
+        }
+        None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agent(id: &str) -> AgentConfig {
+        AgentConfig {
+            id: id.into(),
+            name: id.to_uppercase(),
+            endpoint: "agent".into(),
+            ..AgentConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_registry_edits() {
+        let mut registry = AgentRegistry::new();
+        for id in ["a", "b", "c"] {
+            registry.add_agent(agent(id));
+        }
+        let ids = |r: &AgentRegistry| r.agents().iter().map(|a| a.name.clone()).collect::<Vec<_>>();
+
+        registry.move_agent(0, 2);
+        assert_eq!(ids(&registry), ["B", "C", "A"]);
+
+        let copy = registry.duplicate_agent("c").unwrap();
+        assert_eq!(ids(&registry), ["B", "C", "C (copy)", "A"]);
+
+        registry.update_agent(AgentConfig { name: "D".into(), ..agent(&copy) });
+        assert_eq!(ids(&registry), ["B", "C", "D", "A"]);
+    }
+
+    #[test]
+    fn test_validation() {
+        assert_eq!(
+            parse_env("A=1\n\n B = two=2 ").unwrap(),
+            vec![("A".into(), "1".into()), ("B".into(), "two=2".into())]
+        );
+        assert!(parse_env("A=1\nNOVALUE").unwrap_err().contains("line 2"));
+        assert!(parse_env("=1").is_err());
+
+        assert_eq!(parse_timeout(""), Ok(DEFAULT_TIMEOUT_MS));
+        assert_eq!(parse_timeout(" 5000 "), Ok(5000));
+        assert!(parse_timeout("5s").is_err());
+        assert!(parse_timeout("0").is_err());
+
+        assert!(resolve_command("/definitely/not/an/agent").is_none());
+    }
+
+    #[test]
+    fn test_restart_backoff() {
+        let policy = RestartPolicy::default();
+        let ms = |attempt| policy.backoff(attempt).as_millis();
+        assert_eq!((ms(1), ms(2), ms(3)), (500, 1000, 2000));
+        assert_eq!(ms(7), 30_000);
+        assert_eq!(ms(200), 30_000);
+
+        // Exits and failed respawns both count towards the limit.
+        let policy = RestartPolicy { enabled: true, max_attempts: 2, ..policy };
+        assert_eq!(policy.next_restart(0), Some(Duration::from_millis(500)));
+        assert_eq!(policy.next_restart(1), Some(Duration::from_millis(1000)));
+        assert_eq!(policy.next_restart(2), None);
+        assert_eq!(RestartPolicy::default().next_restart(0), None);
+
+        let old: AgentConfig = serde_json::from_str(
+            r#"{"id":"a","name":"A","endpoint":"a","args":[],"env":[],"auth":{"type":"none"},"timeout_ms":1}"#,
+        )
+        .unwrap();
+        assert!(!old.restart.enabled);
+    }
 }
@@ -14,40 +14,126 @@
 
 #![allow(dead_code)]
 
-use std::path::PathBuf;
+use std::{
+    io::Write as _,
+    path::{Path, PathBuf},
+};
 
-use crate::models::{AppSettings, SettingsStore};
+use anyhow::bail;
 
-/// Persists application settings to a JSON file at
-/// `~/.config/acp-debugger/settings.json`.
+use crate::models::{AppSettings, SavedAgents, SettingsStore, AGENTS_SCHEMA_VERSION};
+
+/// Persists application settings and agents to JSON files in
+/// `~/.config/acp-debugger`.
 pub struct FileSettingsStore {
-    path: PathBuf,
+    dir: PathBuf,
 }
 
 impl FileSettingsStore {
     pub fn new() -> Self {
         let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-        let path = PathBuf::from(home).join(".config").join("acp-debugger").join("settings.json");
-        Self { path }
+        Self::with_dir(PathBuf::from(home).join(".config").join("acp-debugger"))
+    }
+
+    pub fn with_dir(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn settings_path(&self) -> PathBuf {
+        self.dir.join("settings.json")
+    }
+
+    fn agents_path(&self) -> PathBuf {
+        self.dir.join("agents.json")
     }
 }
 
 impl SettingsStore for FileSettingsStore {
     fn load(&self) -> anyhow::Result<AppSettings> {
-        if !self.path.exists() {
+        let path = self.settings_path();
+        if !path.exists() {
             return Ok(AppSettings::default());
         }
-        let content = std::fs::read_to_string(&self.path)?;
+        let content = std::fs::read_to_string(&path)?;
         let settings = serde_json::from_str(&content)?;
         Ok(settings)
     }
 
     fn save(&self, settings: &AppSettings) -> anyhow::Result<()> {
-        if let Some(parent) = self.path.parent() {
-            std::fs::create_dir_all(parent)?;
+        write_atomic(&self.settings_path(), &serde_json::to_string_pretty(settings)?)
+    }
+
+    fn load_agents(&self) -> anyhow::Result<SavedAgents> {
+        let path = self.agents_path();
+        if !path.exists() {
+            return Ok(SavedAgents::default());
         }
-        let content = serde_json::to_string_pretty(settings)?;
-        std::fs::write(&self.path, content)?;
-        Ok(())
+        let content = std::fs::read_to_string(&path)?;
+        let saved: SavedAgents = serde_json::from_str(&content)?;
+        if saved.version > AGENTS_SCHEMA_VERSION {
+            bail!(
+                "{} has schema version {}, newer than the supported {}",
+                path.display(),
+                saved.version,
+                AGENTS_SCHEMA_VERSION
+            );
+        }
+        Ok(saved)
+    }
+
+    fn save_agents(&self, agents: &SavedAgents) -> anyhow::Result<()> {
+        write_atomic(&self.agents_path(), &serde_json::to_string_pretty(agents)?)
+    }
+}
+
+/// Write `content` to a temporary file next to `path` and rename it into
+/// place, so a crash never leaves a truncated file behind.
+pub(crate) fn write_atomic(path: &Path, content: &str) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AgentConfig, AgentRegistry};
+
+    #[test]
+    fn test_agents_round_trip() {
+        let dir = std::env::temp_dir().join(format!("acp-debugger-test-{}", std::process::id()));
+        let store = FileSettingsStore::with_dir(&dir);
+        assert!(store.load_agents().unwrap().agents.is_empty());
+
+        let mut registry = AgentRegistry::new();
+        registry.add_agent(AgentConfig {
+            id: "a1".into(),
+            name: "Echo".into(),
+            endpoint: "echo-agent".into(),
+            args: vec!["--acp".into()],
+            env: vec![("LOG".into(), "debug".into())],
+            timeout_ms: 5_000,
+            ..AgentConfig::default()
+        });
+        registry.set_active(Some("a1".into()));
+        store.save_agents(&registry.to_saved()).unwrap();
+
+        let restored = AgentRegistry::from_saved(store.load_agents().unwrap());
+        assert_eq!(restored.agents().len(), 1);
+        assert_eq!(restored.active_agent().map(|a| a.name.as_str()), Some("Echo"));
+        assert!(!dir.join("agents.json.tmp").exists());
+
+        let mut newer = registry.to_saved();
+        newer.version = AGENTS_SCHEMA_VERSION + 1;
+        store.save_agents(&newer).unwrap();
+        assert!(store.load_agents().is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
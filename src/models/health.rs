@@ -0,0 +1,151 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{fmt::Write as _, time::Duration};
+
+/// The steps of a health check, in the order they run.
+pub const HEALTH_CHECKS: &[&str] = &[
+    "Endpoint resolves",
+    "Endpoint is executable",
+    "Process starts",
+    "initialize",
+    "session/new",
+    "Clean shutdown",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckOutcome {
+    Passed,
+    Failed,
+    /// Not run because an earlier step failed.
+    Skipped,
+}
+
+#[derive(Debug, Clone)]
+pub struct HealthCheck {
+    pub name: &'static str,
+    pub outcome: CheckOutcome,
+    pub detail: Option<String>,
+    pub elapsed: Option<Duration>,
+}
+
+/// The diagnostics report of one "Test Connection" run.
+#[derive(Debug, Clone, Default)]
+pub struct HealthReport {
+    pub agent: String,
+    pub checks: Vec<HealthCheck>,
+    /// How the process exited, if it did before being shut down.
+    pub exit_status: Option<String>,
+    /// The last lines the agent wrote to stderr.
+    pub stderr: Vec<String>,
+}
+
+impl HealthReport {
+    pub fn new(agent: impl Into<String>) -> Self {
+        Self { agent: agent.into(), ..Self::default() }
+    }
+
+    pub fn passed(&self) -> bool {
+        !self.checks.is_empty() && self.checks.iter().all(|c| c.outcome == CheckOutcome::Passed)
+    }
+
+    pub fn record(
+        &mut self,
+        name: &'static str,
+        result: Result<Option<String>, String>,
+        elapsed: Option<Duration>,
+    ) {
+        let (outcome, detail) = match result {
+            Ok(detail) => (CheckOutcome::Passed, detail),
+            Err(detail) => (CheckOutcome::Failed, Some(detail)),
+        };
+        self.checks.push(HealthCheck { name, outcome, detail, elapsed });
+    }
+
+    /// Mark the steps before `until` that have not run as skipped.
+    pub fn skip_until(&mut self, until: Option<&str>) {
+        for name in HEALTH_CHECKS.iter().take_while(|name| Some(**name) != until) {
+            if !self.checks.iter().any(|c| c.name == *name) {
+                self.checks.push(HealthCheck {
+                    name,
+                    outcome: CheckOutcome::Skipped,
+                    detail: None,
+                    elapsed: None,
+                });
+            }
+        }
+    }
+
+    /// A plain-text version for pasting into bug reports.
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "Health check for {}: {}\n",
+            self.agent,
+            if self.passed() { "passed" } else { "failed" }
+        );
+        for check in &self.checks {
+            let mark = match check.outcome {
+                CheckOutcome::Passed => "PASS",
+                CheckOutcome::Failed => "FAIL",
+                CheckOutcome::Skipped => "SKIP",
+            };
+            let _ = write!(text, "[{}] {}", mark, check.name);
+            if let Some(elapsed) = check.elapsed {
+                let _ = write!(text, " ({} ms)", elapsed.as_millis());
+            }
+            if let Some(detail) = &check.detail {
+                let _ = write!(text, ": {}", detail);
+            }
+            text.push('\n');
+        }
+        if let Some(status) = &self.exit_status {
+            let _ = writeln!(text, "Exit status: {}", status);
+        }
+        if !self.stderr.is_empty() {
+            text.push_str("stderr:\n");
+            for line in &self.stderr {
+                let _ = writeln!(text, "  {}", line);
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let mut report = HealthReport::new("Echo");
+        report.record("Endpoint resolves", Ok(Some("/usr/bin/echo".into())), None);
+        report.record("Endpoint is executable", Ok(None), None);
+        report.record("Process starts", Ok(None), Some(Duration::from_millis(3)));
+        report.record("initialize", Err("agent exited with status 1".into()), None);
+        report.skip_until(Some("Clean shutdown"));
+        report.record("Clean shutdown", Ok(None), None);
+        report.stderr.push("panic: no config".into());
+
+        let outcomes: Vec<_> = report.checks.iter().map(|c| (c.name, c.outcome)).collect();
+        assert_eq!(outcomes[4], ("session/new", CheckOutcome::Skipped));
+        assert_eq!(outcomes.len(), HEALTH_CHECKS.len());
+        assert!(!report.passed());
+
+        let text = report.to_text();
+        assert!(text.starts_with("Health check for Echo: failed"));
+        assert!(text.contains("[PASS] Process starts (3 ms)"));
+        assert!(text.contains("[FAIL] initialize: agent exited with status 1"));
+        assert!(text.contains("  panic: no config"));
+    }
+}
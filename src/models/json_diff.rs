@@ -0,0 +1,357 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeSet, HashMap};
+
+use serde_json::Value;
+
+use super::{Trace, TraceDirection, TraceFrame};
+
+/// Keys whose values differ between otherwise identical runs.
+pub const DEFAULT_IGNORED_KEYS: &[&str] = &["id", "sessionId", "toolCallId", "timestamp"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// One difference between two JSON values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffChange {
+    /// JSON pointer to the differing node.
+    pub pointer: String,
+    pub kind: DiffKind,
+    pub left: Option<Value>,
+    pub right: Option<Value>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DiffOptions {
+    /// Object keys skipped at any depth.
+    pub ignored_keys: Vec<String>,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self { ignored_keys: DEFAULT_IGNORED_KEYS.iter().map(|k| k.to_string()).collect() }
+    }
+}
+
+impl DiffOptions {
+    /// Parse a comma or whitespace separated list of keys to ignore.
+    pub fn parse(keys: &str) -> Self {
+        Self {
+            ignored_keys: keys
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|k| !k.is_empty())
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+
+    fn ignores(&self, key: &str) -> bool {
+        self.ignored_keys.iter().any(|k| k == key)
+    }
+}
+
+/// Structural diff of two values. Object key order is ignored; arrays are
+/// compared element by element.
+pub fn diff(left: &Value, right: &Value, options: &DiffOptions) -> Vec<DiffChange> {
+    let mut changes = Vec::new();
+    walk(&mut String::new(), left, right, options, &mut changes);
+    changes
+}
+
+fn walk(
+    pointer: &mut String,
+    left: &Value,
+    right: &Value,
+    options: &DiffOptions,
+    changes: &mut Vec<DiffChange>,
+) {
+    match (left, right) {
+        (Value::Object(l), Value::Object(r)) => {
+            let keys: BTreeSet<&String> = l.keys().chain(r.keys()).collect();
+            for key in keys.into_iter().filter(|k| !options.ignores(k)) {
+                let len = pointer.len();
+                pointer.push('/');
+                pointer.push_str(&key.replace('~', "~0").replace('/', "~1"));
+                match (l.get(key), r.get(key)) {
+                    (Some(l), Some(r)) => walk(pointer, l, r, options, changes),
+                    (l, r) => changes.push(change(pointer, l, r)),
+                }
+                pointer.truncate(len);
+            }
+        }
+        (Value::Array(l), Value::Array(r)) => {
+            for ix in 0..l.len().max(r.len()) {
+                let len = pointer.len();
+                pointer.push_str(&format!("/{}", ix));
+                match (l.get(ix), r.get(ix)) {
+                    (Some(l), Some(r)) => walk(pointer, l, r, options, changes),
+                    (l, r) => changes.push(change(pointer, l, r)),
+                }
+                pointer.truncate(len);
+            }
+        }
+        (l, r) if l != r => changes.push(change(pointer, Some(l), Some(r))),
+        _ => {}
+    }
+}
+
+fn change(pointer: &str, left: Option<&Value>, right: Option<&Value>) -> DiffChange {
+    let kind = match (left, right) {
+        (None, _) => DiffKind::Added,
+        (_, None) => DiffKind::Removed,
+        _ => DiffKind::Changed,
+    };
+    DiffChange { pointer: pointer.to_string(), kind, left: left.cloned(), right: right.cloned() }
+}
+
+/// A pair of aligned trace frames, either of which may be missing.
+#[derive(Debug, Clone)]
+pub struct FrameDiff {
+    pub left: Option<usize>,
+    pub right: Option<usize>,
+    /// Direction and method, e.g. `out session/prompt`.
+    pub label: String,
+    pub changes: Vec<DiffChange>,
+}
+
+impl FrameDiff {
+    pub fn kind(&self) -> Option<DiffKind> {
+        match (self.left, self.right) {
+            (None, _) => Some(DiffKind::Added),
+            (_, None) => Some(DiffKind::Removed),
+            _ if self.changes.is_empty() => None,
+            _ => Some(DiffKind::Changed),
+        }
+    }
+}
+
+/// Align the frames of two traces by their method sequence (longest common
+/// subsequence) and diff the aligned pairs.
+pub fn diff_traces(left: &Trace, right: &Trace, options: &DiffOptions) -> Vec<FrameDiff> {
+    let left_keys = frame_keys(&left.frames);
+    let right_keys = frame_keys(&right.frames);
+
+    // Compare small integers rather than strings in the alignment.
+    let mut ids: HashMap<&str, u32> = HashMap::new();
+    let keys: Vec<u32> = left_keys
+        .iter()
+        .chain(&right_keys)
+        .map(|key| {
+            let next = ids.len() as u32;
+            *ids.entry(key.as_str()).or_insert(next)
+        })
+        .collect();
+    let (a, b) = keys.split_at(left_keys.len());
+
+    let mut pairs = Vec::with_capacity(a.len().max(b.len()));
+    align(a, b, 0, 0, &mut pairs);
+    pairs
+        .into_iter()
+        .map(|pair| match pair {
+            (Some(i), Some(j)) => FrameDiff {
+                left: Some(i),
+                right: Some(j),
+                label: left_keys[i].clone(),
+                changes: diff(&left.frames[i].message, &right.frames[j].message, options),
+            },
+            (None, Some(j)) => FrameDiff {
+                left: None,
+                right: Some(j),
+                label: right_keys[j].clone(),
+                changes: Vec::new(),
+            },
+            (i, _) => FrameDiff {
+                left: i,
+                right: None,
+                label: i.map(|i| left_keys[i].clone()).unwrap_or_default(),
+                changes: Vec::new(),
+            },
+        })
+        .collect()
+}
+
+/// An aligned pair of indexes into `a` and `b` (offset by `a0`, `b0`).
+type Pair = (Option<usize>, Option<usize>);
+
+/// Align `a` and `b` along a longest common subsequence in linear space
+/// (Hirschberg), after stripping their common prefix and suffix.
+fn align(a: &[u32], b: &[u32], a0: usize, b0: usize, out: &mut Vec<Pair>) {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    out.extend((0..prefix).map(|k| (Some(a0 + k), Some(b0 + k))));
+    let (a, b) = (&a[prefix..], &b[prefix..]);
+    let (a0, b0) = (a0 + prefix, b0 + prefix);
+    let suffix = a.iter().rev().zip(b.iter().rev()).take_while(|(x, y)| x == y).count();
+    let (n, m) = (a.len() - suffix, b.len() - suffix);
+
+    if n == 0 || m == 0 {
+        out.extend((0..m).map(|j| (None, Some(b0 + j))));
+        out.extend((0..n).map(|i| (Some(a0 + i), None)));
+    } else if n == 1 {
+        match b[..m].iter().position(|&y| y == a[0]) {
+            Some(j) => {
+                out.extend((0..j).map(|k| (None, Some(b0 + k))));
+                out.push((Some(a0), Some(b0 + j)));
+                out.extend((j + 1..m).map(|k| (None, Some(b0 + k))));
+            }
+            None => {
+                out.extend((0..m).map(|k| (None, Some(b0 + k))));
+                out.push((Some(a0), None));
+            }
+        }
+    } else {
+        let mid = n / 2;
+        let forward = lcs_lengths(a[..mid].iter(), &b[..m]);
+        let backward =
+            lcs_lengths(a[mid..n].iter().rev(), &b[..m].iter().rev().copied().collect::<Vec<_>>());
+        let split = (0..=m).max_by_key(|&k| forward[k] + backward[m - k]).unwrap_or(0);
+        align(&a[..mid], &b[..split], a0, b0, out);
+        align(&a[mid..n], &b[split..m], a0 + mid, b0 + split, out);
+    }
+    out.extend((0..suffix).map(|k| (Some(a0 + n + k), Some(b0 + m + k))));
+}
+
+/// Lengths of the longest common subsequence of `a` with each prefix of `b`.
+fn lcs_lengths<'a>(a: impl Iterator<Item = &'a u32>, b: &[u32]) -> Vec<u32> {
+    let mut row = vec![0u32; b.len() + 1];
+    for x in a {
+        let mut diagonal = 0;
+        for (j, y) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if x == y { diagonal + 1 } else { above.max(row[j]) };
+            diagonal = above;
+        }
+    }
+    row
+}
+
+/// Direction and method of each frame; responses take their request's method.
+fn frame_keys(frames: &[TraceFrame]) -> Vec<String> {
+    let mut methods: HashMap<(TraceDirection, String), String> = HashMap::new();
+    frames
+        .iter()
+        .map(|frame| {
+            let direction = match frame.direction {
+                TraceDirection::Outgoing => "out",
+                TraceDirection::Incoming => "in",
+            };
+            let id = frame.id().map(|id| id.to_string());
+            let method = match (frame.method(), id) {
+                (Some(method), Some(id)) => {
+                    methods.insert((frame.direction, id), method.to_string());
+                    method.to_string()
+                }
+                (Some(method), None) => method.to_string(),
+                (None, Some(id)) => {
+                    let request_direction = match frame.direction {
+                        TraceDirection::Outgoing => TraceDirection::Incoming,
+                        TraceDirection::Incoming => TraceDirection::Outgoing,
+                    };
+                    let method = methods.remove(&(request_direction, id)).unwrap_or_default();
+                    format!("{} response", method).trim_start().to_string()
+                }
+                (None, None) => "unknown".to_string(),
+            };
+            format!("{} {}", direction, method)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_diff_ignores_order_and_volatile_keys() {
+        let left = json!({"id": 1, "params": {"sessionId": "a", "cwd": "/", "modes": [1, 2]}});
+        let right = json!({"params": {"modes": [1, 3, 4], "cwd": "/", "sessionId": "b"}, "id": 2});
+        let changes = diff(&left, &right, &DiffOptions::default());
+        assert_eq!(
+            changes,
+            vec![
+                DiffChange {
+                    pointer: "/params/modes/1".into(),
+                    kind: DiffKind::Changed,
+                    left: Some(json!(2)),
+                    right: Some(json!(3)),
+                },
+                DiffChange {
+                    pointer: "/params/modes/2".into(),
+                    kind: DiffKind::Added,
+                    left: None,
+                    right: Some(json!(4)),
+                },
+            ]
+        );
+
+        let changes = diff(&left, &right, &DiffOptions::parse(""));
+        assert_eq!(changes.len(), 4);
+        assert_eq!(changes[0].pointer, "/id");
+    }
+
+    #[test]
+    fn test_trace_alignment() {
+        let frame = |direction, message: Value| TraceFrame { offset_ms: 0, direction, message };
+        let out = TraceDirection::Outgoing;
+        let incoming = TraceDirection::Incoming;
+        let left = Trace {
+            frames: vec![
+                frame(out, json!({"id": 0, "method": "initialize"})),
+                frame(incoming, json!({"id": 0, "result": {"v": 1}})),
+                frame(out, json!({"id": 1, "method": "session/new"})),
+            ],
+        };
+        let right = Trace {
+            frames: vec![
+                frame(out, json!({"id": 0, "method": "initialize"})),
+                frame(incoming, json!({"method": "session/update"})),
+                frame(incoming, json!({"id": 0, "result": {"v": 2}})),
+            ],
+        };
+        let diffs = diff_traces(&left, &right, &DiffOptions::default());
+        let summary: Vec<_> =
+            diffs.iter().map(|d| (d.label.as_str(), d.left, d.right, d.kind())).collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("out initialize", Some(0), Some(0), None),
+                ("in session/update", None, Some(1), Some(DiffKind::Added)),
+                ("in initialize response", Some(1), Some(2), Some(DiffKind::Changed)),
+                ("out session/new", Some(2), None, Some(DiffKind::Removed)),
+            ]
+        );
+        assert_eq!(diffs[2].changes[0].pointer, "/result/v");
+
+        // Long streams are aligned without a quadratic table.
+        let updates = |n: usize, extra: &str| Trace {
+            frames: (0..n)
+                .map(|ix| {
+                    let method = if ix == n / 2 { extra } else { "session/update" };
+                    frame(incoming, json!({ "method": method }))
+                })
+                .collect(),
+        };
+        let diffs =
+            diff_traces(&updates(20_000, "a"), &updates(20_000, "b"), &DiffOptions::default());
+        assert_eq!(diffs.len(), 20_001);
+        assert_eq!(diffs.iter().filter(|d| d.kind().is_some()).count(), 2);
+    }
+}
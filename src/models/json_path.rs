@@ -0,0 +1,150 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use regex::Regex;
+use serde_json::Value;
+
+/// Look up a value by path.
+///
+/// Paths are either dotted (`params.prompt[0].text`, optionally prefixed
+/// with `$.`) or JSON pointers (`/params/prompt/0/text`).
+pub fn select<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let path = path.trim();
+    if path.starts_with('/') {
+        return value.pointer(path);
+    }
+    let path = path.strip_prefix('$').unwrap_or(path);
+
+    let mut current = value;
+    for segment in path.split('.').filter(|s| !s.is_empty()) {
+        // `key[0][1]` is the key followed by array indexes.
+        let (key, indexes) = segment.split_once('[').unwrap_or((segment, ""));
+        if !key.is_empty() {
+            current = current.get(key)?;
+        }
+        for index in indexes.split('[').filter(|s| !s.is_empty()) {
+            let index: usize = index.strip_suffix(']')?.parse().ok()?;
+            current = current.get(index)?;
+        }
+    }
+    Some(current)
+}
+
+#[derive(Debug, Clone)]
+enum Comparison {
+    Exists,
+    Equals(Value),
+    NotEquals(Value),
+    Matches(Regex),
+}
+
+/// A condition on a JSON value: `path`, `path == value`, `path != value`
+/// or `path ~ regex`.
+///
+/// The right-hand side of `==`/`!=` is parsed as JSON and falls back to a
+/// bare string, so `params.update.sessionUpdate == tool_call` works.
+#[derive(Debug, Clone)]
+pub struct JsonPredicate {
+    path: String,
+    comparison: Comparison,
+}
+
+impl JsonPredicate {
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let expr = expr.trim();
+        let (path, comparison) = if let Some((path, rhs)) = expr.split_once("==") {
+            (path, Comparison::Equals(literal(rhs)))
+        } else if let Some((path, rhs)) = expr.split_once("!=") {
+            (path, Comparison::NotEquals(literal(rhs)))
+        } else if let Some((path, rhs)) = expr.split_once('~') {
+            let regex = Regex::new(rhs.trim()).map_err(|e| format!("Invalid regex: {}", e))?;
+            (path, Comparison::Matches(regex))
+        } else {
+            (expr, Comparison::Exists)
+        };
+
+        let path = path.trim();
+        if path.is_empty() {
+            return Err("Missing path".into());
+        }
+        Ok(Self { path: path.to_string(), comparison })
+    }
+
+    pub fn matches(&self, value: &Value) -> bool {
+        let found = select(value, &self.path).filter(|v| !v.is_null());
+        match &self.comparison {
+            Comparison::Exists => found.is_some(),
+            Comparison::Equals(expected) => found == Some(expected),
+            Comparison::NotEquals(expected) => found != Some(expected),
+            Comparison::Matches(regex) => match found {
+                Some(Value::String(s)) => regex.is_match(s),
+                Some(other) => regex.is_match(&other.to_string()),
+                None => false,
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for JsonPredicate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.comparison {
+            Comparison::Exists => write!(f, "{}", self.path),
+            Comparison::Equals(v) => write!(f, "{} == {}", self.path, v),
+            Comparison::NotEquals(v) => write!(f, "{} != {}", self.path, v),
+            Comparison::Matches(r) => write!(f, "{} ~ {}", self.path, r),
+        }
+    }
+}
+
+fn literal(text: &str) -> Value {
+    let text = text.trim();
+    serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_select_paths() {
+        let value = json!({"params": {"prompt": [{"text": "hi"}], "ids": [[1, 2]]}});
+        assert_eq!(select(&value, "params.prompt[0].text"), Some(&json!("hi")));
+        assert_eq!(select(&value, "$.params.ids[0][1]"), Some(&json!(2)));
+        assert_eq!(select(&value, "/params/prompt/0/text"), Some(&json!("hi")));
+        assert_eq!(select(&value, "params.missing"), None);
+        assert_eq!(select(&value, "params.prompt[5]"), None);
+    }
+
+    #[test]
+    fn test_predicates() {
+        let value = json!({"method": "session/update", "params": {"update": {
+            "sessionUpdate": "tool_call", "status": 2
+        }}});
+
+        let matches = |expr: &str| JsonPredicate::parse(expr).unwrap().matches(&value);
+        assert!(matches("params.update"));
+        assert!(!matches("result"));
+        assert!(matches("params.update.sessionUpdate == tool_call"));
+        assert!(matches("params.update.sessionUpdate == \"tool_call\""));
+        assert!(matches("params.update.status == 2"));
+        assert!(matches("params.update.status != 3"));
+        assert!(matches("method ~ ^session/"));
+        assert!(!matches("method ~ ^fs/"));
+
+        assert!(JsonPredicate::parse("== 1").is_err());
+        assert!(JsonPredicate::parse("method ~ (").is_err());
+    }
+}
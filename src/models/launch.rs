@@ -0,0 +1,308 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    ffi::OsString,
+    io,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{secrets, AgentConfig};
+
+/// Variables a clean environment still takes from the debugger's.
+pub const CLEAN_ENV_KEEP: &[&str] = &["HOME", "USER", "LOGNAME", "SHELL", "PATH", "LANG", "TMPDIR"];
+
+/// Runs the endpoint with its arguments as `$0 "$@"`, so nothing is
+/// re-parsed by the shell, and hands it the stdout kept as fd 3.
+const SHELL_EXEC: &str = r#"exec "$0" "$@" >&3 3>&-"#;
+
+/// Redirections that keep stdout as fd 3 and send the shell's own output,
+/// such as a login profile's, to stderr instead.
+const PROFILE_TO_STDERR: &str = "3>&1 1>&2";
+
+/// Shells whose `-c` does not take POSIX `$0 "$@"` syntax.
+const NON_POSIX_SHELLS: &[&str] = &["fish", "nu", "elvish", "xonsh", "pwsh", "csh", "tcsh"];
+
+/// Used for login-shell launches when `$SHELL` is unset or not POSIX.
+const FALLBACK_SHELL: &str = "/bin/sh";
+
+/// How the agent process is started.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LaunchOptions {
+    /// Working directory, `~` expanded; the debugger's own when unset.
+    pub cwd: Option<String>,
+    /// Start from the debugger's environment rather than a clean one with
+    /// only [`CLEAN_ENV_KEEP`]. The agent's own env goes on top either way.
+    pub inherit_env: bool,
+    /// Run the endpoint through `$SHELL -l`, so that agents installed with
+    /// nvm, asdf and the like find the same PATH as in a terminal.
+    pub login_shell: bool,
+}
+
+impl Default for LaunchOptions {
+    fn default() -> Self {
+        Self { cwd: None, inherit_env: true, login_shell: false }
+    }
+}
+
+impl LaunchOptions {
+    /// The working directory, if one is set.
+    pub fn cwd(&self) -> Option<PathBuf> {
+        self.cwd.as_deref().map(str::trim).filter(|cwd| !cwd.is_empty()).map(expand_home)
+    }
+}
+
+/// The process that is actually spawned for an agent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LaunchPlan {
+    pub program: String,
+    pub args: Vec<String>,
+    pub cwd: Option<PathBuf>,
+    pub clear_env: bool,
+    /// Apply [`PROFILE_TO_STDERR`] to the program, a login shell whose
+    /// profile must not write into the agent's JSON-RPC stream.
+    pub profile_to_stderr: bool,
+}
+
+impl LaunchPlan {
+    pub fn for_agent(config: &AgentConfig) -> Self {
+        Self::new(&config.endpoint, &config.args, &config.launch)
+    }
+
+    pub fn new(endpoint: &str, args: &[String], launch: &LaunchOptions) -> Self {
+        let (program, args) = if launch.login_shell {
+            let mut shell_args = vec!["-l".into(), "-c".into(), SHELL_EXEC.into(), endpoint.into()];
+            shell_args.extend(args.iter().cloned());
+            (login_shell(), shell_args)
+        } else {
+            (endpoint.to_string(), args.to_vec())
+        };
+        Self {
+            program,
+            args,
+            cwd: launch.cwd(),
+            clear_env: !launch.inherit_env,
+            profile_to_stderr: launch.login_shell,
+        }
+    }
+
+    /// The command line as it could be pasted into a terminal.
+    pub fn command_line(&self) -> String {
+        let mut words = Vec::new();
+        if let Some(cwd) = &self.cwd {
+            words.push(format!("cd {} &&", shell_quote(&cwd.display().to_string())));
+        }
+        words.push(shell_quote(&self.program));
+        words.extend(self.args.iter().map(|arg| shell_quote(arg)));
+        if self.profile_to_stderr {
+            words.push(PROFILE_TO_STDERR.into());
+        }
+        words.join(" ")
+    }
+
+    /// A command set up with the program, arguments, cwd and base
+    /// environment; the agent's own env is left to the caller.
+    pub fn command(&self) -> Command {
+        let mut cmd = Command::new(&self.program);
+        cmd.args(&self.args);
+        if let Some(cwd) = &self.cwd {
+            cmd.current_dir(cwd);
+        }
+        if self.clear_env {
+            cmd.env_clear();
+            for key in CLEAN_ENV_KEEP {
+                if let Some(value) = std::env::var_os(key) {
+                    cmd.env(key, value);
+                }
+            }
+        }
+        #[cfg(unix)]
+        if self.profile_to_stderr {
+            // Runs in the child after its stdio is set up: fd 3 takes the
+            // caller's stdout and stdout becomes stderr.
+            // SAFETY: dup2(2) is async-signal-safe and touches no memory.
+            unsafe {
+                std::os::unix::process::CommandExt::pre_exec(&mut cmd, || {
+                    if libc::dup2(1, 3) < 0 || libc::dup2(2, 1) < 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+        }
+        cmd
+    }
+}
+
+/// The user's shell, used for login-shell launches, or `/bin/sh` when it
+/// is not a POSIX shell.
+pub fn login_shell() -> String {
+    posix_shell(std::env::var("SHELL").ok().as_deref())
+}
+
+fn posix_shell(shell: Option<&str>) -> String {
+    match shell.map(str::trim).filter(|shell| !shell.is_empty()) {
+        Some(shell) => {
+            let name = Path::new(shell).file_name().and_then(|name| name.to_str());
+            if name.is_some_and(|name| NON_POSIX_SHELLS.contains(&name)) {
+                FALLBACK_SHELL.into()
+            } else {
+                shell.into()
+            }
+        }
+        None => FALLBACK_SHELL.into(),
+    }
+}
+
+/// Expand a leading `~` to the home directory.
+pub fn expand_home(path: &str) -> PathBuf {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    match (path.strip_prefix('~'), home) {
+        (Some(""), Some(home)) => home,
+        (Some(rest), Some(home)) if rest.starts_with('/') => home.join(&rest[1..]),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Quote `arg` for a POSIX shell if it needs it.
+pub fn shell_quote(arg: &str) -> String {
+    let plain = !arg.is_empty() &&
+        arg.chars().all(|c| c.is_ascii_alphanumeric() || "_-./=:,+@%".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// Where the agent's endpoint will be found: on `PATH` as the agent sees
+/// it, relative to its cwd, or by asking its login shell.
+///
+/// The login-shell lookup runs the shell, so call it off the UI thread.
+pub fn resolve_endpoint(config: &AgentConfig) -> Result<PathBuf, String> {
+    let cwd = config.launch.cwd();
+    if config.launch.login_shell {
+        return resolve_in_login_shell(config, cwd.as_deref());
+    }
+    // An env override of PATH applies to the lookup, unless it is a secret.
+    let path = config
+        .env
+        .iter()
+        .find(|(key, value)| key == "PATH" && secrets::references(value).is_empty())
+        .map(|(_, value)| OsString::from(value))
+        .or_else(|| std::env::var_os("PATH"));
+    let endpoint = Path::new(&config.endpoint);
+    let endpoint = match &cwd {
+        Some(cwd) if endpoint.is_relative() && endpoint.components().count() > 1 => {
+            cwd.join(endpoint)
+        }
+        _ => endpoint.to_path_buf(),
+    };
+    super::resolve_command_in(&endpoint.to_string_lossy(), path.as_deref()).ok_or_else(|| {
+        if endpoint.components().count() > 1 {
+            format!("{}: no such file", endpoint.display())
+        } else {
+            format!("{}: not found on PATH", config.endpoint)
+        }
+    })
+}
+
+fn resolve_in_login_shell(config: &AgentConfig, cwd: Option<&Path>) -> Result<PathBuf, String> {
+    let plan = LaunchPlan {
+        program: login_shell(),
+        args: vec![
+            "-l".into(),
+            "-c".into(),
+            r#"command -v "$0" >&3"#.into(),
+            config.endpoint.clone(),
+        ],
+        cwd: cwd.map(Path::to_path_buf),
+        clear_env: !config.launch.inherit_env,
+        profile_to_stderr: true,
+    };
+    let output = plan
+        .command()
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("{}: {}", plan.program, e))?;
+    let found = String::from_utf8_lossy(&output.stdout);
+    match found.lines().last().map(str::trim) {
+        Some(path) if output.status.success() && !path.is_empty() => Ok(PathBuf::from(path)),
+        _ => Err(format!("{}: not found by {} -l", config.endpoint, plan.program)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_launch_plan() {
+        let args = vec!["--acp".to_string(), "two words".to_string()];
+        let direct = LaunchPlan::new("claude-code", &args, &LaunchOptions::default());
+        assert_eq!(direct.command_line(), "claude-code --acp 'two words'");
+        assert!(!direct.clear_env);
+
+        let launch = LaunchOptions {
+            cwd: Some("/tmp/it's here".into()),
+            inherit_env: false,
+            login_shell: true,
+        };
+        let shell = LaunchPlan::new("claude-code", &args, &launch);
+        assert_eq!(&shell.args[..2], ["-l", "-c"]);
+        assert_eq!(&shell.args[3..], ["claude-code", "--acp", "two words"]);
+        assert!(shell.clear_env);
+        assert!(shell.command_line().starts_with(r"cd '/tmp/it'\''s here' && "));
+        assert!(shell
+            .command_line()
+            .ends_with(r#"'exec "$0" "$@" >&3 3>&-' claude-code --acp 'two words' 3>&1 1>&2"#));
+
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(LaunchOptions { cwd: Some(" ".into()), ..launch }.cwd(), None);
+    }
+
+    #[test]
+    fn test_posix_shell() {
+        assert_eq!(posix_shell(Some("/bin/zsh")), "/bin/zsh");
+        assert_eq!(posix_shell(Some("/usr/bin/fish")), "/bin/sh");
+        assert_eq!(posix_shell(Some("nu")), "/bin/sh");
+        assert_eq!(posix_shell(Some("")), "/bin/sh");
+        assert_eq!(posix_shell(None), "/bin/sh");
+    }
+
+    /// Profile output goes to stderr and the program still gets stdout.
+    #[cfg(unix)]
+    #[test]
+    fn test_profile_output_kept_off_stdout() {
+        let plan = LaunchPlan {
+            program: "/bin/sh".into(),
+            args: vec![
+                "-c".into(),
+                format!("echo profile; {}", SHELL_EXEC),
+                "echo".into(),
+                "{}".into(),
+            ],
+            cwd: None,
+            clear_env: false,
+            profile_to_stderr: true,
+        };
+        let output = plan.command().stdin(Stdio::null()).output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "{}\n");
+        assert_eq!(String::from_utf8_lossy(&output.stderr), "profile\n");
+    }
+}
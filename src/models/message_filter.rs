@@ -0,0 +1,279 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ops::Range;
+
+use regex::Regex;
+use serde_json::Value;
+
+use super::{AcpMessage, JsonPredicate, MessageType};
+
+/// Syntax summary shown next to the filter bar.
+pub const FILTER_HELP: &str =
+    "method:session/* dir:in|out type:request|response|notification|error \
+     session:<id> is:error /regex/ path == value, or free text";
+
+#[derive(Debug, Clone)]
+enum Term {
+    Method(Regex),
+    Incoming(bool),
+    Type(MessageType),
+    Session(String),
+    Text(Regex),
+    Predicate(JsonPredicate),
+}
+
+/// A parsed MessagePanel filter query. Every term must match.
+///
+/// Terms are `method:<glob>`, `dir:in|out`, `type:<message type>`,
+/// `session:<id>`, `is:error`, `/regex/` over the raw JSON, JSON-path
+/// predicates such as `params.update.sessionUpdate == "tool_call"`, and
+/// free text (quoted for phrases), matched case-insensitively.
+#[derive(Debug, Clone, Default)]
+pub struct MessageFilter {
+    terms: Vec<Term>,
+}
+
+impl MessageFilter {
+    pub fn parse(query: &str) -> Result<Self, String> {
+        let tokens = tokenize(query)?;
+        let mut terms = Vec::new();
+        let mut ix = 0;
+        while ix < tokens.len() {
+            let token = &tokens[ix];
+            // `path == value` spelled with spaces around the operator.
+            if let Some(op) = tokens.get(ix + 1).filter(|t| ["==", "!=", "~"].contains(&t.as_str()))
+            {
+                let rhs =
+                    tokens.get(ix + 2).ok_or_else(|| format!("Missing value after {}", op))?;
+                let expr = format!("{} {} {}", token, op, rhs);
+                terms.push(Term::Predicate(JsonPredicate::parse(&expr)?));
+                ix += 3;
+                continue;
+            }
+            terms.push(parse_term(token)?);
+            ix += 1;
+        }
+        Ok(Self { terms })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    pub fn matches(&self, message: &AcpMessage) -> bool {
+        // Only parse the JSON when a predicate needs it.
+        let mut parsed: Option<Value> = None;
+        self.terms.iter().all(|term| match term {
+            Term::Method(glob) => {
+                let method = message.method.as_deref().unwrap_or(&message.title);
+                glob.is_match(method)
+            }
+            Term::Incoming(incoming) => message.is_response == *incoming,
+            Term::Type(message_type) => message.message_type == *message_type,
+            Term::Session(id) => message.session_id() == Some(id.as_str()),
+            Term::Text(regex) => regex.is_match(&message.raw_json),
+            Term::Predicate(predicate) => {
+                let value = parsed.get_or_insert_with(|| {
+                    serde_json::from_str(&message.raw_json).unwrap_or(Value::Null)
+                });
+                predicate.matches(value)
+            }
+        })
+    }
+
+    /// Byte ranges of `text` matched by the free-text and regex terms.
+    pub fn highlights(&self, text: &str) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = self
+            .terms
+            .iter()
+            .filter_map(|term| match term {
+                Term::Text(regex) => Some(regex),
+                _ => None,
+            })
+            .flat_map(|regex| regex.find_iter(text).map(|m| m.range()))
+            .filter(|range| !range.is_empty())
+            .collect();
+
+        // Highlights must not overlap.
+        ranges.sort_by_key(|r| r.start);
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        merged
+    }
+}
+
+fn parse_term(token: &str) -> Result<Term, String> {
+    if let Some(pattern) = token.strip_prefix('/').and_then(|t| t.strip_suffix('/')) {
+        if !pattern.is_empty() {
+            let regex = Regex::new(pattern).map_err(|e| format!("Invalid regex: {}", e))?;
+            return Ok(Term::Text(regex));
+        }
+    }
+    if token.contains("==") || token.contains("!=") {
+        return JsonPredicate::parse(token).map(Term::Predicate);
+    }
+    if let Some((key, value)) = token.split_once(':') {
+        let value = unquote(value);
+        match key {
+            "method" => return Ok(Term::Method(glob(value)?)),
+            "dir" | "direction" => {
+                return match value {
+                    "in" | "incoming" => Ok(Term::Incoming(true)),
+                    "out" | "outgoing" => Ok(Term::Incoming(false)),
+                    _ => Err(format!("Unknown direction: {}", value)),
+                }
+            }
+            "type" => {
+                let message_type = match value {
+                    "request" => MessageType::Request,
+                    "response" => MessageType::Response,
+                    "notification" => MessageType::Notification,
+                    "error" => MessageType::Error,
+                    _ => return Err(format!("Unknown message type: {}", value)),
+                };
+                return Ok(Term::Type(message_type));
+            }
+            "session" => return Ok(Term::Session(value.to_string())),
+            "is" if value == "error" => return Ok(Term::Type(MessageType::Error)),
+            _ => {}
+        }
+    }
+
+    let text = unquote(token);
+    Ok(Term::Text(Regex::new(&format!("(?i){}", regex::escape(text))).unwrap()))
+}
+
+/// Translate a glob with `*` and `?` into an anchored regex.
+fn glob(pattern: &str) -> Result<Regex, String> {
+    let mut regex = String::from("^");
+    for c in pattern.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex).map_err(|e| e.to_string())
+}
+
+fn unquote(text: &str) -> &str {
+    text.strip_prefix('"').and_then(|t| t.strip_suffix('"')).unwrap_or(text)
+}
+
+/// Split on whitespace, keeping quoted sections (and their quotes) intact.
+fn tokenize(query: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for c in query.chars() {
+        match c {
+            _ if escaped => {
+                current.push(c);
+                escaped = false;
+            }
+            '\\' if in_quotes => {
+                current.push(c);
+                escaped = true;
+            }
+            '"' => {
+                current.push(c);
+                in_quotes = !in_quotes;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if in_quotes {
+        return Err("Unterminated quote".into());
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages() -> Vec<AcpMessage> {
+        vec![
+            AcpMessage::parse(
+                "session/prompt",
+                r#"{"jsonrpc":"2.0","id":2,"method":"session/prompt","params":{"sessionId":"s1","prompt":[{"type":"text","text":"Hello World"}]}}"#,
+                false,
+            ),
+            AcpMessage::parse(
+                "session/update",
+                r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"tool_call"}}}"#,
+                true,
+            ),
+            AcpMessage::parse(
+                "session/prompt",
+                r#"{"jsonrpc":"2.0","id":2,"error":{"code":-32602,"message":"Invalid params"}}"#,
+                true,
+            ),
+        ]
+    }
+
+    fn matching(query: &str) -> Vec<usize> {
+        let filter = MessageFilter::parse(query).unwrap();
+        let messages = messages();
+        (0..messages.len()).filter(|&ix| filter.matches(&messages[ix])).collect()
+    }
+
+    #[test]
+    fn test_filter_terms() {
+        assert_eq!(matching(""), vec![0, 1, 2]);
+        assert_eq!(matching("method:session/*"), vec![0, 1, 2]);
+        assert_eq!(matching("method:session/up*"), vec![1]);
+        assert_eq!(matching("dir:out"), vec![0]);
+        assert_eq!(matching("type:notification"), vec![1]);
+        assert_eq!(matching("is:error"), vec![2]);
+        assert_eq!(matching("session:s1 dir:in"), vec![1]);
+        assert_eq!(matching("hello"), vec![0]);
+        assert_eq!(matching("\"hello world\""), vec![0]);
+        assert_eq!(matching("/-326\\d\\d/"), vec![2]);
+        assert_eq!(matching(r#"params.update.sessionUpdate == "tool_call""#), vec![1]);
+        assert_eq!(matching("error.code==-32602"), vec![2]);
+    }
+
+    #[test]
+    fn test_filter_errors() {
+        assert!(MessageFilter::parse("type:bogus").is_err());
+        assert!(MessageFilter::parse("dir:sideways").is_err());
+        assert!(MessageFilter::parse("/(/").is_err());
+        assert!(MessageFilter::parse("\"open").is_err());
+        assert!(MessageFilter::parse("result ==").is_err());
+    }
+
+    #[test]
+    fn test_highlights() {
+        let filter = MessageFilter::parse("hello /llo.W/").unwrap();
+        assert_eq!(filter.highlights("say Hello World"), vec![4..11]);
+        assert!(MessageFilter::parse("type:error").unwrap().highlights("error").is_empty());
+    }
+}
@@ -13,17 +13,68 @@
 // limitations under the License.
 
 mod acp_message;
+mod acp_method;
 mod agent;
 pub mod file_settings_store;
+mod health;
+mod json_diff;
+mod json_path;
+mod launch;
+mod message_filter;
 mod queue;
+mod resources;
+pub mod secrets;
+mod session_preset;
 mod settings;
+mod stats;
+mod timeline;
+mod trace;
+mod transcript;
+mod zed_import;
 
 pub use acp_message::{AcpMessage, MessageType};
 #[allow(unused_imports)]
+pub use acp_method::{AcpMethod, MethodKind, ACP_METHODS};
+#[allow(unused_imports)]
 pub use agent::*;
 #[allow(unused_imports)]
 pub use file_settings_store::FileSettingsStore;
 #[allow(unused_imports)]
+pub use health::{CheckOutcome, HealthCheck, HealthReport, HEALTH_CHECKS};
+#[allow(unused_imports)]
+pub use json_diff::{
+    diff, diff_traces, DiffChange, DiffKind, DiffOptions, FrameDiff, DEFAULT_IGNORED_KEYS,
+};
+#[allow(unused_imports)]
+pub use json_path::{select, JsonPredicate};
+#[allow(unused_imports)]
+pub use launch::{
+    expand_home, login_shell, resolve_endpoint, shell_quote, LaunchOptions, LaunchPlan,
+    CLEAN_ENV_KEEP,
+};
+#[allow(unused_imports)]
+pub use message_filter::{MessageFilter, FILTER_HELP};
+#[allow(unused_imports)]
 pub use queue::*;
 #[allow(unused_imports)]
+pub use resources::{
+    downsample, format_bytes, is_running, read_tree, ProcessInfo, ResourceHistory, ResourceSample,
+};
+#[allow(unused_imports)]
+pub use secrets::{SecretRef, SecretStore, MASK};
+#[allow(unused_imports)]
+pub use session_preset::{McpCapabilities, McpKind, McpServer, McpTransport, SessionPreset};
+#[allow(unused_imports)]
 pub use settings::*;
+#[allow(unused_imports)]
+pub use stats::{ErrorCodeStats, LatencyStats, MethodStats, StreamingStats, TrafficStats};
+#[allow(unused_imports)]
+pub use timeline::{Lane, LaneKind, Span, Tick, Timeline};
+#[allow(unused_imports)]
+pub use trace::{Trace, TraceDirection, TraceFrame};
+#[allow(unused_imports)]
+pub use transcript::{Transcript, TranscriptEntry};
+#[allow(unused_imports)]
+pub use zed_import::{
+    parse_agent_servers, strip_jsonc, zed_settings_path, ImportCandidate, ZedImport,
+};
@@ -36,3 +36,71 @@ pub struct QueueItem {
     pub completed_at: Option<std::time::Instant>,
     pub error_message: Option<String>,
 }
+
+impl QueueItemStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Pending => "Pending",
+            Self::InFlight => "In Flight",
+            Self::Success => "Success",
+            Self::Failed => "Failed",
+        }
+    }
+}
+
+impl QueueItem {
+    /// A request that has been created but not yet written to the agent.
+    pub fn new(id: u64, agent_id: AgentId, method: String, request_json: String) -> Self {
+        Self {
+            id,
+            agent_id,
+            method,
+            request_json,
+            response_json: None,
+            status: QueueItemStatus::Pending,
+            created_at: std::time::Instant::now(),
+            completed_at: None,
+            error_message: None,
+        }
+    }
+
+    /// Whether the request is still waiting for a response.
+    pub fn is_open(&self) -> bool {
+        matches!(self.status, QueueItemStatus::Pending | QueueItemStatus::InFlight)
+    }
+
+    /// Record the outcome. `error` marks the request as failed.
+    pub fn complete(&mut self, response_json: Option<String>, error: Option<String>) {
+        self.status =
+            if error.is_some() { QueueItemStatus::Failed } else { QueueItemStatus::Success };
+        self.response_json = response_json;
+        self.error_message = error;
+        self.completed_at = Some(std::time::Instant::now());
+    }
+
+    /// Time from creation until completion, or until now while open.
+    pub fn elapsed(&self) -> std::time::Duration {
+        self.completed_at.map_or_else(|| self.created_at.elapsed(), |at| at - self.created_at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue_item_lifecycle() {
+        let mut item = QueueItem::new(1, "agent".into(), "session/new".into(), "{}".into());
+        assert_eq!(item.status, QueueItemStatus::Pending);
+        assert!(item.is_open());
+
+        item.complete(Some(r#"{"result":{}}"#.into()), None);
+        assert_eq!(item.status, QueueItemStatus::Success);
+        assert!(!item.is_open() && item.completed_at.is_some());
+
+        let mut failed = QueueItem::new(2, "agent".into(), "session/prompt".into(), "{}".into());
+        failed.complete(None, Some("Cancelled locally".into()));
+        assert_eq!(failed.status, QueueItemStatus::Failed);
+        assert_eq!(failed.error_message.as_deref(), Some("Cancelled locally"));
+    }
+}
@@ -0,0 +1,311 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+/// One hour of samples at the default interval.
+const MAX_SAMPLES: usize = 3600;
+
+/// Clock ticks per second in `/proc/<pid>/stat`, fixed by the Linux ABI.
+const USER_HZ: f64 = 100.;
+
+/// A process in the agent's tree, as read from `/proc`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub ppid: u32,
+    pub name: String,
+    /// User plus system time, in clock ticks.
+    pub cpu_ticks: u64,
+    pub rss_bytes: u64,
+    pub threads: u32,
+    pub fds: u32,
+    /// Depth below the agent process, which is 0.
+    pub depth: usize,
+}
+
+/// Totals over the agent and its descendants at one point in time.
+#[derive(Debug, Clone, Copy)]
+pub struct ResourceSample {
+    pub at: Instant,
+    pub cpu_percent: f32,
+    pub rss_bytes: u64,
+    pub threads: u32,
+    pub fds: u32,
+}
+
+/// Samples of the agent's resource use, oldest first, and its latest
+/// process tree.
+#[derive(Debug, Clone, Default)]
+pub struct ResourceHistory {
+    pub samples: VecDeque<ResourceSample>,
+    /// Depth-first, starting with the agent process.
+    pub tree: Vec<ProcessInfo>,
+    /// Root pid, time and total CPU ticks of the previous sample.
+    last: Option<(u32, Instant, u64)>,
+}
+
+impl ResourceHistory {
+    /// Add a reading of the process tree taken at `at`.
+    pub fn record(&mut self, at: Instant, tree: Vec<ProcessInfo>) {
+        let Some(root) = tree.first().map(|p| p.pid) else {
+            return;
+        };
+        let ticks: u64 = tree.iter().map(|p| p.cpu_ticks).sum();
+        // CPU use is the rate since the previous sample of the same process.
+        let cpu_percent = match self.last {
+            Some((pid, last_at, last_ticks)) if pid == root && at > last_at => {
+                let seconds = ticks.saturating_sub(last_ticks) as f64 / USER_HZ;
+                (seconds / at.duration_since(last_at).as_secs_f64() * 100.) as f32
+            }
+            _ => 0.,
+        };
+        self.last = Some((root, at, ticks));
+
+        if self.samples.len() == MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(ResourceSample {
+            at,
+            cpu_percent,
+            rss_bytes: tree.iter().map(|p| p.rss_bytes).sum(),
+            threads: tree.iter().map(|p| p.threads).sum(),
+            fds: tree.iter().map(|p| p.fds).sum(),
+        });
+        self.tree = tree;
+    }
+}
+
+/// The peak of each `bucket`-long stretch of `values` that overlaps `range`,
+/// so a long history draws as at most one bar per bucket on screen.
+pub fn downsample(
+    values: &[(Duration, f64)],
+    bucket: Duration,
+    range: Range<Duration>,
+) -> Vec<(Duration, f64)> {
+    let bucket_ns = bucket.as_nanos().max(1);
+    let start = range.start.saturating_sub(bucket);
+    let first = values.partition_point(|(at, _)| *at < start);
+    let mut out: Vec<(u128, f64)> = Vec::new();
+    for (at, value) in values[first..].iter().take_while(|(at, _)| *at < range.end) {
+        let ix = at.as_nanos() / bucket_ns;
+        match out.last_mut() {
+            Some((last, peak)) if *last == ix => *peak = peak.max(*value),
+            _ => out.push((ix, *value)),
+        }
+    }
+    out.into_iter()
+        .map(|(ix, peak)| (Duration::from_nanos((ix * bucket_ns) as u64), peak))
+        .collect()
+}
+
+/// Parse `/proc/<pid>/stat` into the process name, parent pid, CPU ticks
+/// and thread count.
+pub fn parse_stat(text: &str) -> Option<(String, u32, u64, u32)> {
+    // The name is in parentheses and may itself contain spaces or ')'.
+    let open = text.find('(')?;
+    let close = text.rfind(')')?;
+    let name = text.get(open + 1..close)?.to_string();
+    // Fields after the name, starting with the state (field 3).
+    let fields: Vec<&str> = text.get(close + 1..)?.split_whitespace().collect();
+    let field = |n: usize| fields.get(n - 3).and_then(|f| f.parse::<u64>().ok());
+    let ppid = field(4)? as u32;
+    let cpu_ticks = field(14)? + field(15)?;
+    let threads = field(20)? as u32;
+    Some((name, ppid, cpu_ticks, threads))
+}
+
+/// The resident set size from `/proc/<pid>/status`, in bytes.
+pub fn parse_rss(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb = line.split_whitespace().nth(1)?.parse::<u64>().ok()?;
+    Some(kb * 1024)
+}
+
+/// Read the process `root` and all of its descendants from `/proc`.
+#[cfg(target_os = "linux")]
+pub fn read_tree(root: u32) -> io::Result<Vec<ProcessInfo>> {
+    let mut stats = HashMap::new();
+    for entry in std::fs::read_dir("/proc")? {
+        let Some(pid) = entry?.file_name().to_str().and_then(|name| name.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        // Processes may exit while we scan.
+        if let Some(stat) = std::fs::read_to_string(format!("/proc/{}/stat", pid))
+            .ok()
+            .as_deref()
+            .and_then(parse_stat)
+        {
+            stats.insert(pid, stat);
+        }
+    }
+    if !stats.contains_key(&root) {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("no process {}", root)));
+    }
+
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for (pid, (_, ppid, ..)) in &stats {
+        children.entry(*ppid).or_default().push(*pid);
+    }
+    let mut tree = Vec::new();
+    let mut stack = vec![(root, 0)];
+    while let Some((pid, depth)) = stack.pop() {
+        let Some((name, ppid, cpu_ticks, threads)) = stats.remove(&pid) else {
+            continue;
+        };
+        let rss_bytes = std::fs::read_to_string(format!("/proc/{}/status", pid))
+            .ok()
+            .as_deref()
+            .and_then(parse_rss)
+            .unwrap_or(0);
+        let fds = std::fs::read_dir(format!("/proc/{}/fd", pid)).map_or(0, |dir| dir.count());
+        tree.push(ProcessInfo {
+            pid,
+            ppid,
+            name,
+            cpu_ticks,
+            rss_bytes,
+            threads,
+            fds: fds as u32,
+            depth,
+        });
+        if let Some(kids) = children.get_mut(&pid) {
+            kids.sort_unstable_by(|a, b| b.cmp(a));
+            stack.extend(kids.iter().map(|kid| (*kid, depth + 1)));
+        }
+    }
+    Ok(tree)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read_tree(_root: u32) -> io::Result<Vec<ProcessInfo>> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "process stats need /proc"))
+}
+
+/// Whether `pid` exists and is not a zombie waiting to be reaped.
+#[cfg(target_os = "linux")]
+pub fn is_running(pid: u32) -> bool {
+    let Ok(stat) = std::fs::read_to_string(format!("/proc/{}/stat", pid)) else {
+        return false;
+    };
+    let state = stat.rfind(')').and_then(|close| stat[close + 1..].split_whitespace().next());
+    !matches!(state, None | Some("Z") | Some("X"))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn is_running(_pid: u32) -> bool {
+    false
+}
+
+/// Format a byte count for display, e.g. `142.3 MB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024. && unit < UNITS.len() - 1 {
+        value /= 1024.;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: u32, cpu_ticks: u64, rss_bytes: u64) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            ppid: 1,
+            name: "agent".into(),
+            cpu_ticks,
+            rss_bytes,
+            threads: 2,
+            fds: 5,
+            depth: 0,
+        }
+    }
+
+    #[test]
+    fn test_parse_proc() {
+        let stat = "4242 (my (odd) agent) S 4200 4242 4200 0 -1 4194560 900 0 0 0 \
+                    120 30 0 0 20 0 7 0 12345 1000000 2000 18446744073709551615";
+        assert_eq!(parse_stat(stat), Some(("my (odd) agent".into(), 4200, 150, 7)));
+        assert_eq!(parse_stat("4242 (agent"), None);
+
+        let status = "Name:\tagent\nVmPeak:\t  9000 kB\nVmRSS:\t  2048 kB\nThreads:\t7\n";
+        assert_eq!(parse_rss(status), Some(2048 * 1024));
+        assert_eq!(format_bytes(2048 * 1024), "2.0 MB");
+        assert_eq!(format_bytes(512), "512 B");
+    }
+
+    #[test]
+    fn test_record_totals_and_cpu() {
+        let t0 = Instant::now();
+        let mut history = ResourceHistory::default();
+        history.record(t0, vec![process(10, 100, 1000), process(11, 50, 500)]);
+        assert_eq!(history.samples.back().unwrap().cpu_percent, 0.);
+
+        // 50 ticks of CPU over one second is half a core.
+        history.record(
+            t0 + Duration::from_secs(1),
+            vec![process(10, 130, 1000), process(11, 70, 700)],
+        );
+        let sample = history.samples.back().unwrap();
+        assert_eq!(sample.cpu_percent, 50.);
+        assert_eq!((sample.rss_bytes, sample.threads, sample.fds), (1700, 4, 10));
+
+        // A restarted agent starts a new baseline.
+        history.record(t0 + Duration::from_secs(2), vec![process(20, 5, 100)]);
+        assert_eq!(history.samples.back().unwrap().cpu_percent, 0.);
+        assert_eq!(history.samples.len(), 3);
+    }
+
+    #[test]
+    fn test_downsample() {
+        let secs = |s: u64| Duration::from_secs(s);
+        let values: Vec<(Duration, f64)> = (0..3600).map(|s| (secs(s), (s % 7) as f64)).collect();
+
+        // Ten-second buckets over the first minute keep each bucket's peak.
+        let bars = downsample(&values, secs(10), secs(0)..secs(60));
+        assert_eq!(bars.len(), 6);
+        assert_eq!(bars[0], (secs(0), 6.));
+        assert_eq!(bars[5].0, secs(50));
+
+        // Only the visible stretch is drawn, plus the bucket before it.
+        let bars = downsample(&values, secs(1), secs(1000)..secs(1100));
+        assert_eq!(bars.len(), 101);
+        assert_eq!(bars.first().map(|b| b.0), Some(secs(999)));
+        assert!(downsample(&values, secs(1), secs(4000)..secs(5000)).is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_read_own_tree() {
+        let tree = read_tree(std::process::id()).unwrap();
+        assert_eq!(tree[0].pid, std::process::id());
+        assert_eq!(tree[0].depth, 0);
+        assert!(tree[0].threads >= 1 && tree[0].rss_bytes > 0 && tree[0].fds > 0);
+    }
+}
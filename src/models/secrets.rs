@@ -0,0 +1,372 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{LazyLock, Mutex},
+};
+
+use aes::Aes256;
+use anyhow::{anyhow, bail, Context as _};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use cbc::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use hmac::{Hmac, Mac};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::models::file_settings_store::write_atomic;
+
+/// Shown in place of a secret value.
+pub const MASK: &str = "••••••";
+
+/// PBKDF2 rounds for newly sealed files; the count is stored in the file.
+const ITERATIONS: u32 = 600_000;
+const SECRETS_VERSION: u32 = 1;
+
+/// `${env:NAME}` or `${secret:NAME}`.
+static REFERENCE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\$\{(env|secret):([A-Za-z0-9_.\-]+)\}").unwrap());
+
+/// The unlocked secrets, shared with every transport spawned while unlocked.
+static UNLOCKED: Mutex<Option<Unlocked>> = Mutex::new(None);
+
+struct Unlocked {
+    store: SecretStore,
+    passphrase: String,
+}
+
+/// A reference to a value kept outside the agent config.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecretRef {
+    /// `${env:NAME}`: a variable of the debugger's own environment.
+    Env(String),
+    /// `${secret:NAME}`: an entry of the encrypted secrets file.
+    Secret(String),
+}
+
+/// Named secrets, stored encrypted with a passphrase.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecretStore {
+    secrets: BTreeMap<String, String>,
+}
+
+/// The on-disk form: AES-256-CBC, then HMAC-SHA256 over IV and ciphertext,
+/// with both keys derived from the passphrase by PBKDF2-HMAC-SHA256.
+#[derive(Serialize, Deserialize)]
+struct SealedSecrets {
+    version: u32,
+    iterations: u32,
+    salt: String,
+    iv: String,
+    ciphertext: String,
+    mac: String,
+}
+
+impl SecretStore {
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.secrets.keys().map(String::as_str)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.secrets.get(name).map(String::as_str)
+    }
+
+    pub fn set(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.secrets.insert(name.into(), value.into());
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.secrets.remove(name);
+    }
+
+    /// Encrypt the store into the JSON text of a secrets file.
+    pub fn seal(&self, passphrase: &str) -> anyhow::Result<String> {
+        self.seal_with(passphrase, ITERATIONS)
+    }
+
+    fn seal_with(&self, passphrase: &str, iterations: u32) -> anyhow::Result<String> {
+        let mut salt = [0u8; 16];
+        let mut iv = [0u8; 16];
+        getrandom::fill(&mut salt).map_err(|e| anyhow!("no randomness available: {}", e))?;
+        getrandom::fill(&mut iv).map_err(|e| anyhow!("no randomness available: {}", e))?;
+
+        let (enc_key, mac_key) = derive_keys(passphrase, &salt, iterations);
+        let plaintext = serde_json::to_vec(self)?;
+        let ciphertext = cbc::Encryptor::<Aes256>::new_from_slices(&enc_key, &iv)
+            .map_err(|e| anyhow!("{}", e))?
+            .encrypt_padded_vec_mut::<Pkcs7>(&plaintext);
+        let mac = mac(&mac_key, &iv, &ciphertext).finalize().into_bytes();
+
+        let sealed = SealedSecrets {
+            version: SECRETS_VERSION,
+            iterations,
+            salt: BASE64.encode(salt),
+            iv: BASE64.encode(iv),
+            ciphertext: BASE64.encode(ciphertext),
+            mac: BASE64.encode(mac),
+        };
+        Ok(serde_json::to_string_pretty(&sealed)?)
+    }
+
+    /// Decrypt the JSON text of a secrets file.
+    pub fn open(text: &str, passphrase: &str) -> anyhow::Result<Self> {
+        let sealed: SealedSecrets = serde_json::from_str(text).context("not a secrets file")?;
+        if sealed.version > SECRETS_VERSION {
+            bail!("unsupported secrets file version {}", sealed.version);
+        }
+        let decode = |field: &str| BASE64.decode(field).context("corrupted secrets file");
+        let (salt, iv) = (decode(&sealed.salt)?, decode(&sealed.iv)?);
+        let (ciphertext, tag) = (decode(&sealed.ciphertext)?, decode(&sealed.mac)?);
+
+        let (enc_key, mac_key) = derive_keys(passphrase, &salt, sealed.iterations);
+        mac(&mac_key, &iv, &ciphertext)
+            .verify_slice(&tag)
+            .map_err(|_| anyhow!("wrong passphrase or corrupted secrets file"))?;
+        let plaintext = cbc::Decryptor::<Aes256>::new_from_slices(&enc_key, &iv)
+            .map_err(|e| anyhow!("{}", e))?
+            .decrypt_padded_vec_mut::<Pkcs7>(&ciphertext)
+            .map_err(|_| anyhow!("corrupted secrets file"))?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    pub fn load(path: &Path, passphrase: &str) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Self::open(&text, passphrase)
+    }
+
+    pub fn save(&self, path: &Path, passphrase: &str) -> anyhow::Result<()> {
+        write_atomic(path, &self.seal(passphrase)?)
+    }
+}
+
+fn derive_keys(passphrase: &str, salt: &[u8], iterations: u32) -> ([u8; 32], [u8; 32]) {
+    let mut key = [0u8; 64];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, iterations, &mut key);
+    let (enc, mac) = key.split_at(32);
+    (enc.try_into().unwrap(), mac.try_into().unwrap())
+}
+
+fn mac(key: &[u8], iv: &[u8], ciphertext: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(iv);
+    mac.update(ciphertext);
+    mac
+}
+
+/// `~/.config/acp-debugger/secrets.json`.
+pub fn secrets_path() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+    PathBuf::from(home).join(".config").join("acp-debugger").join("secrets.json")
+}
+
+/// Make `store` available to agents spawned from now on.
+pub fn unlock(store: SecretStore, passphrase: String) {
+    *UNLOCKED.lock().unwrap() = Some(Unlocked { store, passphrase });
+}
+
+pub fn lock() {
+    *UNLOCKED.lock().unwrap() = None;
+}
+
+/// The unlocked store, if any.
+pub fn unlocked() -> Option<SecretStore> {
+    UNLOCKED.lock().unwrap().as_ref().map(|unlocked| unlocked.store.clone())
+}
+
+/// Replace the unlocked store and write it back to [`secrets_path`].
+pub fn save_unlocked(store: SecretStore) -> anyhow::Result<()> {
+    let mut unlocked = UNLOCKED.lock().unwrap();
+    let unlocked = unlocked.as_mut().context("secrets are locked")?;
+    store.save(&secrets_path(), &unlocked.passphrase)?;
+    unlocked.store = store;
+    Ok(())
+}
+
+/// Whether `name` can be used in a `${secret:NAME}` reference.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || "_.-".contains(c))
+}
+
+/// All references in `value`.
+pub fn references(value: &str) -> Vec<SecretRef> {
+    REFERENCE
+        .captures_iter(value)
+        .map(|caps| match &caps[1] {
+            "env" => SecretRef::Env(caps[2].to_string()),
+            _ => SecretRef::Secret(caps[2].to_string()),
+        })
+        .collect()
+}
+
+/// Reject `${...}` that is not a valid reference, such as a misspelled one.
+pub fn validate_references(value: &str) -> Result<(), String> {
+    let rest = REFERENCE.replace_all(value, "");
+    match rest.find("${") {
+        Some(ix) => Err(format!(
+            "Invalid secret reference \"{}\"; use ${{env:NAME}} or ${{secret:NAME}}",
+            rest[ix..].split('}').next().unwrap_or_default()
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Substitute the references in `value` using the environment and the
+/// unlocked store, adding each substituted `${secret:...}` to `revealed`.
+pub fn resolve(value: &str, revealed: &mut Vec<String>) -> Result<String, String> {
+    let store = unlocked();
+    resolve_with(
+        value,
+        |reference| match reference {
+            SecretRef::Env(name) => std::env::var(name).ok(),
+            SecretRef::Secret(name) => store.as_ref()?.get(name).map(str::to_string),
+        },
+        revealed,
+    )
+}
+
+fn resolve_with(
+    value: &str,
+    lookup: impl Fn(&SecretRef) -> Option<String>,
+    revealed: &mut Vec<String>,
+) -> Result<String, String> {
+    let mut out = String::with_capacity(value.len());
+    let mut last = 0;
+    for (caps, reference) in REFERENCE.captures_iter(value).zip(references(value)) {
+        let whole = caps.get(0).unwrap();
+        let resolved = lookup(&reference).ok_or_else(|| match &reference {
+            SecretRef::Env(name) => format!("environment variable {} is not set", name),
+            SecretRef::Secret(name) if unlocked().is_none() => {
+                format!("secret {} needs the secrets file to be unlocked", name)
+            }
+            SecretRef::Secret(name) => format!("secret {} is not in the secrets file", name),
+        })?;
+        out.push_str(&value[last..whole.start()]);
+        out.push_str(&resolved);
+        if matches!(reference, SecretRef::Secret(_)) {
+            revealed.push(resolved);
+        }
+        last = whole.end();
+    }
+    out.push_str(&value[last..]);
+    Ok(out)
+}
+
+/// Whether an env variable named `name` probably holds a credential, so its
+/// value should be masked even without a `${secret:...}` reference.
+pub fn is_credential_name(name: &str) -> bool {
+    let name = name.to_ascii_uppercase();
+    ["KEY", "TOKEN", "SECRET", "PASSWORD", "PASSWD", "CREDENTIAL", "AUTH"]
+        .iter()
+        .any(|word| name.contains(word))
+}
+
+/// `env` with the literal values of credential-like names replaced by
+/// [`MASK`], for display. References are shown as they are.
+pub fn mask_env(env: &[(String, String)]) -> Vec<(String, String)> {
+    env.iter()
+        .map(|(key, value)| {
+            let hide = is_credential_name(key) && references(value).is_empty();
+            (key.clone(), if hide { MASK.to_string() } else { value.clone() })
+        })
+        .collect()
+}
+
+/// Undo [`mask_env`]: entries still set to [`MASK`] keep their value from
+/// `original`.
+pub fn unmask_env(
+    env: Vec<(String, String)>,
+    original: &[(String, String)],
+) -> Vec<(String, String)> {
+    env.into_iter()
+        .map(|(key, value)| {
+            let kept = (value == MASK)
+                .then(|| original.iter().find(|(k, _)| *k == key).map(|(_, v)| v.clone()))
+                .flatten();
+            (key, kept.unwrap_or(value))
+        })
+        .collect()
+}
+
+/// Replace every occurrence of the `secrets` in `text` with [`MASK`].
+pub fn redact(text: &str, secrets: &[String]) -> String {
+    secrets
+        .iter()
+        // Very short values would mask unrelated text.
+        .filter(|secret| secret.len() >= 4)
+        .fold(text.to_string(), |text, secret| text.replace(secret.as_str(), MASK))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open() {
+        let mut store = SecretStore::default();
+        store.set("OPENAI_KEY", "sk-123");
+        let sealed = store.seal_with("hunter2", 1_000).unwrap();
+
+        assert!(!sealed.contains("sk-123"));
+        assert_eq!(SecretStore::open(&sealed, "hunter2").unwrap(), store);
+        assert!(SecretStore::open(&sealed, "wrong")
+            .unwrap_err()
+            .to_string()
+            .contains("passphrase"));
+    }
+
+    #[test]
+    fn test_resolve_references() {
+        let lookup = |reference: &SecretRef| match reference {
+            SecretRef::Env(name) if name == "USER" => Some("alice".to_string()),
+            SecretRef::Secret(name) if name == "token" => Some("t0ps3cret".to_string()),
+            _ => None,
+        };
+        let mut revealed = Vec::new();
+        assert_eq!(
+            resolve_with("Bearer ${secret:token} for ${env:USER}", lookup, &mut revealed),
+            Ok("Bearer t0ps3cret for alice".to_string())
+        );
+        assert_eq!(revealed, ["t0ps3cret"]);
+        assert!(resolve_with("${env:MISSING}", lookup, &mut revealed).is_err());
+        assert_eq!(resolve_with("plain", lookup, &mut revealed), Ok("plain".to_string()));
+
+        assert!(validate_references("${env:HOME}/bin").is_ok());
+        assert!(validate_references("${ENV:HOME}").is_err());
+        assert_eq!(redact("token=t0ps3cret", &revealed), format!("token={}", MASK));
+
+        assert!(is_credential_name("OPENAI_API_KEY") && is_credential_name("github_token"));
+        assert!(!is_credential_name("PATH") && !is_credential_name("HOME"));
+    }
+
+    #[test]
+    fn test_mask_env() {
+        let env = vec![
+            ("API_KEY".to_string(), "sk-live".to_string()),
+            ("GH_TOKEN".to_string(), "${secret:gh}".to_string()),
+            ("LOG".to_string(), "debug".to_string()),
+        ];
+        let masked = mask_env(&env);
+        assert_eq!(masked[0].1, MASK);
+        assert_eq!(masked[1].1, "${secret:gh}");
+        assert_eq!(unmask_env(masked.clone(), &env), env);
+
+        // An edited value replaces the hidden one.
+        let mut edited = masked;
+        edited[0].1 = "sk-new".into();
+        assert_eq!(unmask_env(edited, &env)[0].1, "sk-new");
+    }
+}
@@ -0,0 +1,302 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::secrets;
+
+/// How the agent reaches an MCP server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum McpKind {
+    Stdio,
+    Http,
+    Sse,
+}
+
+impl McpKind {
+    pub const ALL: [McpKind; 3] = [McpKind::Stdio, McpKind::Http, McpKind::Sse];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Stdio => "stdio",
+            Self::Http => "HTTP",
+            Self::Sse => "SSE",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum McpTransport {
+    Stdio { command: String, args: Vec<String>, env: Vec<(String, String)> },
+    Http { url: String, headers: Vec<(String, String)> },
+    Sse { url: String, headers: Vec<(String, String)> },
+}
+
+/// An MCP server passed to the agent in `session/new`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct McpServer {
+    pub name: String,
+    #[serde(flatten)]
+    pub transport: McpTransport,
+}
+
+impl McpServer {
+    pub fn kind(&self) -> McpKind {
+        match self.transport {
+            McpTransport::Stdio { .. } => McpKind::Stdio,
+            McpTransport::Http { .. } => McpKind::Http,
+            McpTransport::Sse { .. } => McpKind::Sse,
+        }
+    }
+
+    /// The command or URL, for display.
+    pub fn target(&self) -> String {
+        match &self.transport {
+            McpTransport::Stdio { command, args, .. } => {
+                std::iter::once(command).chain(args).cloned().collect::<Vec<_>>().join(" ")
+            }
+            McpTransport::Http { url, .. } | McpTransport::Sse { url, .. } => url.clone(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("MCP server name is required".into());
+        }
+        match &self.transport {
+            McpTransport::Stdio { command, .. } if command.trim().is_empty() => {
+                Err(format!("{}: command is required", self.name))
+            }
+            McpTransport::Http { url, .. } | McpTransport::Sse { url, .. }
+                if !(url.starts_with("http://") || url.starts_with("https://")) =>
+            {
+                Err(format!("{}: URL must start with http:// or https://", self.name))
+            }
+            _ => self.pairs().iter().try_for_each(|(key, value)| {
+                secrets::validate_references(value)
+                    .map_err(|e| format!("{} {}: {}", self.name, key, e))
+            }),
+        }
+    }
+
+    /// The env of a stdio server, or the headers of an HTTP or SSE one.
+    fn pairs(&self) -> &[(String, String)] {
+        match &self.transport {
+            McpTransport::Stdio { env, .. } => env,
+            McpTransport::Http { headers, .. } | McpTransport::Sse { headers, .. } => headers,
+        }
+    }
+
+    /// The server with the references in its env or headers substituted.
+    /// Secrets and values with credential-like names go to `revealed`.
+    pub fn resolve(&self, revealed: &mut Vec<String>) -> Result<Self, String> {
+        let pairs = self
+            .pairs()
+            .iter()
+            .map(|(key, value)| {
+                let resolved = secrets::resolve(value, revealed)
+                    .map_err(|e| format!("{} {}: {}", self.name, key, e))?;
+                if secrets::is_credential_name(key) {
+                    revealed.push(resolved.clone());
+                }
+                Ok((key.clone(), resolved))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let transport = match &self.transport {
+            McpTransport::Stdio { command, args, .. } => {
+                McpTransport::Stdio { command: command.clone(), args: args.clone(), env: pairs }
+            }
+            McpTransport::Http { url, .. } => {
+                McpTransport::Http { url: url.clone(), headers: pairs }
+            }
+            McpTransport::Sse { url, .. } => McpTransport::Sse { url: url.clone(), headers: pairs },
+        };
+        Ok(Self { name: self.name.clone(), transport })
+    }
+
+    /// Parse an entry of the `mcpServers` param, as [`Self::to_acp`] writes it.
+    pub fn from_acp(value: &Value) -> Option<Self> {
+        let text = |key: &str| value.get(key).and_then(Value::as_str).map(str::to_string);
+        let pairs = |key: &str| -> Vec<(String, String)> {
+            value
+                .get(key)
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(|pair| {
+                    Some((
+                        pair.get("name")?.as_str()?.to_string(),
+                        pair.get("value")?.as_str()?.to_string(),
+                    ))
+                })
+                .collect()
+        };
+        let transport = match value.get("type").and_then(Value::as_str) {
+            Some("http") => McpTransport::Http { url: text("url")?, headers: pairs("headers") },
+            Some("sse") => McpTransport::Sse { url: text("url")?, headers: pairs("headers") },
+            _ => McpTransport::Stdio {
+                command: text("command")?,
+                args: value
+                    .get("args")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|arg| arg.as_str().map(str::to_string))
+                    .collect(),
+                env: pairs("env"),
+            },
+        };
+        Some(Self { name: text("name")?, transport })
+    }
+
+    /// The server as an entry of the `mcpServers` param.
+    pub fn to_acp(&self) -> Value {
+        let pairs = |pairs: &[(String, String)]| {
+            pairs
+                .iter()
+                .map(|(name, value)| json!({ "name": name, "value": value }))
+                .collect::<Vec<_>>()
+        };
+        match &self.transport {
+            McpTransport::Stdio { command, args, env } => json!({
+                "name": self.name,
+                "command": command,
+                "args": args,
+                "env": pairs(env),
+            }),
+            McpTransport::Http { url, headers } => json!({
+                "type": "http",
+                "name": self.name,
+                "url": url,
+                "headers": pairs(headers),
+            }),
+            McpTransport::Sse { url, headers } => json!({
+                "type": "sse",
+                "name": self.name,
+                "url": url,
+                "headers": pairs(headers),
+            }),
+        }
+    }
+}
+
+/// The MCP transports an agent accepts besides stdio, from the
+/// `mcpCapabilities` it advertises in `initialize`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct McpCapabilities {
+    pub http: bool,
+    pub sse: bool,
+}
+
+impl McpCapabilities {
+    pub fn from_initialize(result: Option<&Value>) -> Self {
+        let flag = |name: &str| {
+            result
+                .and_then(|r| r.pointer(&format!("/agentCapabilities/mcpCapabilities/{}", name)))
+                .and_then(Value::as_bool)
+                .unwrap_or(false)
+        };
+        Self { http: flag("http"), sse: flag("sse") }
+    }
+
+    pub fn supports(&self, kind: McpKind) -> bool {
+        match kind {
+            McpKind::Stdio => true,
+            McpKind::Http => self.http,
+            McpKind::Sse => self.sse,
+        }
+    }
+}
+
+/// A saved `session/new` setup for one agent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionPreset {
+    pub name: String,
+    pub cwd: String,
+    #[serde(default)]
+    pub mcp_servers: Vec<McpServer>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mcp_servers() {
+        let stdio = McpServer {
+            name: "fs".into(),
+            transport: McpTransport::Stdio {
+                command: "mcp-fs".into(),
+                args: vec!["--root".into(), "/tmp".into()],
+                env: vec![("LOG".into(), "debug".into())],
+            },
+        };
+        assert_eq!(
+            stdio.to_acp(),
+            json!({
+                "name": "fs",
+                "command": "mcp-fs",
+                "args": ["--root", "/tmp"],
+                "env": [{ "name": "LOG", "value": "debug" }],
+            })
+        );
+        assert_eq!(stdio.target(), "mcp-fs --root /tmp");
+
+        let sse = McpServer {
+            name: "events".into(),
+            transport: McpTransport::Sse { url: "localhost:9000".into(), headers: Vec::new() },
+        };
+        assert!(sse.validate().unwrap_err().contains("http://"));
+        assert_eq!(sse.to_acp()["type"], "sse");
+        assert_eq!(McpServer::from_acp(&stdio.to_acp()).as_ref(), Some(&stdio));
+        assert_eq!(McpServer::from_acp(&sse.to_acp()), Some(sse));
+
+        let preset = SessionPreset { name: "p".into(), cwd: "~".into(), mcp_servers: vec![stdio] };
+        let saved = serde_json::to_string(&preset).unwrap();
+        assert_eq!(serde_json::from_str::<SessionPreset>(&saved).unwrap(), preset);
+
+        let init = json!({ "agentCapabilities": { "mcpCapabilities": { "http": true } } });
+        let caps = McpCapabilities::from_initialize(Some(&init));
+        assert!(caps.supports(McpKind::Http) && !caps.supports(McpKind::Sse));
+        assert_eq!(McpCapabilities::from_initialize(None), McpCapabilities::default());
+    }
+
+    #[test]
+    fn test_mcp_secrets() {
+        let http = |value: &str| McpServer {
+            name: "api".into(),
+            transport: McpTransport::Http {
+                url: "https://mcp.example.com".into(),
+                headers: vec![
+                    ("Authorization".into(), "Bearer abcd1234".into()),
+                    ("X-Home".into(), value.into()),
+                ],
+            },
+        };
+        assert!(http("${oops}").validate().unwrap_err().contains("X-Home"));
+
+        let server = http("${env:HOME}");
+        assert!(server.validate().is_ok());
+        let mut revealed = Vec::new();
+        let resolved = server.resolve(&mut revealed).unwrap();
+        let McpTransport::Http { headers, .. } = resolved.transport else {
+            unreachable!();
+        };
+        assert_eq!(headers[1].1, std::env::var("HOME").unwrap());
+        assert_eq!(revealed, ["Bearer abcd1234"]);
+        assert!(http("${secret:missing}").resolve(&mut revealed).is_err());
+    }
+}
@@ -16,6 +16,8 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::models::SavedAgents;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppSettings {
     pub default_timeout_ms: u64,
@@ -54,4 +56,6 @@ pub enum LogLevel {
 pub trait SettingsStore {
     fn load(&self) -> anyhow::Result<AppSettings>;
     fn save(&self, settings: &AppSettings) -> anyhow::Result<()>;
+    fn load_agents(&self) -> anyhow::Result<SavedAgents>;
+    fn save_agents(&self, agents: &SavedAgents) -> anyhow::Result<()>;
 }
@@ -0,0 +1,350 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use super::AcpMessage;
+
+/// Frame counts and response latency for one method.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MethodStats {
+    pub method: String,
+    /// Requests and notifications sent to the agent.
+    pub sent: usize,
+    /// Requests and notifications received from the agent.
+    pub received: usize,
+    /// Responses to this method that carried an error.
+    pub errors: usize,
+    pub latency: Option<LatencyStats>,
+}
+
+/// Response times of a request method, in milliseconds.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencyStats {
+    pub count: usize,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+}
+
+impl LatencyStats {
+    fn from_samples(mut samples: Vec<Duration>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort();
+        // Nearest-rank percentile.
+        let percentile = |p: f64| {
+            let rank = ((p * samples.len() as f64).ceil() as usize).max(1);
+            as_ms(samples[rank - 1])
+        };
+        Some(Self {
+            count: samples.len(),
+            p50_ms: percentile(0.5),
+            p95_ms: percentile(0.95),
+            max_ms: as_ms(samples[samples.len() - 1]),
+        })
+    }
+}
+
+/// How often a JSON-RPC error code was returned.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorCodeStats {
+    pub code: i64,
+    /// The message of the first error seen with this code.
+    pub message: String,
+    pub count: usize,
+    /// Share of all responses, from 0 to 1.
+    pub rate: f64,
+}
+
+/// Notification throughput while a `session/prompt` is running.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamingStats {
+    pub notifications: usize,
+    /// Total time prompts were running.
+    pub seconds: f64,
+    pub per_second: f64,
+    /// The most notifications received within one second of a turn.
+    pub peak_per_second: usize,
+}
+
+/// Traffic statistics computed from the messages of one connection.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrafficStats {
+    pub messages: usize,
+    pub bytes_sent: usize,
+    pub bytes_received: usize,
+    pub responses: usize,
+    /// Sorted by method name.
+    pub methods: Vec<MethodStats>,
+    /// Sorted by error code.
+    pub errors: Vec<ErrorCodeStats>,
+    pub streaming: StreamingStats,
+    /// Count per `session/update` kind, most frequent first.
+    pub update_kinds: Vec<(String, usize)>,
+}
+
+impl TrafficStats {
+    /// Compute statistics over `messages` in capture order.
+    pub fn compute(messages: &[AcpMessage]) -> Self {
+        let mut stats = Self { messages: messages.len(), ..Self::default() };
+        let mut methods: BTreeMap<String, MethodStats> = BTreeMap::new();
+        let mut latencies: HashMap<String, Vec<Duration>> = HashMap::new();
+        let mut errors: BTreeMap<i64, (String, usize)> = BTreeMap::new();
+        let mut update_kinds: HashMap<String, usize> = HashMap::new();
+        // Open requests by (sent by agent, id) → (method, sent at).
+        let mut open: HashMap<(bool, String), (String, Instant)> = HashMap::new();
+        // Ids of running prompt turns, and notifications received during them.
+        let mut prompts: HashSet<String> = HashSet::new();
+        let mut streaming_since: Option<Instant> = None;
+        let mut streaming_time = Duration::ZERO;
+        let mut streamed: Vec<Instant> = Vec::new();
+
+        for message in messages {
+            let bytes = message.raw_json.len();
+            if message.is_response {
+                stats.bytes_received += bytes;
+            } else {
+                stats.bytes_sent += bytes;
+            }
+
+            match (&message.method, &message.id) {
+                (Some(method), id) => {
+                    let entry = methods.entry(method.to_string()).or_insert_with(|| MethodStats {
+                        method: method.to_string(),
+                        ..MethodStats::default()
+                    });
+                    if message.is_response {
+                        entry.received += 1;
+                    } else {
+                        entry.sent += 1;
+                    }
+
+                    match id {
+                        Some(id) => {
+                            let key = (message.is_response, id.to_string());
+                            open.insert(key, (method.to_string(), message.timestamp));
+                            if method == "session/prompt" {
+                                if prompts.is_empty() {
+                                    streaming_since = Some(message.timestamp);
+                                }
+                                prompts.insert(id.to_string());
+                            }
+                        }
+                        None => {
+                            if message.is_response && streaming_since.is_some() {
+                                streamed.push(message.timestamp);
+                            }
+                            if method == "session/update" {
+                                if let Some(kind) = update_kind(message) {
+                                    *update_kinds.entry(kind.to_string()).or_default() += 1;
+                                }
+                            }
+                        }
+                    }
+                }
+                (None, Some(id)) => {
+                    stats.responses += 1;
+                    let key = (!message.is_response, id.to_string());
+                    let method = open.remove(&key).map(|(method, sent_at)| {
+                        latencies
+                            .entry(method.clone())
+                            .or_default()
+                            .push(message.timestamp.saturating_duration_since(sent_at));
+                        method
+                    });
+                    if let Some(error) = &message.error {
+                        let entry = errors
+                            .entry(error.code)
+                            .or_insert_with(|| (error.message.to_string(), 0));
+                        entry.1 += 1;
+                        if let Some(stats) = method.as_ref().and_then(|m| methods.get_mut(m)) {
+                            stats.errors += 1;
+                        }
+                    }
+                    // The end of the last running prompt ends a streaming window.
+                    if message.is_response && prompts.remove(&id.to_string()) && prompts.is_empty()
+                    {
+                        if let Some(since) = streaming_since.take() {
+                            streaming_time += message.timestamp.saturating_duration_since(since);
+                        }
+                    }
+                }
+                (None, None) => {}
+            }
+        }
+
+        // Prompts still running count up to the last message.
+        if let (Some(since), Some(last)) = (streaming_since, messages.last()) {
+            streaming_time += last.timestamp.saturating_duration_since(since);
+        }
+
+        for (method, samples) in latencies {
+            if let Some(stats) = methods.get_mut(&method) {
+                stats.latency = LatencyStats::from_samples(samples);
+            }
+        }
+        stats.methods = methods.into_values().collect();
+
+        stats.errors = errors
+            .into_iter()
+            .map(|(code, (message, count))| ErrorCodeStats {
+                code,
+                message,
+                count,
+                rate: count as f64 / stats.responses.max(1) as f64,
+            })
+            .collect();
+
+        stats.streaming = StreamingStats {
+            notifications: streamed.len(),
+            seconds: streaming_time.as_secs_f64(),
+            per_second: if streaming_time.is_zero() {
+                0.
+            } else {
+                streamed.len() as f64 / streaming_time.as_secs_f64()
+            },
+            peak_per_second: peak_per_second(&streamed),
+        };
+
+        let mut update_kinds: Vec<_> = update_kinds.into_iter().collect();
+        update_kinds.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        stats.update_kinds = update_kinds;
+
+        stats
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+fn update_kind(message: &AcpMessage) -> Option<&str> {
+    message.params.as_ref()?.get("update")?.get("sessionUpdate")?.as_str()
+}
+
+/// The largest number of timestamps within any one-second window.
+fn peak_per_second(timestamps: &[Instant]) -> usize {
+    let mut peak = 0;
+    let mut start = 0;
+    for (end, at) in timestamps.iter().enumerate() {
+        while at.saturating_duration_since(timestamps[start]) >= Duration::from_secs(1) {
+            start += 1;
+        }
+        peak = peak.max(end - start + 1);
+    }
+    peak
+}
+
+fn as_ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compute() {
+        let t0 = Instant::now();
+        let ms = |n| t0 + Duration::from_millis(n);
+        let update = |kind: &str, at| {
+            AcpMessage::captured_at(
+                &format!(
+                    r#"{{"method":"session/update","params":{{"sessionId":"s1","update":{{"sessionUpdate":"{}"}}}}}}"#,
+                    kind
+                ),
+                true,
+                at,
+            )
+        };
+        let messages = vec![
+            AcpMessage::captured_at(r#"{"id":0,"method":"initialize","params":{}}"#, false, ms(0)),
+            AcpMessage::captured_at(r#"{"id":0,"result":{}}"#, true, ms(20)),
+            AcpMessage::captured_at(
+                r#"{"id":1,"method":"session/prompt","params":{"sessionId":"s1"}}"#,
+                false,
+                ms(100),
+            ),
+            update("agent_message_chunk", ms(200)),
+            update("agent_message_chunk", ms(300)),
+            update("tool_call", ms(400)),
+            AcpMessage::captured_at(
+                r#"{"id":1,"result":{"stopReason":"end_turn"}}"#,
+                true,
+                ms(2100),
+            ),
+            AcpMessage::captured_at(
+                r#"{"id":2,"method":"session/prompt","params":{"sessionId":"s1"}}"#,
+                false,
+                ms(3000),
+            ),
+            AcpMessage::captured_at(
+                r#"{"id":2,"error":{"code":-32603,"message":"Internal error"}}"#,
+                true,
+                ms(3040),
+            ),
+        ];
+        let stats = TrafficStats::compute(&messages);
+
+        assert_eq!(stats.messages, 9);
+        assert_eq!(stats.responses, 3);
+        let sent: usize =
+            messages.iter().filter(|m| !m.is_response).map(|m| m.raw_json.len()).sum();
+        assert_eq!(stats.bytes_sent, sent);
+
+        let prompt = stats.methods.iter().find(|m| m.method == "session/prompt").unwrap();
+        assert_eq!((prompt.sent, prompt.received, prompt.errors), (2, 0, 1));
+        let latency = prompt.latency.as_ref().unwrap();
+        assert_eq!(latency.count, 2);
+        assert_eq!(latency.p50_ms, 40.);
+        assert_eq!(latency.max_ms, 2000.);
+
+        let update = stats.methods.iter().find(|m| m.method == "session/update").unwrap();
+        assert_eq!((update.sent, update.received), (0, 3));
+
+        assert_eq!(stats.errors.len(), 1);
+        assert_eq!(stats.errors[0].code, -32603);
+        assert!((stats.errors[0].rate - 1. / 3.).abs() < 1e-9);
+
+        assert_eq!(stats.streaming.notifications, 3);
+        assert!((stats.streaming.seconds - 2.04).abs() < 1e-9);
+        assert_eq!(stats.streaming.peak_per_second, 3);
+
+        assert_eq!(stats.update_kinds[0], ("agent_message_chunk".to_string(), 2));
+        assert!(stats.to_json().unwrap().contains("\"p95Ms\""));
+    }
+
+    #[test]
+    fn test_peak_per_second() {
+        let t0 = Instant::now();
+        let at: Vec<_> = [0, 500, 999, 1000, 1500, 3000]
+            .iter()
+            .map(|n| t0 + Duration::from_millis(*n))
+            .collect();
+        assert_eq!(peak_per_second(&at), 3);
+        assert_eq!(peak_per_second(&[]), 0);
+    }
+}
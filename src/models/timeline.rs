@@ -0,0 +1,375 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::HashMap,
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+use serde_json::Value;
+
+use super::{AcpMessage, MessageType};
+
+/// Stderr lines closer together than this form one burst.
+const BURST_GAP: Duration = Duration::from_millis(250);
+
+/// What a lane groups.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LaneKind {
+    /// Messages outside any session, such as `initialize`.
+    Connection,
+    /// Session messages outside a prompt turn.
+    Session(String),
+    /// Everything between a `session/prompt` and its response.
+    Turn { session_id: String, turn: usize },
+    /// The agent process exiting and being restarted.
+    Process,
+    /// Output the agent wrote to stderr.
+    Stderr,
+}
+
+/// A request drawn as a bar from send to response.
+#[derive(Debug, Clone)]
+pub struct Span {
+    pub label: String,
+    pub start: Duration,
+    /// `None` while the response is outstanding.
+    pub end: Option<Duration>,
+    pub failed: bool,
+    /// Index of the request in the captured messages.
+    pub message_ix: usize,
+}
+
+/// A notification or stderr burst drawn as a tick.
+#[derive(Debug, Clone)]
+pub struct Tick {
+    pub label: String,
+    pub at: Duration,
+    /// Index in the captured messages; `None` for stderr.
+    pub message_ix: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Lane {
+    pub kind: LaneKind,
+    pub spans: Vec<Span>,
+    pub ticks: Vec<Tick>,
+}
+
+impl Lane {
+    fn new(kind: LaneKind) -> Self {
+        Self { kind, spans: Vec::new(), ticks: Vec::new() }
+    }
+
+    /// Spans overlapping `range`; open ones reach `now`.
+    pub fn spans_in(&self, range: Range<Duration>, now: Duration) -> impl Iterator<Item = &Span> {
+        self.spans
+            .iter()
+            .take_while(move |span| span.start < range.end)
+            .filter(move |span| span.end.unwrap_or(now) >= range.start)
+    }
+
+    /// Ticks within `range`, with their index in the lane.
+    pub fn ticks_in(&self, range: Range<Duration>) -> impl Iterator<Item = (usize, &Tick)> {
+        let first = self.ticks.partition_point(|tick| tick.at < range.start);
+        self.ticks[first..]
+            .iter()
+            .take_while(move |tick| tick.at < range.end)
+            .enumerate()
+            .map(move |(ix, tick)| (first + ix, tick))
+    }
+
+    pub fn label(&self) -> String {
+        match &self.kind {
+            LaneKind::Connection => "Connection".into(),
+            LaneKind::Session(id) => format!("Session {}", id),
+            LaneKind::Turn { turn, .. } => format!("  Turn {}", turn),
+            LaneKind::Process => "Process".into(),
+            LaneKind::Stderr => "stderr".into(),
+        }
+    }
+}
+
+/// Captured traffic laid out on a time axis, built up one message at a
+/// time.
+#[derive(Debug, Clone, Default)]
+pub struct Timeline {
+    lanes: Vec<Lane>,
+    /// Time of the first event, which the axis starts at.
+    pub origin: Option<Instant>,
+    /// Open requests by (sent by agent, id) → (lane, span).
+    open: HashMap<(bool, String), (usize, usize)>,
+    /// The lane of the running prompt turn per session.
+    active_turns: HashMap<String, usize>,
+    /// Messages laid out so far.
+    len: usize,
+}
+
+impl Timeline {
+    /// Start the axis at `at` unless it already started.
+    pub fn begin_at(&mut self, at: Instant) {
+        if self.origin.is_none() {
+            self.origin = Some(at);
+            self.lane(LaneKind::Connection);
+        }
+    }
+
+    /// Lay out the next captured message.
+    pub fn push(&mut self, message: &AcpMessage) {
+        self.begin_at(message.timestamp);
+        let ix = self.len;
+        self.len += 1;
+        let at = self.offset(message.timestamp);
+        let method = message.method.as_ref().map(|m| m.to_string());
+        let session_id = message.params.as_ref().and_then(session_of);
+
+        match (method, &message.id) {
+            // Request: open a span in the lane it belongs to.
+            (Some(method), Some(id)) => {
+                let lane = match &session_id {
+                    Some(id) if method == "session/prompt" => self.start_turn(id.clone()),
+                    _ => self.lane_for(session_id.as_deref()),
+                };
+                let span =
+                    Span { label: method, start: at, end: None, failed: false, message_ix: ix };
+                self.lanes[lane].spans.push(span);
+                let span_ix = self.lanes[lane].spans.len() - 1;
+                self.open.insert((message.is_response, id.to_string()), (lane, span_ix));
+            }
+            // Notification: a tick.
+            (Some(method), None) => {
+                let lane = self.lane_for(session_id.as_deref());
+                let label = describe_notification(&method, message.params.as_ref());
+                self.lanes[lane].ticks.push(Tick { label, at, message_ix: Some(ix) });
+            }
+            // Response: close the request's span.
+            (None, Some(id)) => {
+                let key = (!message.is_response, id.to_string());
+                if let Some((lane, span_ix)) = self.open.remove(&key) {
+                    let span = &mut self.lanes[lane].spans[span_ix];
+                    span.end = Some(at);
+                    span.failed = message.message_type == MessageType::Error;
+                    if let LaneKind::Turn { session_id, .. } = &self.lanes[lane].kind {
+                        let session_id = session_id.clone();
+                        self.active_turns.remove(&session_id);
+                    }
+                }
+            }
+            (None, None) => {}
+        }
+    }
+
+    /// Mark a process event, such as an exit or restart, at `at`.
+    pub fn push_event(&mut self, at: Instant, label: String) {
+        self.begin_at(at);
+        let at = self.offset(at);
+        let lane = self.lane(LaneKind::Process);
+        self.lanes[lane].ticks.push(Tick { label, at, message_ix: None });
+    }
+
+    /// The message lanes, without the connection lane while nothing
+    /// happened outside a session.
+    pub fn lanes(&self) -> impl Iterator<Item = &Lane> {
+        self.lanes.iter().filter(|lane| {
+            lane.kind != LaneKind::Connection || !lane.spans.is_empty() || !lane.ticks.is_empty()
+        })
+    }
+
+    /// A lane of stderr bursts, if there were any.
+    pub fn stderr_lane(stderr: &[Instant], origin: Instant) -> Option<Lane> {
+        (!stderr.is_empty())
+            .then(|| Lane { ticks: bursts(stderr, origin), ..Lane::new(LaneKind::Stderr) })
+    }
+
+    fn offset(&self, at: Instant) -> Duration {
+        self.origin.map_or(Duration::ZERO, |origin| at.saturating_duration_since(origin))
+    }
+
+    /// Find or create a lane, keeping turns right below their session.
+    fn lane(&mut self, kind: LaneKind) -> usize {
+        if let Some(ix) = self.lanes.iter().position(|l| l.kind == kind) {
+            return ix;
+        }
+        let ix = match &kind {
+            LaneKind::Turn { session_id, .. } => {
+                let session = self.lane(LaneKind::Session(session_id.clone()));
+                let mut ix = session + 1;
+                while matches!(self.lanes.get(ix).map(|l| &l.kind), Some(LaneKind::Turn { .. })) {
+                    ix += 1;
+                }
+                ix
+            }
+            _ => self.lanes.len(),
+        };
+        self.lanes.insert(ix, Lane::new(kind));
+        // Inserting shifts the lanes below, so fix up stored indexes.
+        for lane in
+            self.open.values_mut().map(|(lane, _)| lane).chain(self.active_turns.values_mut())
+        {
+            if *lane >= ix {
+                *lane += 1;
+            }
+        }
+        ix
+    }
+
+    fn lane_for(&mut self, session_id: Option<&str>) -> usize {
+        match session_id {
+            Some(id) => match self.active_turns.get(id) {
+                Some(&lane) => lane,
+                None => self.lane(LaneKind::Session(id.to_string())),
+            },
+            None => self.lane(LaneKind::Connection),
+        }
+    }
+
+    fn start_turn(&mut self, session_id: String) -> usize {
+        let turn = 1 + self
+            .lanes
+            .iter()
+            .filter(|l| matches!(&l.kind, LaneKind::Turn { session_id: s, .. } if *s == session_id))
+            .count();
+        let lane = self.lane(LaneKind::Turn { session_id: session_id.clone(), turn });
+        self.active_turns.insert(session_id, lane);
+        lane
+    }
+}
+
+fn session_of(params: &Value) -> Option<String> {
+    params.get("sessionId").and_then(|v| v.as_str()).map(str::to_string)
+}
+
+/// `session/update` ticks are labelled with the update kind.
+fn describe_notification(method: &str, params: Option<&Value>) -> String {
+    params
+        .and_then(|p| p.get("update"))
+        .and_then(|u| u.get("sessionUpdate"))
+        .and_then(|k| k.as_str())
+        .map_or_else(|| method.to_string(), str::to_string)
+}
+
+fn bursts(stderr: &[Instant], origin: Instant) -> Vec<Tick> {
+    let mut ticks: Vec<(Instant, Instant, usize)> = Vec::new();
+    for &at in stderr {
+        match ticks.last_mut() {
+            Some((_, last, count)) if at.saturating_duration_since(*last) <= BURST_GAP => {
+                *last = at;
+                *count += 1;
+            }
+            _ => ticks.push((at, at, 1)),
+        }
+    }
+    ticks
+        .into_iter()
+        .map(|(start, _, count)| Tick {
+            label: if count == 1 { "1 line".into() } else { format!("{} lines", count) },
+            at: start.saturating_duration_since(origin),
+            message_ix: None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lanes_per_session_and_turn() {
+        let t0 = Instant::now();
+        let ms = |n| t0 + Duration::from_millis(n);
+        let messages = vec![
+            AcpMessage::captured_at(r#"{"id":0,"method":"initialize","params":{}}"#, false, ms(0)),
+            AcpMessage::captured_at(r#"{"id":0,"result":{}}"#, true, ms(10)),
+            AcpMessage::captured_at(
+                r#"{"id":1,"method":"session/new","params":{"cwd":"/"}}"#,
+                false,
+                ms(20),
+            ),
+            AcpMessage::captured_at(r#"{"id":1,"result":{"sessionId":"s1"}}"#, true, ms(30)),
+            AcpMessage::captured_at(
+                r#"{"id":2,"method":"session/prompt","params":{"sessionId":"s1"}}"#,
+                false,
+                ms(40),
+            ),
+            AcpMessage::captured_at(
+                r#"{"method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"tool_call"}}}"#,
+                true,
+                ms(50),
+            ),
+            AcpMessage::captured_at(
+                r#"{"id":7,"method":"fs/read_text_file","params":{"sessionId":"s1"}}"#,
+                true,
+                ms(60),
+            ),
+            AcpMessage::captured_at(r#"{"id":7,"result":{"content":""}}"#, false, ms(70)),
+            AcpMessage::captured_at(
+                r#"{"id":2,"result":{"stopReason":"end_turn"}}"#,
+                true,
+                ms(100),
+            ),
+            AcpMessage::captured_at(
+                r#"{"id":3,"method":"session/prompt","params":{"sessionId":"s1"}}"#,
+                false,
+                ms(110),
+            ),
+        ];
+        let mut timeline = Timeline::default();
+        for message in &messages {
+            timeline.push(message);
+        }
+        timeline.push_event(ms(200), "exited with code 1".into());
+        let stderr = Timeline::stderr_lane(&[ms(45), ms(46), ms(900)], t0).unwrap();
+        let lanes: Vec<&Lane> = timeline.lanes().chain([&stderr]).collect();
+
+        let kinds: Vec<_> = lanes.iter().map(|l| l.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                LaneKind::Connection,
+                LaneKind::Session("s1".into()),
+                LaneKind::Turn { session_id: "s1".into(), turn: 1 },
+                LaneKind::Turn { session_id: "s1".into(), turn: 2 },
+                LaneKind::Process,
+                LaneKind::Stderr,
+            ]
+        );
+
+        let connection = lanes[0];
+        assert_eq!(connection.spans.len(), 2);
+        assert_eq!(connection.spans[0].end, Some(Duration::from_millis(10)));
+
+        let turn = lanes[2];
+        assert_eq!(turn.spans[0].label, "session/prompt");
+        assert_eq!(turn.spans[0].end, Some(Duration::from_millis(100)));
+        assert_eq!(turn.spans[1].label, "fs/read_text_file");
+        assert_eq!(turn.ticks[0].label, "tool_call");
+
+        // The second prompt is still running.
+        assert_eq!(lanes[3].spans[0].end, None);
+        assert_eq!(lanes[4].ticks[0].at, Duration::from_millis(200));
+
+        assert_eq!(stderr.ticks.len(), 2);
+        assert_eq!(stderr.ticks[0].label, "2 lines");
+
+        // Only what overlaps the visible range is drawn.
+        let visible = Duration::from_millis(55)..Duration::from_millis(105);
+        let spans: Vec<_> = turn.spans_in(visible.clone(), ms(1000) - t0).collect();
+        assert_eq!(spans.len(), 2);
+        assert_eq!(turn.ticks_in(visible).count(), 0);
+        let ticks: Vec<_> = stderr.ticks_in(Duration::from_millis(100)..ms(1000) - t0).collect();
+        assert_eq!(ticks.len(), 1);
+        assert_eq!(ticks[0].0, 1);
+    }
+}
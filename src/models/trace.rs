@@ -0,0 +1,135 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![allow(dead_code)]
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Direction of a traced frame, seen from the client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TraceDirection {
+    /// Client → agent.
+    Outgoing,
+    /// Agent → client.
+    Incoming,
+}
+
+/// A single JSON-RPC frame in a recorded trace.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceFrame {
+    /// Milliseconds since the start of the recording.
+    pub offset_ms: u64,
+    pub direction: TraceDirection,
+    pub message: Value,
+}
+
+impl TraceFrame {
+    pub fn method(&self) -> Option<&str> {
+        self.message.get("method").and_then(|v| v.as_str())
+    }
+
+    pub fn id(&self) -> Option<&Value> {
+        self.message.get("id")
+    }
+
+    /// Whether the frame is a JSON-RPC response (result or error).
+    pub fn is_response(&self) -> bool {
+        self.method().is_none() &&
+            (self.message.get("result").is_some() || self.message.get("error").is_some())
+    }
+}
+
+/// A recorded sequence of frames, stored on disk as JSON Lines
+/// (one `TraceFrame` per line).
+#[derive(Debug, Clone, Default)]
+pub struct Trace {
+    pub frames: Vec<TraceFrame>,
+}
+
+impl Trace {
+    /// Parse a trace from JSON Lines content. Blank lines are ignored.
+    pub fn parse(content: &str) -> anyhow::Result<Self> {
+        let frames = content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(ix, line)| {
+                serde_json::from_str(line)
+                    .map_err(|e| anyhow::anyhow!("invalid trace frame on line {}: {}", ix + 1, e))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Self { frames })
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Self::parse(&content)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, self.to_jsonl()?)?;
+        Ok(())
+    }
+
+    pub fn to_jsonl(&self) -> anyhow::Result<String> {
+        let mut out = String::new();
+        for frame in &self.frames {
+            out.push_str(&serde_json::to_string(frame)?);
+            out.push('\n');
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_roundtrip() {
+        let trace = Trace {
+            frames: vec![
+                TraceFrame {
+                    offset_ms: 0,
+                    direction: TraceDirection::Outgoing,
+                    message: serde_json::json!({"jsonrpc":"2.0","id":0,"method":"initialize"}),
+                },
+                TraceFrame {
+                    offset_ms: 12,
+                    direction: TraceDirection::Incoming,
+                    message: serde_json::json!({"jsonrpc":"2.0","id":0,"result":{}}),
+                },
+            ],
+        };
+        let content = trace.to_jsonl().unwrap();
+        let parsed = Trace::parse(&content).unwrap();
+        assert_eq!(parsed.frames.len(), 2);
+        assert_eq!(parsed.frames[0].method(), Some("initialize"));
+        assert!(parsed.frames[1].is_response());
+        assert_eq!(parsed.frames[1].direction, TraceDirection::Incoming);
+    }
+
+    #[test]
+    fn test_trace_parse_error_reports_line() {
+        let err = Trace::parse("\n{not json}\n").unwrap_err();
+        assert!(err.to_string().contains("line 2"));
+    }
+}
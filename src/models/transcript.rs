@@ -0,0 +1,198 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+/// One item of an agent's reply to a prompt.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TranscriptEntry {
+    /// Consecutive `agent_message_chunk`s joined together.
+    Message(String),
+    /// Consecutive `agent_thought_chunk`s joined together.
+    Thought(String),
+    ToolCall {
+        id: String,
+        title: String,
+        kind: String,
+        status: String,
+    },
+    /// Plan entries as (content, status).
+    Plan(Vec<(String, String)>),
+}
+
+/// The streamed reply to one `session/prompt`, with timing.
+#[derive(Debug, Clone)]
+pub struct Transcript {
+    pub entries: Vec<TranscriptEntry>,
+    pub started_at: Instant,
+    /// Time from the prompt to the first `session/update`.
+    pub first_update: Option<Duration>,
+    /// Time from the prompt to its response.
+    pub finished: Option<Duration>,
+    pub stop_reason: Option<String>,
+    pub error: Option<String>,
+    pub updates: usize,
+}
+
+impl Transcript {
+    pub fn new(started_at: Instant) -> Self {
+        Self {
+            entries: Vec::new(),
+            started_at,
+            first_update: None,
+            finished: None,
+            stop_reason: None,
+            error: None,
+            updates: 0,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.finished.is_none()
+    }
+
+    pub fn tool_calls(&self) -> usize {
+        self.entries.iter().filter(|e| matches!(e, TranscriptEntry::ToolCall { .. })).count()
+    }
+
+    /// Fold the `update` of a `session/update` notification received at `at`.
+    pub fn apply(&mut self, update: &Value, at: Instant) {
+        self.updates += 1;
+        self.first_update.get_or_insert_with(|| at.saturating_duration_since(self.started_at));
+
+        let field = |name: &str| update.get(name).and_then(Value::as_str).unwrap_or_default();
+        let text = || update.pointer("/content/text").and_then(Value::as_str).unwrap_or_default();
+
+        match field("sessionUpdate") {
+            "agent_message_chunk" => match self.entries.last_mut() {
+                Some(TranscriptEntry::Message(message)) => message.push_str(text()),
+                _ => self.entries.push(TranscriptEntry::Message(text().to_string())),
+            },
+            "agent_thought_chunk" => match self.entries.last_mut() {
+                Some(TranscriptEntry::Thought(thought)) => thought.push_str(text()),
+                _ => self.entries.push(TranscriptEntry::Thought(text().to_string())),
+            },
+            "tool_call" => self.entries.push(TranscriptEntry::ToolCall {
+                id: field("toolCallId").to_string(),
+                title: field("title").to_string(),
+                kind: field("kind").to_string(),
+                status: match field("status") {
+                    "" => "pending".to_string(),
+                    status => status.to_string(),
+                },
+            }),
+            "tool_call_update" => {
+                let id = field("toolCallId");
+                let call = self.entries.iter_mut().rev().find_map(|entry| match entry {
+                    TranscriptEntry::ToolCall { id: call_id, title, status, .. }
+                        if call_id == id =>
+                    {
+                        Some((title, status))
+                    }
+                    _ => None,
+                });
+                if let Some((title, status)) = call {
+                    if !field("title").is_empty() {
+                        *title = field("title").to_string();
+                    }
+                    if !field("status").is_empty() {
+                        *status = field("status").to_string();
+                    }
+                }
+            }
+            "plan" => {
+                let entries = update
+                    .get("entries")
+                    .and_then(Value::as_array)
+                    .map(|entries| {
+                        entries
+                            .iter()
+                            .map(|entry| {
+                                let field = |name: &str| {
+                                    entry.get(name).and_then(Value::as_str).unwrap_or_default()
+                                };
+                                (field("content").to_string(), field("status").to_string())
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                // A plan replaces the previous one.
+                self.entries.retain(|entry| !matches!(entry, TranscriptEntry::Plan(_)));
+                self.entries.push(TranscriptEntry::Plan(entries));
+            }
+            _ => {}
+        }
+    }
+
+    /// Record the prompt's response, or the error it failed with.
+    pub fn finish(&mut self, result: Result<&Value, String>, at: Instant) {
+        self.finished = Some(at.saturating_duration_since(self.started_at));
+        match result {
+            Ok(result) => {
+                self.stop_reason =
+                    result.get("stopReason").and_then(Value::as_str).map(str::to_string)
+            }
+            Err(error) => self.error = Some(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_apply_updates() {
+        let t0 = Instant::now();
+        let ms = |n| t0 + Duration::from_millis(n);
+        let mut transcript = Transcript::new(t0);
+
+        let chunk = |kind: &str, text: &str| json!({"sessionUpdate": kind, "content": {"type": "text", "text": text}});
+        transcript.apply(&chunk("agent_thought_chunk", "Let me "), ms(120));
+        transcript.apply(&chunk("agent_thought_chunk", "look."), ms(130));
+        transcript.apply(
+            &json!({"sessionUpdate": "tool_call", "toolCallId": "t1", "title": "Read", "kind": "read"}),
+            ms(200),
+        );
+        transcript.apply(
+            &json!({"sessionUpdate": "tool_call_update", "toolCallId": "t1", "status": "completed"}),
+            ms(300),
+        );
+        transcript.apply(&chunk("agent_message_chunk", "Done"), ms(400));
+        transcript.apply(&chunk("agent_message_chunk", "."), ms(410));
+        transcript.finish(Ok(&json!({"stopReason": "end_turn"})), ms(500));
+
+        assert_eq!(
+            transcript.entries,
+            vec![
+                TranscriptEntry::Thought("Let me look.".into()),
+                TranscriptEntry::ToolCall {
+                    id: "t1".into(),
+                    title: "Read".into(),
+                    kind: "read".into(),
+                    status: "completed".into(),
+                },
+                TranscriptEntry::Message("Done.".into()),
+            ]
+        );
+        assert_eq!(transcript.first_update, Some(Duration::from_millis(120)));
+        assert_eq!(transcript.finished, Some(Duration::from_millis(500)));
+        assert_eq!(transcript.stop_reason.as_deref(), Some("end_turn"));
+        assert_eq!((transcript.updates, transcript.tool_calls()), (6, 1));
+    }
+}
@@ -0,0 +1,252 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::PathBuf;
+
+use anyhow::Context as _;
+use serde_json::Value;
+
+use crate::models::{generate_agent_id, secrets, AgentConfig, SecretStore};
+
+/// An agent found in Zed's settings.
+#[derive(Debug, Clone)]
+pub struct ImportCandidate {
+    pub config: AgentConfig,
+    /// Name of an existing agent with the same command and arguments.
+    pub duplicate_of: Option<String>,
+}
+
+impl ImportCandidate {
+    /// Env keys that look like credentials and hold a literal value, which
+    /// importing as is would write to agents.json in plain text.
+    pub fn credentials(&self) -> Vec<&str> {
+        self.config
+            .env
+            .iter()
+            .filter(|(key, value)| {
+                secrets::is_credential_name(key) && secrets::references(value).is_empty()
+            })
+            .map(|(key, _)| key.as_str())
+            .collect()
+    }
+
+    /// Move the [`Self::credentials`] into `store`, leaving `${secret:NAME}`
+    /// references in the env. A name already holding another value gets a
+    /// numbered suffix.
+    pub fn move_credentials(&mut self, store: &mut SecretStore) {
+        let keys: Vec<String> = self.credentials().into_iter().map(str::to_string).collect();
+        for (key, value) in self.config.env.iter_mut().filter(|(key, _)| keys.contains(key)) {
+            let name = std::iter::once(key.clone())
+                .chain((2..).map(|n| format!("{}_{}", key, n)))
+                .find(|name| store.get(name).is_none_or(|stored| stored == value))
+                .unwrap_or_default();
+            store.set(name.clone(), std::mem::take(value));
+            *value = format!("${{secret:{}}}", name);
+        }
+    }
+}
+
+/// The result of reading `agent_servers` from a Zed settings file.
+#[derive(Debug, Clone, Default)]
+pub struct ZedImport {
+    pub candidates: Vec<ImportCandidate>,
+    /// Entries that could not be imported, as (name, reason).
+    pub skipped: Vec<(String, String)>,
+}
+
+/// Zed's user settings file for this platform.
+pub fn zed_settings_path() -> Option<PathBuf> {
+    if cfg!(windows) {
+        let appdata = std::env::var_os("APPDATA")?;
+        return Some(PathBuf::from(appdata).join("Zed").join("settings.json"));
+    }
+    let config = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if cfg!(target_os = "linux") => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(config.join("zed").join("settings.json"))
+}
+
+/// Read the `agent_servers` of a Zed settings file (JSON with comments and
+/// trailing commas), marking entries already present in `existing`.
+pub fn parse_agent_servers(text: &str, existing: &[AgentConfig]) -> anyhow::Result<ZedImport> {
+    let settings: Value =
+        serde_json::from_str(&strip_jsonc(text)).context("settings are not valid JSON")?;
+    let Some(servers) = settings.get("agent_servers").and_then(Value::as_object) else {
+        return Ok(ZedImport::default());
+    };
+
+    let mut import = ZedImport::default();
+    for (ix, (name, server)) in servers.iter().enumerate() {
+        // Older settings nest the command as {"path", "args", "env"}.
+        let spec = match server.get("command") {
+            Some(command @ Value::Object(_)) => command,
+            _ => server,
+        };
+        let command = spec.get("command").or_else(|| spec.get("path")).and_then(Value::as_str);
+        let Some(command) = command.filter(|c| !c.trim().is_empty()) else {
+            import.skipped.push((name.clone(), "no command (built-in agent)".into()));
+            continue;
+        };
+
+        let args: Vec<String> = spec
+            .get("args")
+            .and_then(Value::as_array)
+            .map(|args| args.iter().filter_map(Value::as_str).map(str::to_string).collect())
+            .unwrap_or_default();
+        let mut env: Vec<(String, String)> = spec
+            .get("env")
+            .and_then(Value::as_object)
+            .map(|env| {
+                env.iter()
+                    .map(|(k, v)| {
+                        (k.clone(), v.as_str().map_or_else(|| v.to_string(), str::to_string))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        env.sort();
+
+        let duplicate_of = existing
+            .iter()
+            .find(|agent| agent.endpoint == command && agent.args == args)
+            .map(|agent| agent.name.clone());
+        import.candidates.push(ImportCandidate {
+            config: AgentConfig {
+                id: format!("{}-{}", generate_agent_id(), ix),
+                name: name.clone(),
+                endpoint: command.to_string(),
+                args,
+                env,
+                ..AgentConfig::default()
+            },
+            duplicate_of,
+        });
+    }
+    Ok(import)
+}
+
+/// Remove `//` and `/* */` comments and trailing commas, leaving strings intact.
+pub fn strip_jsonc(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                out.push(c);
+                while let Some(c) = chars.next() {
+                    out.push(c);
+                    match c {
+                        '\\' => out.extend(chars.next()),
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => while chars.next_if(|&c| c != '\n').is_some() {},
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = '\0';
+                for c in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+                out.push(' ');
+            }
+            '}' | ']' => {
+                // Drop a comma that only has whitespace between it and `c`.
+                let trimmed = out.trim_end().len();
+                if out[..trimmed].ends_with(',') {
+                    out.remove(trimmed - 1);
+                }
+                out.push(c);
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_jsonc() {
+        let text = r#"{
+            // line comment
+            "url": "http://example.com", /* block */
+            "list": [1, 2,],
+            "quote": "a \" // not a comment",
+        }"#;
+        let value: Value = serde_json::from_str(&strip_jsonc(text)).unwrap();
+        assert_eq!(value["url"], "http://example.com");
+        assert_eq!(value["list"], serde_json::json!([1, 2]));
+        assert_eq!(value["quote"], "a \" // not a comment");
+    }
+
+    #[test]
+    fn test_parse_agent_servers() {
+        let text = r#"{
+            "theme": "One Dark",
+            "agent_servers": {
+                "Echo": { "command": "echo-agent", "args": ["--acp"], "env": { "LOG": "debug" } },
+                "Legacy": { "command": { "path": "/opt/legacy", "args": [] } },
+                "claude": { "default_mode": "plan" },
+            },
+        }"#;
+        let existing = [AgentConfig {
+            id: "a1".into(),
+            name: "My Echo".into(),
+            endpoint: "echo-agent".into(),
+            args: vec!["--acp".into()],
+            ..AgentConfig::default()
+        }];
+
+        let import = parse_agent_servers(text, &existing).unwrap();
+        let names: Vec<_> = import.candidates.iter().map(|c| c.config.name.as_str()).collect();
+        assert_eq!(names, ["Echo", "Legacy"]);
+        assert_eq!(import.candidates[0].duplicate_of.as_deref(), Some("My Echo"));
+        assert_eq!(import.candidates[0].config.env, vec![("LOG".into(), "debug".into())]);
+        assert_eq!(import.candidates[1].config.endpoint, "/opt/legacy");
+        assert!(import.candidates[1].duplicate_of.is_none());
+        assert_eq!(import.skipped.len(), 1);
+    }
+
+    #[test]
+    fn test_move_credentials() {
+        let text = r#"{ "agent_servers": { "Echo": { "command": "echo-agent", "env": {
+            "API_KEY": "sk-1", "AUTH_TOKEN": "${env:TOKEN}", "LOG": "debug"
+        } } } }"#;
+        let mut candidate = parse_agent_servers(text, &[]).unwrap().candidates.remove(0);
+        assert_eq!(candidate.credentials(), ["API_KEY"]);
+
+        let mut store = SecretStore::default();
+        store.set("API_KEY", "sk-other");
+        candidate.move_credentials(&mut store);
+        assert!(candidate.credentials().is_empty());
+        assert_eq!(
+            candidate.config.env,
+            [
+                ("API_KEY".into(), "${secret:API_KEY_2}".into()),
+                ("AUTH_TOKEN".into(), "${env:TOKEN}".into()),
+                ("LOG".into(), "debug".into()),
+            ]
+        );
+        assert_eq!(store.get("API_KEY_2"), Some("sk-1"));
+        assert_eq!(store.get("API_KEY"), Some("sk-other"));
+    }
+}
@@ -0,0 +1,376 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use gpui::{prelude::*, *};
+use gpui_component::{
+    button::{Button, ButtonVariants as _},
+    checkbox::Checkbox,
+    h_flex,
+    input::{Input, InputState},
+    radio::RadioGroup,
+    v_flex, ActiveTheme as _, Disableable as _, IconName, Sizable as _, StyledExt as _,
+};
+use serde_json::Value;
+
+use crate::{
+    client::{AcpConnection, FrameDirection, HeldFrame},
+    models::JsonPredicate,
+};
+
+const DIRECTIONS: [Option<FrameDirection>; 3] =
+    [None, Some(FrameDirection::Outgoing), Some(FrameDirection::Incoming)];
+
+/// A panel for managing breakpoints and stepping through held frames.
+pub struct BreakpointPanel {
+    connection: Entity<AcpConnection>,
+    method_input: Entity<InputState>,
+    predicate_input: Entity<InputState>,
+    direction: Option<FrameDirection>,
+    /// The held frame being edited before it is forwarded.
+    editing: Option<u64>,
+    edit_input: Entity<InputState>,
+    error: Option<String>,
+    _subscriptions: Vec<Subscription>,
+}
+
+impl BreakpointPanel {
+    pub fn new(
+        connection: Entity<AcpConnection>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let method_input = cx.new(|cx| {
+            InputState::new(window, cx).placeholder("Method, e.g. session/prompt or fs/*")
+        });
+        let predicate_input = cx.new(|cx| {
+            InputState::new(window, cx)
+                .placeholder("JSON path, e.g. params.update.sessionUpdate == tool_call")
+        });
+        let edit_input = cx.new(|cx| InputState::new(window, cx).multi_line(true).auto_grow(8, 24));
+
+        let subscriptions = vec![
+            cx.observe(&connection, |_, _, cx| cx.notify()),
+            cx.observe(&predicate_input, |_, _, cx| cx.notify()),
+            cx.observe(&edit_input, |_, _, cx| cx.notify()),
+        ];
+
+        Self {
+            connection,
+            method_input,
+            predicate_input,
+            direction: None,
+            editing: None,
+            edit_input,
+            error: None,
+            _subscriptions: subscriptions,
+        }
+    }
+
+    /// Parse the predicate input; empty means no predicate.
+    fn predicate(&self, cx: &App) -> Result<Option<JsonPredicate>, String> {
+        let text = self.predicate_input.read(cx).value();
+        if text.trim().is_empty() {
+            return Ok(None);
+        }
+        JsonPredicate::parse(&text).map(Some)
+    }
+
+    fn add_breakpoint(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Ok(predicate) = self.predicate(cx) else {
+            return;
+        };
+        let method = self.method_input.read(cx).value().trim().to_string();
+        let method = (!method.is_empty()).then_some(method);
+        let direction = self.direction;
+
+        self.connection
+            .update(cx, |conn, cx| conn.add_breakpoint(method, direction, predicate, cx));
+        self.method_input.update(cx, |input, cx| input.set_value("", window, cx));
+        self.predicate_input.update(cx, |input, cx| input.set_value("", window, cx));
+    }
+
+    fn start_editing(&mut self, held: &HeldFrame, window: &mut Window, cx: &mut Context<Self>) {
+        let pretty = serde_json::to_string_pretty(&held.frame).unwrap_or_default();
+        self.edit_input.update(cx, |input, cx| input.set_value(pretty, window, cx));
+        self.editing = Some(held.id);
+        cx.notify();
+    }
+
+    fn forward(&mut self, id: u64, edited: bool, cx: &mut Context<Self>) {
+        let frame = if edited {
+            match serde_json::from_str::<Value>(&self.edit_input.read(cx).value()) {
+                Ok(frame) if frame.is_object() => Some(frame),
+                _ => return,
+            }
+        } else {
+            None
+        };
+        if self.editing == Some(id) {
+            self.editing = None;
+        }
+        let result = self.connection.update(cx, |conn, cx| conn.forward_held(id, frame, cx));
+        self.error = result.err().map(|e| e.to_string());
+        cx.notify();
+    }
+
+    fn forward_all(&mut self, cx: &mut Context<Self>) {
+        self.editing = None;
+        let result = self.connection.update(cx, |conn, cx| conn.forward_all_held(cx));
+        self.error = result.err().map(|e| e.to_string());
+        cx.notify();
+    }
+
+    fn drop_frame(&mut self, id: u64, cx: &mut Context<Self>) {
+        if self.editing == Some(id) {
+            self.editing = None;
+        }
+        self.connection.update(cx, |conn, cx| conn.drop_held(id, cx));
+    }
+
+    fn new_breakpoint_form(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let predicate_error = self.predicate(cx).err();
+        let selected = DIRECTIONS.iter().position(|d| *d == self.direction);
+
+        v_flex()
+            .gap_2()
+            .child(section_title("New Breakpoint", cx))
+            .child(Input::new(&self.method_input))
+            .child(Input::new(&self.predicate_input))
+            .when_some(predicate_error.clone(), |el, error| {
+                el.child(div().text_xs().text_color(cx.theme().danger).child(error))
+            })
+            .child(
+                h_flex()
+                    .justify_between()
+                    .child(
+                        RadioGroup::horizontal("breakpoint-direction")
+                            .selected_index(selected)
+                            .child("Both")
+                            .child("Outgoing")
+                            .child("Incoming")
+                            .on_click(cx.listener(|this, ix: &usize, _, cx| {
+                                this.direction = DIRECTIONS[*ix];
+                                cx.notify();
+                            })),
+                    )
+                    .child(
+                        Button::new("add-breakpoint")
+                            .small()
+                            .primary()
+                            .label("Add")
+                            .disabled(predicate_error.is_some())
+                            .on_click(
+                                cx.listener(|this, _, window, cx| this.add_breakpoint(window, cx)),
+                            ),
+                    ),
+            )
+    }
+
+    fn breakpoint_list(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let breakpoints = self.connection.read(cx).interceptor().breakpoints().to_vec();
+
+        v_flex()
+            .gap_1()
+            .child(section_title("Breakpoints", cx))
+            .when(breakpoints.is_empty(), |el| el.child(hint("No breakpoints.", cx)))
+            .children(breakpoints.into_iter().map(|breakpoint| {
+                let id = breakpoint.id;
+                h_flex()
+                    .gap_2()
+                    .justify_between()
+                    .child(
+                        Checkbox::new(("breakpoint-enabled", id as usize))
+                            .label(breakpoint.describe())
+                            .checked(breakpoint.enabled)
+                            .on_click(cx.listener(move |this, checked: &bool, _, cx| {
+                                let enabled = *checked;
+                                this.connection.update(cx, |conn, cx| {
+                                    conn.set_breakpoint_enabled(id, enabled, cx)
+                                });
+                            })),
+                    )
+                    .child(
+                        Button::new(("remove-breakpoint", id as usize))
+                            .xsmall()
+                            .ghost()
+                            .icon(IconName::Delete)
+                            .on_click(cx.listener(move |this, _, _, cx| {
+                                this.connection
+                                    .update(cx, |conn, cx| conn.remove_breakpoint(id, cx));
+                            })),
+                    )
+            }))
+    }
+
+    fn held_frame(&self, held: HeldFrame, cx: &mut Context<Self>) -> impl IntoElement {
+        let id = held.id;
+        let title = match held.frame.get("method").and_then(|m| m.as_str()) {
+            Some(method) => method.to_string(),
+            None => format!("response #{}", held.frame.get("id").unwrap_or(&Value::Null)),
+        };
+        let reason = if held.breakpoint.is_some() { "breakpoint" } else { "queued" };
+        let editing = self.editing == Some(id);
+        let edited_valid = editing &&
+            serde_json::from_str::<Value>(&self.edit_input.read(cx).value())
+                .is_ok_and(|v| v.is_object());
+
+        v_flex()
+            .gap_1()
+            .p_2()
+            .border_1()
+            .border_color(cx.theme().border)
+            .rounded_md()
+            .child(
+                h_flex()
+                    .gap_2()
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(cx.theme().muted_foreground)
+                            .child(held.direction.label()),
+                    )
+                    .child(
+                        div()
+                            .text_sm()
+                            .font_semibold()
+                            .text_color(cx.theme().foreground)
+                            .child(title),
+                    )
+                    .child(div().text_xs().text_color(cx.theme().warning).child(reason)),
+            )
+            .when(!editing, |el| {
+                el.child(
+                    div()
+                        .text_xs()
+                        .font_family("monospace")
+                        .text_color(cx.theme().secondary_foreground)
+                        .overflow_hidden()
+                        .text_ellipsis()
+                        .child(self.connection.read(cx).redact(&held.frame.to_string())),
+                )
+            })
+            .when(editing, |el| el.child(Input::new(&self.edit_input)))
+            .child(
+                h_flex()
+                    .gap_2()
+                    .justify_end()
+                    .child(
+                        Button::new(("drop-frame", id as usize))
+                            .xsmall()
+                            .ghost()
+                            .label("Drop")
+                            .on_click(cx.listener(move |this, _, _, cx| this.drop_frame(id, cx))),
+                    )
+                    .map(|el| {
+                        if editing {
+                            el.child(
+                                Button::new(("cancel-edit", id as usize))
+                                    .xsmall()
+                                    .outline()
+                                    .label("Cancel")
+                                    .on_click(cx.listener(|this, _, _, cx| {
+                                        this.editing = None;
+                                        cx.notify();
+                                    })),
+                            )
+                            .child(
+                                Button::new(("forward-edited", id as usize))
+                                    .xsmall()
+                                    .primary()
+                                    .label("Forward Edited")
+                                    .disabled(!edited_valid)
+                                    .on_click(cx.listener(move |this, _, _, cx| {
+                                        this.forward(id, true, cx)
+                                    })),
+                            )
+                        } else {
+                            el.child(
+                                Button::new(("edit-frame", id as usize))
+                                    .xsmall()
+                                    .outline()
+                                    .label("Edit")
+                                    .on_click(cx.listener(move |this, _, window, cx| {
+                                        let held = this
+                                            .connection
+                                            .read(cx)
+                                            .interceptor()
+                                            .held()
+                                            .find(|h| h.id == id)
+                                            .cloned();
+                                        if let Some(held) = held {
+                                            this.start_editing(&held, window, cx);
+                                        }
+                                    })),
+                            )
+                            .child(
+                                Button::new(("forward-frame", id as usize))
+                                    .xsmall()
+                                    .primary()
+                                    .label("Forward")
+                                    .on_click(cx.listener(move |this, _, _, cx| {
+                                        this.forward(id, false, cx)
+                                    })),
+                            )
+                        }
+                    }),
+            )
+    }
+}
+
+impl Render for BreakpointPanel {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let held: Vec<HeldFrame> = self.connection.read(cx).interceptor().held().cloned().collect();
+
+        v_flex()
+            .id("breakpoint-panel")
+            .size_full()
+            .overflow_y_scroll()
+            .gap_4()
+            .p_4()
+            .child(self.new_breakpoint_form(cx))
+            .child(self.breakpoint_list(cx))
+            .child(
+                v_flex()
+                    .gap_2()
+                    .child(
+                        h_flex()
+                            .justify_between()
+                            .child(section_title(&format!("Held Frames ({})", held.len()), cx))
+                            .child(
+                                Button::new("forward-all")
+                                    .xsmall()
+                                    .outline()
+                                    .label("Forward All")
+                                    .disabled(held.is_empty())
+                                    .on_click(cx.listener(|this, _, _, cx| this.forward_all(cx))),
+                            ),
+                    )
+                    .when_some(self.error.clone(), |el, error| {
+                        el.child(div().text_xs().text_color(cx.theme().danger).child(error))
+                    })
+                    .when(held.is_empty(), |el| {
+                        el.child(hint("Frames matching a breakpoint are held here.", cx))
+                    })
+                    .children(held.into_iter().map(|frame| self.held_frame(frame, cx))),
+            )
+    }
+}
+
+fn section_title(text: &str, cx: &App) -> impl IntoElement {
+    div().text_sm().font_semibold().text_color(cx.theme().foreground).child(text.to_string())
+}
+
+fn hint(text: &'static str, cx: &App) -> impl IntoElement {
+    div().text_xs().text_color(cx.theme().muted_foreground).child(text)
+}
@@ -0,0 +1,463 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+use gpui::{prelude::*, *};
+use gpui_component::{
+    button::{Button, ButtonVariants as _},
+    h_flex,
+    input::{Input, InputState},
+    menu::{DropdownMenu as _, PopupMenuItem},
+    v_flex, ActiveTheme as _, Disableable as _, IconName, Sizable as _, StyledExt as _,
+};
+
+use crate::{
+    app::AcpDebugger,
+    client::{AcpConnection, ConnectionEvent},
+    components::{ChatMessage, PlanEntry, PlanEntryStatus, Role, ToolCallKind, ToolCallStatus},
+    models::{AgentId, AgentRegistry, AgentStatus, Transcript, TranscriptEntry},
+};
+
+/// One column of the comparison.
+#[derive(Default)]
+struct Side {
+    agent_id: Option<AgentId>,
+    connection: Option<Entity<AcpConnection>>,
+    session_id: Option<String>,
+    /// What the side is waiting for, or why it failed.
+    phase: Option<String>,
+    transcript: Option<Transcript>,
+    _subscription: Option<Subscription>,
+    _task: Option<Task<()>>,
+}
+
+/// Sends the same prompt to two agents and streams their replies side by side.
+pub struct ComparePanel {
+    registry: Entity<AgentRegistry>,
+    debugger: WeakEntity<AcpDebugger>,
+    cwd_input: Entity<InputState>,
+    prompt_input: Entity<InputState>,
+    sides: [Side; 2],
+    error: Option<String>,
+    _ticker: Task<()>,
+}
+
+impl ComparePanel {
+    pub fn new(
+        registry: Entity<AgentRegistry>,
+        debugger: WeakEntity<AcpDebugger>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let cwd = std::env::current_dir().unwrap_or_default().display().to_string();
+        let cwd_input = cx.new(|cx| {
+            InputState::new(window, cx).placeholder("Working directory").default_value(cwd)
+        });
+        let prompt_input = cx.new(|cx| {
+            InputState::new(window, cx)
+                .placeholder("Prompt sent to both agents")
+                .multi_line(true)
+                .auto_grow(2, 6)
+        });
+
+        // Keep the elapsed time of running prompts current.
+        let ticker = cx.spawn(async move |this, cx| loop {
+            cx.background_executor().timer(Duration::from_millis(500)).await;
+            let Ok(running) = this.read_with(cx, |this, _| this.is_running()) else {
+                break;
+            };
+            if running && this.update(cx, |_, cx| cx.notify()).is_err() {
+                break;
+            }
+        });
+
+        Self {
+            registry,
+            debugger,
+            cwd_input,
+            prompt_input,
+            sides: Default::default(),
+            error: None,
+            _ticker: ticker,
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        self.sides.iter().any(|side| side._task.is_some())
+    }
+
+    fn select_agent(&mut self, side: usize, agent_id: AgentId, cx: &mut Context<Self>) {
+        self.sides[side] = Side { agent_id: Some(agent_id), ..Side::default() };
+        cx.notify();
+    }
+
+    fn run(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let prompt = self.prompt_input.read(cx).value().trim().to_string();
+        let cwd = self.cwd_input.read(cx).value().trim().to_string();
+        let configs: Vec<_> = self
+            .sides
+            .iter()
+            .map(|side| {
+                let registry = self.registry.read(cx);
+                side.agent_id
+                    .as_ref()
+                    .and_then(|id| registry.agents().iter().find(|a| &a.id == id).cloned())
+            })
+            .collect();
+
+        self.error = match () {
+            _ if configs.iter().any(Option::is_none) => {
+                Some("Pick an agent for both sides.".into())
+            }
+            _ if cwd.is_empty() => Some("Enter a working directory.".into()),
+            _ if prompt.is_empty() => Some("Enter a prompt.".into()),
+            _ => None,
+        };
+        if self.error.is_some() {
+            cx.notify();
+            return;
+        }
+
+        for (ix, config) in configs.into_iter().flatten().enumerate() {
+            let timeout = Duration::from_millis(config.timeout_ms);
+            let agent_id = config.id.clone();
+            let Ok(connection) =
+                self.debugger.update(cx, |debugger, cx| debugger.connect_agent(config, window, cx))
+            else {
+                return;
+            };
+
+            let subscription = cx.subscribe(&connection, move |this, _, event, cx| {
+                let side = &mut this.sides[ix];
+                match event {
+                    ConnectionEvent::SessionNotification { session_id, method, params } => {
+                        if method != "session/update" ||
+                            side.session_id.as_ref() != Some(session_id)
+                        {
+                            return;
+                        }
+                        if let (Some(transcript), Some(update)) =
+                            (side.transcript.as_mut(), params.get("update"))
+                        {
+                            transcript.apply(update, Instant::now());
+                            cx.notify();
+                        }
+                    }
+                    // Follow the session to whatever replaced it after a restart.
+                    ConnectionEvent::SessionRestored { previous, session_id, .. }
+                        if side.session_id.as_ref() == Some(previous) =>
+                    {
+                        side.session_id = Some(session_id.clone());
+                        cx.notify();
+                    }
+                    _ => {}
+                }
+            });
+
+            let task =
+                Self::drive(ix, connection.clone(), cwd.clone(), prompt.clone(), timeout, cx);
+            self.sides[ix] = Side {
+                agent_id: Some(agent_id),
+                connection: Some(connection),
+                session_id: None,
+                phase: Some("Connecting…".into()),
+                transcript: None,
+                _subscription: Some(subscription),
+                _task: Some(task),
+            };
+        }
+        cx.notify();
+    }
+
+    /// Wait for the handshake, open a session in `cwd` and send the prompt.
+    fn drive(
+        ix: usize,
+        connection: Entity<AcpConnection>,
+        cwd: String,
+        prompt: String,
+        timeout: Duration,
+        cx: &mut Context<Self>,
+    ) -> Task<()> {
+        cx.spawn(async move |this, cx| {
+            let result: anyhow::Result<()> = async {
+                wait_connected(&connection, timeout, cx).await?;
+                this.update(cx, |this, cx| {
+                    this.sides[ix].phase = Some("Creating session…".into());
+                    cx.notify();
+                })?;
+
+                let session_id = connection
+                    .update(cx, |conn, cx| conn.new_session(&cwd, Vec::new(), cx))?
+                    .await?;
+                this.update(cx, |this, cx| {
+                    let side = &mut this.sides[ix];
+                    side.session_id = Some(session_id.clone());
+                    side.transcript = Some(Transcript::new(Instant::now()));
+                    side.phase = None;
+                    cx.notify();
+                })?;
+
+                let content = vec![serde_json::json!({ "type": "text", "text": prompt })];
+                let response =
+                    connection.update(cx, |conn, cx| conn.prompt(&session_id, content, cx))?.await;
+                this.update(cx, |this, cx| {
+                    if let Some(transcript) = this.sides[ix].transcript.as_mut() {
+                        transcript
+                            .finish(response.as_ref().map_err(|e| e.to_string()), Instant::now());
+                    }
+                    cx.notify();
+                })?;
+                Ok(())
+            }
+            .await;
+
+            this.update(cx, |this, cx| {
+                let side = &mut this.sides[ix];
+                if let Err(e) = result {
+                    side.phase = Some(format!("Failed: {}", e));
+                }
+                side._task = None;
+                cx.notify();
+            })
+            .ok();
+        })
+    }
+
+    fn cancel(&mut self, cx: &mut Context<Self>) {
+        for side in &self.sides {
+            let running = side.transcript.as_ref().is_some_and(Transcript::is_running);
+            if let (true, Some(connection), Some(session_id)) =
+                (running, &side.connection, &side.session_id)
+            {
+                connection.update(cx, |conn, cx| conn.cancel(session_id, cx)).ok();
+            }
+        }
+    }
+
+    fn agent_picker(&self, ix: usize, cx: &mut Context<Self>) -> impl IntoElement {
+        let registry = self.registry.clone();
+        let this = cx.entity().downgrade();
+        let name = self.sides[ix]
+            .agent_id
+            .as_ref()
+            .and_then(|id| registry.read(cx).agents().iter().find(|a| &a.id == id))
+            .map_or_else(|| "Select Agent".to_string(), |agent| agent.name.clone());
+
+        Button::new(("compare-agent", ix))
+            .xsmall()
+            .outline()
+            .child(h_flex().gap_1().child(name).child(IconName::ChevronDown))
+            .disabled(self.sides[ix]._task.is_some())
+            .dropdown_menu(move |menu, _, cx| {
+                let agents = registry.read(cx).agents().to_vec();
+                if agents.is_empty() {
+                    return menu.item(PopupMenuItem::new("No agents configured").disabled(true));
+                }
+                agents.into_iter().fold(menu, |menu, agent| {
+                    let this = this.clone();
+                    menu.item(PopupMenuItem::new(agent.name.clone()).on_click(move |_, _, cx| {
+                        this.update(cx, |this, cx| this.select_agent(ix, agent.id.clone(), cx))
+                            .ok();
+                    }))
+                })
+            })
+    }
+
+    fn column(&self, ix: usize, cx: &mut Context<Self>) -> impl IntoElement {
+        let side = &self.sides[ix];
+        let timing = side.transcript.as_ref().map(|transcript| {
+            let mut parts = Vec::new();
+            if let Some(first) = transcript.first_update {
+                parts.push(format!("first update {}", format_duration(first)));
+            }
+            parts.push(format!("{} updates", transcript.updates));
+            parts.push(format!("{} tool calls", transcript.tool_calls()));
+            parts.push(match (transcript.finished, &transcript.stop_reason) {
+                (Some(finished), Some(reason)) => {
+                    format!("finished in {} ({})", format_duration(finished), reason)
+                }
+                (Some(finished), None) => format!("finished in {}", format_duration(finished)),
+                (None, _) => {
+                    format!("running {}", format_duration(transcript.started_at.elapsed()))
+                }
+            });
+            parts.join(" · ")
+        });
+        let error = side.transcript.as_ref().and_then(|t| t.error.clone());
+
+        v_flex()
+            .id(("compare-column", ix))
+            .flex_1()
+            .min_w_0()
+            .h_full()
+            .overflow_y_scroll()
+            .gap_2()
+            .p_2()
+            .border_1()
+            .border_color(cx.theme().border)
+            .rounded_md()
+            .child(self.agent_picker(ix, cx))
+            .when_some(side.phase.clone(), |el, phase| {
+                el.child(div().text_xs().text_color(cx.theme().muted_foreground).child(phase))
+            })
+            .when_some(timing, |el, timing| {
+                el.child(div().text_xs().text_color(cx.theme().muted_foreground).child(timing))
+            })
+            .when_some(error, |el, error| {
+                el.child(div().text_xs().text_color(cx.theme().danger).child(error))
+            })
+            .children(
+                side.transcript
+                    .iter()
+                    .flat_map(|transcript| transcript.entries.iter())
+                    .map(chat_message),
+            )
+    }
+}
+
+impl Render for ComparePanel {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let running = self.is_running();
+
+        v_flex()
+            .size_full()
+            .gap_3()
+            .p_4()
+            .child(
+                v_flex()
+                    .gap_2()
+                    .child(Input::new(&self.cwd_input).small())
+                    .child(Input::new(&self.prompt_input))
+                    .child(
+                        h_flex()
+                            .gap_2()
+                            .child(
+                                div()
+                                    .flex_1()
+                                    .text_sm()
+                                    .text_color(cx.theme().danger)
+                                    .children(self.error.clone()),
+                            )
+                            .child(
+                                Button::new("cancel-compare")
+                                    .small()
+                                    .outline()
+                                    .label("Cancel")
+                                    .disabled(!running)
+                                    .on_click(cx.listener(|this, _, _, cx| this.cancel(cx))),
+                            )
+                            .child(
+                                Button::new("run-compare")
+                                    .small()
+                                    .primary()
+                                    .label("Send to Both")
+                                    .disabled(running)
+                                    .on_click(
+                                        cx.listener(|this, _, window, cx| this.run(window, cx)),
+                                    ),
+                            ),
+                    ),
+            )
+            .child(
+                h_flex()
+                    .flex_1()
+                    .min_h_0()
+                    .items_start()
+                    .gap_3()
+                    .child(self.column(0, cx))
+                    .child(self.column(1, cx)),
+            )
+            .child(
+                div()
+                    .text_xs()
+                    .font_semibold()
+                    .text_color(cx.theme().muted_foreground)
+                    .child("Traffic of each run is captured in that agent's message panel."),
+            )
+    }
+}
+
+async fn wait_connected(
+    connection: &Entity<AcpConnection>,
+    timeout: Duration,
+    cx: &mut AsyncApp,
+) -> anyhow::Result<()> {
+    let started = Instant::now();
+    loop {
+        match connection.read_with(cx, |conn, _| conn.status())? {
+            AgentStatus::Connected => return Ok(()),
+            AgentStatus::Error => bail!("agent failed to start or rejected initialize"),
+            AgentStatus::Disconnected => bail!("agent disconnected"),
+            AgentStatus::Connecting if started.elapsed() > timeout => {
+                bail!("timed out waiting for initialize")
+            }
+            AgentStatus::Connecting => {
+                cx.background_executor().timer(Duration::from_millis(100)).await
+            }
+        }
+    }
+}
+
+fn chat_message(entry: &TranscriptEntry) -> ChatMessage {
+    match entry {
+        TranscriptEntry::Message(text) => ChatMessage::text(Role::Assistant, text.clone()),
+        TranscriptEntry::Thought(text) => ChatMessage::thought(text.clone()),
+        TranscriptEntry::ToolCall { title, kind, status, .. } => {
+            let kind = match kind.as_str() {
+                "read" => ToolCallKind::Read,
+                "edit" => ToolCallKind::Edit,
+                "delete" => ToolCallKind::Delete,
+                "move" => ToolCallKind::Move,
+                "search" => ToolCallKind::Search,
+                "execute" => ToolCallKind::Execute,
+                "think" => ToolCallKind::Think,
+                "fetch" => ToolCallKind::Fetch,
+                _ => ToolCallKind::Other,
+            };
+            ChatMessage::tool_call(title.clone(), kind, tool_call_status(status))
+        }
+        TranscriptEntry::Plan(entries) => ChatMessage::plan(
+            entries
+                .iter()
+                .map(|(content, status)| PlanEntry {
+                    content: content.clone().into(),
+                    status: match status.as_str() {
+                        "in_progress" => PlanEntryStatus::InProgress,
+                        "completed" => PlanEntryStatus::Completed,
+                        _ => PlanEntryStatus::Pending,
+                    },
+                })
+                .collect(),
+        ),
+    }
+}
+
+fn tool_call_status(status: &str) -> ToolCallStatus {
+    match status {
+        "in_progress" => ToolCallStatus::InProgress,
+        "completed" => ToolCallStatus::Completed,
+        "failed" => ToolCallStatus::Failed,
+        _ => ToolCallStatus::Pending,
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    if duration < Duration::from_secs(1) {
+        format!("{} ms", duration.as_millis())
+    } else {
+        format!("{:.1} s", duration.as_secs_f64())
+    }
+}
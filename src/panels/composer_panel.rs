@@ -0,0 +1,326 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use gpui::{prelude::*, *};
+use gpui_component::{
+    button::{Button, ButtonVariants as _},
+    h_flex,
+    input::{Input, InputState},
+    menu::{DropdownMenu as _, PopupMenuItem},
+    radio::RadioGroup,
+    text::TextView,
+    v_flex, ActiveTheme as _, Disableable as _, IconName, Sizable as _, StyledExt as _,
+    WindowExt as _,
+};
+use serde_json::Value;
+
+use crate::{
+    client::{AcpConnection, ClientError, ConnectionEvent},
+    models::{AcpMethod, AgentStatus, MethodKind, ACP_METHODS},
+};
+
+use super::{not_connected_warning, revalidate_on_edit};
+
+/// Outcome of the last message sent from the composer.
+enum SendOutcome {
+    Waiting,
+    Sent,
+    Result(Value),
+    Failed(String),
+}
+
+/// A panel for sending arbitrary JSON-RPC requests and notifications.
+pub struct ComposerPanel {
+    connection: Entity<AcpConnection>,
+    method_input: Entity<InputState>,
+    params_input: Entity<InputState>,
+    kind: MethodKind,
+    /// The most recent session seen on the connection, used by templates.
+    session_id: Option<String>,
+    outcome: Option<SendOutcome>,
+    _subscriptions: Vec<Subscription>,
+}
+
+impl ComposerPanel {
+    pub fn new(
+        connection: Entity<AcpConnection>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let method_input =
+            cx.new(|cx| InputState::new(window, cx).placeholder("Method, e.g. session/new"));
+        let params_input = cx.new(|cx| {
+            InputState::new(window, cx).placeholder("{ }").multi_line(true).auto_grow(8, 24)
+        });
+
+        let subscriptions = vec![
+            revalidate_on_edit(&params_input, cx),
+            revalidate_on_edit(&method_input, cx),
+            cx.observe(&connection, |_, _, cx| cx.notify()),
+            cx.subscribe(&connection, |this, _, event, _| match event {
+                ConnectionEvent::MessageCaptured { message, .. } => {
+                    if let Some(session_id) = message.session_id() {
+                        this.session_id = Some(session_id.to_string());
+                    }
+                }
+                ConnectionEvent::SessionRestored { previous, session_id, .. }
+                    if this.session_id.as_ref() == Some(previous) =>
+                {
+                    this.session_id = Some(session_id.clone());
+                }
+                _ => {}
+            }),
+        ];
+
+        Self {
+            connection,
+            method_input,
+            params_input,
+            kind: MethodKind::Request,
+            session_id: None,
+            outcome: None,
+            _subscriptions: subscriptions,
+        }
+    }
+
+    /// Fill the form with `method` and its params template.
+    fn apply_template(&mut self, method: &AcpMethod, window: &mut Window, cx: &mut Context<Self>) {
+        let cwd = std::env::current_dir().map(|p| p.display().to_string()).unwrap_or_default();
+        let params = method.template(self.session_id.as_deref(), &cwd);
+        let params = serde_json::to_string_pretty(&params).unwrap_or_default();
+
+        self.method_input.update(cx, |input, cx| input.set_value(method.name, window, cx));
+        self.params_input.update(cx, |input, cx| input.set_value(params, window, cx));
+        self.kind = method.kind;
+        self.outcome = None;
+        cx.notify();
+    }
+
+    /// Parse the params editor. Empty params are sent as `{}`.
+    fn params(&self, cx: &App) -> Result<Value, String> {
+        let text = self.params_input.read(cx).value();
+        if text.trim().is_empty() {
+            return Ok(Value::Object(Default::default()));
+        }
+        match serde_json::from_str::<Value>(&text) {
+            Ok(value) if value.is_object() || value.is_array() => Ok(value),
+            Ok(_) => Err("Params must be a JSON object or array".into()),
+            Err(e) => Err(format!("Invalid JSON: {}", e)),
+        }
+    }
+
+    fn format_params(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if let Ok(params) = self.params(cx) {
+            let pretty = serde_json::to_string_pretty(&params).unwrap_or_default();
+            self.params_input.update(cx, |input, cx| input.set_value(pretty, window, cx));
+        }
+    }
+
+    fn send(&mut self, cx: &mut Context<Self>) {
+        let method = self.method_input.read(cx).value().trim().to_string();
+        let Ok(params) = self.params(cx) else {
+            return;
+        };
+        if method.is_empty() {
+            return;
+        }
+
+        match self.kind {
+            MethodKind::Request => {
+                let task =
+                    self.connection.update(cx, |conn, cx| conn.send_request(&method, params, cx));
+                self.outcome = Some(SendOutcome::Waiting);
+                cx.spawn(async move |this, cx| {
+                    let outcome = match task.await {
+                        Ok(result) => SendOutcome::Result(result),
+                        Err(ClientError::AgentError { code, message, .. }) => {
+                            SendOutcome::Failed(format!("Error {}: {}", code, message))
+                        }
+                        Err(e) => SendOutcome::Failed(e.to_string()),
+                    };
+                    let _ = this.update(cx, |this, cx| {
+                        this.outcome = Some(outcome);
+                        cx.notify();
+                    });
+                })
+                .detach();
+            }
+            MethodKind::Notification => {
+                let sent = self
+                    .connection
+                    .update(cx, |conn, cx| conn.send_notification(&method, params, cx));
+                self.outcome = Some(match sent {
+                    Ok(()) => SendOutcome::Sent,
+                    Err(e) => SendOutcome::Failed(e.to_string()),
+                });
+            }
+        }
+        cx.notify();
+    }
+
+    fn outcome_view(&self, window: &mut Window, cx: &mut Context<Self>) -> Option<AnyElement> {
+        let element = match self.outcome.as_ref()? {
+            SendOutcome::Waiting => div()
+                .text_sm()
+                .text_color(cx.theme().muted_foreground)
+                .child("Waiting for response…")
+                .into_any_element(),
+            SendOutcome::Sent => div()
+                .text_sm()
+                .text_color(cx.theme().muted_foreground)
+                .child("Notification sent")
+                .into_any_element(),
+            SendOutcome::Failed(message) => div()
+                .text_sm()
+                .text_color(cx.theme().danger)
+                .child(message.clone())
+                .into_any_element(),
+            SendOutcome::Result(result) => {
+                let pretty = serde_json::to_string_pretty(result).unwrap_or_default();
+                let md = format!("```json\n{}\n```", pretty);
+                TextView::markdown("composer-result", md, window, cx).into_any_element()
+            }
+        };
+        Some(element)
+    }
+}
+
+impl Render for ComposerPanel {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let connected = self.connection.read(cx).status() == AgentStatus::Connected;
+        let params_error = self.params(cx).err();
+        let method = self.method_input.read(cx).value();
+        let method_empty = method.trim().is_empty();
+        let description = AcpMethod::find(method.trim()).map(|m| m.description);
+        let can_send = connected && params_error.is_none() && !method_empty;
+        let entity = cx.entity().clone();
+
+        v_flex()
+            .id("composer-panel")
+            .size_full()
+            .overflow_y_scroll()
+            .gap_4()
+            .p_4()
+            .when(!connected, |el| el.child(not_connected_warning(cx)))
+            // Method
+            .child(
+                v_flex()
+                    .gap_1()
+                    .child(field_label("Method", cx))
+                    .child(
+                        h_flex()
+                            .gap_2()
+                            .child(div().flex_1().child(Input::new(&self.method_input)))
+                            .child(
+                                Button::new("method-templates")
+                                    .outline()
+                                    .small()
+                                    .icon(IconName::ChevronDown)
+                                    .label("Templates")
+                                    .dropdown_menu({
+                                        let entity = entity.clone();
+                                        move |menu, _, _| {
+                                            let mut menu = menu;
+                                            for method in ACP_METHODS {
+                                                let entity = entity.clone();
+                                                menu = menu.item(
+                                                    PopupMenuItem::new(method.name).on_click(
+                                                        move |_, window, cx| {
+                                                            entity.update(cx, |this, cx| {
+                                                                this.apply_template(
+                                                                    method, window, cx,
+                                                                )
+                                                            });
+                                                        },
+                                                    ),
+                                                );
+                                            }
+                                            menu
+                                        }
+                                    }),
+                            ),
+                    )
+                    .when_some(description, |el, description| {
+                        el.child(
+                            div()
+                                .text_xs()
+                                .text_color(cx.theme().muted_foreground)
+                                .child(description),
+                        )
+                    }),
+            )
+            // Kind
+            .child(
+                v_flex().gap_1().child(field_label("Send as", cx)).child(
+                    RadioGroup::horizontal("composer-kind")
+                        .selected_index(Some(match self.kind {
+                            MethodKind::Request => 0,
+                            MethodKind::Notification => 1,
+                        }))
+                        .child("Request")
+                        .child("Notification")
+                        .on_click({
+                            let entity = entity.clone();
+                            move |ix, _, cx| {
+                                entity.update(cx, |this, cx| {
+                                    this.kind = if *ix == 1 {
+                                        MethodKind::Notification
+                                    } else {
+                                        MethodKind::Request
+                                    };
+                                    cx.notify();
+                                });
+                            }
+                        }),
+                ),
+            )
+            // Params
+            .child(
+                v_flex()
+                    .gap_1()
+                    .child(h_flex().justify_between().child(field_label("Params", cx)).child(
+                        Button::new("format-params").xsmall().ghost().label("Format").on_click(
+                            cx.listener(|this, _, window, cx| this.format_params(window, cx)),
+                        ),
+                    ))
+                    .child(Input::new(&self.params_input))
+                    .when_some(params_error, |el, error| {
+                        el.child(div().text_xs().text_color(cx.theme().danger).child(error))
+                    }),
+            )
+            // Buttons
+            .child(
+                h_flex()
+                    .justify_end()
+                    .gap_2()
+                    .child(Button::new("close").label("Close").on_click(cx.listener(
+                        |_, _, window, cx| {
+                            window.close_sheet(cx);
+                        },
+                    )))
+                    .child(
+                        Button::new("send")
+                            .primary()
+                            .label("Send")
+                            .disabled(!can_send)
+                            .on_click(cx.listener(|this, _, _, cx| this.send(cx))),
+                    ),
+            )
+            .children(self.outcome_view(window, cx))
+    }
+}
+
+fn field_label(text: &str, cx: &Context<ComposerPanel>) -> impl IntoElement {
+    div().text_sm().font_semibold().text_color(cx.theme().foreground).child(text.to_string())
+}
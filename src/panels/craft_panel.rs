@@ -0,0 +1,194 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use gpui::{prelude::*, *};
+use gpui_component::{
+    button::{Button, ButtonVariants as _},
+    h_flex,
+    input::{Input, InputState},
+    v_flex, ActiveTheme as _, Disableable as _, WindowExt as _,
+};
+use serde_json::Value;
+
+use crate::{
+    client::AcpConnection,
+    models::{AcpMessage, AgentStatus},
+};
+
+use super::{not_connected_warning, revalidate_on_edit};
+
+/// What to do with the edited frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CraftMode {
+    /// Send the frame to the agent.
+    Resend,
+    /// Handle the frame as if the agent had sent it.
+    Inject,
+}
+
+impl CraftMode {
+    /// Resend outgoing messages, inject incoming ones.
+    pub fn for_message(message: &AcpMessage) -> Self {
+        if message.is_response {
+            Self::Inject
+        } else {
+            Self::Resend
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Resend => "Edit & Resend",
+            Self::Inject => "Inject as Incoming",
+        }
+    }
+}
+
+/// A panel for editing a captured frame and sending or injecting it.
+pub struct CraftPanel {
+    connection: Entity<AcpConnection>,
+    mode: CraftMode,
+    frame_input: Entity<InputState>,
+    status: Option<Result<&'static str, String>>,
+    _subscriptions: Vec<Subscription>,
+}
+
+impl CraftPanel {
+    pub fn new(
+        message: &AcpMessage,
+        connection: Entity<AcpConnection>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let frame = message.pretty_json();
+        let frame_input = cx.new(|cx| {
+            let mut state = InputState::new(window, cx).multi_line(true).auto_grow(12, 32);
+            state.set_value(frame, window, cx);
+            state
+        });
+
+        let subscriptions = vec![
+            revalidate_on_edit(&frame_input, cx),
+            cx.observe(&connection, |_, _, cx| cx.notify()),
+        ];
+
+        Self {
+            connection,
+            mode: CraftMode::for_message(message),
+            frame_input,
+            status: None,
+            _subscriptions: subscriptions,
+        }
+    }
+
+    /// Open a sheet for crafting a variation of `message`.
+    pub fn open(
+        message: &AcpMessage,
+        connection: Entity<AcpConnection>,
+        window: &mut Window,
+        cx: &mut App,
+    ) {
+        let title = format!("{} - {}", message.title, CraftMode::for_message(message).label());
+        let panel = cx.new(|cx| CraftPanel::new(message, connection, window, cx));
+        window.open_sheet(cx, move |sheet, _, _| {
+            sheet.size(px(450.)).title(title.clone()).child(panel.clone())
+        });
+    }
+
+    /// Parse the editor; a frame must be a JSON object.
+    fn frame(&self, cx: &App) -> Result<Value, String> {
+        match serde_json::from_str::<Value>(&self.frame_input.read(cx).value()) {
+            Ok(value) if value.is_object() => Ok(value),
+            Ok(_) => Err("A JSON-RPC frame must be a JSON object".into()),
+            Err(e) => Err(format!("Invalid JSON: {}", e)),
+        }
+    }
+
+    fn submit(&mut self, cx: &mut Context<Self>) {
+        let Ok(frame) = self.frame(cx) else {
+            return;
+        };
+        self.status = Some(match self.mode {
+            CraftMode::Resend => self
+                .connection
+                .update(cx, |conn, cx| conn.send_crafted(frame, cx))
+                .map(|_| "Sent to agent")
+                .map_err(|e| e.to_string()),
+            CraftMode::Inject => self
+                .connection
+                .update(cx, |conn, cx| conn.inject_incoming(frame, cx))
+                .map(|_| "Injected as incoming")
+                .map_err(|e| e.to_string()),
+        });
+        cx.notify();
+    }
+}
+
+impl Render for CraftPanel {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let connected = self.connection.read(cx).status() == AgentStatus::Connected;
+        let frame_error = self.frame(cx).err();
+        let can_submit = frame_error.is_none() && (connected || self.mode == CraftMode::Inject);
+
+        v_flex()
+            .id("craft-panel")
+            .size_full()
+            .overflow_y_scroll()
+            .gap_4()
+            .p_4()
+            .child(div().text_sm().text_color(cx.theme().muted_foreground).child(match self.mode {
+                CraftMode::Resend => "Edit the frame and send it to the agent unchanged.",
+                CraftMode::Inject => "Edit the frame and handle it as if the agent had sent it.",
+            }))
+            .when(!connected && self.mode == CraftMode::Resend, |el| {
+                el.child(not_connected_warning(cx))
+            })
+            .child(
+                v_flex().gap_1().child(Input::new(&self.frame_input)).when_some(
+                    frame_error,
+                    |el, error| {
+                        el.child(div().text_xs().text_color(cx.theme().danger).child(error))
+                    },
+                ),
+            )
+            .child(
+                h_flex()
+                    .justify_end()
+                    .gap_2()
+                    .child(Button::new("close").label("Close").on_click(cx.listener(
+                        |_, _, window, cx| {
+                            window.close_sheet(cx);
+                        },
+                    )))
+                    .child(
+                        Button::new("submit")
+                            .primary()
+                            .label(match self.mode {
+                                CraftMode::Resend => "Send",
+                                CraftMode::Inject => "Inject",
+                            })
+                            .disabled(!can_submit)
+                            .on_click(cx.listener(|this, _, _, cx| this.submit(cx))),
+                    ),
+            )
+            .when_some(self.status.clone(), |el, status| {
+                el.child(match status {
+                    Ok(message) => {
+                        div().text_sm().text_color(cx.theme().muted_foreground).child(message)
+                    }
+                    Err(error) => div().text_sm().text_color(cx.theme().danger).child(error),
+                })
+            })
+    }
+}
@@ -12,23 +12,82 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use gpui::*;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use gpui::{prelude::*, *};
 use gpui_component::{
-    accordion::Accordion, description_list::DescriptionList, h_flex, tag::Tag, text::TextView,
-    v_flex, ActiveTheme as _, Sizable as _, StyledExt as _,
+    accordion::Accordion, button::Button, description_list::DescriptionList, h_flex, tag::Tag,
+    text::TextView, v_flex, ActiveTheme as _, Sizable as _, StyledExt as _, WindowExt as _,
 };
 
-use crate::models::{AcpMessage, MessageType};
+use serde_json::Value;
+
+use crate::{
+    client::AcpConnection,
+    components::JsonTree,
+    models::{AcpMessage, MessageType},
+    panels::{craft_panel::CraftMode, CraftPanel},
+};
 
 /// A panel that displays detailed information for a single ACP message.
 #[derive(IntoElement)]
 pub struct DetailPanel {
     message: AcpMessage,
+    in_reply_to: Option<AcpMessage>,
+    connection: Option<Entity<AcpConnection>>,
 }
 
 impl DetailPanel {
     pub fn new(message: AcpMessage) -> Self {
-        Self { message }
+        Self { message, in_reply_to: None, connection: None }
+    }
+
+    /// Link the request this message answers.
+    pub fn in_reply_to(mut self, request: Option<AcpMessage>) -> Self {
+        self.in_reply_to = request;
+        self
+    }
+
+    /// Enable editing the message and sending it over `connection`.
+    pub fn connection(mut self, connection: Entity<AcpConnection>) -> Self {
+        self.connection = Some(connection);
+        self
+    }
+
+    /// Open the details of `message` in a sheet.
+    pub fn open(
+        message: AcpMessage,
+        request: Option<AcpMessage>,
+        connection: Entity<AcpConnection>,
+        window: &mut Window,
+        cx: &mut App,
+    ) {
+        let title = message.title.clone();
+        window.open_sheet(cx, move |sheet, _, _| {
+            sheet
+                .size_full()
+                .margin_top(px(84.)) // Space for window title bar
+                .size(px(400.))
+                .title(format!("{} - Message Details", title))
+                .child(
+                    DetailPanel::new(message.clone())
+                        .in_reply_to(request.clone())
+                        .connection(connection.clone()),
+                )
+        })
+    }
+
+    /// A JSON tree kept across frames, keyed by `name` and the content.
+    fn json_tree(
+        name: &'static str,
+        value: &Value,
+        window: &mut Window,
+        cx: &mut App,
+    ) -> Entity<JsonTree> {
+        let mut hasher = DefaultHasher::new();
+        value.to_string().hash(&mut hasher);
+        let value = value.clone();
+        window.use_keyed_state((name, hasher.finish()), cx, |_, _| JsonTree::new(value))
     }
 
     fn message_type_tag(message_type: MessageType) -> Tag {
@@ -74,12 +133,19 @@ impl RenderOnce for DetailPanel {
             overview = overview.item("Protocol", version.to_string(), 1);
         }
 
-        // Build payload TextView (params or result)
-        let payload_section = msg.payload_json().map(|payload| {
+        if msg.crafted {
+            overview = overview.item("Origin", "Manually crafted".to_string(), 1);
+        }
+
+        if let Some(request) = &self.in_reply_to {
+            let id = request.id.as_ref().map(|id| id.to_string()).unwrap_or_default();
+            overview = overview.item("In Reply To", format!("{} #{}", request.title, id), 1);
+        }
+
+        // Build payload tree (params or result)
+        let payload_section = msg.payload().map(|payload| {
             let section_title: &str = if msg.params.is_some() { "Params" } else { "Result" };
-            let md = format!("```json\n{}\n```", payload);
-            let text_view = TextView::markdown("payload-json", md, window, cx);
-            (section_title, text_view)
+            (section_title, Self::json_tree("payload-json", payload, window, cx))
         });
 
         // Build error section
@@ -93,10 +159,9 @@ impl RenderOnce for DetailPanel {
             TextView::markdown("error-details", md, window, cx)
         });
 
-        // Build raw payload TextView
-        let raw_json = msg.pretty_json();
-        let raw_md = format!("```json\n{}\n```", raw_json);
-        let raw_text_view = TextView::markdown("raw-json", raw_md, window, cx);
+        // Build raw payload tree
+        let raw_value = serde_json::from_str(&msg.raw_json).unwrap_or(Value::Null);
+        let raw_tree = Self::json_tree("raw-json", &raw_value, window, cx);
 
         // Build accordion
         let mut accordion = Accordion::new("detail-sections")
@@ -107,8 +172,8 @@ impl RenderOnce for DetailPanel {
             .item(|item| item.open(true).title("Overview").child(overview));
 
         // Section 2: Params/Result (conditional)
-        if let Some((title, text_view)) = payload_section {
-            accordion = accordion.item(|item| item.open(true).title(title).child(text_view));
+        if let Some((title, tree)) = payload_section {
+            accordion = accordion.item(|item| item.open(true).title(title).child(tree));
         }
 
         // Section 3: Error Details (conditional)
@@ -117,9 +182,26 @@ impl RenderOnce for DetailPanel {
                 accordion.item(|item| item.open(true).title("Error Details").child(text_view));
         }
 
-        // Section 4: Raw Payload (always present, collapsed)
-        accordion =
-            accordion.item(|item| item.open(false).title("Raw Payload").child(raw_text_view));
+        // Section 4: Request this message answers (conditional)
+        if let Some(request) = self.in_reply_to.clone() {
+            let connection = self.connection.clone();
+            let request_value = serde_json::from_str(&request.raw_json).unwrap_or(Value::Null);
+            let tree = Self::json_tree("request-json", &request_value, window, cx);
+            accordion = accordion.item(|item| {
+                item.open(false).title("Request").child(v_flex().gap_2().child(tree).child(
+                    Button::new("open-request").xsmall().label("Open Request").on_click(
+                        move |_, window, cx| {
+                            if let Some(connection) = connection.clone() {
+                                DetailPanel::open(request.clone(), None, connection, window, cx);
+                            }
+                        },
+                    ),
+                ))
+            });
+        }
+
+        // Section 5: Raw Payload (always present, collapsed)
+        accordion = accordion.item(|item| item.open(false).title("Raw Payload").child(raw_tree));
 
         v_flex()
             .id("detail-panel")
@@ -140,7 +222,20 @@ impl RenderOnce for DetailPanel {
                             .font_semibold()
                             .text_color(cx.theme().foreground)
                             .child(msg.title.clone()),
-                    ),
+                    )
+                    .when(msg.crafted, |el| el.child(Tag::secondary().small().child("Crafted")))
+                    .when_some(self.connection.clone(), |el, connection| {
+                        let message = msg.clone();
+                        el.child(div().flex_1()).child(
+                            Button::new("craft")
+                                .xsmall()
+                                .outline()
+                                .label(CraftMode::for_message(msg).label())
+                                .on_click(move |_, window, cx| {
+                                    CraftPanel::open(&message, connection.clone(), window, cx);
+                                }),
+                        )
+                    }),
             )
             // Accordion sections
             .child(accordion)
@@ -0,0 +1,307 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::PathBuf;
+
+use gpui::{prelude::*, *};
+use gpui_component::{
+    button::Button,
+    h_flex,
+    input::{Input, InputEvent, InputState},
+    v_flex, ActiveTheme as _, Sizable as _, StyledExt as _, WindowExt,
+};
+use serde_json::Value;
+
+use crate::models::{
+    diff, diff_traces, AcpMessage, DiffChange, DiffKind, DiffOptions, Trace, DEFAULT_IGNORED_KEYS,
+};
+
+/// Longest value shown inline in a change.
+const MAX_VALUE_LEN: usize = 200;
+
+/// One side of a trace comparison.
+struct LoadedTrace {
+    path: PathBuf,
+    trace: Trace,
+}
+
+enum DiffSource {
+    Messages { left: Box<AcpMessage>, right: Box<AcpMessage> },
+    Traces { left: Option<LoadedTrace>, right: Option<LoadedTrace> },
+}
+
+/// A structural diff of two captured messages or two saved traces.
+pub struct DiffPanel {
+    source: DiffSource,
+    ignore_input: Entity<InputState>,
+    options: DiffOptions,
+    error: Option<String>,
+    _subscription: Subscription,
+}
+
+impl DiffPanel {
+    fn new(source: DiffSource, window: &mut Window, cx: &mut Context<Self>) -> Self {
+        let ignore_input = cx.new(|cx| {
+            InputState::new(window, cx)
+                .placeholder("Keys to ignore, comma separated")
+                .default_value(DEFAULT_IGNORED_KEYS.join(", "))
+        });
+        let subscription = cx.subscribe(&ignore_input, |this, input, event: &InputEvent, cx| {
+            if let InputEvent::Change = event {
+                this.options = DiffOptions::parse(&input.read(cx).value());
+                cx.notify();
+            }
+        });
+
+        Self {
+            source,
+            ignore_input,
+            options: DiffOptions::default(),
+            error: None,
+            _subscription: subscription,
+        }
+    }
+
+    /// Open a diff of two captured messages in a sheet.
+    pub fn open_messages(left: AcpMessage, right: AcpMessage, window: &mut Window, cx: &mut App) {
+        let source = DiffSource::Messages { left: Box::new(left), right: Box::new(right) };
+        let panel = cx.new(|cx| Self::new(source, window, cx));
+        window.open_sheet(cx, move |sheet, _, _| {
+            sheet.size(px(700.)).title("Compare Messages").child(panel.clone())
+        });
+    }
+
+    /// Open an empty trace comparison; traces are picked from the panel.
+    pub fn open_traces(window: &mut Window, cx: &mut App) {
+        let source = DiffSource::Traces { left: None, right: None };
+        let panel = cx.new(|cx| Self::new(source, window, cx));
+        window.open_sheet(cx, move |sheet, _, _| {
+            sheet.size(px(700.)).title("Compare Traces").child(panel.clone())
+        });
+    }
+
+    fn load_trace(&mut self, right: bool, cx: &mut Context<Self>) {
+        let paths = cx.prompt_for_paths(PathPromptOptions {
+            files: true,
+            directories: false,
+            multiple: false,
+            prompt: Some("Open Trace".into()),
+        });
+        cx.spawn(async move |this, cx| {
+            let Ok(Ok(Some(paths))) = paths.await else {
+                return;
+            };
+            let Some(path) = paths.into_iter().next() else {
+                return;
+            };
+            let loaded = Trace::load(&path);
+            this.update(cx, |this, cx| {
+                match loaded {
+                    Ok(trace) => {
+                        this.error = None;
+                        if let DiffSource::Traces { left, right: right_trace } = &mut this.source {
+                            let slot = if right { right_trace } else { left };
+                            *slot = Some(LoadedTrace { path, trace });
+                        }
+                    }
+                    Err(e) => this.error = Some(format!("{}: {}", path.display(), e)),
+                }
+                cx.notify();
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    fn change(change: &DiffChange, cx: &App) -> impl IntoElement {
+        let (marker, color) = match change.kind {
+            DiffKind::Added => ("+", cx.theme().success),
+            DiffKind::Removed => ("-", cx.theme().danger),
+            DiffKind::Changed => ("~", cx.theme().warning),
+        };
+        let pointer = if change.pointer.is_empty() { "/" } else { &change.pointer };
+        let value = |value: &Option<Value>, prefix: &str, color: Hsla| {
+            value.as_ref().map(|value| {
+                div().pl_4().text_color(color).child(format!("{} {}", prefix, compact(value)))
+            })
+        };
+
+        v_flex()
+            .py_0p5()
+            .text_xs()
+            .font_family("monospace")
+            .child(div().text_color(color).child(format!("{} {}", marker, pointer)))
+            .children(value(&change.left, "-", cx.theme().danger))
+            .children(value(&change.right, "+", cx.theme().success))
+    }
+
+    fn changes(changes: &[DiffChange], cx: &App) -> Div {
+        if changes.is_empty() {
+            return div().text_sm().text_color(cx.theme().muted_foreground).child("No differences.");
+        }
+        v_flex().children(changes.iter().map(|change| Self::change(change, cx)))
+    }
+
+    fn trace_slot(
+        &self,
+        id: &'static str,
+        label: &'static str,
+        trace: Option<&LoadedTrace>,
+        right: bool,
+        cx: &mut Context<Self>,
+    ) -> impl IntoElement {
+        h_flex()
+            .gap_2()
+            .child(
+                Button::new(id)
+                    .xsmall()
+                    .outline()
+                    .label(label)
+                    .on_click(cx.listener(move |this, _, _, cx| this.load_trace(right, cx))),
+            )
+            .child(div().text_xs().text_color(cx.theme().muted_foreground).child(match trace {
+                Some(loaded) => {
+                    format!("{} ({} frames)", loaded.path.display(), loaded.trace.frames.len())
+                }
+                None => "No trace loaded".to_string(),
+            }))
+    }
+
+    fn render_traces(
+        &self,
+        left: Option<&LoadedTrace>,
+        right: Option<&LoadedTrace>,
+        cx: &mut Context<Self>,
+    ) -> impl IntoElement {
+        let diffs = match (left, right) {
+            (Some(left), Some(right)) => {
+                Some(diff_traces(&left.trace, &right.trace, &self.options))
+            }
+            _ => None,
+        };
+
+        v_flex()
+            .gap_2()
+            .child(self.trace_slot("load-left-trace", "Left Trace…", left, false, cx))
+            .child(self.trace_slot("load-right-trace", "Right Trace…", right, true, cx))
+            .when_some(diffs, |el, diffs| {
+                let differing = diffs.iter().filter(|d| d.kind().is_some()).count();
+                el.child(div().text_sm().text_color(cx.theme().muted_foreground).child(format!(
+                    "{} aligned frames, {} differ",
+                    diffs.len(),
+                    differing
+                )))
+                .children(diffs.into_iter().map(|frame| {
+                    let (marker, color) = match frame.kind() {
+                        None => ("=", cx.theme().muted_foreground),
+                        Some(DiffKind::Added) => ("+", cx.theme().success),
+                        Some(DiffKind::Removed) => ("-", cx.theme().danger),
+                        Some(DiffKind::Changed) => ("~", cx.theme().warning),
+                    };
+                    let position =
+                        |ix: Option<usize>| ix.map_or("–".to_string(), |ix| ix.to_string());
+                    v_flex()
+                        .py_1()
+                        .border_b_1()
+                        .border_color(cx.theme().border)
+                        .child(
+                            h_flex()
+                                .gap_2()
+                                .text_sm()
+                                .child(div().text_color(color).child(marker))
+                                .child(
+                                    div()
+                                        .flex_1()
+                                        .font_semibold()
+                                        .text_color(cx.theme().foreground)
+                                        .child(frame.label.clone()),
+                                )
+                                .child(
+                                    div().text_xs().text_color(cx.theme().muted_foreground).child(
+                                        format!(
+                                            "{} ↔ {}",
+                                            position(frame.left),
+                                            position(frame.right)
+                                        ),
+                                    ),
+                                ),
+                        )
+                        .when(!frame.changes.is_empty(), |el| {
+                            el.child(div().pl_4().child(Self::changes(&frame.changes, cx)))
+                        })
+                }))
+            })
+    }
+}
+
+impl Render for DiffPanel {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let body = match &self.source {
+            DiffSource::Messages { left, right } => {
+                let parse =
+                    |m: &AcpMessage| serde_json::from_str(&m.raw_json).unwrap_or(Value::Null);
+                let changes = diff(&parse(left), &parse(right), &self.options);
+                v_flex()
+                    .gap_2()
+                    .child(div().text_sm().text_color(cx.theme().muted_foreground).child(format!(
+                        "{} ({}) ↔ {} ({})",
+                        left.title,
+                        left.direction_label(),
+                        right.title,
+                        right.direction_label()
+                    )))
+                    .child(Self::changes(&changes, cx))
+                    .into_any_element()
+            }
+            DiffSource::Traces { left, right } => {
+                self.render_traces(left.as_ref(), right.as_ref(), cx).into_any_element()
+            }
+        };
+
+        v_flex()
+            .id("diff-panel")
+            .size_full()
+            .overflow_y_scroll()
+            .gap_3()
+            .p_4()
+            .child(
+                v_flex()
+                    .gap_1()
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(cx.theme().muted_foreground)
+                            .child("Ignored keys"),
+                    )
+                    .child(Input::new(&self.ignore_input).small()),
+            )
+            .when_some(self.error.clone(), |el, error| {
+                el.child(div().text_sm().text_color(cx.theme().danger).child(error))
+            })
+            .child(body)
+    }
+}
+
+fn compact(value: &Value) -> String {
+    let mut text = value.to_string();
+    if text.len() > MAX_VALUE_LEN {
+        let mut end = MAX_VALUE_LEN;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push('…');
+    }
+    text
+}
@@ -12,16 +12,290 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use gpui::*;
-use gpui_component::{button::Button, v_flex, ActiveTheme as _, IconName, WindowExt};
+use std::collections::HashMap;
 
-use crate::{components::MessageItem, models::AcpMessage, panels::DetailPanel};
+use gpui::{prelude::*, *};
+use gpui_component::{
+    button::{Button, ButtonVariants as _},
+    checkbox::Checkbox,
+    h_flex,
+    input::{Input, InputEvent, InputState},
+    v_flex, ActiveTheme as _, Disableable as _, IconName, Sizable as _, WindowExt,
+};
 
-#[derive(IntoElement)]
-pub struct MessagePanel;
+use crate::{
+    client::{AcpConnection, ConnectionEvent},
+    components::MessageItem,
+    models::{AcpMessage, MessageFilter, FILTER_HELP},
+    panels::{
+        BreakpointPanel, ComposerPanel, DetailPanel, DiffPanel, RequestPanel, StatsPanel,
+        TimelinePanel,
+    },
+};
+
+const COMPARE_TOOLTIP: &str = "Cmd/Ctrl-click two messages to compare them, or compare two traces";
+
+/// The list of messages exchanged with the connected agent.
+pub struct MessagePanel {
+    connection: Entity<AcpConnection>,
+    messages: Vec<AcpMessage>,
+    composer: Option<Entity<ComposerPanel>>,
+    breakpoints: Option<Entity<BreakpointPanel>>,
+    requests: Option<Entity<RequestPanel>>,
+    /// Created up front so they see all traffic.
+    timeline: Entity<TimelinePanel>,
+    stats: Entity<StatsPanel>,
+    filter_input: Entity<InputState>,
+    filter: MessageFilter,
+    filter_error: Option<String>,
+    /// Indexes of the messages matching `filter`.
+    matches: Vec<usize>,
+    /// Position in `matches` of the match navigated to.
+    current_match: Option<usize>,
+    only_matches: bool,
+    /// Messages picked for comparison with a secondary (Cmd/Ctrl) click.
+    compare: Vec<usize>,
+    /// Index of the request each reply answers.
+    replies: HashMap<usize, usize>,
+    /// Latest request by direction and id, for pairing replies as they arrive.
+    requests_by_id: HashMap<(bool, String), usize>,
+    /// The rows shown: matches when filtering, all messages otherwise.
+    list_state: ListState,
+    _subscriptions: Vec<Subscription>,
+}
 
 impl MessagePanel {
-    fn header(&self, cx: &App) -> impl IntoElement {
+    pub fn new(
+        connection: Entity<AcpConnection>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let filter_input = cx
+            .new(|cx| InputState::new(window, cx).placeholder(format!("Filter: {}", FILTER_HELP)));
+
+        let timeline = cx.new(|cx| TimelinePanel::new(connection.clone(), cx));
+        let stats = cx.new(|cx| StatsPanel::new(connection.clone(), cx));
+
+        let subscriptions = vec![
+            cx.subscribe(&connection, |this, _, event, cx| {
+                if let ConnectionEvent::MessageCaptured { message, .. } = event {
+                    this.push_message((**message).clone());
+                    cx.notify();
+                }
+            }),
+            // Keep the held frame and open request counts current.
+            cx.observe(&connection, |_, _, cx| cx.notify()),
+            cx.subscribe(&filter_input, |this, _, event: &InputEvent, cx| match event {
+                InputEvent::Change => this.apply_filter(cx),
+                InputEvent::PressEnter { secondary } => {
+                    this.select_match(if *secondary { -1 } else { 1 }, cx)
+                }
+                _ => {}
+            }),
+        ];
+
+        Self {
+            connection,
+            messages: Vec::new(),
+            composer: None,
+            breakpoints: None,
+            requests: None,
+            timeline,
+            stats,
+            filter_input,
+            filter: MessageFilter::default(),
+            filter_error: None,
+            matches: Vec::new(),
+            current_match: None,
+            only_matches: true,
+            compare: Vec::new(),
+            replies: HashMap::new(),
+            requests_by_id: HashMap::new(),
+            list_state: ListState::new(0, ListAlignment::Bottom, px(500.)),
+            _subscriptions: subscriptions,
+        }
+    }
+
+    /// Append a captured message, pairing it with the request it answers.
+    fn push_message(&mut self, message: AcpMessage) {
+        let ix = self.messages.len();
+        let rows = self.row_count();
+        if let Some(id) = message.id.as_ref().map(|id| id.to_string()) {
+            if message.method.is_some() {
+                self.requests_by_id.insert((message.is_response, id), ix);
+            } else if let Some(&request) = self.requests_by_id.get(&(!message.is_response, id)) {
+                self.replies.insert(ix, request);
+            }
+        }
+        if self.filter.matches(&message) {
+            self.matches.push(ix);
+        }
+        self.messages.push(message);
+
+        let added = self.row_count() - rows;
+        if added > 0 {
+            self.list_state.splice(rows..rows, added);
+        }
+        // Follow new traffic unless the user is stepping through matches.
+        if self.current_match.is_none() {
+            let end = self.list_state.item_count();
+            self.list_state.scroll_to(ListOffset { item_ix: end, offset_in_item: px(0.) });
+        }
+    }
+
+    fn row_count(&self) -> usize {
+        if self.filtering() {
+            self.matches.len()
+        } else {
+            self.messages.len()
+        }
+    }
+
+    /// The message shown in list row `row`.
+    fn row_message(&self, row: usize) -> Option<usize> {
+        if self.filtering() {
+            self.matches.get(row).copied()
+        } else {
+            (row < self.messages.len()).then_some(row)
+        }
+    }
+
+    /// Re-parse the filter query. An invalid query keeps the previous filter.
+    fn apply_filter(&mut self, cx: &mut Context<Self>) {
+        match MessageFilter::parse(&self.filter_input.read(cx).value()) {
+            Ok(filter) => {
+                self.filter = filter;
+                self.filter_error = None;
+            }
+            Err(error) => self.filter_error = Some(error),
+        }
+        self.matches = (0..self.messages.len())
+            .filter(|&ix| self.filter.matches(&self.messages[ix]))
+            .collect();
+        self.current_match = None;
+        self.list_state.reset(self.row_count());
+        cx.notify();
+    }
+
+    fn clear(&mut self, cx: &mut Context<Self>) {
+        self.messages.clear();
+        self.matches.clear();
+        self.current_match = None;
+        self.compare.clear();
+        self.replies.clear();
+        self.requests_by_id.clear();
+        self.list_state.reset(0);
+        self.timeline.update(cx, |timeline, cx| timeline.clear(cx));
+        cx.notify();
+    }
+
+    /// Whether only matching messages are listed.
+    fn filtering(&self) -> bool {
+        self.only_matches && !self.filter.is_empty()
+    }
+
+    /// Move to the next (`1`) or previous (`-1`) match and scroll to it.
+    fn select_match(&mut self, delta: isize, cx: &mut Context<Self>) {
+        let count = self.matches.len();
+        if count == 0 || self.filter.is_empty() {
+            return;
+        }
+        let next = match self.current_match {
+            None if delta < 0 => count - 1,
+            None => 0,
+            Some(current) => (current as isize + delta).rem_euclid(count as isize) as usize,
+        };
+        self.current_match = Some(next);
+        let row = if self.filtering() { next } else { self.matches[next] };
+        self.list_state.scroll_to_reveal_item(row);
+        cx.notify();
+    }
+
+    /// The request that the message at `ix` answers, if any.
+    fn request_for(&self, ix: usize) -> Option<&AcpMessage> {
+        self.replies.get(&ix).map(|&request| &self.messages[request])
+    }
+
+    fn open_composer(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let connection = self.connection.clone();
+        let composer = self
+            .composer
+            .get_or_insert_with(|| cx.new(|cx| ComposerPanel::new(connection, window, cx)))
+            .clone();
+        window.open_sheet(cx, move |sheet, _, _| {
+            sheet.size(px(450.)).title("Compose Message").child(composer.clone())
+        });
+    }
+
+    fn open_breakpoints(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let connection = self.connection.clone();
+        let breakpoints = self
+            .breakpoints
+            .get_or_insert_with(|| cx.new(|cx| BreakpointPanel::new(connection, window, cx)))
+            .clone();
+        window.open_sheet(cx, move |sheet, _, _| {
+            sheet.size(px(450.)).title("Breakpoints").child(breakpoints.clone())
+        });
+    }
+
+    fn open_requests(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let connection = self.connection.clone();
+        let requests = self
+            .requests
+            .get_or_insert_with(|| cx.new(|cx| RequestPanel::new(connection, cx)))
+            .clone();
+        window.open_sheet(cx, move |sheet, _, _| {
+            sheet.size(px(450.)).title("Requests").child(requests.clone())
+        });
+    }
+
+    fn open_timeline(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let timeline = self.timeline.clone();
+        window.open_sheet(cx, move |sheet, _, _| {
+            sheet.size(px(800.)).title("Timeline").child(timeline.clone())
+        });
+    }
+
+    fn open_stats(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let stats = self.stats.clone();
+        window.open_sheet(cx, move |sheet, _, _| {
+            sheet.size(px(600.)).title("Statistics").child(stats.clone())
+        });
+    }
+
+    /// Pick a message for comparison, keeping at most the last two.
+    fn toggle_compare(&mut self, ix: usize, cx: &mut Context<Self>) {
+        if let Some(pos) = self.compare.iter().position(|&c| c == ix) {
+            self.compare.remove(pos);
+        } else {
+            if self.compare.len() == 2 {
+                self.compare.remove(0);
+            }
+            self.compare.push(ix);
+        }
+        cx.notify();
+    }
+
+    /// Diff the two picked messages, or compare saved traces otherwise.
+    fn open_diff(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        match self.compare[..] {
+            [left, right] => {
+                let (left, right) = (left.min(right), left.max(right));
+                DiffPanel::open_messages(
+                    self.messages[left].clone(),
+                    self.messages[right].clone(),
+                    window,
+                    cx,
+                );
+            }
+            _ => DiffPanel::open_traces(window, cx),
+        }
+    }
+
+    fn header(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let connection = self.connection.read(cx);
+        let held = connection.interceptor().held_count();
+        let open = connection.queue().iter().filter(|item| item.is_open()).count();
         div()
             .flex()
             .items_center()
@@ -49,7 +323,8 @@ impl MessagePanel {
                             .rounded_lg()
                             .outline()
                             .icon(IconName::CircleX)
-                            .label("Clean"),
+                            .label("Clean")
+                            .on_click(cx.listener(|this, _, _, cx| this.clear(cx))),
                     )
                     .child(
                         Button::new("copy")
@@ -58,148 +333,219 @@ impl MessagePanel {
                             .outline()
                             .icon(IconName::Copy)
                             .label("Copy"),
+                    )
+                    .child(
+                        Button::new("requests")
+                            .compact()
+                            .rounded_lg()
+                            .outline()
+                            .icon(IconName::Loader)
+                            .label(if open > 0 {
+                                format!("Requests ({} open)", open)
+                            } else {
+                                "Requests".to_string()
+                            })
+                            .on_click(
+                                cx.listener(|this, _, window, cx| this.open_requests(window, cx)),
+                            ),
+                    )
+                    .child(
+                        Button::new("timeline")
+                            .compact()
+                            .rounded_lg()
+                            .outline()
+                            .icon(IconName::GalleryVerticalEnd)
+                            .label("Timeline")
+                            .on_click(
+                                cx.listener(|this, _, window, cx| this.open_timeline(window, cx)),
+                            ),
+                    )
+                    .child(
+                        Button::new("stats")
+                            .compact()
+                            .rounded_lg()
+                            .outline()
+                            .icon(IconName::ChartPie)
+                            .label("Stats")
+                            .on_click(
+                                cx.listener(|this, _, window, cx| this.open_stats(window, cx)),
+                            ),
+                    )
+                    .child(
+                        Button::new("diff")
+                            .compact()
+                            .rounded_lg()
+                            .outline()
+                            .icon(IconName::Replace)
+                            .label(if self.compare.len() == 2 {
+                                "Compare (2 selected)"
+                            } else {
+                                "Compare"
+                            })
+                            .tooltip(COMPARE_TOOLTIP)
+                            .on_click(
+                                cx.listener(|this, _, window, cx| this.open_diff(window, cx)),
+                            ),
+                    )
+                    .child(
+                        Button::new("breakpoints")
+                            .compact()
+                            .rounded_lg()
+                            .outline()
+                            .icon(IconName::Inspector)
+                            .label(if held > 0 {
+                                format!("Breakpoints ({} held)", held)
+                            } else {
+                                "Breakpoints".to_string()
+                            })
+                            .on_click(
+                                cx.listener(|this, _, window, cx| {
+                                    this.open_breakpoints(window, cx)
+                                }),
+                            ),
                     ),
             )
-            .child(Button::new("new-message").rounded_lg().outline().icon(IconName::Plus))
+            .child(
+                Button::new("new-message")
+                    .rounded_lg()
+                    .outline()
+                    .icon(IconName::Plus)
+                    .on_click(cx.listener(|this, _, window, cx| this.open_composer(window, cx))),
+            )
     }
 
-    fn message(
-        title: impl Into<SharedString>,
-        json_content: impl Into<SharedString>,
-        is_response: bool,
-    ) -> impl IntoElement {
-        let title: SharedString = title.into();
-        let json_content: SharedString = json_content.into();
-        let acp_message = AcpMessage::parse(title.clone(), json_content.clone(), is_response);
+    fn filter_bar(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let active = !self.filter.is_empty();
+        let status = match (&self.filter_error, self.current_match) {
+            (Some(error), _) => error.clone(),
+            _ if !active => String::new(),
+            (None, Some(current)) => format!("{} of {}", current + 1, self.matches.len()),
+            (None, None) => format!("{} matches", self.matches.len()),
+        };
+
+        h_flex()
+            .gap_2()
+            .px_3()
+            .py_1()
+            .border_b_1()
+            .border_color(cx.theme().title_bar_border)
+            .child(div().flex_1().child(Input::new(&self.filter_input).small()))
+            .child(
+                div()
+                    .text_xs()
+                    .text_color(if self.filter_error.is_some() {
+                        cx.theme().danger
+                    } else {
+                        cx.theme().muted_foreground
+                    })
+                    .child(status),
+            )
+            .child(
+                Button::new("previous-match")
+                    .xsmall()
+                    .ghost()
+                    .icon(IconName::ChevronUp)
+                    .disabled(!active || self.matches.is_empty())
+                    .on_click(cx.listener(|this, _, _, cx| this.select_match(-1, cx))),
+            )
+            .child(
+                Button::new("next-match")
+                    .xsmall()
+                    .ghost()
+                    .icon(IconName::ChevronDown)
+                    .disabled(!active || self.matches.is_empty())
+                    .on_click(cx.listener(|this, _, _, cx| this.select_match(1, cx))),
+            )
+            .child(
+                Checkbox::new("only-matches")
+                    .small()
+                    .label("Only matches")
+                    .checked(self.only_matches)
+                    .on_click(cx.listener(|this, checked: &bool, _, cx| {
+                        this.only_matches = *checked;
+                        this.current_match = None;
+                        this.list_state.reset(this.row_count());
+                        cx.notify();
+                    })),
+            )
+    }
+
+    fn message(&self, ix: usize, cx: &mut Context<Self>) -> impl IntoElement {
+        let message = self.messages[ix].clone();
+        let request = self.request_for(ix).cloned();
+        let reply_to = request.as_ref().and_then(|r| r.id.as_ref()).map(|id| format!("#{}", id));
+        let connection = self.connection.clone();
+        let matched = !self.filter.is_empty() && self.matches.binary_search(&ix).is_ok();
+        let highlights =
+            if matched { self.filter.highlights(&self.messages[ix].raw_json) } else { Vec::new() };
+        let selected = self.current_match.map(|current| self.matches[current]) == Some(ix) ||
+            self.compare.contains(&ix);
 
         div()
-            .on_mouse_down(MouseButton::Left, move |_, window, cx| {
-                let msg = acp_message.clone();
-                let msg_title = msg.title.clone();
-                window.open_sheet(cx, move |sheet, _, _| {
-                    sheet
-                        .size_full()
-                        .margin_top(px(84.)) // Space for window title bar
-                        .size(px(400.))
-                        .title(format!("{} - Message Details", msg_title))
-                        .child(DetailPanel::new(msg.clone()))
-                })
-            })
-            .child(MessageItem::new(title, json_content, is_response))
+            .on_mouse_down(
+                MouseButton::Left,
+                cx.listener(move |this, event: &MouseDownEvent, window, cx| {
+                    if event.modifiers.secondary() {
+                        this.toggle_compare(ix, cx);
+                    } else {
+                        DetailPanel::open(
+                            message.clone(),
+                            request.clone(),
+                            connection.clone(),
+                            window,
+                            cx,
+                        );
+                    }
+                }),
+            )
+            .child(
+                MessageItem::new(
+                    self.messages[ix].title.clone(),
+                    self.messages[ix].raw_json.clone(),
+                    self.messages[ix].is_response,
+                )
+                .reply_to(reply_to)
+                .crafted(self.messages[ix].crafted)
+                .highlights(highlights)
+                .selected(selected),
+            )
     }
 }
 
-impl RenderOnce for MessagePanel {
-    fn render(self, _: &mut Window, cx: &mut App) -> impl IntoElement {
-        v_flex().size_full().overflow_hidden().child(self.header(cx)).child(
-            div()
-                .id("scrollable-messages-container")
+impl Render for MessagePanel {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let empty = if self.messages.is_empty() {
+            Some("No messages yet. Select an agent to connect, or press + to compose one.")
+        } else if self.row_count() == 0 {
+            Some("No messages match the filter.")
+        } else {
+            None
+        };
+
+        v_flex()
+            .size_full()
+            .overflow_hidden()
+            .child(self.header(cx))
+            .child(self.filter_bar(cx))
+            .child(match empty {
+                Some(text) => div()
+                    .flex_1()
+                    .p_4()
+                    .text_sm()
+                    .text_color(cx.theme().muted_foreground)
+                    .child(text)
+                    .into_any_element(),
+                None => list(
+                    self.list_state.clone(),
+                    cx.processor(|this, row, _, cx| match this.row_message(row) {
+                        Some(ix) => this.message(ix, cx).into_any_element(),
+                        None => Empty.into_any_element(),
+                    }),
+                )
                 .flex_1()
-                .overflow_y_scroll()
                 .px_3()
                 .py_2()
-                // initialize request
-                .child(Self::message(
-                    "initialize",
-                    r#"{"jsonrpc":"2.0","id":0,"method":"initialize","params":{"protocolVersion":1,"clientCapabilities":{"fs":{"readTextFile":true,"writeTextFile":true},"terminal":true},"clientInfo":{"name":"my-client","title":"My Client","version":"1.0.0"}}}"#,
-                    false,
-                ))
-                // initialize response
-                .child(Self::message(
-                    "initialize",
-                    r#"{"jsonrpc":"2.0","id":0,"result":{"protocolVersion":1,"agentCapabilities":{"loadSession":true,"promptCapabilities":{"image":true,"audio":true},"mcp":{"http":true,"sse":true}},"agentInfo":{"name":"my-agent","title":"My Agent","version":"1.0.0"},"authMethods":[]}}"#,
-                    true,
-                ))
-                // authenticate request
-                .child(Self::message(
-                    "authenticate",
-                    r#"{"jsonrpc":"2.0","id":1,"method":"authenticate","params":{"method":"api_key","credentials":{"key":"sk-..."}}}"#,
-                    false,
-                ))
-                // authenticate response
-                .child(Self::message(
-                    "authenticate",
-                    r#"{"jsonrpc":"2.0","id":1,"result":{"success":true}}"#,
-                    true,
-                ))
-                // session/new request
-                .child(Self::message(
-                    "session/new",
-                    r#"{"jsonrpc":"2.0","id":2,"method":"session/new","params":{"cwd":"/home/user/project","mcpServers":[{"name":"filesystem","command":"/path/to/mcp-server","args":["--stdio"],"env":[]}]}}"#,
-                    false,
-                ))
-                // session/new response
-                .child(Self::message(
-                    "session/new",
-                    r#"{"jsonrpc":"2.0","id":2,"result":{"sessionId":"sess_abc123def456"}}"#,
-                    true,
-                ))
-                // session/prompt request
-                .child(Self::message(
-                    "session/prompt",
-                    r#"{"jsonrpc":"2.0","id":3,"method":"session/prompt","params":{"sessionId":"sess_abc123def456","prompt":[{"type":"text","text":"Can you analyze this code?"}]}}"#,
-                    false,
-                ))
-                // session/update notification (agent_message_chunk)
-                .child(Self::message(
-                    "session/update",
-                    r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"sess_abc123def456","update":{"sessionUpdate":"agent_message_chunk","delta":"I'll analyze the code..."}}}"#,
-                    true,
-                ))
-                // session/update notification (tool_call)
-                .child(Self::message(
-                    "session/update",
-                    r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"sess_abc123def456","update":{"sessionUpdate":"tool_call","toolCallId":"call_001","title":"Reading file","kind":"read","status":"pending"}}}"#,
-                    true,
-                ))
-                // session/update notification (tool_call_update)
-                .child(Self::message(
-                    "session/update",
-                    r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"sess_abc123def456","update":{"sessionUpdate":"tool_call_update","toolCallId":"call_001","status":"completed","content":[{"type":"text","text":"File content here..."}]}}}"#,
-                    true,
-                ))
-                // fs/read_text_file request (agent to client)
-                .child(Self::message(
-                    "fs/read_text_file",
-                    r#"{"jsonrpc":"2.0","id":4,"method":"fs/read_text_file","params":{"path":"/home/user/project/main.py"}}"#,
-                    false,
-                ))
-                // fs/read_text_file response
-                .child(Self::message(
-                    "fs/read_text_file",
-                    r#"{"jsonrpc":"2.0","id":4,"result":{"content":"def main():\n    print('Hello')\n"}}"#,
-                    true,
-                ))
-                // fs/write_text_file request
-                .child(Self::message(
-                    "fs/write_text_file",
-                    r#"{"jsonrpc":"2.0","id":5,"method":"fs/write_text_file","params":{"path":"/home/user/project/output.txt","content":"Result data"}}"#,
-                    false,
-                ))
-                // fs/write_text_file response
-                .child(Self::message(
-                    "fs/write_text_file",
-                    r#"{"jsonrpc":"2.0","id":5,"result":{"success":true}}"#,
-                    true,
-                ))
-                // terminal/create request
-                .child(Self::message(
-                    "terminal/create",
-                    r#"{"jsonrpc":"2.0","id":6,"method":"terminal/create","params":{"cwd":"/home/user/project"}}"#,
-                    false,
-                ))
-                // terminal/create response
-                .child(Self::message(
-                    "terminal/create",
-                    r#"{"jsonrpc":"2.0","id":6,"result":{"terminalId":"term_xyz789"}}"#,
-                    true,
-                ))
-                // session/cancel notification
-                .child(Self::message(
-                    "session/cancel",
-                    r#"{"jsonrpc":"2.0","method":"session/cancel","params":{"sessionId":"sess_abc123def456"}}"#,
-                    false,
-                )),
-        )
+                .into_any_element(),
+            })
     }
 }
@@ -13,13 +13,45 @@
 // limitations under the License.
 
 mod agent_panel;
+mod breakpoint_panel;
+mod compare_panel;
+mod composer_panel;
+mod craft_panel;
 mod detail_panel;
+mod diff_panel;
 mod message_panel;
+mod request_panel;
 mod settings_panel;
+mod stats_panel;
+mod timeline_panel;
+
+use gpui::*;
+use gpui_component::{input::InputState, ActiveTheme as _};
 
 // Re-exports
 pub use agent_panel::AgentPanel;
+pub use breakpoint_panel::BreakpointPanel;
+pub use compare_panel::ComparePanel;
+pub use composer_panel::ComposerPanel;
+pub use craft_panel::CraftPanel;
 pub use detail_panel::DetailPanel;
+pub use diff_panel::DiffPanel;
 pub use message_panel::MessagePanel;
+pub use request_panel::RequestPanel;
 #[allow(unused_imports)]
 pub use settings_panel::SettingsPanel;
+pub use stats_panel::StatsPanel;
+pub use timeline_panel::TimelinePanel;
+
+/// Re-render `cx`'s view on edits to `input`, so validation stays current.
+fn revalidate_on_edit<T: 'static>(input: &Entity<InputState>, cx: &mut Context<T>) -> Subscription {
+    cx.observe(input, |_, _, cx| cx.notify())
+}
+
+/// Shown by panels that send to the agent while none is connected.
+fn not_connected_warning(cx: &App) -> Div {
+    div()
+        .text_sm()
+        .text_color(cx.theme().warning)
+        .child("No agent connected. Select an agent to send messages.")
+}
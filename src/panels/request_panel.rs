@@ -0,0 +1,240 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{ops::Range, time::Duration};
+
+use gpui::{prelude::*, *};
+use gpui_component::{
+    button::{Button, ButtonVariants as _},
+    h_flex,
+    tag::Tag,
+    v_flex, ActiveTheme as _, Disableable as _, Sizable as _, StyledExt as _,
+};
+
+use crate::{
+    client::AcpConnection,
+    models::{AcpMessage, QueueItem, QueueItemStatus},
+    panels::DetailPanel,
+};
+
+/// A panel tracking every request sent on the connection.
+pub struct RequestPanel {
+    connection: Entity<AcpConnection>,
+    _subscription: Subscription,
+    _ticker: Task<()>,
+}
+
+impl RequestPanel {
+    pub fn new(connection: Entity<AcpConnection>, cx: &mut Context<Self>) -> Self {
+        let subscription = cx.observe(&connection, |_, _, cx| cx.notify());
+
+        // Tick once a second so the age of open requests stays current.
+        let ticker = cx.spawn(async move |this, cx| loop {
+            cx.background_executor().timer(Duration::from_secs(1)).await;
+            let Ok(open) = this.read_with(cx, |this, cx| {
+                this.connection.read(cx).queue().iter().any(|item| item.is_open())
+            }) else {
+                break;
+            };
+            if open && this.update(cx, |_, cx| cx.notify()).is_err() {
+                break;
+            }
+        });
+
+        Self { connection, _subscription: subscription, _ticker: ticker }
+    }
+
+    /// Show the response (linked to its request), or the request while open.
+    fn open_item(&self, item: &QueueItem, window: &mut Window, cx: &mut App) {
+        // The queue keeps what was sent, so resending works; show it masked.
+        let redact = |json: &str| self.connection.read(cx).redact(json);
+        let request = AcpMessage::parse(item.method.clone(), redact(&item.request_json), false);
+        let connection = self.connection.clone();
+        match &item.response_json {
+            Some(response) => {
+                let response = AcpMessage::parse(item.method.clone(), redact(response), true);
+                DetailPanel::open(response, Some(request), connection, window, cx);
+            }
+            None => DetailPanel::open(request, None, connection, window, cx),
+        }
+    }
+
+    fn status_tag(status: QueueItemStatus) -> Tag {
+        let tag = match status {
+            QueueItemStatus::Pending => Tag::secondary(),
+            QueueItemStatus::InFlight => Tag::info(),
+            QueueItemStatus::Success => Tag::success(),
+            QueueItemStatus::Failed => Tag::danger(),
+        };
+        tag.small().child(status.label())
+    }
+
+    fn row(&self, ix: usize, item: &QueueItem, cx: &mut Context<Self>) -> impl IntoElement {
+        let id = item.id;
+        let timing = match item.completed_at {
+            Some(_) => format!("completed in {}", format_duration(item.elapsed())),
+            None => format!("{} ago", format_duration(item.elapsed())),
+        };
+
+        v_flex()
+            .gap_1()
+            .p_2()
+            .border_1()
+            .border_color(cx.theme().border)
+            .rounded_md()
+            .child(
+                h_flex()
+                    .gap_2()
+                    .child(Self::status_tag(item.status))
+                    .child(
+                        div()
+                            .text_sm()
+                            .font_semibold()
+                            .text_color(cx.theme().foreground)
+                            .child(item.method.clone()),
+                    )
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(cx.theme().muted_foreground)
+                            .child(format!("#{}", id)),
+                    )
+                    // Kept on the header line so every row has the same height.
+                    .child(
+                        div()
+                            .flex_1()
+                            .min_w_0()
+                            .truncate()
+                            .text_xs()
+                            .text_color(cx.theme().danger)
+                            .children(item.error_message.clone()),
+                    )
+                    .child(div().text_xs().text_color(cx.theme().muted_foreground).child(timing)),
+            )
+            .child(
+                h_flex()
+                    .gap_2()
+                    .justify_end()
+                    .child(
+                        Button::new(("cancel-request", ix))
+                            .xsmall()
+                            .ghost()
+                            .label("Cancel Locally")
+                            .disabled(!item.is_open())
+                            .on_click(cx.listener(move |this, _, _, cx| {
+                                this.connection.update(cx, |conn, cx| conn.cancel_locally(id, cx));
+                            })),
+                    )
+                    .child(
+                        Button::new(("resend-request", ix))
+                            .xsmall()
+                            .outline()
+                            .label("Resend")
+                            .on_click(cx.listener(move |this, _, _, cx| {
+                                this.connection.update(cx, |conn, cx| conn.resend(id, cx)).detach();
+                            })),
+                    )
+                    .child(
+                        Button::new(("open-request", ix))
+                            .xsmall()
+                            .outline()
+                            .label(if item.response_json.is_some() {
+                                "Open Response"
+                            } else {
+                                "Open Request"
+                            })
+                            .on_click(cx.listener(move |this, _, window, cx| {
+                                let item = this.connection.read(cx).queue().get(ix).cloned();
+                                if let Some(item) = item {
+                                    this.open_item(&item, window, cx);
+                                }
+                            })),
+                    ),
+            )
+    }
+
+    /// The rows in `range`, newest first, cloning only the visible items.
+    fn rows(&self, range: Range<usize>, cx: &mut Context<Self>) -> Vec<AnyElement> {
+        let queue = self.connection.read(cx).queue();
+        let items: Vec<(usize, QueueItem)> = range
+            .filter_map(|row| {
+                let ix = queue.len().checked_sub(row + 1)?;
+                Some((ix, queue[ix].clone()))
+            })
+            .collect();
+        items
+            .into_iter()
+            .map(|(ix, item)| div().pb_2().child(self.row(ix, &item, cx)).into_any_element())
+            .collect()
+    }
+}
+
+impl Render for RequestPanel {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let queue = self.connection.read(cx).queue();
+        let len = queue.len();
+        let open = queue.iter().filter(|item| item.is_open()).count();
+
+        v_flex()
+            .size_full()
+            .gap_2()
+            .p_4()
+            .child(
+                h_flex()
+                    .justify_between()
+                    .child(
+                        div()
+                            .text_sm()
+                            .text_color(cx.theme().muted_foreground)
+                            .child(format!("{} requests, {} open", len, open)),
+                    )
+                    .child(
+                        Button::new("clear-completed")
+                            .xsmall()
+                            .outline()
+                            .label("Clear Completed")
+                            .disabled(len == open)
+                            .on_click(cx.listener(|this, _, _, cx| {
+                                this.connection.update(cx, |conn, cx| conn.clear_completed(cx));
+                            })),
+                    ),
+            )
+            .when(len == 0, |el| {
+                el.child(
+                    div()
+                        .text_sm()
+                        .text_color(cx.theme().muted_foreground)
+                        .child("No requests sent on this connection yet."),
+                )
+            })
+            .child(
+                uniform_list(
+                    "request-rows",
+                    len,
+                    cx.processor(|this, range, _, cx| this.rows(range, cx)),
+                )
+                .flex_1(),
+            )
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    if duration < Duration::from_secs(1) {
+        format!("{} ms", duration.as_millis())
+    } else if duration < Duration::from_secs(60) {
+        format!("{:.1} s", duration.as_secs_f64())
+    } else {
+        format!("{}m {}s", duration.as_secs() / 60, duration.as_secs() % 60)
+    }
+}
@@ -0,0 +1,284 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use gpui::{prelude::*, *};
+use gpui_component::{
+    button::Button, h_flex, v_flex, ActiveTheme as _, Disableable as _, Sizable as _,
+    StyledExt as _,
+};
+
+use crate::{
+    client::{AcpConnection, ConnectionEvent},
+    models::{AcpMessage, LatencyStats, TrafficStats},
+};
+
+/// Traffic statistics for the current connection.
+pub struct StatsPanel {
+    messages: Vec<AcpMessage>,
+    /// Result of the last export.
+    export_status: Option<String>,
+    _subscription: Subscription,
+}
+
+impl StatsPanel {
+    pub fn new(connection: Entity<AcpConnection>, cx: &mut Context<Self>) -> Self {
+        let subscription = cx.subscribe(&connection, |this, _, event, cx| match event {
+            ConnectionEvent::MessageCaptured { message, .. } => {
+                this.messages.push((**message).clone());
+                cx.notify();
+            }
+            // Start over for every connection, but not for restarts.
+            ConnectionEvent::Reset => {
+                this.messages.clear();
+                this.export_status = None;
+                cx.notify();
+            }
+            _ => {}
+        });
+
+        Self { messages: Vec::new(), export_status: None, _subscription: subscription }
+    }
+
+    fn export(&mut self, cx: &mut Context<Self>) {
+        let json = match TrafficStats::compute(&self.messages).to_json() {
+            Ok(json) => json,
+            Err(e) => {
+                self.export_status = Some(format!("Export failed: {}", e));
+                cx.notify();
+                return;
+            }
+        };
+        let directory = std::env::current_dir().unwrap_or_default();
+        let path = cx.prompt_for_new_path(&directory, Some("acp-stats.json"));
+
+        cx.spawn(async move |this, cx| {
+            let Ok(Ok(Some(path))) = path.await else {
+                return;
+            };
+            let status = match std::fs::write(&path, json) {
+                Ok(()) => format!("Exported to {}", path.display()),
+                Err(e) => format!("Export failed: {}", e),
+            };
+            this.update(cx, |this, cx| {
+                this.export_status = Some(status);
+                cx.notify();
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    fn section(title: &str, cx: &App) -> Div {
+        v_flex().gap_1().child(
+            div()
+                .text_sm()
+                .font_semibold()
+                .text_color(cx.theme().foreground)
+                .child(title.to_string()),
+        )
+    }
+
+    /// A table row; the first cell takes the remaining width.
+    fn row(cells: Vec<String>, header: bool, cx: &App) -> Div {
+        let color = if header { cx.theme().muted_foreground } else { cx.theme().foreground };
+        h_flex().gap_2().py_0p5().border_b_1().border_color(cx.theme().border).children(
+            cells.into_iter().enumerate().map(move |(ix, cell)| {
+                let cell = div().text_xs().text_color(color).overflow_hidden().child(cell);
+                if ix == 0 {
+                    cell.flex_1()
+                } else {
+                    cell.w(px(64.)).text_right()
+                }
+            }),
+        )
+    }
+
+    fn summary_item(label: &str, value: String, cx: &App) -> impl IntoElement {
+        v_flex()
+            .flex_1()
+            .p_2()
+            .border_1()
+            .border_color(cx.theme().border)
+            .rounded_md()
+            .child(div().text_xs().text_color(cx.theme().muted_foreground).child(label.to_string()))
+            .child(div().text_sm().font_semibold().text_color(cx.theme().foreground).child(value))
+    }
+}
+
+impl Render for StatsPanel {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let stats = TrafficStats::compute(&self.messages);
+        let update_total: usize = stats.update_kinds.iter().map(|(_, count)| count).sum();
+
+        v_flex()
+            .id("stats-panel")
+            .size_full()
+            .overflow_y_scroll()
+            .gap_4()
+            .p_4()
+            .child(
+                h_flex()
+                    .gap_2()
+                    .child(div().flex_1().text_sm().text_color(cx.theme().muted_foreground).child(
+                        self.export_status.clone().unwrap_or_else(|| {
+                            "Statistics for the current connection.".to_string()
+                        }),
+                    ))
+                    .child(
+                        Button::new("export-stats")
+                            .xsmall()
+                            .outline()
+                            .label("Export JSON")
+                            .disabled(self.messages.is_empty())
+                            .on_click(cx.listener(|this, _, _, cx| this.export(cx))),
+                    ),
+            )
+            .child(
+                h_flex()
+                    .gap_2()
+                    .child(Self::summary_item("Messages", stats.messages.to_string(), cx))
+                    .child(Self::summary_item("Sent", format_bytes(stats.bytes_sent), cx))
+                    .child(Self::summary_item("Received", format_bytes(stats.bytes_received), cx))
+                    .child(Self::summary_item(
+                        "Notifications / s",
+                        format!(
+                            "{:.1} (peak {})",
+                            stats.streaming.per_second, stats.streaming.peak_per_second
+                        ),
+                        cx,
+                    )),
+            )
+            .child(
+                Self::section("Methods", cx)
+                    .child(Self::row(
+                        ["Method", "Out", "In", "Errors", "p50", "p95", "Max"]
+                            .map(String::from)
+                            .to_vec(),
+                        true,
+                        cx,
+                    ))
+                    .children(stats.methods.iter().map(|method| {
+                        let latency = |value: fn(&LatencyStats) -> f64| {
+                            method
+                                .latency
+                                .as_ref()
+                                .map_or_else(|| "–".to_string(), |l| format_ms(value(l)))
+                        };
+                        Self::row(
+                            vec![
+                                method.method.clone(),
+                                method.sent.to_string(),
+                                method.received.to_string(),
+                                method.errors.to_string(),
+                                latency(|l| l.p50_ms),
+                                latency(|l| l.p95_ms),
+                                latency(|l| l.max_ms),
+                            ],
+                            false,
+                            cx,
+                        )
+                    })),
+            )
+            .child(
+                Self::section("Errors", cx)
+                    .child(Self::row(
+                        ["Code", "Count", "Rate"].map(String::from).to_vec(),
+                        true,
+                        cx,
+                    ))
+                    .children(stats.errors.iter().map(|error| {
+                        Self::row(
+                            vec![
+                                format!("{} {}", error.code, error.message),
+                                error.count.to_string(),
+                                format!("{:.1}%", error.rate * 100.),
+                            ],
+                            false,
+                            cx,
+                        )
+                    }))
+                    .when(stats.errors.is_empty(), |el| {
+                        el.child(
+                            div()
+                                .text_xs()
+                                .text_color(cx.theme().muted_foreground)
+                                .child("No error responses."),
+                        )
+                    }),
+            )
+            .child(
+                Self::section("Session Updates", cx)
+                    .children(stats.update_kinds.iter().map(|(kind, count)| {
+                        let share = *count as f32 / update_total.max(1) as f32;
+                        h_flex()
+                            .gap_2()
+                            .child(
+                                div()
+                                    .w(px(160.))
+                                    .text_xs()
+                                    .text_color(cx.theme().foreground)
+                                    .child(kind.clone()),
+                            )
+                            .child(
+                                div()
+                                    .flex_1()
+                                    .h(px(8.))
+                                    .rounded(px(2.))
+                                    .bg(cx.theme().muted)
+                                    .child(
+                                        div()
+                                            .h_full()
+                                            .w(relative(share))
+                                            .rounded(px(2.))
+                                            .bg(cx.theme().primary),
+                                    ),
+                            )
+                            .child(
+                                div()
+                                    .w(px(48.))
+                                    .text_right()
+                                    .text_xs()
+                                    .text_color(cx.theme().muted_foreground)
+                                    .child(count.to_string()),
+                            )
+                    }))
+                    .when(stats.update_kinds.is_empty(), |el| {
+                        el.child(
+                            div()
+                                .text_xs()
+                                .text_color(cx.theme().muted_foreground)
+                                .child("No session/update notifications."),
+                        )
+                    }),
+            )
+    }
+}
+
+fn format_bytes(bytes: usize) -> String {
+    if bytes < 1024 {
+        format!("{} B", bytes)
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1} KB", bytes as f64 / 1024.)
+    } else {
+        format!("{:.1} MB", bytes as f64 / (1024. * 1024.))
+    }
+}
+
+fn format_ms(ms: f64) -> String {
+    if ms < 1000. {
+        format!("{:.0} ms", ms)
+    } else {
+        format!("{:.2} s", ms / 1000.)
+    }
+}
@@ -0,0 +1,509 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+use gpui::{prelude::*, *};
+use gpui_component::{
+    button::{Button, ButtonVariants as _},
+    h_flex,
+    tooltip::Tooltip,
+    v_flex, ActiveTheme as _, IconName, Sizable as _, StyledExt as _,
+};
+
+use crate::{
+    client::{AcpConnection, ConnectionEvent, SAMPLE_INTERVAL},
+    models::{
+        downsample, format_bytes, AcpMessage, Lane, LaneKind, ProcessInfo, ResourceSample, Timeline,
+    },
+    panels::DetailPanel,
+};
+
+const LANE_HEIGHT: f32 = 24.;
+const METRIC_HEIGHT: f32 = 40.;
+const LABEL_WIDTH: f32 = 140.;
+const MIN_SCALE: f32 = 10.;
+const MAX_SCALE: f32 = 20_000.;
+/// Narrowest resource bar; samples closer together are merged.
+const MIN_BAR_WIDTH: f32 = 2.;
+
+/// Axis tick intervals to choose from, in milliseconds.
+const AXIS_STEPS: [u64; 12] = [10, 25, 50, 100, 250, 500, 1000, 2000, 5000, 10_000, 30_000, 60_000];
+
+/// A resource metric plotted under the lanes.
+struct MetricRow {
+    /// Name and latest value.
+    label: String,
+    values: Vec<(Duration, f64)>,
+    /// The value drawn at full height.
+    max: f64,
+}
+
+impl MetricRow {
+    fn new(
+        name: &str,
+        samples: &[(Duration, ResourceSample)],
+        value: impl Fn(&ResourceSample) -> f64,
+        format: impl Fn(f64) -> String,
+        floor: f64,
+    ) -> Self {
+        let values: Vec<(Duration, f64)> = samples.iter().map(|(at, s)| (*at, value(s))).collect();
+        let max = values.iter().map(|(_, v)| *v).fold(floor, f64::max);
+        let latest = values.last().map_or_else(|| "–".to_string(), |(_, v)| format(*v));
+        Self { label: format!("{} {}", name, latest), values, max }
+    }
+}
+
+/// A waterfall of captured traffic, with lanes per session and prompt turn,
+/// and the agent's resource use on the same axis.
+pub struct TimelinePanel {
+    connection: Entity<AcpConnection>,
+    messages: Vec<AcpMessage>,
+    /// `messages` laid out, updated as they arrive.
+    timeline: Timeline,
+    /// Horizontal zoom in pixels per second.
+    scale: f32,
+    lanes_scroll: ScrollHandle,
+    _subscriptions: Vec<Subscription>,
+    _ticker: Task<()>,
+}
+
+impl TimelinePanel {
+    pub fn new(connection: Entity<AcpConnection>, cx: &mut Context<Self>) -> Self {
+        let subscriptions = vec![
+            cx.subscribe(&connection, |this, connection, event, cx| match event {
+                ConnectionEvent::MessageCaptured { message, .. } => {
+                    // Stderr written before the first message starts the axis.
+                    if let Some(line) = connection.read(cx).stderr().first() {
+                        this.timeline.begin_at(line.timestamp.min(message.timestamp));
+                    }
+                    this.timeline.push(message);
+                    this.messages.push((**message).clone());
+                    cx.notify();
+                }
+                ConnectionEvent::Reset => this.clear(cx),
+                ConnectionEvent::ProcessExited(exit) => {
+                    this.timeline.push_event(Instant::now(), exit.to_string());
+                    cx.notify();
+                }
+                ConnectionEvent::Restarting { attempt, max_attempts, delay } => {
+                    let label = format!(
+                        "restart {}/{} in {}",
+                        attempt,
+                        max_attempts,
+                        format_offset(*delay)
+                    );
+                    this.timeline.push_event(Instant::now(), label);
+                    cx.notify();
+                }
+                ConnectionEvent::RestartFailed { attempts } => {
+                    let label = format!("gave up after {} restarts", attempts);
+                    this.timeline.push_event(Instant::now(), label);
+                    cx.notify();
+                }
+                ConnectionEvent::SessionRestored { previous, session_id, loaded } => {
+                    let label = if *loaded {
+                        format!("session {} loaded again", previous)
+                    } else {
+                        format!("session {} replaced by {}", previous, session_id)
+                    };
+                    this.timeline.push_event(Instant::now(), label);
+                    cx.notify();
+                }
+                ConnectionEvent::ResourcesSampled => cx.notify(),
+                _ => {}
+            }),
+            // Stderr lines are stored on the connection.
+            cx.observe(&connection, |_, _, cx| cx.notify()),
+        ];
+
+        // Keep open requests growing while they wait for a response.
+        let ticker = cx.spawn(async move |this, cx| loop {
+            cx.background_executor().timer(Duration::from_millis(500)).await;
+            let Ok(open) = this.read_with(cx, |this, cx| {
+                this.connection.read(cx).queue().iter().any(|item| item.is_open())
+            }) else {
+                break;
+            };
+            if open && this.update(cx, |_, cx| cx.notify()).is_err() {
+                break;
+            }
+        });
+
+        Self {
+            connection,
+            messages: Vec::new(),
+            timeline: Timeline::default(),
+            scale: 200.,
+            lanes_scroll: ScrollHandle::new(),
+            _subscriptions: subscriptions,
+            _ticker: ticker,
+        }
+    }
+
+    pub fn clear(&mut self, cx: &mut Context<Self>) {
+        self.messages.clear();
+        self.timeline = Timeline::default();
+        cx.notify();
+    }
+
+    fn zoom(&mut self, factor: f32, cx: &mut Context<Self>) {
+        self.scale = (self.scale * factor).clamp(MIN_SCALE, MAX_SCALE);
+        cx.notify();
+    }
+
+    fn x(&self, at: Duration) -> Pixels {
+        px(at.as_secs_f32() * self.scale)
+    }
+
+    fn open_message(&self, ix: usize, window: &mut Window, cx: &mut App) {
+        let Some(message) = self.messages.get(ix).cloned() else {
+            return;
+        };
+        let request = self.messages[..ix].iter().rev().find(|r| message.is_reply_to(r)).cloned();
+        DetailPanel::open(message, request, self.connection.clone(), window, cx);
+    }
+
+    fn axis(
+        &self,
+        duration: Duration,
+        width: Pixels,
+        visible: Range<Duration>,
+        cx: &mut Context<Self>,
+    ) -> impl IntoElement {
+        // Keep labels at least ~80px apart.
+        let step = AXIS_STEPS
+            .iter()
+            .map(|ms| Duration::from_millis(*ms))
+            .find(|step| step.as_secs_f32() * self.scale >= 80.)
+            .unwrap_or(Duration::from_secs(60));
+        let count = (duration.as_secs_f32() / step.as_secs_f32()) as u32 + 1;
+        let first = (visible.start.as_secs_f32() / step.as_secs_f32()) as u32;
+        let last = count.min((visible.end.as_secs_f32() / step.as_secs_f32()) as u32 + 1);
+
+        div()
+            .relative()
+            .h(px(LANE_HEIGHT))
+            .w(width)
+            .border_b_1()
+            .border_color(cx.theme().border)
+            .children((first..=last).map(|i| {
+                let at = step * i;
+                div()
+                    .absolute()
+                    .left(self.x(at))
+                    .top_0()
+                    .h_full()
+                    .pl_1()
+                    .border_l_1()
+                    .border_color(cx.theme().border)
+                    .text_xs()
+                    .text_color(cx.theme().muted_foreground)
+                    .child(format_offset(at))
+            }))
+    }
+
+    fn lane(
+        &self,
+        lane_ix: usize,
+        lane: &Lane,
+        duration: Duration,
+        width: Pixels,
+        visible: Range<Duration>,
+        cx: &mut Context<Self>,
+    ) -> impl IntoElement {
+        let tick_color = match lane.kind {
+            LaneKind::Stderr => cx.theme().warning,
+            LaneKind::Process => cx.theme().danger,
+            _ => cx.theme().muted_foreground,
+        };
+
+        div()
+            .id(("lane", lane_ix))
+            .relative()
+            .h(px(LANE_HEIGHT))
+            .w(width)
+            .border_b_1()
+            .border_color(cx.theme().border)
+            .children(lane.spans_in(visible.clone(), duration).map(|span| {
+                let ix = span.message_ix;
+                let end = span.end.unwrap_or(duration);
+                let color = if span.failed {
+                    cx.theme().danger
+                } else if span.end.is_none() {
+                    cx.theme().warning
+                } else {
+                    cx.theme().primary
+                };
+                div()
+                    .absolute()
+                    .left(self.x(span.start))
+                    .top(px(5.))
+                    .h(px(14.))
+                    .w((self.x(end) - self.x(span.start)).max(px(2.)))
+                    .px_1()
+                    .rounded(px(2.))
+                    .bg(color.opacity(0.8))
+                    .overflow_hidden()
+                    .whitespace_nowrap()
+                    .text_xs()
+                    .text_color(cx.theme().primary_foreground)
+                    .child(format!("{} {}", span.label, format_offset(end - span.start)))
+                    .on_mouse_down(
+                        MouseButton::Left,
+                        cx.listener(move |this, _, window, cx| this.open_message(ix, window, cx)),
+                    )
+            }))
+            .children(lane.ticks_in(visible).map(|(ix, tick)| {
+                let message_ix = tick.message_ix;
+                let label = SharedString::from(tick.label.clone());
+                div()
+                    .id(ix)
+                    .absolute()
+                    .left(self.x(tick.at))
+                    .top(px(3.))
+                    .h(px(18.))
+                    .w(px(2.))
+                    .bg(tick_color)
+                    .tooltip(move |window, cx| Tooltip::new(label.clone()).build(window, cx))
+                    .when_some(message_ix, |el, ix| {
+                        el.on_mouse_down(
+                            MouseButton::Left,
+                            cx.listener(move |this, _, window, cx| {
+                                this.open_message(ix, window, cx)
+                            }),
+                        )
+                    })
+            }))
+    }
+
+    fn metric(
+        &self,
+        ix: usize,
+        row: &MetricRow,
+        width: Pixels,
+        visible: Range<Duration>,
+        cx: &mut Context<Self>,
+    ) -> impl IntoElement {
+        let bucket = SAMPLE_INTERVAL.max(Duration::from_secs_f32(MIN_BAR_WIDTH / self.scale));
+        let bar = self.x(bucket).max(px(1.));
+        div()
+            .id(("metric", ix))
+            .relative()
+            .h(px(METRIC_HEIGHT))
+            .w(width)
+            .border_b_1()
+            .border_color(cx.theme().border)
+            .children(downsample(&row.values, bucket, visible).into_iter().map(|(at, value)| {
+                let height = (value / row.max).clamp(0., 1.) as f32 * (METRIC_HEIGHT - 4.);
+                div()
+                    .absolute()
+                    .left(self.x(at))
+                    .bottom_0()
+                    .w(bar)
+                    .h(px(height))
+                    .bg(cx.theme().primary.opacity(0.5))
+            }))
+    }
+}
+
+fn metric_rows(samples: &[(Duration, ResourceSample)]) -> Vec<MetricRow> {
+    vec![
+        MetricRow::new("CPU", samples, |s| s.cpu_percent as f64, |v| format!("{:.0}%", v), 100.),
+        MetricRow::new("RSS", samples, |s| s.rss_bytes as f64, |v| format_bytes(v as u64), 1.),
+        MetricRow::new("Threads", samples, |s| s.threads as f64, |v| v.to_string(), 1.),
+        MetricRow::new("FDs", samples, |s| s.fds as f64, |v| v.to_string(), 1.),
+    ]
+}
+
+fn process_row(process: &ProcessInfo) -> String {
+    format!(
+        "{}{} {} · {} · {} threads · {} fds",
+        "  ".repeat(process.depth),
+        process.pid,
+        process.name,
+        format_bytes(process.rss_bytes),
+        process.threads,
+        process.fds
+    )
+}
+
+impl Render for TimelinePanel {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let connection = self.connection.read(cx);
+        let stderr: Vec<Instant> = connection.stderr().iter().map(|line| line.timestamp).collect();
+        let resources = connection.resources();
+        let tree = resources.tree.clone();
+        let now = Instant::now();
+        // Before any traffic, the axis starts at the first stderr line or
+        // sample.
+        let origin = self
+            .timeline
+            .origin
+            .or(stderr.first().copied())
+            .or(resources.samples.front().map(|s| s.at));
+        let samples: Vec<(Duration, ResourceSample)> = origin.map_or_else(Vec::new, |origin| {
+            resources
+                .samples
+                .iter()
+                .filter(|s| s.at >= origin)
+                .map(|s| (s.at.duration_since(origin), *s))
+                .collect()
+        });
+        let metrics = if samples.is_empty() { Vec::new() } else { metric_rows(&samples) };
+        let duration =
+            origin.map_or(Duration::ZERO, |origin| now.saturating_duration_since(origin));
+        let stderr_lane = origin.and_then(|origin| Timeline::stderr_lane(&stderr, origin));
+        let lanes: Vec<&Lane> = self.timeline.lanes().chain(stderr_lane.as_ref()).collect();
+        // Leave room after the last event for its label.
+        let width = self.x(duration) + px(200.);
+        // Resource bars are only drawn where the lanes are scrolled to.
+        let viewport = self.lanes_scroll.bounds().size.width;
+        let viewport = if viewport > px(0.) { viewport } else { window.viewport_size().width };
+        let left = (-self.lanes_scroll.offset().x).max(px(0.));
+        let visible = Duration::from_secs_f32(f32::from(left) / self.scale)..
+            Duration::from_secs_f32(f32::from(left + viewport) / self.scale);
+
+        v_flex()
+            .size_full()
+            .gap_2()
+            .p_4()
+            .child(
+                h_flex()
+                    .gap_2()
+                    .child(
+                        div().flex_1().text_sm().text_color(cx.theme().muted_foreground).child(
+                            format!("{} lanes over {}", lanes.len(), format_offset(duration)),
+                        ),
+                    )
+                    .child(
+                        Button::new("zoom-out")
+                            .xsmall()
+                            .ghost()
+                            .icon(IconName::Minus)
+                            .on_click(cx.listener(|this, _, _, cx| this.zoom(0.5, cx))),
+                    )
+                    .child(
+                        Button::new("zoom-in")
+                            .xsmall()
+                            .ghost()
+                            .icon(IconName::Plus)
+                            .on_click(cx.listener(|this, _, _, cx| this.zoom(2., cx))),
+                    ),
+            )
+            .when(lanes.is_empty(), |el| {
+                el.child(
+                    div()
+                        .text_sm()
+                        .text_color(cx.theme().muted_foreground)
+                        .child("No traffic captured yet."),
+                )
+            })
+            .child(
+                div().id("timeline-scroll").flex_1().overflow_y_scroll().child(
+                    h_flex()
+                        .items_start()
+                        // Lane labels
+                        .child(
+                            v_flex()
+                                .flex_none()
+                                .w(px(LABEL_WIDTH))
+                                .child(div().h(px(LANE_HEIGHT)))
+                                .children(lanes.iter().map(|lane| {
+                                    div()
+                                        .h(px(LANE_HEIGHT))
+                                        .flex()
+                                        .items_center()
+                                        .overflow_hidden()
+                                        .whitespace_nowrap()
+                                        .text_xs()
+                                        .text_color(cx.theme().foreground)
+                                        .child(lane.label())
+                                }))
+                                .children(metrics.iter().map(|row| {
+                                    div()
+                                        .h(px(METRIC_HEIGHT))
+                                        .flex()
+                                        .items_center()
+                                        .overflow_hidden()
+                                        .whitespace_nowrap()
+                                        .text_xs()
+                                        .text_color(cx.theme().muted_foreground)
+                                        .child(row.label.clone())
+                                })),
+                        )
+                        // Lanes on a shared time axis
+                        .child(
+                            div()
+                                .id("timeline-lanes")
+                                .flex_1()
+                                .overflow_x_scroll()
+                                .track_scroll(&self.lanes_scroll)
+                                .child(
+                                    v_flex()
+                                        .child(self.axis(duration, width, visible.clone(), cx))
+                                        .children(lanes.iter().enumerate().map(|(ix, lane)| {
+                                            self.lane(
+                                                ix,
+                                                lane,
+                                                duration,
+                                                width,
+                                                visible.clone(),
+                                                cx,
+                                            )
+                                        }))
+                                        .children(metrics.iter().enumerate().map(|(ix, row)| {
+                                            self.metric(ix, row, width, visible.clone(), cx)
+                                        })),
+                                ),
+                        ),
+                ),
+            )
+            .when(!tree.is_empty(), |el| {
+                el.child(
+                    v_flex()
+                        .id("process-tree")
+                        .flex_none()
+                        .max_h(px(120.))
+                        .overflow_y_scroll()
+                        .gap_1()
+                        .child(
+                            div()
+                                .text_xs()
+                                .font_semibold()
+                                .text_color(cx.theme().muted_foreground)
+                                .child("Processes"),
+                        )
+                        .children(tree.iter().map(|process| {
+                            div()
+                                .text_xs()
+                                .font_family("monospace")
+                                .whitespace_nowrap()
+                                .child(process_row(process))
+                        })),
+                )
+            })
+    }
+}
+
+fn format_offset(offset: Duration) -> String {
+    if offset < Duration::from_secs(1) {
+        format!("{} ms", offset.as_millis())
+    } else {
+        format!("{:.2} s", offset.as_secs_f64())
+    }
+}
@@ -0,0 +1,33 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod replayer;
+
+use std::path::Path;
+
+use crate::models::Trace;
+
+#[allow(unused_imports)]
+pub use replayer::{Divergence, ReplayReport, TraceReplayer};
+
+/// Act as the recorded agent on this process's stdin/stdout.
+///
+/// Divergences are reported on stderr, which ACP clients typically surface
+/// in their agent logs.
+pub fn replay_stdio(trace_path: &Path) -> anyhow::Result<ReplayReport> {
+    let trace = Trace::load(trace_path)?;
+    let stdin = std::io::stdin().lock();
+    let stdout = std::io::stdout().lock();
+    Ok(TraceReplayer::new(trace).run(stdin, stdout)?)
+}
@@ -0,0 +1,289 @@
+// Copyright (c) wangeguo. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, BufRead, Write},
+    time::{Duration, Instant},
+};
+
+use serde_json::Value;
+
+use crate::models::{Trace, TraceDirection, TraceFrame};
+
+/// JSON-RPC error code returned to the client when its request diverges
+/// from the trace.
+const DIVERGENCE_ERROR_CODE: i64 = -32000;
+
+/// A client frame that did not match the next expected frame in the trace.
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    /// Index of the expected frame in the trace.
+    pub frame_index: usize,
+    /// Description of the expected frame, `None` past the end of the trace.
+    pub expected: Option<String>,
+    /// Description of what the client actually sent.
+    pub actual: String,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.expected {
+            Some(expected) => write!(
+                f,
+                "divergence at frame {}: expected {}, got {}",
+                self.frame_index, expected, self.actual
+            ),
+            None => write!(
+                f,
+                "divergence at frame {}: trace ended, got {}",
+                self.frame_index, self.actual
+            ),
+        }
+    }
+}
+
+/// Summary of a replay run.
+#[derive(Debug, Default)]
+pub struct ReplayReport {
+    pub frames_replayed: usize,
+    pub divergences: Vec<Divergence>,
+}
+
+/// Impersonates an agent by replaying the agent → client half of a trace.
+///
+/// Client frames are matched in order against the recorded outgoing frames.
+/// Once a frame matches, the recorded agent frames that follow it are written
+/// back with their original relative timing, and response ids are rewritten
+/// to the ids the live client actually used.
+pub struct TraceReplayer {
+    frames: Vec<TraceFrame>,
+    cursor: usize,
+    /// Recorded request id → id used by the live client.
+    id_map: HashMap<String, Value>,
+    /// Wall-clock time and recorded offset of the last matched client frame.
+    anchor: Option<(Instant, u64)>,
+    report: ReplayReport,
+}
+
+impl TraceReplayer {
+    pub fn new(trace: Trace) -> Self {
+        Self {
+            frames: trace.frames,
+            cursor: 0,
+            id_map: HashMap::new(),
+            anchor: None,
+            report: ReplayReport::default(),
+        }
+    }
+
+    /// Serve the client over the given streams until it closes its input.
+    pub fn run(mut self, input: impl BufRead, mut output: impl Write) -> io::Result<ReplayReport> {
+        let mut lines = input.lines();
+        loop {
+            self.emit_agent_frames(&mut output)?;
+
+            let Some(line) = lines.next() else {
+                break;
+            };
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let received_at = Instant::now();
+
+            let value = match serde_json::from_str::<Value>(&line) {
+                Ok(value) => value,
+                Err(e) => {
+                    self.diverge(format!("unparseable frame ({})", e));
+                    continue;
+                }
+            };
+
+            match self.frames.get(self.cursor) {
+                Some(expected) if frame_matches(expected, &value) => {
+                    if let (Some(recorded), Some(live)) = (expected.id(), value.get("id")) {
+                        if expected.method().is_some() {
+                            self.id_map.insert(recorded.to_string(), live.clone());
+                        }
+                    }
+                    self.anchor = Some((received_at, expected.offset_ms));
+                    self.cursor += 1;
+                }
+                _ => {
+                    self.diverge(describe(&value));
+                    if let (Some(id), Some(_)) = (value.get("id"), value.get("method")) {
+                        let message = self.report.divergences.last().map(|d| d.to_string());
+                        write_frame(
+                            &mut output,
+                            &serde_json::json!({
+                                "jsonrpc": "2.0",
+                                "id": id,
+                                "error": {
+                                    "code": DIVERGENCE_ERROR_CODE,
+                                    "message": message.unwrap_or_default(),
+                                },
+                            }),
+                        )?;
+                    }
+                }
+            }
+        }
+
+        if let Some(expected) =
+            self.frames[self.cursor..].iter().position(|f| f.direction == TraceDirection::Outgoing)
+        {
+            let frame_index = self.cursor + expected;
+            let divergence = Divergence {
+                frame_index,
+                expected: Some(describe(&self.frames[frame_index].message)),
+                actual: "end of input".into(),
+            };
+            eprintln!("{}", divergence);
+            self.report.divergences.push(divergence);
+        }
+
+        Ok(self.report)
+    }
+
+    /// Write every recorded agent frame up to the next expected client frame.
+    fn emit_agent_frames(&mut self, output: &mut impl Write) -> io::Result<()> {
+        while let Some(frame) = self.frames.get(self.cursor) {
+            if frame.direction != TraceDirection::Incoming {
+                break;
+            }
+
+            if let Some((at, offset)) = self.anchor {
+                let due = at + Duration::from_millis(frame.offset_ms.saturating_sub(offset));
+                let now = Instant::now();
+                if due > now {
+                    std::thread::sleep(due - now);
+                }
+            }
+
+            let mut message = frame.message.clone();
+            if frame.is_response() {
+                if let Some(live) = frame.id().and_then(|id| self.id_map.remove(&id.to_string())) {
+                    message["id"] = live;
+                }
+            }
+            write_frame(output, &message)?;
+
+            self.report.frames_replayed += 1;
+            self.cursor += 1;
+        }
+        Ok(())
+    }
+
+    fn diverge(&mut self, actual: String) {
+        let divergence = Divergence {
+            frame_index: self.cursor,
+            expected: self.frames.get(self.cursor).map(|f| describe(&f.message)),
+            actual,
+        };
+        eprintln!("{}", divergence);
+        self.report.divergences.push(divergence);
+    }
+}
+
+/// Whether a live client frame matches the recorded one: same method for
+/// requests and notifications, same id for responses to agent requests.
+fn frame_matches(expected: &TraceFrame, actual: &Value) -> bool {
+    let actual_method = actual.get("method").and_then(|v| v.as_str());
+    match expected.method() {
+        Some(method) => {
+            actual_method == Some(method) && expected.id().is_some() == actual.get("id").is_some()
+        }
+        None => actual_method.is_none() && expected.id() == actual.get("id"),
+    }
+}
+
+fn describe(value: &Value) -> String {
+    let id = value.get("id").map(|id| format!(" (id={})", id)).unwrap_or_default();
+    match value.get("method").and_then(|v| v.as_str()) {
+        Some(method) if value.get("id").is_some() => format!("request `{}`{}", method, id),
+        Some(method) => format!("notification `{}`", method),
+        None => format!("response{}", id),
+    }
+}
+
+fn write_frame(output: &mut impl Write, value: &Value) -> io::Result<()> {
+    writeln!(output, "{}", value)?;
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trace(frames: &[(u64, TraceDirection, Value)]) -> Trace {
+        Trace {
+            frames: frames
+                .iter()
+                .map(|(offset_ms, direction, message)| TraceFrame {
+                    offset_ms: *offset_ms,
+                    direction: *direction,
+                    message: message.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_replay_rewrites_response_ids() {
+        let trace = trace(&[
+            (
+                0,
+                TraceDirection::Outgoing,
+                serde_json::json!({"jsonrpc":"2.0","id":0,"method":"initialize","params":{}}),
+            ),
+            (
+                5,
+                TraceDirection::Incoming,
+                serde_json::json!({"jsonrpc":"2.0","id":0,"result":{"protocolVersion":1}}),
+            ),
+        ]);
+        let input = r#"{"jsonrpc":"2.0","id":42,"method":"initialize","params":{}}"#;
+        let mut output = Vec::new();
+
+        let report = TraceReplayer::new(trace).run(input.as_bytes(), &mut output).unwrap();
+
+        assert!(report.divergences.is_empty());
+        assert_eq!(report.frames_replayed, 1);
+        let response: Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(response["id"], 42);
+        assert_eq!(response["result"]["protocolVersion"], 1);
+    }
+
+    #[test]
+    fn test_replay_reports_method_mismatch() {
+        let trace = trace(&[(
+            0,
+            TraceDirection::Outgoing,
+            serde_json::json!({"jsonrpc":"2.0","id":0,"method":"initialize","params":{}}),
+        )]);
+        let input = r#"{"jsonrpc":"2.0","id":7,"method":"session/new","params":{}}"#;
+        let mut output = Vec::new();
+
+        let report = TraceReplayer::new(trace).run(input.as_bytes(), &mut output).unwrap();
+
+        // One for the mismatch, one for the unmatched expected frame at EOF.
+        assert_eq!(report.divergences.len(), 2);
+        assert_eq!(report.divergences[0].actual, "request `session/new` (id=7)");
+        let response: Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(response["id"], 7);
+        assert_eq!(response["error"]["code"], DIVERGENCE_ERROR_CODE);
+    }
+}